@@ -1,32 +1,146 @@
+mod admin;
+mod audit_log;
+mod certified;
+mod config;
 mod download_file;
+mod download_token;
+mod drop_box;
+mod file_versions;
 mod get_alias_info;
 mod get_requests;
 mod get_users;
+mod http;
+mod inspect_message;
+mod metrics;
+mod notifications;
+mod organizations;
+mod quotas;
+mod rate_limit;
+mod request_checklist;
 mod request_file;
 mod share_file;
+mod transfer_file;
 mod upload_file;
 mod upload_file_atomic;
 mod user_info;
+mod webhooks;
 
-use crate::{FileContent, State, UploadFileContinueRequest};
-pub use download_file::download_file;
+use crate::{
+    certification::certify_file, validation, ChunkKey, FileContent, State,
+    UploadFileContinueRequest, UploadFileError, ValidationError,
+};
+pub use admin::{
+    add_admin, check_uploads_allowed, get_admin_log, get_storage_usage, remove_admin, remove_file,
+    remove_user, set_config, set_uploads_frozen,
+};
+pub use audit_log::{get_audit_log, get_file_audit_log, record_upload};
+pub use certified::{download_file_certified, get_alias_info_certified, get_requests_certified};
+pub use config::{get_config, init, upgrade};
+pub use download_file::{download_file, download_file_audited, download_file_version};
+pub use download_token::create_download_token;
+use drop_box::receive_drop_box_chunk;
+pub use drop_box::{
+    close_drop_box, create_drop_box, get_drop_box_info, get_drop_boxes, upload_to_drop_box,
+};
+pub use file_versions::{cancel_file_version, get_file_versions, upload_file_version};
 pub use get_alias_info::get_alias_info;
 pub use get_requests::get_requests;
 pub use get_users::get_users;
+pub use http::{http_request, http_request_streaming_callback};
+pub use inspect_message::{inspect_message, Message, MAX_ARG_SIZE};
+pub use metrics::get_metrics;
+use notifications::notify_upload_completed;
+pub use notifications::{
+    ack_notifications, expiry_check_due, get_notifications, get_unread_notification_count,
+    notify_expiring_requests,
+};
+pub use organizations::{
+    add_file_to_organization, create_organization, get_organization_files, get_organizations,
+    remove_organization_member, request_organization_file, set_organization_member,
+};
+pub(crate) use quotas::add_usage;
+use quotas::check_quota;
+pub use quotas::get_usage;
+pub use rate_limit::{
+    check_rate_limit, consume_chunk_rate_limit, consume_rate_limit, get_rate_limits, pending_alias,
+    set_rate_limits,
+};
+pub use request_checklist::{get_checklists, request_checklist};
 pub use request_file::request_file;
-pub use share_file::{get_shared_files, revoke_share, share_file};
+pub use share_file::{
+    get_file_shares, get_shared_files, revoke_share, share_file, share_file_with_download_limit,
+    share_file_with_permission,
+};
+pub use transfer_file::{
+    accept_file_transfer, cancel_file_transfer, get_transfer_offers, offer_file_transfer,
+};
 pub use upload_file::upload_file;
-pub use upload_file_atomic::{upload_file_atomic, UploadFileAtomicRequest};
+pub use upload_file_atomic::upload_file_atomic;
 pub use user_info::set_user_info;
 pub use user_info::username_exists;
+pub use webhooks::{
+    add_webhook, complete_webhook_delivery, get_webhooks, remove_webhook,
+    take_due_webhook_requests, webhook_deliveries_due, WebhookRequest,
+};
 
-pub fn upload_file_continue(request: UploadFileContinueRequest, state: &mut State) {
-    // Update the file's contents.
+// The requests of the endpoints are defined along with the other types of the interface.
+pub use crate::{
+    CreateDropBoxRequest, UploadFileAtomicRequest, UploadFileVersionRequest, UploadToDropBoxRequest,
+};
+
+/// Validates the size of a chunk uploaded with `upload_file_continue` against the first chunk
+/// of its file.
+///
+/// Chunks of files that aren't being uploaded are left to `upload_file_continue` to reject.
+pub fn validate_chunk_size(
+    state: &State,
+    request: &UploadFileContinueRequest,
+) -> Result<(), ValidationError> {
+    let file = match state.file_data.get(&request.file_id) {
+        Some(file) => file,
+        None => return Ok(()),
+    };
+    let num_chunks = match file.content {
+        FileContent::PartiallyUploaded { num_chunks, .. } => num_chunks,
+        _ => return Ok(()),
+    };
+    let first_chunk = match state.get_chunk(ChunkKey(request.file_id, file.version, 0)) {
+        Some(chunk) => chunk,
+        None => return Ok(()),
+    };
+    validation::validate_chunk_size(
+        first_chunk.len(),
+        &request.contents,
+        request.chunk_id + 1 == num_chunks,
+    )
+}
+
+pub fn upload_file_continue(
+    request: UploadFileContinueRequest,
+    state: &mut State,
+) -> Result<(), UploadFileError> {
     let file_id = request.file_id;
     let chunk_id = request.chunk_id;
+    let completed;
 
+    // Check the limits of the owner and of the drop box before the chunk is stored.
+    let owner = match state.file_data.get(&file_id) {
+        Some(file) if matches!(file.content, FileContent::PartiallyUploaded { .. }) => {
+            Some(file.metadata.requester_principal)
+        }
+        _ => None,
+    };
+    if let Some(owner) = owner {
+        let bytes = request.contents.len() as u64;
+        check_quota(state, owner, bytes, 0)?;
+        receive_drop_box_chunk(state, file_id, bytes)
+            .map_err(|_| UploadFileError::DropBoxLimitExceeded)?;
+    }
+
+    // Update the file's contents.
     let updated_file_data = match state.file_data.remove(&file_id) {
         Some(mut file) => {
+            let version = file.version;
             let updated_contents = match file.content {
                 FileContent::PartiallyUploaded {
                     num_chunks,
@@ -37,16 +151,17 @@ pub fn upload_file_continue(request: UploadFileContinueRequest, state: &mut Stat
                     // Add the chunk to the partially uploaded file.
                     assert!(chunk_id < num_chunks, "invalid chunk id");
                     assert!(
-                        !state.file_contents.contains_key(&(file_id, chunk_id)),
+                        !state.contains_chunk(ChunkKey(file_id, version, chunk_id)),
                         "chunk already uploaded"
                     );
+                    let owner = file.metadata.requester_principal;
+                    add_usage(state, owner, request.contents.len() as u64, 0);
 
                     // Add the chunk.
-                    state
-                        .file_contents
-                        .insert((file_id, chunk_id), request.contents);
+                    state.insert_chunk(ChunkKey(file_id, version, chunk_id), request.contents);
 
-                    if state.num_chunks_uploaded(file_id) == num_chunks {
+                    completed = state.num_chunks_uploaded(file_id, version) == num_chunks;
+                    if completed {
                         // The file is complete. Assemble the file.
                         FileContent::Uploaded {
                             file_type,
@@ -74,13 +189,18 @@ pub fn upload_file_continue(request: UploadFileContinueRequest, state: &mut Stat
 
     // Insert the file back into the state.
     assert_eq!(state.file_data.insert(file_id, updated_file_data), None);
+    certify_file(state, file_id);
+    if completed {
+        notify_upload_completed(state, file_id);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::api::user_info::get_user_key;
-    use crate::{api::set_user_info, get_time, File, FileMetadata, User};
+    use crate::{api::set_user_info, File, FileMetadata, User};
     use candid::Principal;
     use maplit::btreemap;
     use std::collections::BTreeMap;
@@ -109,7 +229,8 @@ mod test {
                 file_type: "image/jpeg".to_string(),
             },
             &mut state,
-        );
+        )
+        .unwrap();
 
         // The first chunk is added to the state.
         assert_eq!(
@@ -120,20 +241,25 @@ mod test {
                         file_name: "file_name".to_string(),
                         user_public_key: get_user_key(&state, Principal::anonymous()),
                         requester_principal: Principal::anonymous(),
-                        requested_at: get_time(),
-                        uploaded_at: Some(get_time()),
+                        requested_at: state.env.now(),
+                        uploaded_at: Some(state.env.now()),
                     },
                     content: FileContent::PartiallyUploaded {
                         num_chunks: 3,
                         file_type: "image/jpeg".to_string(),
                         owner_key: vec![1,2,3],
                         shared_keys: BTreeMap::new()
-                    }
+                    },
+                    version: 0,
+                    versions: vec![],
                 }
             }
         );
-        assert_eq!(state.file_contents.get(&(file_id, 0)), Some(vec![1, 2, 3]));
-        assert_eq!(state.num_chunks_uploaded(file_id), 1);
+        assert_eq!(
+            state.file_contents.get(&ChunkKey(file_id, 0, 0)),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(state.num_chunks_uploaded(file_id, 0), 1);
 
         // Upload the second chunk.
         upload_file_continue(
@@ -143,7 +269,8 @@ mod test {
                 contents: vec![4, 5, 6],
             },
             &mut state,
-        );
+        )
+        .unwrap();
 
         // The second chunk is added to the state.
         assert_eq!(
@@ -154,21 +281,29 @@ mod test {
                         file_name: "file_name".to_string(),
                         user_public_key: get_user_key(&state, Principal::anonymous()),
                         requester_principal: Principal::anonymous(),
-                        requested_at: get_time(),
-                        uploaded_at: Some(get_time()),
+                        requested_at: state.env.now(),
+                        uploaded_at: Some(state.env.now()),
                     },
                     content: FileContent::PartiallyUploaded {
                         num_chunks: 3,
                         file_type: "image/jpeg".to_string(),
                         owner_key: vec![1,2,3],
                         shared_keys: BTreeMap::new()
-                    }
+                    },
+                    version: 0,
+                    versions: vec![],
                 }
             }
         );
-        assert_eq!(state.file_contents.get(&(file_id, 0)), Some(vec![1, 2, 3]));
-        assert_eq!(state.file_contents.get(&(file_id, 1)), Some(vec![4, 5, 6]));
-        assert_eq!(state.num_chunks_uploaded(file_id), 2);
+        assert_eq!(
+            state.file_contents.get(&ChunkKey(file_id, 0, 0)),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(
+            state.file_contents.get(&ChunkKey(file_id, 0, 1)),
+            Some(vec![4, 5, 6])
+        );
+        assert_eq!(state.num_chunks_uploaded(file_id, 0), 2);
 
         // Upload the third and final chunk.
         upload_file_continue(
@@ -178,7 +313,8 @@ mod test {
                 contents: vec![7, 8, 9, 10],
             },
             &mut state,
-        );
+        )
+        .unwrap();
 
         // The last chunk is added to the state.
         assert_eq!(
@@ -189,24 +325,79 @@ mod test {
                         file_name: "file_name".to_string(),
                         user_public_key: get_user_key(&state, Principal::anonymous()),
                         requester_principal: Principal::anonymous(),
-                        requested_at: get_time(),
-                        uploaded_at: Some(get_time()),
+                        requested_at: state.env.now(),
+                        uploaded_at: Some(state.env.now()),
                     },
                     content: FileContent::Uploaded {
                         file_type: "image/jpeg".to_string(),
                         owner_key: vec![1,2,3],
                         shared_keys: BTreeMap::new(),
                         num_chunks: 3
-                    }
+                    },
+                    version: 0,
+                    versions: vec![],
                 }
             }
         );
-        assert_eq!(state.file_contents.get(&(file_id, 0)), Some(vec![1, 2, 3]));
-        assert_eq!(state.file_contents.get(&(file_id, 1)), Some(vec![4, 5, 6]));
         assert_eq!(
-            state.file_contents.get(&(file_id, 2)),
+            state.file_contents.get(&ChunkKey(file_id, 0, 0)),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(
+            state.file_contents.get(&ChunkKey(file_id, 0, 1)),
+            Some(vec![4, 5, 6])
+        );
+        assert_eq!(
+            state.file_contents.get(&ChunkKey(file_id, 0, 2)),
             Some(vec![7, 8, 9, 10])
         );
-        assert_eq!(state.num_chunks_uploaded(file_id), 3);
+        assert_eq!(state.num_chunks_uploaded(file_id, 0), 3);
+    }
+
+    #[test]
+    fn chunks_have_the_size_of_the_first_chunk() {
+        let mut state = State::default();
+        set_user_info(
+            &mut state,
+            Principal::anonymous(),
+            User {
+                username: "John".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+        let file_id = upload_file_atomic(
+            Principal::anonymous(),
+            UploadFileAtomicRequest {
+                num_chunks: 3,
+                name: "file_name".to_string(),
+                content: vec![1; 10],
+                owner_key: vec![1, 2, 3],
+                file_type: "image/jpeg".to_string(),
+            },
+            &mut state,
+        )
+        .unwrap();
+        let chunk = |chunk_id, size| UploadFileContinueRequest {
+            file_id,
+            chunk_id,
+            contents: vec![2; size],
+        };
+
+        // A small first chunk can't be followed by large chunks.
+        assert_eq!(
+            validate_chunk_size(&state, &chunk(1, 11)),
+            Err(ValidationError::InvalidChunkSize)
+        );
+        assert_eq!(
+            validate_chunk_size(&state, &chunk(1, 9)),
+            Err(ValidationError::InvalidChunkSize)
+        );
+        assert_eq!(validate_chunk_size(&state, &chunk(1, 10)), Ok(()));
+        // Only the last chunk can be smaller.
+        assert_eq!(validate_chunk_size(&state, &chunk(2, 1)), Ok(()));
+        assert_eq!(
+            validate_chunk_size(&state, &chunk(2, 11)),
+            Err(ValidationError::InvalidChunkSize)
+        );
     }
 }
@@ -0,0 +1,546 @@
+use crate::{
+    certification::{certify_alias, certify_file, certify_user},
+    AdminAction, AdminError, AdminLogEntry, AdminLogPage, Config, FileContent, State, StorageUsage,
+    UploadsFrozenError, MAX_AUDIT_LOG_PAGE_SIZE,
+};
+use ic_cdk::export::candid::Principal;
+use std::cmp::Reverse;
+
+use super::config::validate_config;
+use super::organizations::is_last_admin;
+use super::quotas::{file_bytes, get_usage, release_usage};
+use super::share_file::remove_share;
+use super::webhooks::remove_webhook;
+
+/// Appends an action to the admin log.
+pub(crate) fn record_admin_action(state: &mut State, caller: Principal, action: AdminAction) {
+    state
+        .admin_log
+        .append(&AdminLogEntry {
+            timestamp: state.env.now(),
+            caller,
+            action,
+        })
+        .expect("failed to append to the admin log");
+}
+
+pub(crate) fn check_admin(state: &State, caller: Principal) -> Result<(), AdminError> {
+    if state.admins.contains(&caller) {
+        Ok(())
+    } else {
+        Err(AdminError::PermissionError)
+    }
+}
+
+/// Checks that admins haven't frozen uploads.
+pub fn check_uploads_allowed(state: &State) -> Result<(), UploadsFrozenError> {
+    if state.uploads_frozen {
+        Err(UploadsFrozenError)
+    } else {
+        Ok(())
+    }
+}
+
+pub fn add_admin(state: &mut State, caller: Principal, user: Principal) -> Result<(), AdminError> {
+    check_admin(state, caller)?;
+    if state.admins.insert(user) {
+        record_admin_action(state, caller, AdminAction::AdminAdded { user });
+    }
+    Ok(())
+}
+
+pub fn remove_admin(
+    state: &mut State,
+    caller: Principal,
+    user: Principal,
+) -> Result<(), AdminError> {
+    check_admin(state, caller)?;
+    if state.admins.contains(&user) && state.admins.len() == 1 {
+        return Err(AdminError::LastAdmin);
+    }
+    if state.admins.remove(&user) {
+        record_admin_action(state, caller, AdminAction::AdminRemoved { user });
+    }
+    Ok(())
+}
+
+/// Returns the storage consumed across the canister, along with the usage of every user.
+pub fn get_storage_usage(state: &State, caller: Principal) -> Result<StorageUsage, AdminError> {
+    check_admin(state, caller)?;
+
+    let mut users = get_usage(state, caller);
+    users.sort_by_key(|usage| Reverse(usage.bytes));
+
+    Ok(StorageUsage {
+        stored_bytes: state.stored_bytes,
+        max_stored_bytes: state.config.max_stored_bytes,
+        file_count: state.file_data.len() as u64,
+        user_count: state.users.len() as u64,
+        users,
+    })
+}
+
+/// Removes a file along with all its versions and shares, e.g., because it is abusive.
+pub fn remove_file(state: &mut State, caller: Principal, file_id: u64) -> Result<(), AdminError> {
+    check_admin(state, caller)?;
+    let owner = match state.file_data.get(&file_id) {
+        Some(file) => file.metadata.requester_principal,
+        None => return Err(AdminError::NotFoundFile),
+    };
+
+    delete_file(state, file_id);
+    record_admin_action(state, caller, AdminAction::FileRemoved { file_id, owner });
+    Ok(())
+}
+
+/// Removes a user along with the files they own, their shares, their memberships, and the
+/// drop boxes and checklists they requested.
+///
+/// The last admin of an organization can't be removed, as the organization would be left
+/// without anyone to manage it.
+pub fn remove_user(
+    state: &mut State,
+    caller: Principal,
+    user: Principal,
+) -> Result<(), AdminError> {
+    check_admin(state, caller)?;
+    if !state.users.contains_key(&user) {
+        return Err(AdminError::UnknownUser);
+    }
+    if state.admins.contains(&user) && state.admins.len() == 1 {
+        return Err(AdminError::LastAdmin);
+    }
+    if state
+        .organizations
+        .values()
+        .any(|org| is_last_admin(org, user))
+    {
+        return Err(AdminError::LastOrganizationAdmin);
+    }
+
+    for file_id in state.file_owners.get(&user).cloned().unwrap_or_default() {
+        delete_file(state, file_id);
+    }
+    for file_id in state.file_shares.get(&user).cloned().unwrap_or_default() {
+        remove_share(state, user, file_id);
+    }
+    state
+        .drop_boxes
+        .retain(|_, drop_box| drop_box.requester_principal != user);
+    state
+        .file_transfers
+        .retain(|_, transfer| transfer.to != user);
+    for org in state.organizations.values_mut() {
+        org.members.remove(&user);
+    }
+
+    state.users.remove(&user);
+    certify_user(state, user);
+    state.file_owners.remove(&user);
+    state.file_shares.remove(&user);
+    state.usage.remove(&user);
+    state.admins.remove(&user);
+    state.notifications.remove(&user);
+    let webhook_ids: Vec<u64> = state
+        .webhooks
+        .iter()
+        .filter(|(_, webhook)| webhook.owner == user)
+        .map(|(id, _)| *id)
+        .collect();
+    for webhook_id in webhook_ids {
+        remove_webhook(state, user, webhook_id).expect("the webhook must exist");
+    }
+
+    record_admin_action(state, caller, AdminAction::UserRemoved { user });
+    Ok(())
+}
+
+/// Deletes a file from all the places that refer to it.
+fn delete_file(state: &mut State, file_id: u64) {
+    let owner = match state.file_data.get(&file_id) {
+        Some(file) => file.metadata.requester_principal,
+        None => return,
+    };
+    let file = &state.file_data[&file_id];
+    // Pending files aren't accounted to their owner until they are uploaded.
+    let files = match file.content {
+        FileContent::Pending { .. } => 0,
+        _ => 1,
+    };
+    release_usage(state, owner, file_bytes(state, file_id), files);
+
+    let file = state.file_data.remove(&file_id).unwrap();
+    certify_file(state, file_id);
+    match &file.content {
+        FileContent::Pending { alias } => {
+            if state.file_alias_index.get(alias) == Some(&file_id) {
+                state.file_alias_index.remove(alias);
+                certify_alias(state, alias);
+            }
+        }
+        FileContent::PartiallyUploaded { num_chunks, .. }
+        | FileContent::Uploaded { num_chunks, .. } => {
+            state.remove_chunks(file_id, file.version, *num_chunks);
+        }
+    }
+    for version in &file.versions {
+        state.remove_chunks(file_id, version.version, version.num_chunks);
+    }
+
+    if let Some(files) = state.file_owners.get_mut(&owner) {
+        files.retain(|id| *id != file_id);
+    }
+    for files in state.file_shares.values_mut() {
+        files.retain(|id| *id != file_id);
+    }
+    state.share_grants.remove(&file_id);
+    state
+        .download_sessions
+        .retain(|(session_file_id, _)| *session_file_id != file_id);
+    state.file_transfers.remove(&file_id);
+    state.file_organizations.remove(&file_id);
+    state
+        .download_tokens
+        .retain(|_, grant| grant.file_id != file_id);
+
+    let mut checklist_aliases = vec![];
+    for (alias, checklist) in state.checklists.iter_mut() {
+        if checklist.file_ids.contains(&file_id) {
+            checklist.file_ids.retain(|id| *id != file_id);
+            checklist_aliases.push(alias.clone());
+        }
+    }
+    state
+        .checklists
+        .retain(|_, checklist| !checklist.file_ids.is_empty());
+    for alias in checklist_aliases {
+        certify_alias(state, &alias);
+    }
+    if let Some(alias) = state.drop_box_files.remove(&file_id) {
+        if let Some(drop_box) = state.drop_boxes.get_mut(&alias) {
+            drop_box.uploads.retain(|upload| upload.file_id != file_id);
+        }
+    }
+}
+
+/// Freezes or unfreezes all uploads.
+pub fn set_uploads_frozen(
+    state: &mut State,
+    caller: Principal,
+    frozen: bool,
+) -> Result<(), AdminError> {
+    check_admin(state, caller)?;
+    if state.uploads_frozen != frozen {
+        state.uploads_frozen = frozen;
+        let action = if frozen {
+            AdminAction::UploadsFrozen
+        } else {
+            AdminAction::UploadsUnfrozen
+        };
+        record_admin_action(state, caller, action);
+    }
+    Ok(())
+}
+
+/// Replaces the settings of the canister, such as quotas and TTLs.
+///
+/// New quotas only apply to new uploads: files that are already stored are kept.
+pub fn set_config(state: &mut State, caller: Principal, config: Config) -> Result<(), AdminError> {
+    check_admin(state, caller)?;
+    validate_config(&config).map_err(AdminError::InvalidConfig)?;
+    state.config = config.clone();
+    record_admin_action(state, caller, AdminAction::ConfigUpdated { config });
+    Ok(())
+}
+
+/// Returns a page of the admin log starting at the given index. Only admins can read it.
+///
+/// At most `MAX_AUDIT_LOG_PAGE_SIZE` entries are returned.
+pub fn get_admin_log(
+    state: &State,
+    caller: Principal,
+    start: u64,
+    limit: u64,
+) -> Result<AdminLogPage, AdminError> {
+    check_admin(state, caller)?;
+
+    let total = state.admin_log.len();
+    let end = start
+        .saturating_add(limit.min(MAX_AUDIT_LOG_PAGE_SIZE))
+        .min(total);
+
+    Ok(AdminLogPage {
+        entries: (start..end)
+            .filter_map(|idx| state.admin_log.get(idx))
+            .collect(),
+        total,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            create_download_token, create_organization, request_file, set_organization_member,
+            set_user_info, share_file, upload_file, upload_file_version, UploadFileVersionRequest,
+        },
+        ChunkKey, ConfigError, Role, User,
+    };
+
+    fn admin() -> Principal {
+        Principal::from_slice(&[0, 1, 0])
+    }
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn recipient() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn setup() -> State {
+        let mut state = State::default();
+        state.admins.insert(admin());
+        for principal in [owner(), recipient()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+
+        // The owner uploads a file with two versions and shares it.
+        request_file(owner(), "request", &mut state);
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        upload_file_version(
+            owner(),
+            UploadFileVersionRequest {
+                file_id: 0,
+                content: vec![4, 5],
+                owner_key: vec![1, 2, 3],
+                file_type: "jpeg".to_string(),
+                num_chunks: 1,
+            },
+            &mut state,
+        )
+        .unwrap();
+        share_file(&mut state, owner(), recipient(), 0, vec![4]);
+        state
+    }
+
+    fn actions(state: &State) -> Vec<AdminAction> {
+        get_admin_log(state, admin(), 0, MAX_AUDIT_LOG_PAGE_SIZE)
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|entry| entry.action)
+            .collect()
+    }
+
+    #[test]
+    fn only_admins_moderate() {
+        let mut state = setup();
+
+        assert_eq!(
+            remove_file(&mut state, owner(), 0),
+            Err(AdminError::PermissionError)
+        );
+        assert_eq!(
+            remove_user(&mut state, owner(), recipient()),
+            Err(AdminError::PermissionError)
+        );
+        assert_eq!(
+            set_uploads_frozen(&mut state, owner(), true),
+            Err(AdminError::PermissionError)
+        );
+        assert_eq!(
+            get_storage_usage(&state, owner()),
+            Err(AdminError::PermissionError)
+        );
+        assert_eq!(
+            get_admin_log(&state, owner(), 0, 10),
+            Err(AdminError::PermissionError)
+        );
+        assert!(actions(&state).is_empty());
+    }
+
+    #[test]
+    fn removing_a_file_removes_its_contents_and_shares() {
+        let mut state = setup();
+        assert_eq!(state.stored_bytes, 5);
+
+        assert_eq!(remove_file(&mut state, admin(), 0), Ok(()));
+
+        assert!(state.file_data.is_empty());
+        assert_eq!(state.file_contents.get(&ChunkKey(0, 0, 0)), None);
+        assert_eq!(state.file_contents.get(&ChunkKey(0, 1, 0)), None);
+        assert_eq!(state.file_owners[&owner()], Vec::<u64>::new());
+        assert_eq!(state.file_shares[&recipient()], Vec::<u64>::new());
+        assert!(state.share_grants.is_empty());
+        assert_eq!(state.stored_bytes, 0);
+        assert_eq!(state.usage[&owner()].file_count, 0);
+
+        assert_eq!(
+            remove_file(&mut state, admin(), 0),
+            Err(AdminError::NotFoundFile)
+        );
+        assert_eq!(
+            actions(&state),
+            vec![AdminAction::FileRemoved {
+                file_id: 0,
+                owner: owner()
+            }]
+        );
+    }
+
+    #[test]
+    fn removing_a_pending_file_keeps_the_usage() {
+        let mut state = setup();
+        request_file(owner(), "request", &mut state);
+
+        // The pending file isn't accounted to the owner, unlike their uploaded file.
+        assert_eq!(remove_file(&mut state, admin(), 1), Ok(()));
+        assert_eq!(state.usage[&owner()].file_count, 1);
+        assert_eq!(state.stored_bytes, 5);
+    }
+
+    #[test]
+    fn removing_a_user_removes_their_files() {
+        let mut state = setup();
+        request_file(owner(), "pending request", &mut state);
+        create_download_token(&mut state, recipient(), 0, "token".to_string()).unwrap();
+
+        assert_eq!(remove_user(&mut state, admin(), owner()), Ok(()));
+
+        assert!(!state.users.contains_key(&owner()));
+        assert!(state.file_data.is_empty());
+        assert!(state.file_alias_index.is_empty());
+        assert_eq!(state.file_shares[&recipient()], Vec::<u64>::new());
+        assert!(state.download_tokens.is_empty());
+        assert_eq!(state.stored_bytes, 0);
+
+        assert_eq!(
+            remove_user(&mut state, admin(), owner()),
+            Err(AdminError::UnknownUser)
+        );
+        assert_eq!(
+            actions(&state),
+            vec![AdminAction::UserRemoved { user: owner() }]
+        );
+    }
+
+    #[test]
+    fn the_last_admin_is_kept() {
+        let mut state = setup();
+
+        assert_eq!(
+            remove_admin(&mut state, admin(), admin()),
+            Err(AdminError::LastAdmin)
+        );
+        assert_eq!(add_admin(&mut state, admin(), owner()), Ok(()));
+        assert_eq!(remove_admin(&mut state, owner(), admin()), Ok(()));
+        assert_eq!(
+            remove_user(&mut state, owner(), owner()),
+            Err(AdminError::LastAdmin)
+        );
+
+        assert_eq!(
+            get_admin_log(&state, owner(), 0, 10)
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|entry| (entry.caller, entry.action))
+                .collect::<Vec<_>>(),
+            vec![
+                (admin(), AdminAction::AdminAdded { user: owner() }),
+                (owner(), AdminAction::AdminRemoved { user: admin() }),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_last_admin_of_an_organization_is_kept() {
+        let mut state = setup();
+        let org_id = create_organization(&mut state, owner(), "org").unwrap();
+
+        assert_eq!(
+            remove_user(&mut state, admin(), owner()),
+            Err(AdminError::LastOrganizationAdmin)
+        );
+        assert!(state.users.contains_key(&owner()));
+        assert_eq!(actions(&state), vec![]);
+
+        // Once the organization has another admin, the user can be removed.
+        set_organization_member(&mut state, owner(), org_id, recipient(), Role::Admin).unwrap();
+        assert_eq!(remove_user(&mut state, admin(), owner()), Ok(()));
+        assert_eq!(
+            state.organizations[&org_id]
+                .members
+                .keys()
+                .collect::<Vec<_>>(),
+            vec![&recipient()]
+        );
+    }
+
+    #[test]
+    fn uploads_can_be_frozen() {
+        let mut state = setup();
+
+        assert_eq!(check_uploads_allowed(&state), Ok(()));
+        assert_eq!(set_uploads_frozen(&mut state, admin(), true), Ok(()));
+        assert_eq!(check_uploads_allowed(&state), Err(UploadsFrozenError));
+        assert_eq!(set_uploads_frozen(&mut state, admin(), false), Ok(()));
+        assert_eq!(check_uploads_allowed(&state), Ok(()));
+
+        assert_eq!(
+            actions(&state),
+            vec![AdminAction::UploadsFrozen, AdminAction::UploadsUnfrozen]
+        );
+    }
+
+    #[test]
+    fn config_can_be_rotated() {
+        let mut state = setup();
+        let config = Config {
+            max_user_files: 1,
+            ..Config::default()
+        };
+
+        assert_eq!(set_config(&mut state, admin(), config.clone()), Ok(()));
+        assert_eq!(state.config, config);
+        assert_eq!(
+            get_storage_usage(&state, admin()).unwrap().users[0].max_files,
+            1
+        );
+        assert_eq!(actions(&state), vec![AdminAction::ConfigUpdated { config }]);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let mut state = setup();
+        let config = Config {
+            max_file_versions: 0,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            set_config(&mut state, admin(), config),
+            Err(AdminError::InvalidConfig(ConfigError::ZeroFileVersions))
+        );
+        assert_eq!(state.config, Config::default());
+        assert_eq!(actions(&state), vec![]);
+    }
+}
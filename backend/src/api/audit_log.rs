@@ -0,0 +1,277 @@
+use crate::{
+    AuditEntry, AuditEvent, AuditLogError, AuditLogPage, FileAuditLogPage, FileContent, State,
+    MAX_AUDIT_LOG_PAGE_SIZE, MAX_AUDIT_LOG_SCAN,
+};
+use ic_cdk::export::candid::Principal;
+
+use super::download_file::is_file_owned_by_me;
+
+/// Appends an event to the audit log.
+pub(crate) fn record_event(state: &mut State, caller: Principal, event: AuditEvent) {
+    state
+        .audit_log
+        .append(&AuditEntry {
+            timestamp: state.env.now(),
+            caller,
+            event,
+        })
+        .expect("failed to append to the audit log");
+}
+
+/// Records the upload of a chunk, and the completion of the upload if it was the last chunk.
+pub fn record_upload(state: &mut State, caller: Principal, file_id: u64, chunk_id: u64) {
+    record_event(
+        state,
+        caller,
+        AuditEvent::ChunkUploaded { file_id, chunk_id },
+    );
+
+    let is_complete = state.file_data.get(&file_id).map_or(false, |file| {
+        matches!(file.content, FileContent::Uploaded { .. })
+    });
+    if is_complete {
+        record_event(state, caller, AuditEvent::FileUploaded { file_id });
+    }
+}
+
+/// Returns a page of the audit log entries of a file, oldest first, reading the log from the
+/// given index. Only the owner can read them.
+///
+/// At most `MAX_AUDIT_LOG_PAGE_SIZE` entries are returned, and at most `MAX_AUDIT_LOG_SCAN`
+/// entries of the log are read, so a page can have fewer entries than requested even if the
+/// end of the log wasn't reached.
+pub fn get_file_audit_log(
+    state: &State,
+    caller: Principal,
+    file_id: u64,
+    start: u64,
+    limit: u64,
+) -> Result<FileAuditLogPage, AuditLogError> {
+    if !state.file_data.contains_key(&file_id) {
+        return Err(AuditLogError::NotFoundFile);
+    }
+    if !is_file_owned_by_me(state, file_id, caller) {
+        return Err(AuditLogError::PermissionError);
+    }
+
+    let total = state.audit_log.len();
+    let limit = limit.min(MAX_AUDIT_LOG_PAGE_SIZE) as usize;
+    let end = start.saturating_add(MAX_AUDIT_LOG_SCAN).min(total);
+    let mut entries = vec![];
+    let mut idx = start;
+    while idx < end && entries.len() < limit {
+        if let Some(entry) = state.audit_log.get(idx) {
+            if entry.event.file_id() == file_id {
+                entries.push(entry);
+            }
+        }
+        idx += 1;
+    }
+
+    Ok(FileAuditLogPage {
+        entries,
+        next: (idx < total).then_some(idx),
+    })
+}
+
+/// Returns a page of the whole audit log starting at the given index. Only admins can read it.
+///
+/// At most `MAX_AUDIT_LOG_PAGE_SIZE` entries are returned.
+pub fn get_audit_log(
+    state: &State,
+    caller: Principal,
+    start: u64,
+    limit: u64,
+) -> Result<AuditLogPage, AuditLogError> {
+    if !state.admins.contains(&caller) {
+        return Err(AuditLogError::PermissionError);
+    }
+
+    let total = state.audit_log.len();
+    let end = start
+        .saturating_add(limit.min(MAX_AUDIT_LOG_PAGE_SIZE))
+        .min(total);
+
+    Ok(AuditLogPage {
+        entries: (start..end)
+            .filter_map(|idx| state.audit_log.get(idx))
+            .collect(),
+        total,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            download_file_audited, request_file, revoke_share, set_user_info, share_file,
+            upload_file,
+        },
+        FileSharingResponse, SharePermission, User,
+    };
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn recipient() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn setup() -> State {
+        let mut state = State::default();
+        for principal in [owner(), recipient()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+
+        request_file(owner(), "request", &mut state);
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        record_upload(&mut state, Principal::anonymous(), 0, 0);
+        state
+    }
+
+    #[test]
+    fn file_accesses_and_mutations_are_recorded() {
+        let mut state = setup();
+
+        share_file(&mut state, owner(), recipient(), 0, vec![4]);
+        download_file_audited(&mut state, 0, 0, recipient());
+        revoke_share(&mut state, owner(), recipient(), 0);
+        // Failed revocations and downloads aren't recorded.
+        assert_eq!(
+            revoke_share(&mut state, owner(), recipient(), 0),
+            FileSharingResponse::PermissionError
+        );
+        download_file_audited(&mut state, 0, 0, recipient());
+
+        let page = get_file_audit_log(&state, owner(), 0, 0, 100).unwrap();
+        assert_eq!(page.next, None);
+        let events: Vec<(Principal, AuditEvent)> = page
+            .entries
+            .into_iter()
+            .map(|entry| (entry.caller, entry.event))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (owner(), AuditEvent::FileRequested { file_id: 0 }),
+                (
+                    Principal::anonymous(),
+                    AuditEvent::ChunkUploaded {
+                        file_id: 0,
+                        chunk_id: 0
+                    }
+                ),
+                (
+                    Principal::anonymous(),
+                    AuditEvent::FileUploaded { file_id: 0 }
+                ),
+                (
+                    owner(),
+                    AuditEvent::FileShared {
+                        file_id: 0,
+                        user: recipient(),
+                        permission: SharePermission::View
+                    }
+                ),
+                (
+                    recipient(),
+                    AuditEvent::ChunkDownloaded {
+                        file_id: 0,
+                        chunk_id: 0
+                    }
+                ),
+                (
+                    owner(),
+                    AuditEvent::ShareRevoked {
+                        file_id: 0,
+                        user: recipient()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_owners_read_the_file_log() {
+        let state = setup();
+
+        assert_eq!(
+            get_file_audit_log(&state, recipient(), 0, 0, 100),
+            Err(AuditLogError::PermissionError)
+        );
+        assert_eq!(
+            get_file_audit_log(&state, owner(), 1, 0, 100),
+            Err(AuditLogError::NotFoundFile)
+        );
+    }
+
+    #[test]
+    fn the_file_log_is_read_in_pages() {
+        let mut state = setup();
+        request_file(recipient(), "another request", &mut state);
+        share_file(&mut state, owner(), recipient(), 0, vec![4]);
+
+        // The entries of the other file are skipped.
+        let page = get_file_audit_log(&state, owner(), 0, 0, 2).unwrap();
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.next, Some(2));
+        let page = get_file_audit_log(&state, owner(), 0, 2, 2).unwrap();
+        assert_eq!(
+            page.entries
+                .into_iter()
+                .map(|entry| entry.event)
+                .collect::<Vec<_>>(),
+            vec![
+                AuditEvent::FileUploaded { file_id: 0 },
+                AuditEvent::FileShared {
+                    file_id: 0,
+                    user: recipient(),
+                    permission: SharePermission::View
+                },
+            ]
+        );
+        assert_eq!(page.next, None);
+    }
+
+    #[test]
+    fn only_admins_read_the_whole_log() {
+        let mut state = setup();
+        request_file(recipient(), "another request", &mut state);
+
+        assert_eq!(
+            get_audit_log(&state, owner(), 0, 10),
+            Err(AuditLogError::PermissionError)
+        );
+
+        state.admins.insert(owner());
+        let page = get_audit_log(&state, owner(), 2, 10).unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(
+            page.entries
+                .into_iter()
+                .map(|entry| entry.event)
+                .collect::<Vec<_>>(),
+            vec![
+                AuditEvent::FileUploaded { file_id: 0 },
+                AuditEvent::FileRequested { file_id: 1 },
+            ]
+        );
+    }
+}
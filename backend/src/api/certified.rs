@@ -0,0 +1,250 @@
+use crate::{
+    certification::{
+        alias_path, chunk_path, data_certificate, encode_hash_tree, file_metadata_path, user_path,
+        Path,
+    },
+    AliasInfo, Certified, FileDownloadResponse, GetAliasInfoError, PublicFileMetadata, State,
+};
+use ic_cdk::export::candid::Principal;
+
+use super::download_file::{download_file, served_version};
+use super::get_alias_info::get_alias_info;
+use super::get_requests::get_requests;
+
+/// Same as `download_file`, along with a witness of the metadata of the file and of the hash of
+/// the chunk.
+pub fn download_file_certified(
+    state: &State,
+    file_id: u64,
+    chunk_id: u64,
+    caller: Principal,
+) -> Certified<FileDownloadResponse> {
+    let data = download_file(state, file_id, chunk_id, caller);
+    let paths = match data {
+        FileDownloadResponse::FoundFile(_) => vec![
+            file_metadata_path(file_id),
+            chunk_path(file_id, served_version(state, file_id, caller), chunk_id),
+        ],
+        _ => vec![],
+    };
+    certify(state, data, &paths)
+}
+
+/// Same as `get_alias_info`, along with a witness of the files requested through the alias,
+/// their metadata, and the public key of the requester.
+pub fn get_alias_info_certified(
+    state: &State,
+    alias: String,
+) -> Certified<Result<AliasInfo, GetAliasInfoError>> {
+    let data = get_alias_info(state, alias.clone());
+    let paths = match &data {
+        Ok(info) => [alias_path(&alias), user_path(info.user.ic_principal)]
+            .into_iter()
+            .chain(
+                info.items
+                    .iter()
+                    .map(|item| file_metadata_path(item.file_id)),
+            )
+            .collect(),
+        Err(_) => vec![],
+    };
+    certify(state, data, &paths)
+}
+
+/// Same as `get_requests`, along with a witness of the metadata of the files and of the users
+/// they are shared with.
+pub fn get_requests_certified(
+    state: &State,
+    caller: Principal,
+) -> Certified<Vec<PublicFileMetadata>> {
+    let data = get_requests(state, caller);
+    let paths: Vec<Path> = data
+        .iter()
+        .flat_map(|file| {
+            std::iter::once(file_metadata_path(file.file_id)).chain(
+                file.shared_with
+                    .iter()
+                    .map(|user| user_path(user.ic_principal)),
+            )
+        })
+        .collect();
+    certify(state, data, &paths)
+}
+
+fn certify<T>(state: &State, data: T, paths: &[Path]) -> Certified<T> {
+    Certified {
+        data,
+        witness: encode_hash_tree(&state.certified_data.witness(paths)),
+        certificate: data_certificate(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            remove_file, request_file, set_user_info, share_file, upload_file, upload_file_version,
+            UploadFileVersionRequest,
+        },
+        certification, User,
+    };
+    use sha2::{Digest, Sha256};
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn recipient() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn setup() -> (State, String) {
+        let mut state = State::default();
+        for principal in [owner(), recipient()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: principal.as_slice().to_vec(),
+                },
+            );
+        }
+        let alias = request_file(owner(), "request", &mut state);
+        (state, alias)
+    }
+
+    fn lookup(state: &State, path: &Path) -> Option<Vec<u8>> {
+        let labels: Vec<&[u8]> = path.iter().map(|label| label.as_slice()).collect();
+        let witness = state.certified_data.witness(std::slice::from_ref(path));
+        certification::lookup(&witness, &labels).map(|leaf| leaf.to_vec())
+    }
+
+    fn metadata(state: &State, file_id: u64, field: &str) -> Option<Vec<u8>> {
+        let mut path = file_metadata_path(file_id);
+        path.push(field.as_bytes().to_vec());
+        lookup(state, &path)
+    }
+
+    #[test]
+    fn alias_info_is_certified() {
+        let (state, alias) = setup();
+
+        let certified = get_alias_info_certified(&state, alias.clone());
+        assert!(certified.data.is_ok());
+        assert_eq!(
+            certified.witness,
+            encode_hash_tree(&state.certified_data.witness(&[
+                alias_path(&alias),
+                user_path(owner()),
+                file_metadata_path(0),
+            ]))
+        );
+        assert_eq!(
+            lookup(&state, &alias_path(&alias)),
+            Some(0u64.to_be_bytes().to_vec())
+        );
+        let mut public_key = user_path(owner());
+        public_key.push(b"public_key".to_vec());
+        assert_eq!(
+            lookup(&state, &public_key),
+            Some(owner().as_slice().to_vec())
+        );
+        assert_eq!(metadata(&state, 0, "status"), Some(b"pending".to_vec()));
+        assert_eq!(metadata(&state, 0, "name"), Some(b"request".to_vec()));
+    }
+
+    #[test]
+    fn downloads_are_certified_across_versions() {
+        let (mut state, alias) = setup();
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "image/jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+
+        // The alias is no longer pending once the file is uploaded.
+        assert_eq!(lookup(&state, &alias_path(&alias)), None);
+        assert_eq!(metadata(&state, 0, "status"), Some(b"uploaded".to_vec()));
+
+        share_file(&mut state, owner(), recipient(), 0, vec![4]);
+        upload_file_version(
+            owner(),
+            UploadFileVersionRequest {
+                file_id: 0,
+                content: vec![4, 5, 6],
+                owner_key: vec![1, 2, 3],
+                file_type: "image/jpeg".to_string(),
+                num_chunks: 1,
+            },
+            &mut state,
+        )
+        .unwrap();
+        assert_eq!(metadata(&state, 0, "version"), Some(vec![1]));
+
+        // The owner gets the new version, whereas the recipient can only decrypt the first one.
+        for (user, version, chunk) in [(owner(), 1, [4, 5, 6]), (recipient(), 0, [1, 2, 3])] {
+            let certified = download_file_certified(&state, 0, 0, user);
+            assert!(matches!(certified.data, FileDownloadResponse::FoundFile(_)));
+            assert_eq!(
+                certified.witness,
+                encode_hash_tree(
+                    &state
+                        .certified_data
+                        .witness(&[file_metadata_path(0), chunk_path(0, version, 0)])
+                )
+            );
+            assert_eq!(
+                lookup(&state, &chunk_path(0, version, 0)),
+                Some(Sha256::digest(chunk).to_vec())
+            );
+        }
+
+        let certified = get_requests_certified(&state, owner());
+        assert_eq!(
+            certified.witness,
+            encode_hash_tree(
+                &state
+                    .certified_data
+                    .witness(&[file_metadata_path(0), user_path(recipient())])
+            )
+        );
+    }
+
+    #[test]
+    fn removed_files_are_no_longer_certified() {
+        let (mut state, _) = setup();
+        let empty_root = {
+            let mut state = State::default();
+            for user in [owner(), recipient()] {
+                set_user_info(
+                    &mut state,
+                    user,
+                    User {
+                        username: user.to_text(),
+                        public_key: user.as_slice().to_vec(),
+                    },
+                );
+            }
+            state.certified_data.root_hash()
+        };
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "image/jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        state.admins.insert(owner());
+
+        remove_file(&mut state, owner(), 0).unwrap();
+        assert_eq!(state.certified_data.root_hash(), empty_root);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{AdminAction, Config, ConfigError, InitArgs, State, UpgradeArgs};
+use ic_cdk::export::candid::Principal;
+
+use super::admin::record_admin_action;
+
+/// Sets up the state from the arguments of the canister installation.
+///
+/// The principal installing the canister is one of its controllers, and becomes an admin.
+pub fn init(state: &mut State, installer: Principal, args: InitArgs) -> Result<(), ConfigError> {
+    let config = args.config.unwrap_or_default();
+    validate_config(&config)?;
+    state.admins.insert(installer);
+    state.admins.extend(args.admins.unwrap_or_default());
+    state.config = config;
+    state.rate_limits = args.rate_limits.unwrap_or_default();
+    Ok(())
+}
+
+/// Applies the arguments of a canister upgrade. Changes are recorded in the admin log.
+///
+/// The principal upgrading the canister is one of its controllers, and becomes an admin.
+pub fn upgrade(
+    state: &mut State,
+    upgrader: Principal,
+    args: UpgradeArgs,
+) -> Result<(), ConfigError> {
+    if let Some(config) = &args.config {
+        validate_config(config)?;
+    }
+    let admins = std::iter::once(upgrader).chain(args.admins.unwrap_or_default());
+    for user in admins {
+        if state.admins.insert(user) {
+            record_admin_action(state, upgrader, AdminAction::AdminAdded { user });
+        }
+    }
+    if let Some(config) = args.config {
+        state.config = config.clone();
+        record_admin_action(state, upgrader, AdminAction::ConfigUpdated { config });
+    }
+    if let Some(rate_limits) = args.rate_limits {
+        state.rate_limits = rate_limits;
+        record_admin_action(
+            state,
+            upgrader,
+            AdminAction::RateLimitsUpdated { rate_limits },
+        );
+    }
+    Ok(())
+}
+
+/// Checks that a config keeps the canister usable.
+pub(crate) fn validate_config(config: &Config) -> Result<(), ConfigError> {
+    if config.max_chunk_size == 0 {
+        return Err(ConfigError::ZeroChunkSize);
+    }
+    if config.max_file_size == 0 {
+        return Err(ConfigError::ZeroFileSize);
+    }
+    if config.max_file_versions == 0 {
+        return Err(ConfigError::ZeroFileVersions);
+    }
+    let ttls = [
+        config.request_ttl_ns,
+        config.share_ttl_ns,
+        config.max_drop_box_ttl_ns,
+        Some(config.download_token_ttl_ns),
+    ];
+    if ttls.contains(&Some(0)) {
+        return Err(ConfigError::ZeroTtl);
+    }
+    Ok(())
+}
+
+pub fn get_config(state: &State) -> Config {
+    state.config.clone()
+}
+
+/// Returns true if something created at the given time has outlived its TTL.
+pub(crate) fn has_expired(state: &State, created_at: u64, ttl_ns: Option<u64>) -> bool {
+    ttl_ns.map_or(false, |ttl| {
+        state.env.now() >= created_at.saturating_add(ttl)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            get_alias_info, get_shared_files, request_file, set_user_info, share_file, upload_file,
+            upload_file_atomic, upload_file_version, UploadFileAtomicRequest,
+            UploadFileVersionRequest,
+        },
+        env::TestEnvironment,
+        GetAliasInfoError, RateLimits, UploadFileError, User,
+    };
+
+    fn admin() -> Principal {
+        Principal::from_slice(&[0, 1, 0])
+    }
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn recipient() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn setup(config: Config) -> State {
+        let mut state = State::default();
+        init(
+            &mut state,
+            admin(),
+            InitArgs {
+                admins: None,
+                config: Some(config),
+                rate_limits: None,
+            },
+        )
+        .unwrap();
+        for principal in [owner(), recipient()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+        state
+    }
+
+    #[test]
+    fn init_and_upgrade_args_set_the_config() {
+        let config = Config {
+            max_user_files: 1,
+            ..Config::default()
+        };
+        let mut state = setup(config.clone());
+        assert_eq!(get_config(&state), config);
+        assert_eq!(state.admins.iter().collect::<Vec<_>>(), vec![&admin()]);
+
+        // Settings that aren't given at upgrade are kept.
+        upgrade(
+            &mut state,
+            admin(),
+            UpgradeArgs {
+                admins: Some(vec![owner()]),
+                ..UpgradeArgs::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(get_config(&state), config);
+        assert!(state.admins.contains(&owner()));
+
+        upgrade(
+            &mut state,
+            admin(),
+            UpgradeArgs {
+                config: Some(Config::default()),
+                rate_limits: Some(RateLimits::default()),
+                ..UpgradeArgs::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(get_config(&state), Config::default());
+        assert_eq!(state.admin_log.len(), 3);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let cases = [
+            (
+                Config {
+                    max_chunk_size: 0,
+                    ..Config::default()
+                },
+                ConfigError::ZeroChunkSize,
+            ),
+            (
+                Config {
+                    max_file_size: 0,
+                    ..Config::default()
+                },
+                ConfigError::ZeroFileSize,
+            ),
+            (
+                Config {
+                    max_file_versions: 0,
+                    ..Config::default()
+                },
+                ConfigError::ZeroFileVersions,
+            ),
+            (
+                Config {
+                    share_ttl_ns: Some(0),
+                    ..Config::default()
+                },
+                ConfigError::ZeroTtl,
+            ),
+            (
+                Config {
+                    download_token_ttl_ns: 0,
+                    ..Config::default()
+                },
+                ConfigError::ZeroTtl,
+            ),
+        ];
+
+        for (config, err) in cases {
+            let mut state = State::default();
+            assert_eq!(
+                init(
+                    &mut state,
+                    admin(),
+                    InitArgs {
+                        admins: None,
+                        config: Some(config.clone()),
+                        rate_limits: None,
+                    },
+                ),
+                Err(err)
+            );
+            assert!(state.admins.is_empty());
+
+            // Invalid configs given at upgrade leave the state untouched.
+            let mut state = setup(Config::default());
+            assert_eq!(
+                upgrade(
+                    &mut state,
+                    admin(),
+                    UpgradeArgs {
+                        admins: Some(vec![owner()]),
+                        config: Some(config),
+                        ..UpgradeArgs::default()
+                    },
+                ),
+                Err(err)
+            );
+            assert_eq!(get_config(&state), Config::default());
+            assert!(!state.admins.contains(&owner()));
+            assert_eq!(state.admin_log.len(), 0);
+        }
+    }
+
+    #[test]
+    fn expired_requests_cant_be_uploaded() {
+        let mut state = setup(Config {
+            request_ttl_ns: Some(100),
+            ..Config::default()
+        });
+        let env = TestEnvironment::default();
+        state.env = Box::new(env.clone());
+        let alias = request_file(owner(), "request", &mut state);
+
+        env.advance_time(99);
+        assert!(get_alias_info(&state, alias.clone()).is_ok());
+        env.advance_time(1);
+        assert_eq!(
+            get_alias_info(&state, alias),
+            Err(GetAliasInfoError::NotFound)
+        );
+        assert_eq!(
+            upload_file(
+                0,
+                vec![1, 2, 3],
+                "jpeg".to_string(),
+                vec![1, 2, 3],
+                1,
+                &mut state
+            ),
+            Err(UploadFileError::NotRequested)
+        );
+    }
+
+    #[test]
+    fn expired_shares_give_no_access() {
+        let mut state = setup(Config {
+            share_ttl_ns: Some(100),
+            ..Config::default()
+        });
+        request_file(owner(), "request", &mut state);
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        share_file(&mut state, owner(), recipient(), 0, vec![4]);
+        assert_eq!(get_shared_files(&state, recipient()).len(), 1);
+
+        state
+            .share_grants
+            .get_mut(&0)
+            .unwrap()
+            .get_mut(&recipient())
+            .unwrap()
+            .granted_at = 0;
+        assert!(get_shared_files(&state, recipient()).is_empty());
+
+        // Sharing again renews the share.
+        share_file(&mut state, owner(), recipient(), 0, vec![4]);
+        assert_eq!(get_shared_files(&state, recipient()).len(), 1);
+    }
+
+    #[test]
+    fn configured_number_of_versions_is_kept() {
+        let mut state = setup(Config {
+            max_file_versions: 2,
+            ..Config::default()
+        });
+        upload_file_atomic(
+            owner(),
+            UploadFileAtomicRequest {
+                name: "file".to_string(),
+                content: vec![1],
+                owner_key: vec![1, 2, 3],
+                file_type: "image/jpeg".to_string(),
+                num_chunks: 1,
+            },
+            &mut state,
+        )
+        .unwrap();
+
+        for _ in 0..3 {
+            upload_file_version(
+                owner(),
+                UploadFileVersionRequest {
+                    file_id: 0,
+                    content: vec![1],
+                    owner_key: vec![1, 2, 3],
+                    file_type: "image/jpeg".to_string(),
+                    num_chunks: 1,
+                },
+                &mut state,
+            )
+            .unwrap();
+        }
+
+        let file = &state.file_data[&0];
+        assert_eq!(file.version, 3);
+        assert_eq!(file.versions.len(), 1);
+    }
+}
@@ -1,21 +1,34 @@
 pub use crate::ceil_division;
-use crate::{FileContent, FileData, FileDownloadResponse, State};
+use crate::{
+    AuditEvent, ChunkKey, FileContent, FileData, FileDownloadResponse, FileVersion, State,
+};
 use ic_cdk::export::candid::Principal;
 
+use super::audit_log::record_event;
+use super::config::has_expired;
+use super::share_file::remove_share;
+
 fn get_file_data(s: &State, file_id: u64, chunk_id: u64) -> FileDownloadResponse {
     // unwrap is safe because we already know the file exists
     let this_file = s.file_data.get(&file_id).unwrap();
     match &this_file.content {
-        FileContent::Pending { .. } | FileContent::PartiallyUploaded { .. } => {
-            FileDownloadResponse::NotUploadedFile
-        }
+        FileContent::Pending { .. } => FileDownloadResponse::NotUploadedFile,
+        // A new version is being uploaded. Fall back to the latest uploaded version.
+        FileContent::PartiallyUploaded { .. } => match this_file.versions.last() {
+            Some(version) => {
+                get_version_data(s, file_id, version, chunk_id, version.owner_key.clone())
+            }
+            None => FileDownloadResponse::NotUploadedFile,
+        },
         FileContent::Uploaded {
             file_type,
             owner_key,
             shared_keys: _,
             num_chunks,
         } => FileDownloadResponse::FoundFile(FileData {
-            contents: s.file_contents.get(&(file_id, chunk_id)).unwrap(),
+            contents: s
+                .get_chunk(ChunkKey(file_id, this_file.version, chunk_id))
+                .unwrap(),
             file_type: file_type.clone(),
             owner_key: owner_key.clone(),
             num_chunks: *num_chunks,
@@ -32,65 +45,269 @@ fn get_shared_file_data(
     // unwrap is safe because we already know the file exists
     let this_file = s.file_data.get(&file_id).unwrap();
     match &this_file.content {
-        FileContent::Pending { .. } | FileContent::PartiallyUploaded { .. } => {
-            FileDownloadResponse::NotUploadedFile
-        }
         FileContent::Uploaded {
             file_type,
             owner_key: _,
             shared_keys,
             num_chunks,
-        } => FileDownloadResponse::FoundFile(FileData {
-            contents: s.file_contents.get(&(file_id, chunk_id)).unwrap(),
+        } if shared_keys.contains_key(&user) => FileDownloadResponse::FoundFile(FileData {
+            contents: s
+                .get_chunk(ChunkKey(file_id, this_file.version, chunk_id))
+                .unwrap(),
             file_type: file_type.clone(),
             owner_key: shared_keys.get(&user).unwrap().clone(),
             num_chunks: *num_chunks,
         }),
+        content => {
+            // The current version is either not fully uploaded or the owner hasn't shared
+            // its key with the user yet. Fall back to the latest version the user can decrypt.
+            match this_file
+                .versions
+                .iter()
+                .rev()
+                .find(|version| version.shared_keys.contains_key(&user))
+            {
+                Some(version) => get_version_data(
+                    s,
+                    file_id,
+                    version,
+                    chunk_id,
+                    version.shared_keys.get(&user).unwrap().clone(),
+                ),
+                None => match content {
+                    FileContent::Uploaded { .. } => FileDownloadResponse::PermissionError,
+                    _ => FileDownloadResponse::NotUploadedFile,
+                },
+            }
+        }
     }
 }
+
+fn get_version_data(
+    s: &State,
+    file_id: u64,
+    version: &FileVersion,
+    chunk_id: u64,
+    key: Vec<u8>,
+) -> FileDownloadResponse {
+    FileDownloadResponse::FoundFile(FileData {
+        contents: s
+            .get_chunk(ChunkKey(file_id, version.version, chunk_id))
+            .unwrap(),
+        file_type: version.file_type.clone(),
+        owner_key: key,
+        num_chunks: version.num_chunks,
+    })
+}
+
 pub fn download_file(
     s: &State,
     file_id: u64,
     chunk_id: u64,
     caller: Principal,
 ) -> FileDownloadResponse {
-    match s.file_owners.get(&caller) {
-        // This is the case where the files is owned by this user.
-        Some(files) => match files.contains(&file_id) {
-            true => get_file_data(s, file_id, chunk_id),
-            false => {
-                if is_file_shared_with_me(s, file_id, caller) {
-                    get_shared_file_data(s, file_id, chunk_id, caller)
-                } else {
-                    FileDownloadResponse::PermissionError
-                }
+    // Downloads of limited shares are counted, which requires an update call.
+    if is_download_limited(s, file_id, caller) {
+        return FileDownloadResponse::PermissionError;
+    }
+    download_chunk(s, file_id, chunk_id, caller)
+}
+
+/// Downloads a chunk of a file like `download_file` and records the download in the audit log.
+///
+/// Unlike `download_file`, this counts the downloads of shares with a download limit. Such a
+/// download starts with the first chunk and ends with the last chunk, and the share is revoked
+/// once its last download ends.
+pub fn download_file_audited(
+    s: &mut State,
+    file_id: u64,
+    chunk_id: u64,
+    caller: Principal,
+) -> FileDownloadResponse {
+    let downloads_remaining = get_downloads_remaining(s, file_id, caller);
+    let session = (file_id, caller);
+    let is_new_download = chunk_id == 0;
+
+    match downloads_remaining {
+        Some(0) if is_new_download => return FileDownloadResponse::PermissionError,
+        Some(_) if !is_new_download && !s.download_sessions.contains(&session) => {
+            return FileDownloadResponse::PermissionError
+        }
+        _ => {}
+    }
+
+    let response = download_chunk(s, file_id, chunk_id, caller);
+    let num_chunks = match &response {
+        FileDownloadResponse::FoundFile(data) => data.num_chunks,
+        _ => return response,
+    };
+    record_event(s, caller, AuditEvent::ChunkDownloaded { file_id, chunk_id });
+
+    if let Some(mut downloads_remaining) = downloads_remaining {
+        if is_new_download {
+            downloads_remaining -= 1;
+            let grant = s
+                .share_grants
+                .get_mut(&file_id)
+                .and_then(|grants| grants.get_mut(&caller))
+                .unwrap();
+            grant.downloads_remaining = Some(downloads_remaining);
+            s.download_sessions.insert(session);
+        }
+
+        if chunk_id + 1 >= num_chunks {
+            s.download_sessions.remove(&session);
+            if downloads_remaining == 0 {
+                remove_share(s, caller, file_id);
+                record_event(
+                    s,
+                    caller,
+                    AuditEvent::ShareRevoked {
+                        file_id,
+                        user: caller,
+                    },
+                );
             }
-        },
-        // But it could also be the case that the file is shared with this user.
-        None => {
-            if is_file_shared_with_me(s, file_id, caller) {
+        }
+    }
+
+    response
+}
+
+fn download_chunk(
+    s: &State,
+    file_id: u64,
+    chunk_id: u64,
+    caller: Principal,
+) -> FileDownloadResponse {
+    if is_file_owned_by_me(s, file_id, caller) {
+        get_file_data(s, file_id, chunk_id)
+    } else if can_download_shared(s, file_id, caller) {
+        get_shared_file_data(s, file_id, chunk_id, caller)
+    } else {
+        FileDownloadResponse::PermissionError
+    }
+}
+
+/// Downloads a chunk of a specific version of a file.
+pub fn download_file_version(
+    s: &State,
+    file_id: u64,
+    version: u64,
+    chunk_id: u64,
+    caller: Principal,
+) -> FileDownloadResponse {
+    let is_owner = is_file_owned_by_me(s, file_id, caller);
+    if !is_owner && !can_download_shared(s, file_id, caller) {
+        return FileDownloadResponse::PermissionError;
+    }
+    if is_download_limited(s, file_id, caller) {
+        return FileDownloadResponse::PermissionError;
+    }
+
+    let this_file = match s.file_data.get(&file_id) {
+        Some(file) => file,
+        None => return FileDownloadResponse::NotFoundFile,
+    };
+
+    if version == this_file.version {
+        if is_owner {
+            return get_file_data(s, file_id, chunk_id);
+        }
+        return match &this_file.content {
+            FileContent::Uploaded { shared_keys, .. } if shared_keys.contains_key(&caller) => {
                 get_shared_file_data(s, file_id, chunk_id, caller)
-            } else {
-                FileDownloadResponse::PermissionError
             }
-        }
+            FileContent::Uploaded { .. } => FileDownloadResponse::PermissionError,
+            _ => FileDownloadResponse::NotUploadedFile,
+        };
+    }
+
+    match this_file.versions.iter().find(|v| v.version == version) {
+        None => FileDownloadResponse::NotFoundFile,
+        Some(v) if is_owner => get_version_data(s, file_id, v, chunk_id, v.owner_key.clone()),
+        Some(v) => match v.shared_keys.get(&caller) {
+            Some(key) => get_version_data(s, file_id, v, chunk_id, key.clone()),
+            None => FileDownloadResponse::PermissionError,
+        },
     }
 }
 
-fn is_file_shared_with_me(s: &State, file_id: u64, caller: Principal) -> bool {
-    match s.file_shares.get(&caller) {
+pub(crate) fn is_file_owned_by_me(s: &State, file_id: u64, caller: Principal) -> bool {
+    match s.file_owners.get(&caller) {
         None => false,
         Some(arr) => arr.contains(&file_id),
     }
 }
 
+/// Returns the version of the file that `download_file` serves to the user.
+///
+/// Precondition: the file can be downloaded by the user.
+pub(crate) fn served_version(state: &State, file_id: u64, user: Principal) -> u64 {
+    let file = state.file_data.get(&file_id).unwrap();
+    match &file.content {
+        FileContent::Uploaded { shared_keys, .. }
+            if is_file_owned_by_me(state, file_id, user) || shared_keys.contains_key(&user) =>
+        {
+            file.version
+        }
+        _ => file
+            .versions
+            .iter()
+            .rev()
+            .find(|version| version.shared_keys.contains_key(&user))
+            .map_or(file.version, |version| version.version),
+    }
+}
+
+/// Returns the number of downloads left if the file is shared with the user for a limited
+/// number of downloads.
+fn get_downloads_remaining(s: &State, file_id: u64, user: Principal) -> Option<u64> {
+    if is_file_owned_by_me(s, file_id, user) {
+        return None;
+    }
+    s.share_grants
+        .get(&file_id)
+        .and_then(|grants| grants.get(&user))
+        .and_then(|grant| grant.downloads_remaining)
+}
+
+fn is_download_limited(s: &State, file_id: u64, user: Principal) -> bool {
+    get_downloads_remaining(s, file_id, user).is_some()
+}
+
+/// Returns true if the file is shared with the caller and the share hasn't expired.
+pub(crate) fn is_file_shared_with_me(s: &State, file_id: u64, caller: Principal) -> bool {
+    let is_shared = match s.file_shares.get(&caller) {
+        None => false,
+        Some(arr) => arr.contains(&file_id),
+    };
+    // Shares without a grant predate share TTLs and never expire.
+    is_shared
+        && !s
+            .share_grants
+            .get(&file_id)
+            .and_then(|grants| grants.get(&caller))
+            .map_or(false, |grant| {
+                has_expired(s, grant.granted_at, s.config.share_ttl_ns)
+            })
+}
+
+/// Returns true if the user can download the file with a key that was shared with them.
+///
+/// Members of the organization that owns the file are no exception: they need a share of the
+/// file, as its key is only wrapped for the users it is shared with.
+pub(crate) fn can_download_shared(s: &State, file_id: u64, user: Principal) -> bool {
+    is_file_shared_with_me(s, file_id, user)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::{
-        api::{request_file, share_file},
-        api::{set_user_info, upload_file},
-        User,
+        api::{get_shared_files, request_file, share_file, share_file_with_download_limit},
+        api::{set_user_info, upload_file, upload_file_continue},
+        UploadFileContinueRequest, User,
     };
     use ic_cdk::export::Principal;
 
@@ -262,4 +479,116 @@ mod test {
             })
         )
     }
+
+    fn setup_limited_share(num_chunks: u64, max_downloads: u64) -> State {
+        let mut state = State::default();
+        for principal in [Principal::anonymous(), Principal::from_slice(&[0, 1, 2])] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: "John".to_string(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+
+        request_file(Principal::anonymous(), "request", &mut state);
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            num_chunks,
+            &mut state,
+        )
+        .unwrap();
+        for chunk_id in 1..num_chunks {
+            upload_file_continue(
+                UploadFileContinueRequest {
+                    file_id: 0,
+                    chunk_id,
+                    contents: vec![4, 5, 6],
+                },
+                &mut state,
+            )
+            .unwrap();
+        }
+
+        share_file_with_download_limit(
+            &mut state,
+            Principal::anonymous(),
+            Principal::from_slice(&[0, 1, 2]),
+            0,
+            vec![10, 11, 12],
+            max_downloads,
+        );
+        state
+    }
+
+    #[test]
+    fn one_time_share_is_revoked_after_download() {
+        let mut state = setup_limited_share(1, 1);
+        let recipient = Principal::from_slice(&[0, 1, 2]);
+
+        // Limited shares can't be downloaded with a query.
+        assert_eq!(
+            download_file(&state, 0, 0, recipient),
+            FileDownloadResponse::PermissionError
+        );
+
+        assert_eq!(
+            download_file_audited(&mut state, 0, 0, recipient),
+            FileDownloadResponse::FoundFile(FileData {
+                contents: vec![1, 2, 3],
+                file_type: "jpeg".to_string(),
+                owner_key: vec![10, 11, 12],
+                num_chunks: 1
+            })
+        );
+
+        assert!(get_shared_files(&state, recipient).is_empty());
+        assert_eq!(
+            download_file_audited(&mut state, 0, 0, recipient),
+            FileDownloadResponse::PermissionError
+        );
+    }
+
+    #[test]
+    fn limited_download_spans_all_chunks() {
+        let mut state = setup_limited_share(2, 2);
+        let recipient = Principal::from_slice(&[0, 1, 2]);
+
+        // A download has to start with the first chunk.
+        assert_eq!(
+            download_file_audited(&mut state, 0, 1, recipient),
+            FileDownloadResponse::PermissionError
+        );
+
+        assert!(matches!(
+            download_file_audited(&mut state, 0, 0, recipient),
+            FileDownloadResponse::FoundFile(_)
+        ));
+        assert!(matches!(
+            download_file_audited(&mut state, 0, 1, recipient),
+            FileDownloadResponse::FoundFile(_)
+        ));
+
+        // The download is complete, so the last chunk can't be downloaded again without
+        // starting a new download.
+        assert_eq!(
+            download_file_audited(&mut state, 0, 1, recipient),
+            FileDownloadResponse::PermissionError
+        );
+        assert_eq!(
+            state.share_grants[&0][&recipient].downloads_remaining,
+            Some(1)
+        );
+
+        // The owner isn't limited.
+        assert!(matches!(
+            download_file(&state, 0, 1, Principal::anonymous()),
+            FileDownloadResponse::FoundFile(_)
+        ));
+    }
 }
@@ -0,0 +1,189 @@
+use crate::{DownloadGrant, DownloadToken, DownloadTokenError, FileDownloadResponse, State};
+use ic_cdk::export::candid::Principal;
+
+use super::download_file::{download_file, download_file_version, served_version};
+
+/// Issues a token giving the caller access to the file over HTTP, at the returned URL.
+///
+/// The token is a capability: whoever holds it can download the encrypted file until it
+/// expires, so it must be unguessable. The access of the caller is checked again on every
+/// download. Shares with a download limit can't be downloaded over HTTP, as those downloads
+/// aren't counted.
+pub fn create_download_token(
+    state: &mut State,
+    caller: Principal,
+    file_id: u64,
+    token: String,
+) -> Result<DownloadToken, DownloadTokenError> {
+    let data = match download_file(state, file_id, 0, caller) {
+        FileDownloadResponse::FoundFile(data) => data,
+        FileDownloadResponse::NotFoundFile => return Err(DownloadTokenError::NotFoundFile),
+        FileDownloadResponse::NotUploadedFile => return Err(DownloadTokenError::NotUploadedFile),
+        FileDownloadResponse::PermissionError => return Err(DownloadTokenError::PermissionError),
+    };
+
+    let now = state.env.now();
+    state
+        .download_tokens
+        .retain(|_, grant| grant.expires_at > now);
+
+    let expires_at = now.saturating_add(state.config.download_token_ttl_ns);
+    // The version is pinned, so that a download isn't mixed with a newer version.
+    let grant = DownloadGrant {
+        file_id,
+        version: served_version(state, file_id, caller),
+        user: caller,
+        expires_at,
+    };
+    state.download_tokens.insert(token.clone(), grant);
+
+    Ok(DownloadToken {
+        url: format!("/files/{file_id}?token={token}"),
+        token,
+        expires_at,
+        file_type: data.file_type,
+        owner_key: data.owner_key,
+        num_chunks: data.num_chunks,
+    })
+}
+
+/// Returns a chunk of the file the token gives access to, along with the number of chunks.
+pub(crate) fn download_chunk_with_token(
+    state: &State,
+    token: &str,
+    file_id: u64,
+    chunk_id: u64,
+) -> Result<(Vec<u8>, u64), DownloadTokenError> {
+    let grant = match state.download_tokens.get(token) {
+        Some(grant) if grant.file_id == file_id && grant.expires_at > state.env.now() => grant,
+        _ => return Err(DownloadTokenError::PermissionError),
+    };
+
+    match download_file_version(state, file_id, grant.version, chunk_id, grant.user) {
+        FileDownloadResponse::FoundFile(data) => Ok((data.contents, data.num_chunks)),
+        FileDownloadResponse::NotFoundFile => Err(DownloadTokenError::NotFoundFile),
+        FileDownloadResponse::NotUploadedFile => Err(DownloadTokenError::NotUploadedFile),
+        FileDownloadResponse::PermissionError => Err(DownloadTokenError::PermissionError),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            revoke_share, set_user_info, share_file, share_file_with_download_limit,
+            upload_file_atomic, UploadFileAtomicRequest,
+        },
+        env::TestEnvironment,
+        User,
+    };
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn recipient() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn setup() -> State {
+        let mut state = State::default();
+        for principal in [owner(), recipient()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+        upload_file_atomic(
+            owner(),
+            UploadFileAtomicRequest {
+                name: "file".to_string(),
+                content: vec![1, 2, 3],
+                owner_key: vec![4, 5, 6],
+                file_type: "image/jpeg".to_string(),
+                num_chunks: 1,
+            },
+            &mut state,
+        )
+        .unwrap();
+        state
+    }
+
+    #[test]
+    fn token_gives_access_until_it_expires() {
+        let mut state = setup();
+        let env = TestEnvironment::default();
+        state.env = Box::new(env.clone());
+        let token = create_download_token(&mut state, owner(), 0, "token".to_string()).unwrap();
+        assert_eq!(
+            token,
+            DownloadToken {
+                token: "token".to_string(),
+                url: "/files/0?token=token".to_string(),
+                expires_at: state.env.now() + state.config.download_token_ttl_ns,
+                file_type: "image/jpeg".to_string(),
+                owner_key: vec![4, 5, 6],
+                num_chunks: 1,
+            }
+        );
+        assert_eq!(
+            download_chunk_with_token(&state, "token", 0, 0),
+            Ok((vec![1, 2, 3], 1))
+        );
+
+        // The token only gives access to its file.
+        assert_eq!(
+            download_chunk_with_token(&state, "token", 1, 0),
+            Err(DownloadTokenError::PermissionError)
+        );
+        assert_eq!(
+            download_chunk_with_token(&state, "other", 0, 0),
+            Err(DownloadTokenError::PermissionError)
+        );
+
+        env.advance_time(state.config.download_token_ttl_ns);
+        assert_eq!(
+            download_chunk_with_token(&state, "token", 0, 0),
+            Err(DownloadTokenError::PermissionError)
+        );
+
+        // Expired tokens are dropped when new ones are created.
+        create_download_token(&mut state, owner(), 0, "new token".to_string()).unwrap();
+        assert_eq!(
+            state.download_tokens.keys().collect::<Vec<_>>(),
+            vec!["new token"]
+        );
+    }
+
+    #[test]
+    fn token_follows_the_access_of_the_user() {
+        let mut state = setup();
+        assert_eq!(
+            create_download_token(&mut state, recipient(), 0, "token".to_string()),
+            Err(DownloadTokenError::PermissionError)
+        );
+
+        share_file(&mut state, owner(), recipient(), 0, vec![7, 8, 9]);
+        let token = create_download_token(&mut state, recipient(), 0, "token".to_string()).unwrap();
+        assert_eq!(token.owner_key, vec![7, 8, 9]);
+        assert!(download_chunk_with_token(&state, "token", 0, 0).is_ok());
+
+        revoke_share(&mut state, owner(), recipient(), 0);
+        assert_eq!(
+            download_chunk_with_token(&state, "token", 0, 0),
+            Err(DownloadTokenError::PermissionError)
+        );
+
+        // Downloads of limited shares are counted, which HTTP downloads can't do.
+        share_file_with_download_limit(&mut state, owner(), recipient(), 0, vec![7, 8, 9], 1);
+        assert_eq!(
+            create_download_token(&mut state, recipient(), 0, "token".to_string()),
+            Err(DownloadTokenError::PermissionError)
+        );
+    }
+}
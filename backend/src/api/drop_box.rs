@@ -0,0 +1,421 @@
+use crate::{
+    certification::certify_file, ChunkKey, CreateDropBoxRequest, DropBox, DropBoxError,
+    DropBoxInfo, DropBoxUpload, File, FileContent, FileMetadata, PublicDropBox, PublicUser, State,
+    UploadToDropBoxRequest,
+};
+use ic_cdk::export::Principal;
+use std::collections::BTreeMap;
+
+use super::notifications::notify_upload_completed;
+use super::quotas::{add_usage, check_quota, upload_size};
+use super::user_info::get_user_key;
+
+/// Creates a drop box and returns its alias.
+///
+/// The expiry is capped by the maximum lifetime of drop boxes, if one is configured.
+pub fn create_drop_box(
+    caller: Principal,
+    request: CreateDropBoxRequest,
+    state: &mut State,
+) -> String {
+    let alias = state.generate_alias();
+    let now = state.env.now();
+    let expires_at = match state.config.max_drop_box_ttl_ns {
+        Some(ttl) => {
+            let latest = now.saturating_add(ttl);
+            Some(
+                request
+                    .expires_at
+                    .map_or(latest, |expires_at| expires_at.min(latest)),
+            )
+        }
+        None => request.expires_at,
+    };
+
+    state.drop_boxes.insert(
+        alias.clone(),
+        DropBox {
+            name: request.name,
+            requester_principal: caller,
+            created_at: now,
+            expires_at,
+            max_files: request.max_files,
+            max_bytes: request.max_bytes,
+            total_bytes: 0,
+            closed: false,
+            uploads: vec![],
+        },
+    );
+
+    alias
+}
+
+/// Closes a drop box so that it doesn't accept any more uploads.
+pub fn close_drop_box(
+    caller: Principal,
+    alias: String,
+    state: &mut State,
+) -> Result<(), DropBoxError> {
+    let drop_box = state
+        .drop_boxes
+        .get_mut(&alias)
+        .ok_or(DropBoxError::NotFound)?;
+
+    if drop_box.requester_principal != caller {
+        return Err(DropBoxError::PermissionError);
+    }
+
+    drop_box.closed = true;
+    Ok(())
+}
+
+/// Returns the information an uploader needs to encrypt files for a drop box.
+pub fn get_drop_box_info(state: &State, alias: String) -> Result<DropBoxInfo, DropBoxError> {
+    let drop_box = state.drop_boxes.get(&alias).ok_or(DropBoxError::NotFound)?;
+    check_open(drop_box, state.env.now())?;
+
+    let user = state.users.get(&drop_box.requester_principal).unwrap();
+    Ok(DropBoxInfo {
+        name: drop_box.name.clone(),
+        user: PublicUser {
+            username: user.username.clone(),
+            public_key: user.public_key.clone(),
+            ic_principal: drop_box.requester_principal,
+        },
+    })
+}
+
+/// Uploads a file to a drop box and returns the ID of the new file.
+///
+/// The file is owned by the creator of the drop box. The remaining chunks of the file are
+/// uploaded with `upload_file_continue`.
+pub fn upload_to_drop_box(
+    request: UploadToDropBoxRequest,
+    state: &mut State,
+) -> Result<u64, DropBoxError> {
+    let drop_box = state
+        .drop_boxes
+        .get(&request.alias)
+        .ok_or(DropBoxError::NotFound)?;
+    check_open(drop_box, state.env.now())?;
+
+    // The remaining chunks are accounted as they are received, see `receive_drop_box_chunk`.
+    // Files whose declared size doesn't fit are rejected right away.
+    let bytes = request.content.len() as u64;
+    let reserved_bytes = upload_size(&request.content, request.num_chunks);
+    if let Some(max_files) = drop_box.max_files {
+        if drop_box.uploads.len() as u64 >= max_files {
+            return Err(DropBoxError::LimitExceeded);
+        }
+    }
+    if let Some(max_bytes) = drop_box.max_bytes {
+        if drop_box.total_bytes.saturating_add(reserved_bytes) > max_bytes {
+            return Err(DropBoxError::LimitExceeded);
+        }
+    }
+
+    let owner = drop_box.requester_principal;
+    check_quota(state, owner, reserved_bytes, 1)?;
+    add_usage(state, owner, bytes, 1);
+
+    let file_id = state.generate_file_id();
+
+    let content = if request.num_chunks == 1 {
+        FileContent::Uploaded {
+            num_chunks: request.num_chunks,
+            file_type: request.file_type,
+            owner_key: request.owner_key,
+            shared_keys: BTreeMap::new(),
+        }
+    } else {
+        FileContent::PartiallyUploaded {
+            num_chunks: request.num_chunks,
+            file_type: request.file_type,
+            owner_key: request.owner_key,
+            shared_keys: BTreeMap::new(),
+        }
+    };
+
+    // Add file contents to stable store.
+    let chunk_id = 0;
+    state.insert_chunk(ChunkKey(file_id, 0, chunk_id), request.content);
+
+    state.file_data.insert(
+        file_id,
+        File {
+            metadata: FileMetadata {
+                file_name: request.file_name,
+                user_public_key: get_user_key(state, owner),
+                requester_principal: owner,
+                requested_at: state.env.now(),
+                uploaded_at: Some(state.env.now()),
+            },
+            content,
+            version: 0,
+            versions: vec![],
+        },
+    );
+    certify_file(state, file_id);
+
+    // The creator of the drop box is the owner of this file.
+    state
+        .file_owners
+        .entry(owner)
+        .or_insert_with(Vec::new)
+        .push(file_id);
+
+    state.drop_box_files.insert(file_id, request.alias.clone());
+    let drop_box = state.drop_boxes.get_mut(&request.alias).unwrap();
+    drop_box.total_bytes += bytes;
+    drop_box.uploads.push(DropBoxUpload {
+        file_id,
+        label: request.label,
+        uploaded_at: state.env.now(),
+    });
+    if request.num_chunks == 1 {
+        notify_upload_completed(state, file_id);
+    }
+
+    Ok(file_id)
+}
+
+/// Accounts a chunk uploaded with `upload_file_continue` to the drop box of its file, if the
+/// file was uploaded to a drop box.
+///
+/// Fails if the chunk would take the drop box over its maximum number of bytes.
+pub(crate) fn receive_drop_box_chunk(
+    state: &mut State,
+    file_id: u64,
+    bytes: u64,
+) -> Result<(), DropBoxError> {
+    let drop_box = match state
+        .drop_box_files
+        .get(&file_id)
+        .and_then(|alias| state.drop_boxes.get_mut(alias))
+    {
+        Some(drop_box) => drop_box,
+        None => return Ok(()),
+    };
+
+    let total_bytes = drop_box.total_bytes.saturating_add(bytes);
+    if let Some(max_bytes) = drop_box.max_bytes {
+        if total_bytes > max_bytes {
+            return Err(DropBoxError::LimitExceeded);
+        }
+    }
+    drop_box.total_bytes = total_bytes;
+    Ok(())
+}
+
+/// Returns the drop boxes created by the caller.
+pub fn get_drop_boxes(state: &State, caller: Principal) -> Vec<PublicDropBox> {
+    state
+        .drop_boxes
+        .iter()
+        .filter(|(_, drop_box)| drop_box.requester_principal == caller)
+        .map(|(alias, drop_box)| PublicDropBox {
+            alias: alias.clone(),
+            name: drop_box.name.clone(),
+            created_at: drop_box.created_at,
+            expires_at: drop_box.expires_at,
+            max_files: drop_box.max_files,
+            max_bytes: drop_box.max_bytes,
+            total_bytes: drop_box.total_bytes,
+            closed: drop_box.closed,
+            uploads: drop_box.uploads.clone(),
+        })
+        .collect()
+}
+
+fn check_open(drop_box: &DropBox, now: u64) -> Result<(), DropBoxError> {
+    if drop_box.closed {
+        return Err(DropBoxError::Closed);
+    }
+    match drop_box.expires_at {
+        Some(expires_at) if now >= expires_at => Err(DropBoxError::Expired),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{download_file, get_requests, set_user_info, upload_file_continue},
+        FileDownloadResponse, UploadFileContinueRequest, UploadFileError, User,
+    };
+
+    fn setup() -> State {
+        let mut state = State::default();
+        set_user_info(
+            &mut state,
+            Principal::anonymous(),
+            User {
+                username: "John".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+        state
+    }
+
+    fn drop_box(max_files: Option<u64>, max_bytes: Option<u64>) -> CreateDropBoxRequest {
+        CreateDropBoxRequest {
+            name: "invoices".to_string(),
+            expires_at: None,
+            max_files,
+            max_bytes,
+        }
+    }
+
+    fn upload(alias: &str, label: &str) -> UploadToDropBoxRequest {
+        UploadToDropBoxRequest {
+            alias: alias.to_string(),
+            label: label.to_string(),
+            file_name: "invoice.pdf".to_string(),
+            content: vec![1, 2, 3],
+            owner_key: vec![4, 5, 6],
+            file_type: "application/pdf".to_string(),
+            num_chunks: 1,
+        }
+    }
+
+    #[test]
+    fn accepts_many_uploads() {
+        let mut state = setup();
+        let alias = create_drop_box(Principal::anonymous(), drop_box(None, None), &mut state);
+
+        assert_eq!(
+            upload_to_drop_box(upload(&alias, "january"), &mut state),
+            Ok(0)
+        );
+        assert_eq!(
+            upload_to_drop_box(upload(&alias, "february"), &mut state),
+            Ok(1)
+        );
+
+        // Both files are owned by the creator of the drop box.
+        assert_eq!(get_requests(&state, Principal::anonymous()).len(), 2);
+        assert!(matches!(
+            download_file(&state, 1, 0, Principal::anonymous()),
+            FileDownloadResponse::FoundFile(_)
+        ));
+
+        let drop_boxes = get_drop_boxes(&state, Principal::anonymous());
+        assert_eq!(
+            drop_boxes[0].uploads,
+            vec![
+                DropBoxUpload {
+                    file_id: 0,
+                    label: "january".to_string(),
+                    uploaded_at: state.env.now(),
+                },
+                DropBoxUpload {
+                    file_id: 1,
+                    label: "february".to_string(),
+                    uploaded_at: state.env.now(),
+                }
+            ]
+        );
+        assert_eq!(drop_boxes[0].total_bytes, 6);
+    }
+
+    #[test]
+    fn closed_drop_box_rejects_uploads() {
+        let mut state = setup();
+        let alias = create_drop_box(Principal::anonymous(), drop_box(None, None), &mut state);
+
+        // Only the creator can close the drop box.
+        assert_eq!(
+            close_drop_box(Principal::from_slice(&[0, 1, 2]), alias.clone(), &mut state),
+            Err(DropBoxError::PermissionError)
+        );
+        assert_eq!(
+            close_drop_box(Principal::anonymous(), alias.clone(), &mut state),
+            Ok(())
+        );
+
+        assert_eq!(
+            upload_to_drop_box(upload(&alias, "january"), &mut state),
+            Err(DropBoxError::Closed)
+        );
+        assert_eq!(get_drop_box_info(&state, alias), Err(DropBoxError::Closed));
+    }
+
+    #[test]
+    fn expired_drop_box_rejects_uploads() {
+        let mut state = setup();
+        let alias = create_drop_box(
+            Principal::anonymous(),
+            CreateDropBoxRequest {
+                expires_at: Some(state.env.now()),
+                ..drop_box(None, None)
+            },
+            &mut state,
+        );
+
+        assert_eq!(
+            upload_to_drop_box(upload(&alias, "january"), &mut state),
+            Err(DropBoxError::Expired)
+        );
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let mut state = setup();
+        let alias = create_drop_box(Principal::anonymous(), drop_box(Some(1), None), &mut state);
+        assert!(upload_to_drop_box(upload(&alias, "january"), &mut state).is_ok());
+        assert_eq!(
+            upload_to_drop_box(upload(&alias, "february"), &mut state),
+            Err(DropBoxError::LimitExceeded)
+        );
+
+        let alias = create_drop_box(Principal::anonymous(), drop_box(None, Some(5)), &mut state);
+        assert_eq!(
+            upload_to_drop_box(upload(&alias, "january"), &mut state),
+            Ok(1)
+        );
+        assert_eq!(
+            upload_to_drop_box(upload(&alias, "february"), &mut state),
+            Err(DropBoxError::LimitExceeded)
+        );
+    }
+
+    #[test]
+    fn the_byte_limit_is_enforced_on_every_chunk() {
+        let mut state = setup();
+        let alias = create_drop_box(Principal::anonymous(), drop_box(None, Some(7)), &mut state);
+        let file_id = upload_to_drop_box(
+            UploadToDropBoxRequest {
+                content: vec![1],
+                num_chunks: 3,
+                ..upload(&alias, "january")
+            },
+            &mut state,
+        )
+        .unwrap();
+        let chunk = |chunk_id, contents| UploadFileContinueRequest {
+            file_id,
+            chunk_id,
+            contents,
+        };
+
+        assert_eq!(
+            upload_file_continue(chunk(1, vec![2; 3]), &mut state),
+            Ok(())
+        );
+        assert_eq!(
+            get_drop_boxes(&state, Principal::anonymous())[0].total_bytes,
+            4
+        );
+
+        // The last chunk is larger than what is left of the drop box.
+        assert_eq!(
+            upload_file_continue(chunk(2, vec![3; 4]), &mut state),
+            Err(UploadFileError::DropBoxLimitExceeded)
+        );
+        assert_eq!(
+            get_drop_boxes(&state, Principal::anonymous())[0].total_bytes,
+            4
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use crate::{
+    certification::certify_file, ChunkKey, FileContent, FileVersion, FileVersionError,
+    PublicFileVersion, State, UploadFileVersionRequest,
+};
+use ic_cdk::export::Principal;
+use std::collections::BTreeMap;
+
+use super::download_file::{can_download_shared, is_file_owned_by_me};
+use super::quotas::{add_usage, check_quota, release_usage, upload_size};
+
+/// Uploads a new version of an existing file.
+///
+/// The current content of the file is kept as a previous version. The new version isn't shared
+/// with anyone until the owner shares its key again with `share_file`. The remaining chunks of
+/// the new version are uploaded with `upload_file_continue`.
+pub fn upload_file_version(
+    caller: Principal,
+    request: UploadFileVersionRequest,
+    state: &mut State,
+) -> Result<u64, FileVersionError> {
+    if !is_file_owned_by_me(state, request.file_id, caller) {
+        return Err(FileVersionError::PermissionError);
+    }
+
+    // Only fully uploaded files can get a new version.
+    let file_id = request.file_id;
+    match state.file_data.get(&file_id).map(|file| &file.content) {
+        Some(FileContent::Uploaded { .. }) => {}
+        Some(FileContent::Pending { .. } | FileContent::PartiallyUploaded { .. }) => {
+            return Err(FileVersionError::NotUploadedFile)
+        }
+        None => return Err(FileVersionError::NotFoundFile),
+    }
+    check_quota(
+        state,
+        caller,
+        upload_size(&request.content, request.num_chunks),
+        0,
+    )?;
+    add_usage(state, caller, request.content.len() as u64, 0);
+
+    let max_versions = state.config.max_file_versions as usize;
+    let file = state.file_data.get_mut(&file_id).unwrap();
+
+    let content = if request.num_chunks == 1 {
+        FileContent::Uploaded {
+            num_chunks: request.num_chunks,
+            file_type: request.file_type,
+            owner_key: request.owner_key,
+            shared_keys: BTreeMap::new(),
+        }
+    } else {
+        FileContent::PartiallyUploaded {
+            num_chunks: request.num_chunks,
+            file_type: request.file_type,
+            owner_key: request.owner_key,
+            shared_keys: BTreeMap::new(),
+        }
+    };
+
+    let previous = std::mem::replace(&mut file.content, content);
+
+    if let FileContent::Uploaded {
+        num_chunks,
+        file_type,
+        owner_key,
+        shared_keys,
+    } = previous
+    {
+        file.versions.push(FileVersion {
+            version: file.version,
+            num_chunks,
+            file_type,
+            owner_key,
+            shared_keys,
+            uploaded_at: file.metadata.uploaded_at.unwrap(),
+        });
+    }
+
+    file.version += 1;
+    file.metadata.uploaded_at = Some(state.env.now());
+    let version = file.version;
+
+    // Drop the oldest versions that exceed the retention limit.
+    let num_expired = (file.versions.len() + 1).saturating_sub(max_versions);
+    let expired: Vec<FileVersion> = file.versions.drain(..num_expired).collect();
+    for expired_version in expired {
+        let bytes = state.version_bytes(file_id, expired_version.version);
+        release_usage(state, caller, bytes, 0);
+        state.remove_chunks(file_id, expired_version.version, expired_version.num_chunks);
+    }
+
+    // Add file contents to stable store.
+    let chunk_id = 0;
+    state.insert_chunk(ChunkKey(file_id, version, chunk_id), request.content);
+    certify_file(state, file_id);
+
+    Ok(version)
+}
+
+/// Cancels the upload of a new version of a file that isn't fully uploaded.
+///
+/// The uploaded chunks of the new version are dropped and the latest retained version becomes
+/// the current one again. Returns the id of the restored version.
+pub fn cancel_file_version(
+    state: &mut State,
+    caller: Principal,
+    file_id: u64,
+) -> Result<u64, FileVersionError> {
+    if !is_file_owned_by_me(state, file_id, caller) {
+        return Err(FileVersionError::PermissionError);
+    }
+    let file = state
+        .file_data
+        .get(&file_id)
+        .ok_or(FileVersionError::NotFoundFile)?;
+    let (cancelled, num_chunks) = match (&file.content, file.versions.last()) {
+        (FileContent::PartiallyUploaded { num_chunks, .. }, Some(_)) => (file.version, *num_chunks),
+        _ => return Err(FileVersionError::NoPendingVersion),
+    };
+
+    let bytes = state.version_bytes(file_id, cancelled);
+    release_usage(state, caller, bytes, 0);
+    state.remove_chunks(file_id, cancelled, num_chunks);
+
+    let file = state.file_data.get_mut(&file_id).unwrap();
+    let previous = file.versions.pop().unwrap();
+    file.content = FileContent::Uploaded {
+        num_chunks: previous.num_chunks,
+        file_type: previous.file_type,
+        owner_key: previous.owner_key,
+        shared_keys: previous.shared_keys,
+    };
+    file.version = previous.version;
+    file.metadata.uploaded_at = Some(previous.uploaded_at);
+    certify_file(state, file_id);
+
+    Ok(previous.version)
+}
+
+/// Returns all the retained versions of a file, oldest first.
+pub fn get_file_versions(
+    state: &State,
+    caller: Principal,
+    file_id: u64,
+) -> Result<Vec<PublicFileVersion>, FileVersionError> {
+    if !is_file_owned_by_me(state, file_id, caller) && !can_download_shared(state, file_id, caller)
+    {
+        return Err(FileVersionError::PermissionError);
+    }
+
+    let file = state
+        .file_data
+        .get(&file_id)
+        .ok_or(FileVersionError::NotFoundFile)?;
+
+    let mut versions: Vec<PublicFileVersion> = file
+        .versions
+        .iter()
+        .map(|version| PublicFileVersion {
+            version: version.version,
+            file_type: version.file_type.clone(),
+            num_chunks: version.num_chunks,
+            uploaded_at: version.uploaded_at,
+            is_current: false,
+        })
+        .collect();
+
+    match &file.content {
+        FileContent::Pending { .. } => {}
+        FileContent::Uploaded {
+            num_chunks,
+            file_type,
+            ..
+        }
+        | FileContent::PartiallyUploaded {
+            num_chunks,
+            file_type,
+            ..
+        } => versions.push(PublicFileVersion {
+            version: file.version,
+            file_type: file_type.clone(),
+            num_chunks: *num_chunks,
+            uploaded_at: file.metadata.uploaded_at.unwrap(),
+            is_current: true,
+        }),
+    }
+
+    Ok(versions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            download_file, download_file_version, set_user_info, share_file, upload_file_atomic,
+            UploadFileAtomicRequest,
+        },
+        FileData, FileDownloadResponse, User, MAX_FILE_VERSIONS,
+    };
+
+    fn setup() -> State {
+        let mut state = State::default();
+        set_user_info(
+            &mut state,
+            Principal::anonymous(),
+            User {
+                username: "John".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+        set_user_info(
+            &mut state,
+            Principal::from_slice(&[0, 1, 2]),
+            User {
+                username: "Jane".to_string(),
+                public_key: vec![4, 5, 6],
+            },
+        );
+        upload_file_atomic(
+            Principal::anonymous(),
+            UploadFileAtomicRequest {
+                num_chunks: 1,
+                name: "file_name".to_string(),
+                content: vec![1, 2, 3],
+                owner_key: vec![1, 2, 3],
+                file_type: "image/jpeg".to_string(),
+            },
+            &mut state,
+        )
+        .unwrap();
+        state
+    }
+
+    fn new_version(content: Vec<u8>, owner_key: Vec<u8>) -> UploadFileVersionRequest {
+        UploadFileVersionRequest {
+            file_id: 0,
+            content,
+            owner_key,
+            file_type: "image/png".to_string(),
+            num_chunks: 1,
+        }
+    }
+
+    #[test]
+    fn uploading_a_version_keeps_the_previous_one() {
+        let mut state = setup();
+
+        assert_eq!(
+            upload_file_version(
+                Principal::anonymous(),
+                new_version(vec![4, 5, 6], vec![7, 8, 9]),
+                &mut state
+            ),
+            Ok(1)
+        );
+
+        // The current version is the new one.
+        assert_eq!(
+            download_file(&state, 0, 0, Principal::anonymous()),
+            FileDownloadResponse::FoundFile(FileData {
+                contents: vec![4, 5, 6],
+                file_type: "image/png".to_string(),
+                owner_key: vec![7, 8, 9],
+                num_chunks: 1
+            })
+        );
+
+        // The previous version can still be downloaded.
+        assert_eq!(
+            download_file_version(&state, 0, 0, 0, Principal::anonymous()),
+            FileDownloadResponse::FoundFile(FileData {
+                contents: vec![1, 2, 3],
+                file_type: "image/jpeg".to_string(),
+                owner_key: vec![1, 2, 3],
+                num_chunks: 1
+            })
+        );
+
+        assert_eq!(
+            get_file_versions(&state, Principal::anonymous(), 0),
+            Ok(vec![
+                PublicFileVersion {
+                    version: 0,
+                    file_type: "image/jpeg".to_string(),
+                    num_chunks: 1,
+                    uploaded_at: state.env.now(),
+                    is_current: false,
+                },
+                PublicFileVersion {
+                    version: 1,
+                    file_type: "image/png".to_string(),
+                    num_chunks: 1,
+                    uploaded_at: state.env.now(),
+                    is_current: true,
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn only_owners_can_upload_versions() {
+        let mut state = setup();
+
+        assert_eq!(
+            upload_file_version(
+                Principal::from_slice(&[0, 1, 2]),
+                new_version(vec![4, 5, 6], vec![7, 8, 9]),
+                &mut state
+            ),
+            Err(FileVersionError::PermissionError)
+        );
+    }
+
+    #[test]
+    fn recipients_get_new_version_once_shared() {
+        let mut state = setup();
+        let recipient = Principal::from_slice(&[0, 1, 2]);
+
+        share_file(&mut state, Principal::anonymous(), recipient, 0, vec![10]);
+        upload_file_version(
+            Principal::anonymous(),
+            new_version(vec![4, 5, 6], vec![7, 8, 9]),
+            &mut state,
+        )
+        .unwrap();
+
+        // The recipient still gets the version whose key was shared with them.
+        assert_eq!(
+            download_file(&state, 0, 0, recipient),
+            FileDownloadResponse::FoundFile(FileData {
+                contents: vec![1, 2, 3],
+                file_type: "image/jpeg".to_string(),
+                owner_key: vec![10],
+                num_chunks: 1
+            })
+        );
+
+        // The owner shares the key of the new version.
+        share_file(&mut state, Principal::anonymous(), recipient, 0, vec![11]);
+        assert_eq!(
+            download_file(&state, 0, 0, recipient),
+            FileDownloadResponse::FoundFile(FileData {
+                contents: vec![4, 5, 6],
+                file_type: "image/png".to_string(),
+                owner_key: vec![11],
+                num_chunks: 1
+            })
+        );
+    }
+
+    #[test]
+    fn old_versions_beyond_the_limit_are_removed() {
+        let mut state = setup();
+
+        for i in 0..MAX_FILE_VERSIONS as u8 {
+            upload_file_version(
+                Principal::anonymous(),
+                new_version(vec![i], vec![i]),
+                &mut state,
+            )
+            .unwrap();
+        }
+
+        let versions = get_file_versions(&state, Principal::anonymous(), 0).unwrap();
+        assert_eq!(versions.len(), MAX_FILE_VERSIONS);
+        assert_eq!(versions[0].version, 1);
+
+        // The chunks of the first version are deleted.
+        assert_eq!(state.file_contents.get(&ChunkKey(0, 0, 0)), None);
+        assert_eq!(
+            download_file_version(&state, 0, 0, 0, Principal::anonymous()),
+            FileDownloadResponse::NotFoundFile
+        );
+    }
+
+    #[test]
+    fn pending_versions_can_be_cancelled() {
+        let mut state = setup();
+        let first_version = FileDownloadResponse::FoundFile(FileData {
+            contents: vec![1, 2, 3],
+            file_type: "image/jpeg".to_string(),
+            owner_key: vec![1, 2, 3],
+            num_chunks: 1,
+        });
+
+        let mut request = new_version(vec![4, 5, 6], vec![7, 8, 9]);
+        request.num_chunks = 2;
+        assert_eq!(
+            upload_file_version(Principal::anonymous(), request.clone(), &mut state),
+            Ok(1)
+        );
+
+        // The owner still gets the previous version while the new one is uploaded.
+        assert_eq!(
+            download_file(&state, 0, 0, Principal::anonymous()),
+            first_version
+        );
+        // Another version can't be started until the pending one is done.
+        assert_eq!(
+            upload_file_version(Principal::anonymous(), request, &mut state),
+            Err(FileVersionError::NotUploadedFile)
+        );
+
+        assert_eq!(
+            cancel_file_version(&mut state, Principal::from_slice(&[0, 1, 2]), 0),
+            Err(FileVersionError::PermissionError)
+        );
+        assert_eq!(
+            cancel_file_version(&mut state, Principal::anonymous(), 0),
+            Ok(0)
+        );
+        assert_eq!(state.file_contents.get(&ChunkKey(0, 1, 0)), None);
+        assert_eq!(
+            download_file(&state, 0, 0, Principal::anonymous()),
+            first_version
+        );
+        assert_eq!(
+            cancel_file_version(&mut state, Principal::anonymous(), 0),
+            Err(FileVersionError::NoPendingVersion)
+        );
+
+        // A new version can be uploaded again.
+        assert_eq!(
+            upload_file_version(
+                Principal::anonymous(),
+                new_version(vec![4, 5, 6], vec![7, 8, 9]),
+                &mut state
+            ),
+            Ok(1)
+        );
+    }
+}
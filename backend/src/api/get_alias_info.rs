@@ -1,30 +1,76 @@
-use crate::{AliasInfo, FileMetadata, GetAliasInfoError, PublicUser, State};
+use crate::{
+    AliasInfo, AliasItem, FileContent, FileMetadata, GetAliasInfoError, PublicUser, State,
+};
+use ic_cdk::export::candid::Principal;
+
+use super::config::has_expired;
 
 pub fn get_alias_info(state: &State, alias: String) -> Result<AliasInfo, GetAliasInfoError> {
+    if let Some(checklist) = state.checklists.get(&alias) {
+        if has_expired(state, checklist.requested_at, state.config.request_ttl_ns) {
+            return Err(GetAliasInfoError::NotFound);
+        }
+        let items: Vec<AliasItem> = checklist
+            .file_ids
+            .iter()
+            .map(|file_id| get_alias_item(state, *file_id))
+            .collect();
+
+        // The alias is no longer valid once every item of the checklist has been uploaded.
+        let first_pending = items
+            .iter()
+            .find(|item| item.pending)
+            .ok_or(GetAliasInfoError::NotFound)?;
+
+        return Ok(AliasInfo {
+            file_id: first_pending.file_id,
+            file_name: checklist.name.clone(),
+            user: get_public_user(state, checklist.requester_principal),
+            items,
+        });
+    }
+
     state
         .file_alias_index
         .get(&alias)
+        .filter(|file_id| {
+            !has_expired(
+                state,
+                get_file_metadata(state, **file_id).requested_at,
+                state.config.request_ttl_ns,
+            )
+        })
         .ok_or(GetAliasInfoError::NotFound)
         .map(|file_id| {
             let file_metadata = get_file_metadata(state, *file_id);
-            let user = state
-                .users
-                .get(&file_metadata.requester_principal)
-                .unwrap()
-                .clone();
 
             AliasInfo {
                 file_id: *file_id,
                 file_name: file_metadata.file_name.clone(),
-                user: PublicUser {
-                    username: user.username,
-                    public_key: user.public_key,
-                    ic_principal: file_metadata.requester_principal,
-                },
+                user: get_public_user(state, file_metadata.requester_principal),
+                items: vec![get_alias_item(state, *file_id)],
             }
         })
 }
 
+fn get_alias_item(state: &State, file_id: u64) -> AliasItem {
+    let file = state.file_data.get(&file_id).unwrap();
+    AliasItem {
+        file_id,
+        file_name: file.metadata.file_name.clone(),
+        pending: matches!(file.content, FileContent::Pending { .. }),
+    }
+}
+
+fn get_public_user(state: &State, principal: Principal) -> PublicUser {
+    let user = state.users.get(&principal).unwrap().clone();
+    PublicUser {
+        username: user.username,
+        public_key: user.public_key,
+        ic_principal: principal,
+    }
+}
+
 fn get_file_metadata(state: &State, file_id: u64) -> &FileMetadata {
     &state.file_data.get(&file_id).unwrap().metadata
 }
@@ -61,7 +61,7 @@ mod test {
     use super::*;
     use crate::{
         api::{request_file, set_user_info},
-        get_time, User,
+        User,
     };
     use ic_cdk::export::Principal;
 
@@ -96,7 +96,7 @@ mod test {
                     file_name: "request".to_string(),
                     file_status: FileStatus::Pending {
                         alias: alias1,
-                        requested_at: get_time()
+                        requested_at: state.env.now()
                     },
                     shared_with: vec![]
                 },
@@ -105,7 +105,7 @@ mod test {
                     file_name: "request2".to_string(),
                     file_status: FileStatus::Pending {
                         alias: alias2,
-                        requested_at: get_time()
+                        requested_at: state.env.now()
                     },
                     shared_with: vec![]
                 },
@@ -114,7 +114,7 @@ mod test {
                     file_name: "request3".to_string(),
                     file_status: FileStatus::Pending {
                         alias: alias3,
-                        requested_at: get_time()
+                        requested_at: state.env.now()
                     },
                     shared_with: vec![]
                 },
@@ -123,7 +123,7 @@ mod test {
                     file_name: "request4".to_string(),
                     file_status: FileStatus::Pending {
                         alias: alias4,
-                        requested_at: get_time()
+                        requested_at: state.env.now()
                     },
                     shared_with: vec![]
                 }
@@ -0,0 +1,226 @@
+use crate::{
+    DownloadTokenError, HttpRequest, HttpResponse, State, StreamingCallbackHttpResponse,
+    StreamingCallbackToken, StreamingStrategy,
+};
+use ic_cdk::export::candid::{Func, Principal};
+
+use super::download_token::download_chunk_with_token;
+use super::metrics::{encode_metrics, get_metrics};
+
+/// Serves the HTTP requests made to the canister through the HTTP gateway.
+///
+/// Files are served at `/files/<file_id>?token=<token>`, with a token issued by
+/// `create_download_token`. The first chunk is served in the response, and the gateway fetches
+/// the others from `http_request_streaming_callback`.
+pub fn http_request(state: &State, canister_id: Principal, request: HttpRequest) -> HttpResponse {
+    let (path, query) = request
+        .url
+        .split_once('?')
+        .unwrap_or((request.url.as_str(), ""));
+    match (request.method.as_str(), path) {
+        ("GET", "/metrics") => HttpResponse {
+            status_code: 200,
+            headers: vec![(
+                "Content-Type".to_string(),
+                "text/plain; version=0.0.4".to_string(),
+            )],
+            body: encode_metrics(&get_metrics(state)).into_bytes(),
+            streaming_strategy: None,
+        },
+        ("GET", path) if path.starts_with("/files/") => {
+            match (path["/files/".len()..].parse(), query_param(query, "token")) {
+                (Ok(file_id), Some(token)) => serve_file(state, canister_id, file_id, token),
+                (Ok(_), None) => error(403, "Missing download token"),
+                (Err(_), _) => not_found(),
+            }
+        }
+        _ => not_found(),
+    }
+}
+
+/// Serves the chunk of a file download identified by the token of the previous chunk.
+pub fn http_request_streaming_callback(
+    state: &State,
+    token: StreamingCallbackToken,
+) -> Result<StreamingCallbackHttpResponse, DownloadTokenError> {
+    let (body, num_chunks) =
+        download_chunk_with_token(state, &token.token, token.file_id, token.chunk_id)?;
+    Ok(StreamingCallbackHttpResponse {
+        body,
+        token: next_chunk(token, num_chunks),
+    })
+}
+
+fn serve_file(state: &State, canister_id: Principal, file_id: u64, token: &str) -> HttpResponse {
+    match download_chunk_with_token(state, token, file_id, 0) {
+        Ok((body, num_chunks)) => {
+            let first_chunk = StreamingCallbackToken {
+                file_id,
+                chunk_id: 0,
+                token: token.to_string(),
+            };
+            HttpResponse {
+                status_code: 200,
+                headers: vec![
+                    // The contents are encrypted, whatever the type of the file.
+                    (
+                        "Content-Type".to_string(),
+                        "application/octet-stream".to_string(),
+                    ),
+                    // The URL holds the token, so the response must not outlive it in caches.
+                    ("Cache-Control".to_string(), "no-store".to_string()),
+                ],
+                body,
+                streaming_strategy: next_chunk(first_chunk, num_chunks).map(|token| {
+                    StreamingStrategy::Callback {
+                        callback: Func {
+                            principal: canister_id,
+                            method: "http_request_streaming_callback".to_string(),
+                        },
+                        token,
+                    }
+                }),
+            }
+        }
+        Err(DownloadTokenError::PermissionError) => error(403, "Invalid download token"),
+        Err(DownloadTokenError::NotFoundFile | DownloadTokenError::NotUploadedFile) => not_found(),
+    }
+}
+
+fn next_chunk(token: StreamingCallbackToken, num_chunks: u64) -> Option<StreamingCallbackToken> {
+    let chunk_id = token.chunk_id + 1;
+    (chunk_id < num_chunks).then_some(StreamingCallbackToken { chunk_id, ..token })
+}
+
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn not_found() -> HttpResponse {
+    error(404, "Not found")
+}
+
+fn error(status_code: u16, message: &str) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![],
+        body: message.as_bytes().to_vec(),
+        streaming_strategy: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            create_download_token, set_user_info, upload_file_atomic, upload_file_continue,
+            UploadFileAtomicRequest,
+        },
+        UploadFileContinueRequest, User,
+    };
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    fn canister_id() -> Principal {
+        Principal::from_slice(&[0, 0, 1])
+    }
+
+    #[test]
+    fn serves_metrics() {
+        let state = State::default();
+
+        let response = http_request(&state, canister_id(), get("/metrics?time=1"));
+        assert_eq!(response.status_code, 200);
+        assert!(String::from_utf8(response.body)
+            .unwrap()
+            .contains("docutrack_users 0"));
+
+        assert_eq!(
+            http_request(&state, canister_id(), get("/other")).status_code,
+            404
+        );
+    }
+
+    #[test]
+    fn streams_files_with_a_download_token() {
+        let mut state = State::default();
+        let owner = Principal::from_slice(&[0, 1, 1]);
+        set_user_info(
+            &mut state,
+            owner,
+            User {
+                username: "John".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+        let file_id = upload_file_atomic(
+            owner,
+            UploadFileAtomicRequest {
+                name: "file".to_string(),
+                content: vec![1],
+                owner_key: vec![1, 2, 3],
+                file_type: "image/jpeg".to_string(),
+                num_chunks: 3,
+            },
+            &mut state,
+        )
+        .unwrap();
+        for chunk_id in 1..3 {
+            upload_file_continue(
+                UploadFileContinueRequest {
+                    file_id,
+                    chunk_id,
+                    contents: vec![chunk_id as u8 + 1],
+                },
+                &mut state,
+            )
+            .unwrap();
+        }
+        let token =
+            create_download_token(&mut state, owner, file_id, "secret".to_string()).unwrap();
+
+        let response = http_request(&state, canister_id(), get(&token.url));
+        assert_eq!(response.status_code, 200);
+        let mut body = response.body;
+        let mut next = match response.streaming_strategy {
+            Some(StreamingStrategy::Callback { callback, token }) => {
+                assert_eq!(callback.principal, canister_id());
+                Some(token)
+            }
+            None => None,
+        };
+        while let Some(token) = next {
+            let response = http_request_streaming_callback(&state, token).unwrap();
+            body.extend(response.body);
+            next = response.token;
+        }
+        assert_eq!(body, vec![1, 2, 3]);
+
+        for url in ["/files/0", "/files/0?token=guess"] {
+            assert_eq!(
+                http_request(&state, canister_id(), get(url)).status_code,
+                403
+            );
+        }
+        assert_eq!(
+            http_request(&state, canister_id(), get("/files/1?token=secret")).status_code,
+            403
+        );
+        assert_eq!(
+            http_request(&state, canister_id(), get("/files/x?token=secret")).status_code,
+            404
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use crate::State;
+use ic_cdk::export::candid::Principal;
+
+use super::rate_limit::check_rate_limit;
+use super::share_file::get_grantable_permission;
+
+/// The room left in the arguments of the upload endpoints for the encoding overhead and the
+/// fields of the request other than the chunk.
+pub const UPLOAD_ARG_OVERHEAD: usize = 64 * 1024;
+
+/// The maximum size of the arguments of the other endpoints.
+pub const MAX_ARG_SIZE: usize = 128 * 1024;
+
+/// The endpoints whose arguments contain file contents.
+const UPLOAD_METHODS: &[&str] = &[
+    "upload_file",
+    "upload_file_atomic",
+    "upload_file_continue",
+    "upload_file_version",
+    "upload_to_drop_box",
+];
+
+/// The endpoints that can be called by anyone, including the anonymous principal.
+const PUBLIC_METHODS: &[&str] = &[
+    "get_alias_info",
+    "get_drop_box_info",
+    "http_request",
+    "http_request_streaming_callback",
+    "upload_file",
+    "upload_file_continue",
+    "upload_to_drop_box",
+    "username_exists",
+    "who_am_i",
+];
+
+/// The endpoints that are throttled by the rate limiter.
+const THROTTLED_METHODS: &[&str] = &[
+    "request_file",
+    "request_checklist",
+    "upload_file",
+    "upload_file_continue",
+    "upload_to_drop_box",
+];
+
+/// The reason an ingress message is rejected before it is executed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RejectReason {
+    PayloadTooLarge,
+    AnonymousCaller,
+    UnknownUser,
+    CannotShare,
+    RateLimited,
+    UploadsFrozen,
+}
+
+/// An ingress message, with the arguments that are decoded for inspection.
+pub struct Message<'a> {
+    pub method: &'a str,
+    pub arg_size: usize,
+    /// The ID of the shared file, for the endpoints that share a file.
+    pub shared_file_id: Option<u64>,
+}
+
+/// Checks the cheap preconditions of an ingress message, so that messages that would fail
+/// are rejected before they consume cycles.
+///
+/// The checks are only an optimization: every endpoint enforces its preconditions when it is
+/// executed, as inter-canister calls and query calls aren't inspected.
+pub fn inspect_message(
+    state: &State,
+    caller: Principal,
+    message: &Message,
+) -> Result<(), RejectReason> {
+    let max_arg_size = if UPLOAD_METHODS.contains(&message.method) {
+        state.config.max_chunk_size as usize + UPLOAD_ARG_OVERHEAD
+    } else {
+        MAX_ARG_SIZE
+    };
+    if message.arg_size > max_arg_size {
+        return Err(RejectReason::PayloadTooLarge);
+    }
+    if state.uploads_frozen && UPLOAD_METHODS.contains(&message.method) {
+        return Err(RejectReason::UploadsFrozen);
+    }
+
+    if !PUBLIC_METHODS.contains(&message.method) {
+        if caller == Principal::anonymous() {
+            return Err(RejectReason::AnonymousCaller);
+        }
+        // Unregistered users can only register. Admins may not be registered.
+        if message.method != "set_user"
+            && !state.users.contains_key(&caller)
+            && !state.admins.contains(&caller)
+        {
+            return Err(RejectReason::UnknownUser);
+        }
+    }
+
+    if let Some(file_id) = message.shared_file_id {
+        if get_grantable_permission(state, caller, file_id).is_none() {
+            return Err(RejectReason::CannotShare);
+        }
+    }
+
+    // Anonymous calls are throttled by the alias or the upload they target, which aren't
+    // decoded here, so they are left to the endpoints.
+    if THROTTLED_METHODS.contains(&message.method)
+        && caller != Principal::anonymous()
+        && check_rate_limit(state, caller, None).is_err()
+    {
+        return Err(RejectReason::RateLimited);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{request_file, set_user_info, share_file, upload_file},
+        RateLimit, User,
+    };
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn recipient() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn message(method: &str) -> Message {
+        Message {
+            method,
+            arg_size: 100,
+            shared_file_id: None,
+        }
+    }
+
+    fn setup() -> State {
+        let mut state = State::default();
+        for principal in [owner(), recipient()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+
+        request_file(owner(), "request", &mut state);
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        state
+    }
+
+    #[test]
+    fn anonymous_callers_only_call_public_methods() {
+        let state = setup();
+
+        for method in ["set_user", "request_file", "share_file", "get_requests"] {
+            assert_eq!(
+                inspect_message(&state, Principal::anonymous(), &message(method)),
+                Err(RejectReason::AnonymousCaller),
+                "method: {method}"
+            );
+        }
+        for method in ["upload_file", "upload_file_continue", "get_alias_info"] {
+            assert_eq!(
+                inspect_message(&state, Principal::anonymous(), &message(method)),
+                Ok(()),
+                "method: {method}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_users_only_register() {
+        let state = setup();
+        let unknown = Principal::from_slice(&[0, 1, 3]);
+
+        assert_eq!(
+            inspect_message(&state, unknown, &message("set_user")),
+            Ok(())
+        );
+        assert_eq!(
+            inspect_message(&state, unknown, &message("request_file")),
+            Err(RejectReason::UnknownUser)
+        );
+        assert_eq!(
+            inspect_message(&state, owner(), &message("request_file")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn oversized_payloads_are_rejected() {
+        let state = setup();
+        let max_upload_arg_size = state.config.max_chunk_size as usize + UPLOAD_ARG_OVERHEAD;
+        let oversized = |method, arg_size| Message {
+            method,
+            arg_size,
+            shared_file_id: None,
+        };
+
+        assert_eq!(
+            inspect_message(
+                &state,
+                owner(),
+                &oversized("upload_file", max_upload_arg_size)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            inspect_message(
+                &state,
+                owner(),
+                &oversized("upload_file", max_upload_arg_size + 1)
+            ),
+            Err(RejectReason::PayloadTooLarge)
+        );
+        assert_eq!(
+            inspect_message(&state, owner(), &oversized("set_user", MAX_ARG_SIZE + 1)),
+            Err(RejectReason::PayloadTooLarge)
+        );
+    }
+
+    #[test]
+    fn only_users_who_can_share_call_share_methods() {
+        let mut state = setup();
+        let share = Message {
+            method: "share_file",
+            arg_size: 100,
+            shared_file_id: Some(0),
+        };
+
+        assert_eq!(inspect_message(&state, owner(), &share), Ok(()));
+        assert_eq!(
+            inspect_message(&state, recipient(), &share),
+            Err(RejectReason::CannotShare)
+        );
+
+        // Recipients with the view permission can't share either.
+        share_file(&mut state, owner(), recipient(), 0, vec![4]);
+        assert_eq!(
+            inspect_message(&state, recipient(), &share),
+            Err(RejectReason::CannotShare)
+        );
+    }
+
+    #[test]
+    fn uploads_are_rejected_while_frozen() {
+        let mut state = setup();
+        state.uploads_frozen = true;
+
+        assert_eq!(
+            inspect_message(&state, owner(), &message("upload_file_atomic")),
+            Err(RejectReason::UploadsFrozen)
+        );
+        assert_eq!(
+            inspect_message(&state, owner(), &message("request_file")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn callers_out_of_budget_are_rejected() {
+        let mut state = setup();
+        state.rate_limits.per_principal = RateLimit {
+            max_calls: 0,
+            window_ns: 1_000,
+        };
+
+        assert_eq!(
+            inspect_message(&state, owner(), &message("request_file")),
+            Err(RejectReason::RateLimited)
+        );
+        assert_eq!(
+            inspect_message(&state, owner(), &message("get_requests")),
+            Ok(())
+        );
+    }
+}
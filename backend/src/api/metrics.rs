@@ -0,0 +1,239 @@
+use crate::{FileContent, Metrics, State};
+use std::fmt::Write;
+
+#[cfg(target_arch = "wasm32")]
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// Returns the metrics of the canister.
+pub fn get_metrics(state: &State) -> Metrics {
+    let (mut pending, mut partially_uploaded, mut uploaded) = (0, 0, 0);
+    for file in state.file_data.values() {
+        match file.content {
+            FileContent::Pending { .. } => pending += 1,
+            FileContent::PartiallyUploaded { .. } => partially_uploaded += 1,
+            FileContent::Uploaded { .. } => uploaded += 1,
+        }
+    }
+
+    let pending_checklists = state
+        .checklists
+        .values()
+        .filter(|checklist| {
+            checklist.file_ids.iter().any(|file_id| {
+                matches!(
+                    state.file_data.get(file_id).map(|file| &file.content),
+                    Some(FileContent::Pending { .. })
+                )
+            })
+        })
+        .count() as u64;
+
+    Metrics {
+        timestamp: state.env.now(),
+        user_count: state.users.len() as u64,
+        pending_file_count: pending,
+        partially_uploaded_file_count: partially_uploaded,
+        uploaded_file_count: uploaded,
+        chunk_count: state.file_contents.len() + state.legacy_file_contents.len(),
+        // Reading every chunk would be too expensive, so the bytes accounted in the quotas are
+        // reported instead.
+        stored_bytes: state.stored_bytes,
+        pending_alias_count: state.file_alias_index.len() as u64 + pending_checklists,
+        share_count: state
+            .file_shares
+            .values()
+            .map(|files| files.len() as u64)
+            .sum(),
+        heap_memory_bytes: heap_memory_bytes(),
+        stable_memory_bytes: stable_memory_bytes(),
+        cycle_balance: cycle_balance(),
+    }
+}
+
+/// Encodes the metrics in the Prometheus text format.
+pub fn encode_metrics(metrics: &Metrics) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: &[(&str, u128)]| {
+        writeln!(out, "# HELP docutrack_{name} {help}").unwrap();
+        writeln!(out, "# TYPE docutrack_{name} gauge").unwrap();
+        for (labels, value) in samples {
+            writeln!(out, "docutrack_{name}{labels} {value}").unwrap();
+        }
+    };
+
+    gauge(
+        "users",
+        "Number of registered users.",
+        &[("", metrics.user_count.into())],
+    );
+    gauge(
+        "files",
+        "Number of files by upload status.",
+        &[
+            (r#"{status="pending"}"#, metrics.pending_file_count.into()),
+            (
+                r#"{status="partially_uploaded"}"#,
+                metrics.partially_uploaded_file_count.into(),
+            ),
+            (r#"{status="uploaded"}"#, metrics.uploaded_file_count.into()),
+        ],
+    );
+    gauge(
+        "chunks",
+        "Number of stored chunks, across all versions.",
+        &[("", metrics.chunk_count.into())],
+    );
+    gauge(
+        "stored_bytes",
+        "Number of stored bytes, across all versions.",
+        &[("", metrics.stored_bytes.into())],
+    );
+    gauge(
+        "pending_aliases",
+        "Number of aliases through which files can still be uploaded.",
+        &[("", metrics.pending_alias_count.into())],
+    );
+    gauge(
+        "shares",
+        "Number of files shared with users.",
+        &[("", metrics.share_count.into())],
+    );
+    gauge(
+        "heap_memory_bytes",
+        "Size of the heap memory.",
+        &[("", metrics.heap_memory_bytes.into())],
+    );
+    gauge(
+        "stable_memory_bytes",
+        "Size of the stable memory.",
+        &[("", metrics.stable_memory_bytes.into())],
+    );
+    gauge(
+        "cycle_balance",
+        "Cycle balance of the canister.",
+        &[("", metrics.cycle_balance)],
+    );
+
+    out
+}
+
+#[cfg(target_arch = "wasm32")]
+fn heap_memory_bytes() -> u64 {
+    core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_SIZE
+}
+
+#[cfg(target_arch = "wasm32")]
+fn stable_memory_bytes() -> u64 {
+    ic_cdk::api::stable::stable64_size() * WASM_PAGE_SIZE
+}
+
+#[cfg(target_arch = "wasm32")]
+fn cycle_balance() -> u128 {
+    ic_cdk::api::canister_balance128()
+}
+
+// The system metrics are only available in the canister.
+
+#[cfg(not(target_arch = "wasm32"))]
+fn heap_memory_bytes() -> u64 {
+    0
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn stable_memory_bytes() -> u64 {
+    0
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn cycle_balance() -> u128 {
+    0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{request_checklist, request_file, set_user_info, share_file, upload_file},
+        User,
+    };
+    use ic_cdk::export::candid::Principal;
+
+    #[test]
+    fn counts_files_aliases_and_shares() {
+        let mut state = State::default();
+        set_user_info(
+            &mut state,
+            Principal::anonymous(),
+            User {
+                username: "John".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+
+        request_file(Principal::anonymous(), "uploaded", &mut state);
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        request_file(Principal::anonymous(), "partially uploaded", &mut state);
+        upload_file(
+            1,
+            vec![1, 2],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            2,
+            &mut state,
+        )
+        .unwrap();
+        request_file(Principal::anonymous(), "pending", &mut state);
+        request_checklist(
+            Principal::anonymous(),
+            "checklist",
+            vec!["a".to_string(), "b".to_string()],
+            &mut state,
+        );
+        share_file(
+            &mut state,
+            Principal::anonymous(),
+            Principal::from_slice(&[0, 1, 1]),
+            0,
+            vec![4],
+        );
+
+        assert_eq!(
+            get_metrics(&state),
+            Metrics {
+                timestamp: state.env.now(),
+                user_count: 1,
+                pending_file_count: 3,
+                partially_uploaded_file_count: 1,
+                uploaded_file_count: 1,
+                chunk_count: 2,
+                stored_bytes: 5,
+                pending_alias_count: 2,
+                share_count: 1,
+                heap_memory_bytes: 0,
+                stable_memory_bytes: 0,
+                cycle_balance: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn metrics_are_encoded_for_prometheus() {
+        let encoded = encode_metrics(&get_metrics(&State::default()));
+
+        assert!(encoded.contains(
+            "# HELP docutrack_users Number of registered users.\n\
+             # TYPE docutrack_users gauge\n\
+             docutrack_users 0\n"
+        ));
+        assert!(encoded.contains("docutrack_files{status=\"partially_uploaded\"} 0\n"));
+        assert!(encoded.contains("docutrack_cycle_balance 0\n"));
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{
+    FileContent, Notification, NotificationEvent, State, MAX_NOTIFICATIONS,
+    REQUEST_EXPIRY_NOTICE_NS,
+};
+use ic_cdk::export::candid::Principal;
+
+use super::webhooks::{enqueue_webhook_event, WebhookEvent};
+
+/// How often the pending requests are checked for expiry.
+const EXPIRY_CHECK_INTERVAL_NS: u64 = 10 * 60 * 1_000_000_000;
+
+/// Adds a notification about the file to the inbox of the user.
+///
+/// Inboxes are bounded, so the oldest notification is dropped when the inbox is full, whether
+/// it has been read or not.
+pub(crate) fn notify(state: &mut State, user: Principal, file_id: u64, event: NotificationEvent) {
+    let file_name = match state.file_data.get(&file_id) {
+        Some(file) => file.metadata.file_name.clone(),
+        None => return,
+    };
+    let notification = Notification {
+        id: state.generate_notification_id(),
+        file_id,
+        file_name,
+        event,
+        created_at: state.env.now(),
+        read: false,
+    };
+
+    let inbox = state.notifications.entry(user).or_default();
+    if inbox.len() == MAX_NOTIFICATIONS {
+        inbox.pop_front();
+    }
+    inbox.push_back(notification);
+}
+
+/// Notifies the owner that the upload of their file has completed, in their inbox unless they
+/// uploaded it, and through their webhooks.
+pub(crate) fn notify_upload_completed(state: &mut State, file_id: u64) {
+    enqueue_webhook_event(state, file_id, WebhookEvent::UploadCompleted);
+    let owner = state.file_data[&file_id].metadata.requester_principal;
+    if state.env.caller() != owner {
+        notify(state, owner, file_id, NotificationEvent::UploadCompleted);
+    }
+}
+
+/// Returns the notifications of the caller, newest first.
+pub fn get_notifications(state: &State, caller: Principal) -> Vec<Notification> {
+    state
+        .notifications
+        .get(&caller)
+        .map(|inbox| inbox.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub fn get_unread_notification_count(state: &State, caller: Principal) -> u64 {
+    state.notifications.get(&caller).map_or(0, |inbox| {
+        inbox
+            .iter()
+            .filter(|notification| !notification.read)
+            .count() as u64
+    })
+}
+
+/// Marks the notifications of the caller up to the given ID as read.
+pub fn ack_notifications(state: &mut State, caller: Principal, up_to: u64) {
+    if let Some(inbox) = state.notifications.get_mut(&caller) {
+        for notification in inbox
+            .iter_mut()
+            .take_while(|notification| notification.id <= up_to)
+        {
+            notification.read = true;
+        }
+    }
+}
+
+/// Returns true if the pending requests haven't been checked for expiry recently.
+pub fn expiry_check_due(state: &State) -> bool {
+    state.env.now()
+        >= state
+            .expiry_notices_sent_until
+            .saturating_add(EXPIRY_CHECK_INTERVAL_NS)
+}
+
+/// Notifies the requesters of the pending requests that expire within
+/// `REQUEST_EXPIRY_NOTICE_NS`.
+///
+/// Each request is notified once, when it is first found within the notice period. Requests
+/// that are made within the notice period, because the TTL is shorter, are notified right away.
+pub fn notify_expiring_requests(state: &mut State) {
+    let now = state.env.now();
+    let since = std::mem::replace(&mut state.expiry_notices_sent_until, now);
+    let ttl = match state.config.request_ttl_ns {
+        Some(ttl) => ttl,
+        None => return,
+    };
+
+    let expiring: Vec<(Principal, u64, u64)> = state
+        .file_data
+        .iter()
+        .filter(|(_, file)| matches!(file.content, FileContent::Pending { .. }))
+        .filter_map(|(file_id, file)| {
+            let requested_at = file.metadata.requested_at;
+            let expires_at = requested_at.saturating_add(ttl);
+            let notice_at = expires_at
+                .saturating_sub(REQUEST_EXPIRY_NOTICE_NS)
+                .max(requested_at);
+            (since < notice_at && notice_at <= now && now < expires_at).then_some((
+                file.metadata.requester_principal,
+                *file_id,
+                expires_at,
+            ))
+        })
+        .collect();
+
+    for (requester, file_id, expires_at) in expiring {
+        notify(
+            state,
+            requester,
+            file_id,
+            NotificationEvent::RequestExpiring { expires_at },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            request_file, revoke_share, set_user_info, share_file, upload_file, upload_file_atomic,
+            UploadFileAtomicRequest,
+        },
+        env::{Environment, TestEnvironment},
+        Config, SharePermission, User,
+    };
+
+    const HOUR_NS: u64 = 60 * 60 * 1_000_000_000;
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn recipient() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn setup() -> (State, TestEnvironment) {
+        let env = TestEnvironment::default();
+        let mut state = State::new(Box::new(env.clone()));
+        for principal in [owner(), recipient()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+        (state, env)
+    }
+
+    fn events(state: &State, user: Principal) -> Vec<NotificationEvent> {
+        get_notifications(state, user)
+            .into_iter()
+            .map(|notification| notification.event)
+            .collect()
+    }
+
+    #[test]
+    fn requesters_are_notified_of_uploads_by_others() {
+        let (mut state, env) = setup();
+        request_file(owner(), "passport", &mut state);
+        request_file(owner(), "visa", &mut state);
+
+        upload_file(0, vec![1], "pdf".to_string(), vec![2], 1, &mut state).unwrap();
+        env.set_caller(owner());
+        upload_file(1, vec![1], "pdf".to_string(), vec![2], 1, &mut state).unwrap();
+
+        let notifications = get_notifications(&state, owner());
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].file_id, 0);
+        assert_eq!(notifications[0].file_name, "passport");
+        assert_eq!(notifications[0].event, NotificationEvent::UploadCompleted);
+        assert_eq!(notifications[0].created_at, env.now());
+    }
+
+    #[test]
+    fn recipients_are_notified_of_new_and_revoked_shares() {
+        let (mut state, _) = setup();
+        let request = UploadFileAtomicRequest {
+            name: "notes".to_string(),
+            content: vec![1],
+            owner_key: vec![2],
+            file_type: "txt".to_string(),
+            num_chunks: 1,
+        };
+        let file_id = upload_file_atomic(owner(), request, &mut state).unwrap();
+
+        share_file(&mut state, owner(), recipient(), file_id, vec![3]);
+        // Sharing again only updates the key.
+        share_file(&mut state, owner(), recipient(), file_id, vec![4]);
+        revoke_share(&mut state, owner(), recipient(), file_id);
+
+        assert_eq!(
+            events(&state, recipient()),
+            vec![
+                NotificationEvent::ShareRevoked { by: owner() },
+                NotificationEvent::FileShared {
+                    by: owner(),
+                    permission: SharePermission::View
+                },
+            ]
+        );
+        assert!(get_notifications(&state, owner()).is_empty());
+    }
+
+    #[test]
+    fn notifications_are_acknowledged() {
+        let (mut state, _) = setup();
+        for _ in 0..3 {
+            let file_id = state.file_data.len() as u64;
+            request_file(owner(), "request", &mut state);
+            upload_file(file_id, vec![1], "pdf".to_string(), vec![2], 1, &mut state).unwrap();
+        }
+        assert_eq!(get_unread_notification_count(&state, owner()), 3);
+
+        let second = get_notifications(&state, owner())[1].id;
+        ack_notifications(&mut state, owner(), second);
+        assert_eq!(get_unread_notification_count(&state, owner()), 1);
+        assert_eq!(
+            get_notifications(&state, owner())
+                .iter()
+                .map(|notification| notification.read)
+                .collect::<Vec<_>>(),
+            vec![false, true, true]
+        );
+
+        // Users can only acknowledge their own notifications.
+        ack_notifications(&mut state, recipient(), u64::MAX);
+        assert_eq!(get_unread_notification_count(&state, owner()), 1);
+    }
+
+    #[test]
+    fn inboxes_keep_the_latest_notifications() {
+        let (mut state, _) = setup();
+        request_file(owner(), "request", &mut state);
+        for _ in 0..MAX_NOTIFICATIONS + 1 {
+            notify(&mut state, owner(), 0, NotificationEvent::UploadCompleted);
+        }
+
+        let notifications = get_notifications(&state, owner());
+        assert_eq!(notifications.len(), MAX_NOTIFICATIONS);
+        assert_eq!(notifications[0].id, MAX_NOTIFICATIONS as u64);
+        assert_eq!(notifications[MAX_NOTIFICATIONS - 1].id, 1);
+    }
+
+    #[test]
+    fn requesters_are_notified_once_before_their_requests_expire() {
+        let (mut state, env) = setup();
+        state.config = Config {
+            request_ttl_ns: Some(48 * HOUR_NS),
+            ..Config::default()
+        };
+        request_file(owner(), "passport", &mut state);
+        request_file(owner(), "visa", &mut state);
+        upload_file(1, vec![1], "pdf".to_string(), vec![2], 1, &mut state).unwrap();
+        let expires_at = env.now() + 48 * HOUR_NS;
+
+        env.advance_time(23 * HOUR_NS);
+        assert!(expiry_check_due(&state));
+        notify_expiring_requests(&mut state);
+        assert!(!expiry_check_due(&state));
+        assert_eq!(
+            events(&state, owner()),
+            vec![NotificationEvent::UploadCompleted]
+        );
+
+        env.advance_time(2 * HOUR_NS);
+        notify_expiring_requests(&mut state);
+        env.advance_time(HOUR_NS);
+        notify_expiring_requests(&mut state);
+        assert_eq!(
+            events(&state, owner()),
+            vec![
+                NotificationEvent::RequestExpiring { expires_at },
+                NotificationEvent::UploadCompleted
+            ]
+        );
+
+        // Expired requests aren't notified.
+        request_file(owner(), "permit", &mut state);
+        env.advance_time(48 * HOUR_NS);
+        notify_expiring_requests(&mut state);
+        assert_eq!(get_notifications(&state, owner()).len(), 2);
+    }
+}
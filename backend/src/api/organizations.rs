@@ -0,0 +1,539 @@
+use crate::{
+    Organization, OrganizationError, OrganizationMember, PublicFileMetadata, PublicOrganization,
+    PublicUser, Role, State,
+};
+use ic_cdk::export::candid::Principal;
+use std::collections::BTreeMap;
+
+use super::download_file::is_file_owned_by_me;
+use super::get_requests::{get_allowed_users, get_file_status};
+use super::request_file::request_file;
+use super::share_file::remove_share;
+
+/// Creates an organization with the caller as its only admin.
+///
+/// The caller must be a registered user, so that they can be listed as a member.
+pub fn create_organization<S: Into<String>>(
+    state: &mut State,
+    caller: Principal,
+    name: S,
+) -> Result<u64, OrganizationError> {
+    if caller == Principal::anonymous() {
+        return Err(OrganizationError::PermissionError);
+    }
+    if !state.users.contains_key(&caller) {
+        return Err(OrganizationError::UnknownUser);
+    }
+
+    let org_id = state.generate_organization_id();
+    let mut members = BTreeMap::new();
+    members.insert(caller, Role::Admin);
+
+    state.organizations.insert(
+        org_id,
+        Organization {
+            name: name.into(),
+            created_at: state.env.now(),
+            members,
+        },
+    );
+
+    Ok(org_id)
+}
+
+/// Adds a member to an organization or changes the role of an existing member.
+pub fn set_organization_member(
+    state: &mut State,
+    caller: Principal,
+    org_id: u64,
+    user: Principal,
+    role: Role,
+) -> Result<(), OrganizationError> {
+    if !state.users.contains_key(&user) {
+        return Err(OrganizationError::UnknownUser);
+    }
+
+    let org = get_organization_as_admin(state, caller, org_id)?;
+    if role != Role::Admin && is_last_admin(org, user) {
+        return Err(OrganizationError::LastAdmin);
+    }
+
+    state
+        .organizations
+        .get_mut(&org_id)
+        .unwrap()
+        .members
+        .insert(user, role);
+    Ok(())
+}
+
+/// Removes a member from an organization.
+///
+/// The shares of the organization's files that other members granted to the member are
+/// revoked. Shares granted by the owner of a file are kept.
+pub fn remove_organization_member(
+    state: &mut State,
+    caller: Principal,
+    org_id: u64,
+    user: Principal,
+) -> Result<(), OrganizationError> {
+    let org = get_organization_as_admin(state, caller, org_id)?;
+    if is_last_admin(org, user) {
+        return Err(OrganizationError::LastAdmin);
+    }
+
+    state
+        .organizations
+        .get_mut(&org_id)
+        .unwrap()
+        .members
+        .remove(&user);
+
+    let org_files: Vec<u64> = get_organization_file_ids(state, org_id);
+    for file_id in org_files {
+        let owner = state
+            .file_data
+            .get(&file_id)
+            .expect("file must exist")
+            .metadata
+            .requester_principal;
+        // Shares without a grant predate organizations and were granted by the owner.
+        let granted_by_member = state
+            .share_grants
+            .get(&file_id)
+            .and_then(|grants| grants.get(&user))
+            .map_or(false, |grant| grant.granted_by != owner);
+        if granted_by_member {
+            remove_share(state, user, file_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes an organization the owner of a file.
+///
+/// The caller must own the file and be an admin or an editor of the organization.
+pub fn add_file_to_organization(
+    state: &mut State,
+    caller: Principal,
+    file_id: u64,
+    org_id: u64,
+) -> Result<(), OrganizationError> {
+    let org = state
+        .organizations
+        .get(&org_id)
+        .ok_or(OrganizationError::NotFound)?;
+    if !can_edit(org.members.get(&caller).copied()) {
+        return Err(OrganizationError::PermissionError);
+    }
+    if !state.file_data.contains_key(&file_id) {
+        return Err(OrganizationError::NotFoundFile);
+    }
+    if !is_file_owned_by_me(state, file_id, caller) {
+        return Err(OrganizationError::PermissionError);
+    }
+
+    state.file_organizations.insert(file_id, org_id);
+    Ok(())
+}
+
+/// Requests a file on behalf of an organization.
+///
+/// The file is owned by the organization and encrypted for the caller, who can then share it
+/// with the other members.
+pub fn request_organization_file<S: Into<String>>(
+    caller: Principal,
+    org_id: u64,
+    request_name: S,
+    state: &mut State,
+) -> Result<String, OrganizationError> {
+    let org = state
+        .organizations
+        .get(&org_id)
+        .ok_or(OrganizationError::NotFound)?;
+    if !can_edit(org.members.get(&caller).copied()) {
+        return Err(OrganizationError::PermissionError);
+    }
+
+    let alias = request_file(caller, request_name, state);
+    let file_id = *state.file_alias_index.get(&alias).unwrap();
+    state.file_organizations.insert(file_id, org_id);
+
+    Ok(alias)
+}
+
+/// Returns the organizations the caller is a member of.
+///
+/// Members that are no longer registered users aren't listed.
+pub fn get_organizations(state: &State, caller: Principal) -> Vec<PublicOrganization> {
+    state
+        .organizations
+        .iter()
+        .filter_map(|(org_id, org)| {
+            org.members.get(&caller).map(|role| PublicOrganization {
+                org_id: *org_id,
+                name: org.name.clone(),
+                role: *role,
+                members: org
+                    .members
+                    .iter()
+                    .filter_map(|(principal, role)| {
+                        let user = state.users.get(principal)?;
+                        Some(OrganizationMember {
+                            user: PublicUser {
+                                username: user.username.clone(),
+                                public_key: user.public_key.clone(),
+                                ic_principal: *principal,
+                            },
+                            role: *role,
+                        })
+                    })
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the files owned by an organization the caller is a member of.
+pub fn get_organization_files(
+    state: &State,
+    caller: Principal,
+    org_id: u64,
+) -> Result<Vec<PublicFileMetadata>, OrganizationError> {
+    let org = state
+        .organizations
+        .get(&org_id)
+        .ok_or(OrganizationError::NotFound)?;
+    if !org.members.contains_key(&caller) {
+        return Err(OrganizationError::PermissionError);
+    }
+
+    Ok(get_organization_file_ids(state, org_id)
+        .into_iter()
+        .map(|file_id| PublicFileMetadata {
+            file_id,
+            file_name: state
+                .file_data
+                .get(&file_id)
+                .expect("file must exist")
+                .metadata
+                .file_name
+                .clone(),
+            shared_with: get_allowed_users(state, file_id),
+            file_status: get_file_status(state, file_id),
+        })
+        .collect())
+}
+
+/// Returns the role of the user in the organization that owns the file, if any.
+pub(crate) fn get_file_role(state: &State, file_id: u64, user: Principal) -> Option<Role> {
+    state
+        .file_organizations
+        .get(&file_id)
+        .and_then(|org_id| state.organizations.get(org_id))
+        .and_then(|org| org.members.get(&user).copied())
+}
+
+/// Returns true if the role allows sharing files and requesting files for the organization.
+pub(crate) fn can_edit(role: Option<Role>) -> bool {
+    matches!(role, Some(Role::Admin) | Some(Role::Editor))
+}
+
+fn get_organization_file_ids(state: &State, org_id: u64) -> Vec<u64> {
+    state
+        .file_organizations
+        .iter()
+        .filter(|(_, id)| **id == org_id)
+        .map(|(file_id, _)| *file_id)
+        .collect()
+}
+
+fn get_organization_as_admin(
+    state: &State,
+    caller: Principal,
+    org_id: u64,
+) -> Result<&Organization, OrganizationError> {
+    let org = state
+        .organizations
+        .get(&org_id)
+        .ok_or(OrganizationError::NotFound)?;
+    match org.members.get(&caller) {
+        Some(Role::Admin) => Ok(org),
+        _ => Err(OrganizationError::PermissionError),
+    }
+}
+
+pub(crate) fn is_last_admin(org: &Organization, user: Principal) -> bool {
+    org.members.get(&user) == Some(&Role::Admin)
+        && org
+            .members
+            .values()
+            .filter(|role| **role == Role::Admin)
+            .count()
+            == 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            download_file, get_shared_files, revoke_share, set_user_info, share_file, upload_file,
+        },
+        env::TestEnvironment,
+        Config, FileDownloadResponse, FileSharingResponse, User,
+    };
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 0])
+    }
+
+    fn admin() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn editor() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn viewer() -> Principal {
+        Principal::from_slice(&[0, 1, 3])
+    }
+
+    fn outsider() -> Principal {
+        Principal::from_slice(&[0, 1, 4])
+    }
+
+    /// Sets up an organization with a member of every role, and an uploaded file owned by
+    /// the organization that is shared with every member.
+    fn setup() -> (State, u64) {
+        let mut state = State::default();
+        for principal in [owner(), admin(), editor(), viewer(), outsider()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+
+        let org_id = create_organization(&mut state, owner(), "ACME").unwrap();
+        for (user, role) in [
+            (admin(), Role::Admin),
+            (editor(), Role::Editor),
+            (viewer(), Role::Viewer),
+        ] {
+            set_organization_member(&mut state, owner(), org_id, user, role).unwrap();
+        }
+
+        request_organization_file(owner(), org_id, "contract", &mut state).unwrap();
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "pdf".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        for user in [admin(), editor(), viewer()] {
+            share_file(&mut state, owner(), user, 0, vec![4, 5, 6]);
+        }
+
+        (state, org_id)
+    }
+
+    #[test]
+    fn every_role_can_download() {
+        let (state, _) = setup();
+
+        for user in [admin(), editor(), viewer()] {
+            assert!(matches!(
+                download_file(&state, 0, 0, user),
+                FileDownloadResponse::FoundFile(_)
+            ));
+        }
+        assert_eq!(
+            download_file(&state, 0, 0, outsider()),
+            FileDownloadResponse::PermissionError
+        );
+    }
+
+    #[test]
+    fn members_shares_expire() {
+        let (mut state, _) = setup();
+        let env = TestEnvironment::default();
+        state.env = Box::new(env.clone());
+        state.config = Config {
+            share_ttl_ns: Some(1_000),
+            ..Config::default()
+        };
+        env.advance_time(1_000);
+
+        // Members download with the keys shared with them, which expire like any other share.
+        for user in [admin(), editor(), viewer()] {
+            assert_eq!(
+                download_file(&state, 0, 0, user),
+                FileDownloadResponse::PermissionError
+            );
+        }
+    }
+
+    #[test]
+    fn members_cannot_download_without_a_key() {
+        let (mut state, org_id) = setup();
+        set_organization_member(&mut state, owner(), org_id, outsider(), Role::Admin).unwrap();
+
+        assert_eq!(
+            download_file(&state, 0, 0, outsider()),
+            FileDownloadResponse::PermissionError
+        );
+    }
+
+    #[test]
+    fn only_registered_users_create_organizations() {
+        let mut state = State::default();
+
+        assert_eq!(
+            create_organization(&mut state, Principal::anonymous(), "ACME"),
+            Err(OrganizationError::PermissionError)
+        );
+        assert_eq!(
+            create_organization(&mut state, outsider(), "ACME"),
+            Err(OrganizationError::UnknownUser)
+        );
+        assert!(state.organizations.is_empty());
+    }
+
+    #[test]
+    fn unregistered_members_are_not_listed() {
+        let (mut state, _) = setup();
+        state.users.remove(&viewer());
+
+        let members = &get_organizations(&state, owner())[0].members;
+        assert_eq!(members.len(), 3);
+        assert!(members
+            .iter()
+            .all(|member| member.user.ic_principal != viewer()));
+    }
+
+    #[test]
+    fn admin_can_share_and_revoke() {
+        let (mut state, _) = setup();
+
+        assert_eq!(
+            share_file(&mut state, admin(), outsider(), 0, vec![7]),
+            FileSharingResponse::Ok
+        );
+        assert_eq!(
+            revoke_share(&mut state, admin(), outsider(), 0),
+            FileSharingResponse::Ok
+        );
+        assert!(get_shared_files(&state, outsider()).is_empty());
+    }
+
+    #[test]
+    fn editor_can_share_but_not_revoke() {
+        let (mut state, _) = setup();
+
+        assert_eq!(
+            share_file(&mut state, editor(), outsider(), 0, vec![7]),
+            FileSharingResponse::Ok
+        );
+        // The share of the viewer was granted by the owner.
+        assert_eq!(
+            revoke_share(&mut state, editor(), viewer(), 0),
+            FileSharingResponse::PermissionError
+        );
+    }
+
+    #[test]
+    fn viewer_can_neither_share_nor_revoke() {
+        let (mut state, _) = setup();
+
+        assert_eq!(
+            share_file(&mut state, viewer(), outsider(), 0, vec![7]),
+            FileSharingResponse::PermissionError
+        );
+        assert_eq!(
+            revoke_share(&mut state, viewer(), editor(), 0),
+            FileSharingResponse::PermissionError
+        );
+    }
+
+    #[test]
+    fn only_admins_manage_members() {
+        let (mut state, org_id) = setup();
+
+        assert_eq!(
+            set_organization_member(&mut state, editor(), org_id, outsider(), Role::Viewer),
+            Err(OrganizationError::PermissionError)
+        );
+        assert_eq!(
+            set_organization_member(&mut state, admin(), org_id, outsider(), Role::Viewer),
+            Ok(())
+        );
+        assert_eq!(get_organizations(&state, outsider())[0].role, Role::Viewer);
+    }
+
+    #[test]
+    fn only_editors_and_admins_request_files() {
+        let (mut state, org_id) = setup();
+
+        assert!(request_organization_file(editor(), org_id, "invoice", &mut state).is_ok());
+        assert_eq!(
+            request_organization_file(viewer(), org_id, "invoice", &mut state),
+            Err(OrganizationError::PermissionError)
+        );
+        assert_eq!(
+            get_organization_files(&state, viewer(), org_id)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            get_organization_files(&state, outsider(), org_id),
+            Err(OrganizationError::PermissionError)
+        );
+    }
+
+    #[test]
+    fn removed_members_lose_the_shares_of_members() {
+        let (mut state, org_id) = setup();
+        set_organization_member(&mut state, owner(), org_id, outsider(), Role::Viewer).unwrap();
+        share_file(&mut state, editor(), outsider(), 0, vec![7]);
+
+        remove_organization_member(&mut state, admin(), org_id, outsider()).unwrap();
+        remove_organization_member(&mut state, admin(), org_id, viewer()).unwrap();
+
+        // The share granted by the editor is revoked, the one granted by the owner is kept.
+        assert_eq!(
+            download_file(&state, 0, 0, outsider()),
+            FileDownloadResponse::PermissionError
+        );
+        assert!(matches!(
+            download_file(&state, 0, 0, viewer()),
+            FileDownloadResponse::FoundFile(_)
+        ));
+        assert!(get_organizations(&state, outsider()).is_empty());
+        assert!(get_organizations(&state, viewer()).is_empty());
+    }
+
+    #[test]
+    fn last_admin_cannot_be_removed() {
+        let (mut state, org_id) = setup();
+
+        remove_organization_member(&mut state, owner(), org_id, admin()).unwrap();
+        assert_eq!(
+            remove_organization_member(&mut state, owner(), org_id, owner()),
+            Err(OrganizationError::LastAdmin)
+        );
+        assert_eq!(
+            set_organization_member(&mut state, owner(), org_id, owner(), Role::Viewer),
+            Err(OrganizationError::LastAdmin)
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{QuotaError, State, Usage, UserUsage};
+use ic_cdk::export::candid::Principal;
+
+/// Returns an upper bound of the size of an upload, given its first chunk.
+///
+/// Uploaders split files into chunks of the same size, except for the last one.
+pub(crate) fn upload_size(first_chunk: &[u8], num_chunks: u64) -> u64 {
+    (first_chunk.len() as u64).saturating_mul(num_chunks)
+}
+
+/// Checks that storing the given number of bytes and files for the owner doesn't exceed the
+/// owner's quotas or the capacity of the canister.
+pub(crate) fn check_quota(
+    state: &State,
+    owner: Principal,
+    bytes: u64,
+    new_files: u64,
+) -> Result<(), QuotaError> {
+    let usage = state.usage.get(&owner).cloned().unwrap_or_default();
+    if usage.file_count.saturating_add(new_files) > state.config.max_user_files {
+        return Err(QuotaError::UserFiles);
+    }
+    if usage.bytes.saturating_add(bytes) > state.config.max_user_bytes {
+        return Err(QuotaError::UserBytes);
+    }
+    if state.stored_bytes.saturating_add(bytes) > state.config.max_stored_bytes {
+        return Err(QuotaError::Capacity);
+    }
+    Ok(())
+}
+
+/// Accounts stored bytes and files to the owner.
+pub(crate) fn add_usage(state: &mut State, owner: Principal, bytes: u64, new_files: u64) {
+    let usage = state.usage.entry(owner).or_default();
+    usage.file_count += new_files;
+    usage.bytes += bytes;
+    state.stored_bytes += bytes;
+}
+
+/// Releases bytes and files that were accounted to the owner.
+pub(crate) fn release_usage(state: &mut State, owner: Principal, bytes: u64, files: u64) {
+    let usage = state.usage.entry(owner).or_default();
+    usage.file_count = usage.file_count.saturating_sub(files);
+    usage.bytes = usage.bytes.saturating_sub(bytes);
+    state.stored_bytes = state.stored_bytes.saturating_sub(bytes);
+}
+
+/// Moves the usage of a file to a new owner, e.g., when its ownership is transferred.
+///
+/// The quotas of the new owner aren't enforced, as the file is already stored.
+pub(crate) fn move_usage(state: &mut State, file_id: u64, from: Principal, to: Principal) {
+    let bytes = file_bytes(state, file_id);
+    release_usage(state, from, bytes, 1);
+    add_usage(state, to, bytes, 1);
+}
+
+/// Returns the number of bytes stored for all the versions of a file.
+pub(crate) fn file_bytes(state: &State, file_id: u64) -> u64 {
+    let file = match state.file_data.get(&file_id) {
+        Some(file) => file,
+        None => return 0,
+    };
+
+    file.versions.iter().fold(
+        state.version_bytes(file_id, file.version),
+        |bytes, version| bytes + state.version_bytes(file_id, version.version),
+    )
+}
+
+/// Returns the storage consumed by users along with their quotas.
+///
+/// Admins get the usage of every user, while other users only get their own.
+pub fn get_usage(state: &State, caller: Principal) -> Vec<UserUsage> {
+    let to_user_usage = |user: Principal, usage: &Usage| UserUsage {
+        user,
+        file_count: usage.file_count,
+        bytes: usage.bytes,
+        max_files: state.config.max_user_files,
+        max_bytes: state.config.max_user_bytes,
+    };
+
+    if state.admins.contains(&caller) {
+        state
+            .usage
+            .iter()
+            .map(|(user, usage)| to_user_usage(*user, usage))
+            .collect()
+    } else {
+        vec![to_user_usage(
+            caller,
+            &state.usage.get(&caller).cloned().unwrap_or_default(),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            request_file, set_user_info, upload_file, upload_file_atomic, upload_file_version,
+            UploadFileAtomicRequest, UploadFileVersionRequest,
+        },
+        FileVersionError, UploadFileError, User, MAX_FILE_VERSIONS, MAX_STORED_BYTES,
+        MAX_USER_BYTES, MAX_USER_FILES,
+    };
+
+    fn setup() -> State {
+        let mut state = State::default();
+        set_user_info(
+            &mut state,
+            Principal::anonymous(),
+            User {
+                username: "John".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+        state
+    }
+
+    fn atomic_upload(content: Vec<u8>, num_chunks: u64) -> UploadFileAtomicRequest {
+        UploadFileAtomicRequest {
+            name: "file".to_string(),
+            content,
+            owner_key: vec![1, 2, 3],
+            file_type: "image/jpeg".to_string(),
+            num_chunks,
+        }
+    }
+
+    #[test]
+    fn uploads_are_accounted() {
+        let mut state = setup();
+
+        // Only the first of the three chunks is stored.
+        upload_file_atomic(
+            Principal::anonymous(),
+            atomic_upload(vec![1, 2], 3),
+            &mut state,
+        )
+        .unwrap();
+        request_file(Principal::anonymous(), "request", &mut state);
+        upload_file(
+            1,
+            vec![1, 2, 3],
+            "image/jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_usage(&state, Principal::anonymous()),
+            vec![UserUsage {
+                user: Principal::anonymous(),
+                file_count: 2,
+                bytes: 5,
+                max_files: MAX_USER_FILES,
+                max_bytes: MAX_USER_BYTES,
+            }]
+        );
+        assert_eq!(state.stored_bytes, 5);
+    }
+
+    #[test]
+    fn upload_exceeding_user_bytes_fails_before_storing() {
+        let mut state = setup();
+
+        assert_eq!(
+            upload_file_atomic(
+                Principal::anonymous(),
+                atomic_upload(vec![1, 2, 3], MAX_USER_BYTES),
+                &mut state
+            ),
+            Err(UploadFileError::QuotaExceeded(QuotaError::UserBytes))
+        );
+        assert!(state.file_data.is_empty());
+        assert_eq!(state.file_contents.len(), 0);
+        assert_eq!(state.stored_bytes, 0);
+    }
+
+    #[test]
+    fn upload_exceeding_user_files_fails() {
+        let mut state = setup();
+        state.usage.insert(
+            Principal::anonymous(),
+            Usage {
+                file_count: MAX_USER_FILES,
+                bytes: 0,
+            },
+        );
+
+        request_file(Principal::anonymous(), "request", &mut state);
+        assert_eq!(
+            upload_file(
+                0,
+                vec![1, 2, 3],
+                "image/jpeg".to_string(),
+                vec![1, 2, 3],
+                1,
+                &mut state,
+            ),
+            Err(UploadFileError::QuotaExceeded(QuotaError::UserFiles))
+        );
+    }
+
+    #[test]
+    fn upload_exceeding_capacity_fails() {
+        let mut state = setup();
+        state.stored_bytes = MAX_STORED_BYTES;
+
+        assert_eq!(
+            upload_file_atomic(
+                Principal::anonymous(),
+                atomic_upload(vec![1], 1),
+                &mut state
+            ),
+            Err(UploadFileError::QuotaExceeded(QuotaError::Capacity))
+        );
+    }
+
+    #[test]
+    fn pruned_versions_release_storage() {
+        let mut state = setup();
+        upload_file_atomic(
+            Principal::anonymous(),
+            atomic_upload(vec![1, 2], 1),
+            &mut state,
+        )
+        .unwrap();
+
+        for _ in 0..MAX_FILE_VERSIONS {
+            upload_file_version(
+                Principal::anonymous(),
+                UploadFileVersionRequest {
+                    file_id: 0,
+                    content: vec![1, 2],
+                    owner_key: vec![1, 2, 3],
+                    file_type: "image/jpeg".to_string(),
+                    num_chunks: 1,
+                },
+                &mut state,
+            )
+            .unwrap();
+        }
+
+        let usage = &get_usage(&state, Principal::anonymous())[0];
+        assert_eq!(usage.file_count, 1);
+        assert_eq!(usage.bytes, 2 * MAX_FILE_VERSIONS as u64);
+
+        state.usage.get_mut(&Principal::anonymous()).unwrap().bytes = MAX_USER_BYTES;
+        assert_eq!(
+            upload_file_version(
+                Principal::anonymous(),
+                UploadFileVersionRequest {
+                    file_id: 0,
+                    content: vec![1, 2, 3],
+                    owner_key: vec![1, 2, 3],
+                    file_type: "image/jpeg".to_string(),
+                    num_chunks: 1,
+                },
+                &mut state,
+            ),
+            Err(FileVersionError::QuotaExceeded(QuotaError::UserBytes))
+        );
+    }
+}
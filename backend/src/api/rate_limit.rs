@@ -0,0 +1,374 @@
+use crate::{AdminAction, AdminError, FileContent, RateLimit, RateLimitError, RateLimits, State};
+use ic_cdk::export::candid::Principal;
+use std::collections::VecDeque;
+
+use super::admin::{check_admin, record_admin_action};
+
+/// Returns the alias through which a pending file is uploaded.
+pub fn pending_alias(state: &State, file_id: u64) -> Option<&str> {
+    match state.file_data.get(&file_id).map(|file| &file.content) {
+        Some(FileContent::Pending { alias }) => Some(alias),
+        _ => None,
+    }
+}
+
+/// Counts an `upload_file_continue` call of the caller, unless it exceeds the rate limits.
+///
+/// Anonymous calls of uploads that have started aren't counted: the budget they would count
+/// against is shared by all anonymous callers, and the upload already counted against the
+/// budget of its alias when it started.
+pub fn consume_chunk_rate_limit(
+    state: &mut State,
+    caller: Principal,
+    file_id: u64,
+) -> Result<(), RateLimitError> {
+    let started = matches!(
+        state.file_data.get(&file_id).map(|file| &file.content),
+        Some(FileContent::PartiallyUploaded { .. })
+    );
+    if caller == Principal::anonymous() && started {
+        return Ok(());
+    }
+    consume_rate_limit(state, caller, None)
+}
+
+/// Checks, without counting it, whether a call of the caller through the given alias is within
+/// the rate limits.
+pub fn check_rate_limit(
+    state: &State,
+    caller: Principal,
+    alias: Option<&str>,
+) -> Result<(), RateLimitError> {
+    check_rate_limit_at(state, caller, alias, state.env.now())
+}
+
+/// Counts a call of the caller through the given alias, unless it exceeds the rate limits.
+///
+/// Calls that are rejected don't count against the budgets.
+pub fn consume_rate_limit(
+    state: &mut State,
+    caller: Principal,
+    alias: Option<&str>,
+) -> Result<(), RateLimitError> {
+    consume_rate_limit_at(state, caller, alias, state.env.now())
+}
+
+fn check_rate_limit_at(
+    state: &State,
+    caller: Principal,
+    alias: Option<&str>,
+    now: u64,
+) -> Result<(), RateLimitError> {
+    let caller_limit = caller_limit(&state.rate_limits, caller);
+    if counts_against_caller(caller, alias)
+        && is_exhausted(
+            state.rate_limiter.principal_calls.get(&caller),
+            caller_limit,
+            now,
+        )
+    {
+        return Err(RateLimitError::Caller);
+    }
+
+    if let Some(alias) = alias {
+        if is_exhausted(
+            state.rate_limiter.alias_calls.get(alias),
+            state.rate_limits.per_alias,
+            now,
+        ) {
+            return Err(RateLimitError::Alias);
+        }
+    }
+
+    Ok(())
+}
+
+fn consume_rate_limit_at(
+    state: &mut State,
+    caller: Principal,
+    alias: Option<&str>,
+    now: u64,
+) -> Result<(), RateLimitError> {
+    check_rate_limit_at(state, caller, alias, now)?;
+
+    if counts_against_caller(caller, alias) {
+        let caller_limit = caller_limit(&state.rate_limits, caller);
+        record_call(
+            state
+                .rate_limiter
+                .principal_calls
+                .entry(caller)
+                .or_default(),
+            caller_limit,
+            now,
+        );
+    }
+    if let Some(alias) = alias {
+        record_call(
+            state
+                .rate_limiter
+                .alias_calls
+                .entry(alias.to_string())
+                .or_default(),
+            state.rate_limits.per_alias,
+            now,
+        );
+    }
+
+    Ok(())
+}
+
+/// Anonymous calls through an alias are only throttled by the alias, so that a caller spending
+/// the shared anonymous budget can't block the uploads through other aliases.
+fn counts_against_caller(caller: Principal, alias: Option<&str>) -> bool {
+    caller != Principal::anonymous() || alias.is_none()
+}
+
+fn caller_limit(limits: &RateLimits, caller: Principal) -> RateLimit {
+    if caller == Principal::anonymous() {
+        limits.anonymous
+    } else {
+        limits.per_principal
+    }
+}
+
+/// Returns true if the calls within the window already use up the budget.
+fn is_exhausted(calls: Option<&VecDeque<u64>>, limit: RateLimit, now: u64) -> bool {
+    let calls_in_window = calls.map_or(0, |calls| {
+        calls
+            .iter()
+            .filter(|time| is_in_window(**time, limit, now))
+            .count() as u64
+    });
+    calls_in_window >= limit.max_calls
+}
+
+/// Records a call, dropping the calls that are out of the window.
+fn record_call(calls: &mut VecDeque<u64>, limit: RateLimit, now: u64) {
+    while calls
+        .front()
+        .map_or(false, |time| !is_in_window(*time, limit, now))
+    {
+        calls.pop_front();
+    }
+    calls.push_back(now);
+}
+
+fn is_in_window(time: u64, limit: RateLimit, now: u64) -> bool {
+    now.saturating_sub(time) < limit.window_ns
+}
+
+/// Returns the budgets of the throttled endpoints.
+pub fn get_rate_limits(state: &State) -> RateLimits {
+    state.rate_limits
+}
+
+/// Sets the budgets of the throttled endpoints. Only admins can set them.
+pub fn set_rate_limits(
+    state: &mut State,
+    caller: Principal,
+    rate_limits: RateLimits,
+) -> Result<(), AdminError> {
+    check_admin(state, caller)?;
+    state.rate_limits = rate_limits;
+    record_admin_action(
+        state,
+        caller,
+        AdminAction::RateLimitsUpdated { rate_limits },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{inspect_message, request_file, set_user_info, upload_file, Message},
+        User,
+    };
+
+    const WINDOW: u64 = 1_000;
+
+    fn user() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn setup() -> State {
+        let mut state = State::default();
+        let limit = RateLimit {
+            max_calls: 2,
+            window_ns: WINDOW,
+        };
+        state.rate_limits = RateLimits {
+            per_principal: limit,
+            anonymous: RateLimit {
+                max_calls: 3,
+                window_ns: WINDOW,
+            },
+            per_alias: limit,
+        };
+        state
+    }
+
+    #[test]
+    fn calls_are_limited_within_a_sliding_window() {
+        let mut state = setup();
+
+        assert_eq!(consume_rate_limit_at(&mut state, user(), None, 0), Ok(()));
+        assert_eq!(consume_rate_limit_at(&mut state, user(), None, 500), Ok(()));
+        assert_eq!(
+            check_rate_limit_at(&state, user(), None, 999),
+            Err(RateLimitError::Caller)
+        );
+        assert_eq!(
+            consume_rate_limit_at(&mut state, user(), None, 999),
+            Err(RateLimitError::Caller)
+        );
+
+        // The first call leaves the window, while the second one is still in it.
+        assert_eq!(
+            consume_rate_limit_at(&mut state, user(), None, WINDOW),
+            Ok(())
+        );
+        assert_eq!(
+            consume_rate_limit_at(&mut state, user(), None, WINDOW + 1),
+            Err(RateLimitError::Caller)
+        );
+        assert_eq!(
+            state.rate_limiter.principal_calls[&user()],
+            VecDeque::from(vec![500, WINDOW])
+        );
+    }
+
+    #[test]
+    fn anonymous_callers_share_a_budget() {
+        let mut state = setup();
+
+        for _ in 0..3 {
+            assert_eq!(
+                consume_rate_limit_at(&mut state, Principal::anonymous(), None, 0),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            consume_rate_limit_at(&mut state, Principal::anonymous(), None, 0),
+            Err(RateLimitError::Caller)
+        );
+        assert_eq!(consume_rate_limit_at(&mut state, user(), None, 0), Ok(()));
+    }
+
+    #[test]
+    fn calls_are_limited_per_alias() {
+        let mut state = setup();
+        state.rate_limits.per_principal.max_calls = 10;
+
+        for _ in 0..2 {
+            assert_eq!(
+                consume_rate_limit_at(&mut state, user(), Some("alias"), 0),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            consume_rate_limit_at(&mut state, user(), Some("alias"), 0),
+            Err(RateLimitError::Alias)
+        );
+        assert_eq!(
+            consume_rate_limit_at(&mut state, user(), Some("other alias"), 0),
+            Ok(())
+        );
+
+        // Rejected calls don't count against the budget of the caller.
+        assert_eq!(state.rate_limiter.principal_calls[&user()].len(), 3);
+    }
+
+    #[test]
+    fn anonymous_calls_through_an_alias_are_only_limited_by_the_alias() {
+        let mut state = setup();
+
+        for _ in 0..2 {
+            assert_eq!(
+                consume_rate_limit_at(&mut state, Principal::anonymous(), Some("spam"), 0),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            consume_rate_limit_at(&mut state, Principal::anonymous(), Some("spam"), 0),
+            Err(RateLimitError::Alias)
+        );
+        assert_eq!(
+            state
+                .rate_limiter
+                .principal_calls
+                .get(&Principal::anonymous()),
+            None
+        );
+    }
+
+    #[test]
+    fn spammers_cannot_block_uploads_through_other_aliases() {
+        let mut state = setup();
+        set_user_info(
+            &mut state,
+            user(),
+            User {
+                username: "user".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+        let spammed_alias = request_file(user(), "spammed", &mut state);
+        let alias = request_file(user(), "request", &mut state);
+
+        // A spammer uses up the budgets of an alias and of the anonymous callers.
+        while consume_rate_limit(&mut state, Principal::anonymous(), Some(&spammed_alias)).is_ok() {
+        }
+        while consume_rate_limit(&mut state, Principal::anonymous(), None).is_ok() {}
+
+        // The uploads through the other alias go through, chunk by chunk.
+        let file_id = 1;
+        assert_eq!(pending_alias(&state, file_id), Some(alias.as_str()));
+        assert_eq!(
+            consume_rate_limit(&mut state, Principal::anonymous(), Some(&alias)),
+            Ok(())
+        );
+        upload_file(file_id, vec![1], "jpeg".to_string(), vec![1], 3, &mut state).unwrap();
+        for _ in 0..5 {
+            assert_eq!(
+                consume_chunk_rate_limit(&mut state, Principal::anonymous(), file_id),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            inspect_message(
+                &state,
+                Principal::anonymous(),
+                &Message {
+                    method: "upload_file_continue",
+                    arg_size: 100,
+                    shared_file_id: None,
+                }
+            ),
+            Ok(())
+        );
+
+        // Chunks of uploads that haven't started still count against the anonymous budget.
+        assert_eq!(
+            consume_chunk_rate_limit(&mut state, Principal::anonymous(), 0),
+            Err(RateLimitError::Caller)
+        );
+    }
+
+    #[test]
+    fn only_admins_set_rate_limits() {
+        let mut state = setup();
+        let limits = RateLimits::default();
+
+        assert_eq!(
+            set_rate_limits(&mut state, user(), limits),
+            Err(AdminError::PermissionError)
+        );
+
+        state.admins.insert(user());
+        assert_eq!(set_rate_limits(&mut state, user(), limits), Ok(()));
+        assert_eq!(get_rate_limits(&state), limits);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{certification::certify_alias, Checklist, PublicChecklist, PublicFileMetadata, State};
+use ic_cdk::export::Principal;
+
+use super::get_requests::{get_allowed_users, get_file_status};
+use super::request_file::add_pending_file;
+
+/// Requests several files through a single alias.
+///
+/// Every item of the checklist becomes its own pending file, which can be uploaded
+/// independently with `upload_file`. The alias remains valid until all items are uploaded.
+pub fn request_checklist<S: Into<String>>(
+    caller: Principal,
+    name: S,
+    items: Vec<String>,
+    state: &mut State,
+) -> String {
+    assert!(!items.is_empty(), "a checklist must have at least one item");
+
+    let alias = state.generate_alias();
+
+    let file_ids = items
+        .into_iter()
+        .map(|item| add_pending_file(caller, item, alias.clone(), state))
+        .collect();
+
+    state.checklists.insert(
+        alias.clone(),
+        Checklist {
+            name: name.into(),
+            requester_principal: caller,
+            requested_at: state.env.now(),
+            file_ids,
+        },
+    );
+    certify_alias(state, &alias);
+
+    alias
+}
+
+/// Returns the checklists requested by the caller, along with the status of each item.
+pub fn get_checklists(state: &State, caller: Principal) -> Vec<PublicChecklist> {
+    state
+        .checklists
+        .iter()
+        .filter(|(_, checklist)| checklist.requester_principal == caller)
+        .map(|(alias, checklist)| PublicChecklist {
+            alias: alias.clone(),
+            name: checklist.name.clone(),
+            requested_at: checklist.requested_at,
+            items: checklist
+                .file_ids
+                .iter()
+                .map(|file_id| PublicFileMetadata {
+                    file_id: *file_id,
+                    file_name: state
+                        .file_data
+                        .get(file_id)
+                        .expect("file must exist")
+                        .metadata
+                        .file_name
+                        .clone(),
+                    shared_with: get_allowed_users(state, *file_id),
+                    file_status: get_file_status(state, *file_id),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{get_alias_info, set_user_info, upload_file},
+        AliasItem, FileStatus, GetAliasInfoError, User,
+    };
+
+    fn setup() -> State {
+        let mut state = State::default();
+        set_user_info(
+            &mut state,
+            Principal::anonymous(),
+            User {
+                username: "John".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+        state
+    }
+
+    fn items() -> Vec<String> {
+        vec![
+            "passport".to_string(),
+            "proof of address".to_string(),
+            "bank statement".to_string(),
+        ]
+    }
+
+    #[test]
+    fn alias_info_lists_all_items() {
+        let mut state = setup();
+        let alias = request_checklist(Principal::anonymous(), "onboarding", items(), &mut state);
+
+        let info = get_alias_info(&state, alias).unwrap();
+        assert_eq!(info.file_name, "onboarding");
+        assert_eq!(
+            info.items,
+            vec![
+                AliasItem {
+                    file_id: 0,
+                    file_name: "passport".to_string(),
+                    pending: true,
+                },
+                AliasItem {
+                    file_id: 1,
+                    file_name: "proof of address".to_string(),
+                    pending: true,
+                },
+                AliasItem {
+                    file_id: 2,
+                    file_name: "bank statement".to_string(),
+                    pending: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn items_are_uploaded_independently() {
+        let mut state = setup();
+        let alias = request_checklist(Principal::anonymous(), "onboarding", items(), &mut state);
+
+        // Upload the second item.
+        upload_file(
+            1,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+
+        // The alias remains valid and the uploaded item is no longer pending.
+        let info = get_alias_info(&state, alias.clone()).unwrap();
+        assert_eq!(info.file_id, 0);
+        assert_eq!(
+            info.items.iter().map(|i| i.pending).collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+
+        // The requester sees the status of every item.
+        let checklists = get_checklists(&state, Principal::anonymous());
+        assert_eq!(checklists.len(), 1);
+        assert_eq!(
+            checklists[0]
+                .items
+                .iter()
+                .map(|item| item.file_status.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                FileStatus::Pending {
+                    alias: alias.clone(),
+                    requested_at: state.env.now()
+                },
+                FileStatus::Uploaded {
+                    uploaded_at: state.env.now(),
+                    document_key: vec![1, 2, 3]
+                },
+                FileStatus::Pending {
+                    alias: alias.clone(),
+                    requested_at: state.env.now()
+                },
+            ]
+        );
+
+        // Upload the remaining items.
+        for file_id in [0, 2] {
+            upload_file(
+                file_id,
+                vec![1, 2, 3],
+                "jpeg".to_string(),
+                vec![1, 2, 3],
+                1,
+                &mut state,
+            )
+            .unwrap();
+        }
+
+        // The alias is no longer valid.
+        assert_eq!(
+            get_alias_info(&state, alias),
+            Err(GetAliasInfoError::NotFound)
+        );
+    }
+}
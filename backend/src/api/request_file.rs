@@ -1,6 +1,10 @@
-use crate::{get_time, File, FileContent, FileMetadata, State};
+use crate::{
+    certification::{certify_alias, certify_file},
+    AuditEvent, File, FileContent, FileMetadata, State,
+};
 use ic_cdk::export::Principal;
 
+use super::audit_log::record_event;
 use super::user_info::get_user_key;
 
 /// Requests a file,
@@ -10,26 +14,40 @@ pub fn request_file<S: Into<String>>(
     state: &mut State,
 ) -> String {
     // TODO: verify that file alias has not been used before.
-    let alias = state.alias_generator.next();
+    let alias = state.generate_alias();
 
+    let file_id = add_pending_file(caller, request_name.into(), alias.clone(), state);
+
+    state.file_alias_index.insert(alias.clone(), file_id);
+    certify_alias(state, &alias);
+
+    alias
+}
+
+/// Adds a pending file that can be uploaded using the given alias, and returns its ID.
+pub(crate) fn add_pending_file(
+    caller: Principal,
+    file_name: String,
+    alias: String,
+    state: &mut State,
+) -> u64 {
     let file_id = state.generate_file_id();
 
     let file = File {
         metadata: FileMetadata {
-            file_name: request_name.into(),
+            file_name,
             user_public_key: get_user_key(state, caller),
             requester_principal: caller,
-            requested_at: get_time(),
+            requested_at: state.env.now(),
             uploaded_at: None,
         },
-        content: FileContent::Pending {
-            alias: alias.clone(),
-        },
+        content: FileContent::Pending { alias },
+        version: 0,
+        versions: vec![],
     };
 
     state.file_data.insert(file_id, file);
-
-    state.file_alias_index.insert(alias.clone(), file_id);
+    certify_file(state, file_id);
 
     // The caller is the owner of this file.
     state
@@ -38,12 +56,14 @@ pub fn request_file<S: Into<String>>(
         .or_insert_with(Vec::new)
         .push(file_id);
 
-    alias
+    record_event(state, caller, AuditEvent::FileRequested { file_id });
+
+    file_id
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{api::set_user_info, User};
+    use crate::{api::set_user_info, env::TestEnvironment, User};
 
     use super::*;
     use maplit::btreemap;
@@ -69,10 +89,12 @@ mod test {
                         file_name: "request".to_string(),
                         user_public_key: get_user_key(&state, Principal::anonymous()),
                         requester_principal: Principal::anonymous(),
-                        requested_at: get_time(),
+                        requested_at: state.env.now(),
                         uploaded_at: None,
                     },
-                    content: FileContent::Pending { alias: "puzzling-mountain".to_string() }
+                    content: FileContent::Pending { alias: "past-apparel".to_string() },
+                    version: 0,
+                    versions: vec![],
                 }
             }
         );
@@ -108,4 +130,31 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn aliases_are_drawn_from_the_environment() {
+        let env = TestEnvironment::default();
+        let mut state = State::new(Box::new(env.clone()));
+        set_user_info(
+            &mut state,
+            Principal::anonymous(),
+            User {
+                username: "John".to_string(),
+                public_key: vec![1, 2, 3],
+            },
+        );
+        env.set_seed([1; 32]);
+        let alias = request_file(Principal::anonymous(), "request".to_string(), &mut state);
+        assert_ne!(
+            request_file(Principal::anonymous(), "request".to_string(), &mut state),
+            alias
+        );
+
+        // The same seed draws the same alias again.
+        env.set_seed([1; 32]);
+        assert_eq!(
+            request_file(Principal::anonymous(), "request".to_string(), &mut state),
+            alias
+        );
+    }
 }
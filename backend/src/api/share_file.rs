@@ -1,8 +1,20 @@
-use crate::{FileContent, FileSharingResponse, PublicFileMetadata, State};
+use crate::{
+    AuditEvent, FileContent, FileSharingResponse, NotificationEvent, PublicFileMetadata,
+    PublicShare, PublicUser, Role, ShareGrant, SharePermission, State,
+};
 use ic_cdk::export::candid::Principal;
 
+use super::audit_log::record_event;
+use super::download_file::is_file_shared_with_me;
 use super::get_requests::{get_allowed_users, get_file_status};
+use super::notifications::notify;
+use super::organizations::{can_edit, get_file_role};
+use super::webhooks::{enqueue_webhook_event, WebhookEvent};
 
+/// Shares a file with a user.
+///
+/// A new share only allows viewing the file. Sharing again with the same user updates their
+/// key and keeps their current permission and download limit.
 pub fn share_file(
     state: &mut State,
     caller: Principal,
@@ -10,7 +22,85 @@ pub fn share_file(
     file_id: u64,
     file_key_encrypted_for_user: Vec<u8>,
 ) -> FileSharingResponse {
-    if !can_share(state, caller, file_id) {
+    let (permission, downloads_remaining) = state
+        .share_grants
+        .get(&file_id)
+        .and_then(|grants| grants.get(&sharing_with))
+        .map_or((SharePermission::View, None), |grant| {
+            (grant.permission, grant.downloads_remaining)
+        });
+
+    grant_share(
+        state,
+        caller,
+        sharing_with,
+        file_id,
+        file_key_encrypted_for_user,
+        permission,
+        downloads_remaining,
+    )
+}
+
+/// Shares a file with a user at the given permission level.
+///
+/// Owners, co-owners and the admins and editors of the owning organization can grant any
+/// permission. Recipients with the reshare permission can grant view and reshare permissions.
+/// Only owners reset the download limit of an existing share, and who granted it and when.
+pub fn share_file_with_permission(
+    state: &mut State,
+    caller: Principal,
+    sharing_with: Principal,
+    file_id: u64,
+    file_key_encrypted_for_user: Vec<u8>,
+    permission: SharePermission,
+) -> FileSharingResponse {
+    grant_share(
+        state,
+        caller,
+        sharing_with,
+        file_id,
+        file_key_encrypted_for_user,
+        permission,
+        None,
+    )
+}
+
+/// Shares a file with a user for a limited number of downloads.
+///
+/// The share only allows viewing the file, and must be downloaded with
+/// `download_file_audited` so that downloads are counted. It is revoked once the last
+/// download completes.
+pub fn share_file_with_download_limit(
+    state: &mut State,
+    caller: Principal,
+    sharing_with: Principal,
+    file_id: u64,
+    file_key_encrypted_for_user: Vec<u8>,
+    max_downloads: u64,
+) -> FileSharingResponse {
+    assert!(max_downloads > 0, "at least one download must be allowed");
+
+    grant_share(
+        state,
+        caller,
+        sharing_with,
+        file_id,
+        file_key_encrypted_for_user,
+        SharePermission::View,
+        Some(max_downloads),
+    )
+}
+
+fn grant_share(
+    state: &mut State,
+    caller: Principal,
+    sharing_with: Principal,
+    file_id: u64,
+    file_key_encrypted_for_user: Vec<u8>,
+    permission: SharePermission,
+    downloads_remaining: Option<u64>,
+) -> FileSharingResponse {
+    if !can_share(state, caller, sharing_with, file_id, permission) {
         FileSharingResponse::PermissionError
     } else {
         let file = state.file_data.get_mut(&file_id).unwrap();
@@ -19,14 +109,75 @@ pub fn share_file(
                 FileSharingResponse::PendingError
             }
             FileContent::Uploaded { shared_keys, .. } => {
+                let previous_grant = state
+                    .share_grants
+                    .get(&file_id)
+                    .and_then(|grants| grants.get(&sharing_with))
+                    .cloned();
+                let previous_permission = previous_grant.as_ref().map(|grant| grant.permission);
                 let file_shares = state
                     .file_shares
                     .entry(sharing_with)
                     .or_insert_with(Vec::new);
 
-                if !file_shares.contains(&file_id) {
+                let is_new_share = !file_shares.contains(&file_id);
+                if is_new_share {
                     file_shares.push(file_id);
-                    shared_keys.insert(sharing_with, file_key_encrypted_for_user);
+                }
+
+                // Sharing again with the same user updates their key, e.g., when a new
+                // version of the file has been uploaded.
+                shared_keys.insert(sharing_with, file_key_encrypted_for_user);
+
+                // Only owners can lift the download limit of a share or take it over.
+                let grant = match previous_grant {
+                    Some(grant) if !is_owner(state, caller, file_id) => ShareGrant {
+                        permission,
+                        ..grant
+                    },
+                    _ => ShareGrant {
+                        permission,
+                        granted_by: caller,
+                        granted_at: state.env.now(),
+                        downloads_remaining,
+                    },
+                };
+                state
+                    .share_grants
+                    .entry(file_id)
+                    .or_default()
+                    .insert(sharing_with, grant);
+
+                record_event(
+                    state,
+                    caller,
+                    AuditEvent::FileShared {
+                        file_id,
+                        user: sharing_with,
+                        permission,
+                    },
+                );
+                if previous_permission != Some(permission) {
+                    enqueue_webhook_event(
+                        state,
+                        file_id,
+                        WebhookEvent::FileShared {
+                            user: sharing_with,
+                            by: caller,
+                            permission,
+                        },
+                    );
+                }
+                if is_new_share {
+                    notify(
+                        state,
+                        sharing_with,
+                        file_id,
+                        NotificationEvent::FileShared {
+                            by: caller,
+                            permission,
+                        },
+                    );
                 }
 
                 FileSharingResponse::Ok
@@ -35,40 +186,186 @@ pub fn share_file(
     }
 }
 
-fn can_share(state: &State, user: Principal, file_id: u64) -> bool {
+fn is_owner(state: &State, user: Principal, file_id: u64) -> bool {
     match state.file_owners.get(&user) {
         None => false,
         Some(arr) => arr.contains(&file_id),
     }
 }
 
+/// Returns the permission of the user's share of the file, if the file is shared with them.
+fn get_share_permission(state: &State, user: Principal, file_id: u64) -> Option<SharePermission> {
+    if !is_file_shared_with_me(state, file_id, user) {
+        return None;
+    }
+    // Shares without a grant predate permission levels and only allow viewing.
+    Some(
+        state
+            .share_grants
+            .get(&file_id)
+            .and_then(|grants| grants.get(&user))
+            .map_or(SharePermission::View, |grant| grant.permission),
+    )
+}
+
+/// Returns the highest permission the user can grant to others for the file.
+pub(crate) fn get_grantable_permission(
+    state: &State,
+    user: Principal,
+    file_id: u64,
+) -> Option<SharePermission> {
+    if is_owner(state, user, file_id) || can_edit(get_file_role(state, file_id, user)) {
+        return Some(SharePermission::CoOwner);
+    }
+    match get_share_permission(state, user, file_id) {
+        Some(SharePermission::View) | None => None,
+        permission => permission,
+    }
+}
+
+/// The caller must be able to grant the requested permission and must not downgrade a share
+/// that is above what they can grant.
+fn can_share(
+    state: &State,
+    user: Principal,
+    sharing_with: Principal,
+    file_id: u64,
+    permission: SharePermission,
+) -> bool {
+    let grantable = match get_grantable_permission(state, user, file_id) {
+        Some(grantable) => grantable,
+        None => return false,
+    };
+    let current = get_share_permission(state, sharing_with, file_id);
+    permission <= grantable && current.map_or(true, |current| current <= grantable)
+}
+
+/// Owners can revoke shares of their files, as can the admins of the owning organization,
+/// co-owners, and the users who granted the share.
+fn can_revoke(state: &State, user: Principal, sharing_with: Principal, file_id: u64) -> bool {
+    is_owner(state, user, file_id)
+        || get_file_role(state, file_id, user) == Some(Role::Admin)
+        || get_share_permission(state, user, file_id) == Some(SharePermission::CoOwner)
+        || state
+            .share_grants
+            .get(&file_id)
+            .and_then(|grants| grants.get(&sharing_with))
+            .map_or(false, |grant| grant.granted_by == user)
+}
+
+/// Removes the file from the user's shares along with the user's keys of every version.
+pub(crate) fn remove_share(state: &mut State, user: Principal, file_id: u64) {
+    if let Some(arr) = state.file_shares.get_mut(&user) {
+        arr.retain(|&val| val != file_id);
+    }
+    if let Some(grants) = state.share_grants.get_mut(&file_id) {
+        grants.remove(&user);
+    }
+    state.download_sessions.remove(&(file_id, user));
+    if let Some(file) = state.file_data.get_mut(&file_id) {
+        match &mut file.content {
+            FileContent::Pending { .. } => {}
+            FileContent::Uploaded { shared_keys, .. }
+            | FileContent::PartiallyUploaded { shared_keys, .. } => {
+                shared_keys.remove(&user);
+            }
+        }
+        for version in &mut file.versions {
+            version.shared_keys.remove(&user);
+        }
+    }
+}
+
 pub fn revoke_share(
     state: &mut State,
     caller: Principal,
     sharing_with: Principal,
     file_id: u64,
 ) -> FileSharingResponse {
-    if !can_share(state, caller, file_id) {
-        FileSharingResponse::PermissionError
-    } else {
-        match state.file_shares.get_mut(&sharing_with) {
-            None => FileSharingResponse::PermissionError,
-            Some(arr) => {
-                arr.retain(|&val| val != file_id);
-                let file = state.file_data.get_mut(&file_id).unwrap();
-                match &mut file.content {
-                    FileContent::Pending { .. } | FileContent::PartiallyUploaded { .. } => {
-                        FileSharingResponse::PendingError
-                    }
-                    FileContent::Uploaded { shared_keys, .. } => {
-                        shared_keys.remove(&sharing_with);
-
-                        FileSharingResponse::Ok
-                    }
-                }
-            }
-        }
+    if !can_revoke(state, caller, sharing_with, file_id) {
+        return FileSharingResponse::PermissionError;
+    }
+    let is_shared = state
+        .file_shares
+        .get(&sharing_with)
+        .map_or(false, |file_ids| file_ids.contains(&file_id));
+    if !is_shared || !state.file_data.contains_key(&file_id) {
+        return FileSharingResponse::PermissionError;
+    }
+
+    remove_share(state, sharing_with, file_id);
+    record_event(
+        state,
+        caller,
+        AuditEvent::ShareRevoked {
+            file_id,
+            user: sharing_with,
+        },
+    );
+    enqueue_webhook_event(
+        state,
+        file_id,
+        WebhookEvent::ShareRevoked {
+            user: sharing_with,
+            by: caller,
+        },
+    );
+    notify(
+        state,
+        sharing_with,
+        file_id,
+        NotificationEvent::ShareRevoked { by: caller },
+    );
+
+    FileSharingResponse::Ok
+}
+
+/// Returns the shares of a file along with their permissions and who granted them.
+///
+/// Only the owner, the recipients of the file and the members of the owning organization can
+/// see the shares.
+pub fn get_file_shares(state: &State, caller: Principal, file_id: u64) -> Vec<PublicShare> {
+    let file = match state.file_data.get(&file_id) {
+        Some(file) => file,
+        None => return vec![],
+    };
+    if !is_owner(state, caller, file_id)
+        && !is_file_shared_with_me(state, file_id, caller)
+        && get_file_role(state, file_id, caller).is_none()
+    {
+        return vec![];
     }
+
+    state
+        .file_shares
+        .iter()
+        .filter(|(_, file_ids)| file_ids.contains(&file_id))
+        .map(|(principal, _)| {
+            let user = state.users.get(principal).unwrap();
+            let grant = state
+                .share_grants
+                .get(&file_id)
+                .and_then(|grants| grants.get(principal))
+                .cloned()
+                .unwrap_or(ShareGrant {
+                    permission: SharePermission::View,
+                    granted_by: file.metadata.requester_principal,
+                    granted_at: file.metadata.uploaded_at.unwrap_or(0),
+                    downloads_remaining: None,
+                });
+            PublicShare {
+                user: PublicUser {
+                    username: user.username.clone(),
+                    public_key: user.public_key.clone(),
+                    ic_principal: *principal,
+                },
+                permission: grant.permission,
+                granted_by: grant.granted_by,
+                granted_at: grant.granted_at,
+                downloads_remaining: grant.downloads_remaining,
+            }
+        })
+        .collect()
 }
 
 pub fn get_shared_files(state: &State, caller: Principal) -> Vec<PublicFileMetadata> {
@@ -76,6 +373,7 @@ pub fn get_shared_files(state: &State, caller: Principal) -> Vec<PublicFileMetad
         None => vec![],
         Some(file_ids) => file_ids
             .iter()
+            .filter(|file_id| is_file_shared_with_me(state, **file_id, caller))
             .map(|file_id| PublicFileMetadata {
                 file_id: *file_id,
                 file_name: state
@@ -96,8 +394,11 @@ pub fn get_shared_files(state: &State, caller: Principal) -> Vec<PublicFileMetad
 mod test {
     use super::*;
     use crate::{
-        api::{request_file, set_user_info, upload_file},
-        get_time, FileStatus, PublicFileMetadata, PublicUser, User,
+        api::{
+            request_file, set_user_info, upload_file, upload_file_version, UploadFileVersionRequest,
+        },
+        env::TestEnvironment,
+        FileStatus, PublicFileMetadata, PublicUser, User,
     };
     use ic_cdk::export::Principal;
 
@@ -174,7 +475,7 @@ mod test {
                     file_id: 0,
                     file_name: "request".to_string(),
                     file_status: FileStatus::Uploaded {
-                        uploaded_at: get_time(),
+                        uploaded_at: state.env.now(),
                         document_key: vec![1, 2, 3],
                     },
                     shared_with: vec![PublicUser {
@@ -187,7 +488,7 @@ mod test {
                     file_id: 2,
                     file_name: "request3".to_string(),
                     file_status: FileStatus::Uploaded {
-                        uploaded_at: get_time(),
+                        uploaded_at: state.env.now(),
                         document_key: vec![1, 2, 3],
                     },
                     shared_with: vec![PublicUser {
@@ -309,7 +610,7 @@ mod test {
                 file_id: 2,
                 file_name: "request3".to_string(),
                 file_status: FileStatus::Uploaded {
-                    uploaded_at: get_time(),
+                    uploaded_at: state.env.now(),
                     document_key: vec![1, 2, 3],
                 },
                 shared_with: vec![PublicUser {
@@ -385,4 +686,218 @@ mod test {
             FileSharingResponse::PermissionError
         );
     }
+
+    fn setup_permissions() -> State {
+        let mut state = State::default();
+        for i in 0..5 {
+            set_user_info(
+                &mut state,
+                Principal::from_slice(&[0, 1, i]),
+                User {
+                    username: format!("user{}", i),
+                    public_key: vec![i],
+                },
+            );
+        }
+
+        request_file(Principal::from_slice(&[0, 1, 0]), "request", &mut state);
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![1, 2, 3],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        state
+    }
+
+    #[test]
+    fn revocations_remove_the_keys_of_every_version() {
+        let mut state = setup_permissions();
+        let owner = Principal::from_slice(&[0, 1, 0]);
+        let viewer = Principal::from_slice(&[0, 1, 2]);
+        share_file(&mut state, owner, viewer, 0, vec![1]);
+        // The new version is never finished.
+        upload_file_version(
+            owner,
+            UploadFileVersionRequest {
+                file_id: 0,
+                content: vec![4],
+                owner_key: vec![5],
+                file_type: "jpeg".to_string(),
+                num_chunks: 2,
+            },
+            &mut state,
+        )
+        .unwrap();
+
+        assert_eq!(
+            revoke_share(&mut state, owner, viewer, 0),
+            FileSharingResponse::Ok
+        );
+        assert!(get_shared_files(&state, viewer).is_empty());
+        assert!(get_file_shares(&state, owner, 0).is_empty());
+        assert!(state.file_data[&0].versions[0].shared_keys.is_empty());
+    }
+
+    #[test]
+    fn reshare_recipient_can_share_onward() {
+        let mut state = setup_permissions();
+        let owner = Principal::from_slice(&[0, 1, 0]);
+        let resharer = Principal::from_slice(&[0, 1, 1]);
+        let viewer = Principal::from_slice(&[0, 1, 2]);
+
+        assert_eq!(
+            share_file_with_permission(
+                &mut state,
+                owner,
+                resharer,
+                0,
+                vec![1],
+                SharePermission::Reshare
+            ),
+            FileSharingResponse::Ok
+        );
+        assert_eq!(
+            share_file(&mut state, resharer, viewer, 0, vec![2]),
+            FileSharingResponse::Ok
+        );
+
+        // Resharers can't grant more than they have.
+        assert_eq!(
+            share_file_with_permission(
+                &mut state,
+                resharer,
+                Principal::from_slice(&[0, 1, 3]),
+                0,
+                vec![3],
+                SharePermission::CoOwner
+            ),
+            FileSharingResponse::PermissionError
+        );
+
+        // Viewers can't share at all.
+        assert_eq!(
+            share_file(
+                &mut state,
+                viewer,
+                Principal::from_slice(&[0, 1, 3]),
+                0,
+                vec![3]
+            ),
+            FileSharingResponse::PermissionError
+        );
+
+        let shares = get_file_shares(&state, owner, 0);
+        assert_eq!(shares.len(), 2);
+        assert_eq!(shares[0].permission, SharePermission::Reshare);
+        assert_eq!(shares[0].granted_by, owner);
+        assert_eq!(shares[1].permission, SharePermission::View);
+        assert_eq!(shares[1].granted_by, resharer);
+    }
+
+    #[test]
+    fn resharing_keeps_the_grant_unless_by_the_owner() {
+        let mut state = setup_permissions();
+        let env = TestEnvironment::default();
+        state.env = Box::new(env.clone());
+        let owner = Principal::from_slice(&[0, 1, 0]);
+        let co_owner = Principal::from_slice(&[0, 1, 1]);
+        let viewer = Principal::from_slice(&[0, 1, 2]);
+        share_file_with_permission(
+            &mut state,
+            owner,
+            co_owner,
+            0,
+            vec![1],
+            SharePermission::CoOwner,
+        );
+        share_file_with_download_limit(&mut state, owner, viewer, 0, vec![2], 2);
+        let grant = |state: &State| state.share_grants[&0][&viewer].clone();
+        let limited = grant(&state);
+
+        // A co-owner can raise the permission, but not lift the limit.
+        env.advance_time(1);
+        assert_eq!(
+            share_file_with_permission(
+                &mut state,
+                co_owner,
+                viewer,
+                0,
+                vec![3],
+                SharePermission::Reshare
+            ),
+            FileSharingResponse::Ok
+        );
+        assert_eq!(
+            grant(&state),
+            ShareGrant {
+                permission: SharePermission::Reshare,
+                ..limited.clone()
+            }
+        );
+
+        // The owner can.
+        share_file_with_permission(&mut state, owner, viewer, 0, vec![4], SharePermission::View);
+        assert_eq!(grant(&state).downloads_remaining, None);
+        assert_eq!(grant(&state).granted_at, state.env.now());
+    }
+
+    #[test]
+    fn co_owner_can_revoke_others() {
+        let mut state = setup_permissions();
+        let owner = Principal::from_slice(&[0, 1, 0]);
+        let co_owner = Principal::from_slice(&[0, 1, 1]);
+        let resharer = Principal::from_slice(&[0, 1, 2]);
+        let viewer = Principal::from_slice(&[0, 1, 3]);
+
+        share_file_with_permission(
+            &mut state,
+            owner,
+            co_owner,
+            0,
+            vec![1],
+            SharePermission::CoOwner,
+        );
+        share_file_with_permission(
+            &mut state,
+            owner,
+            resharer,
+            0,
+            vec![2],
+            SharePermission::Reshare,
+        );
+        share_file(&mut state, owner, viewer, 0, vec![3]);
+
+        // Resharers can only revoke the shares they granted.
+        assert_eq!(
+            revoke_share(&mut state, resharer, viewer, 0),
+            FileSharingResponse::PermissionError
+        );
+        // Resharers can't downgrade a co-owner.
+        assert_eq!(
+            share_file_with_permission(
+                &mut state,
+                resharer,
+                co_owner,
+                0,
+                vec![4],
+                SharePermission::View
+            ),
+            FileSharingResponse::PermissionError
+        );
+
+        assert_eq!(
+            revoke_share(&mut state, co_owner, viewer, 0),
+            FileSharingResponse::Ok
+        );
+        assert_eq!(
+            revoke_share(&mut state, co_owner, resharer, 0),
+            FileSharingResponse::Ok
+        );
+        assert!(get_shared_files(&state, viewer).is_empty());
+        assert_eq!(get_file_shares(&state, owner, 0).len(), 1);
+    }
 }
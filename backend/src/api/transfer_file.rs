@@ -0,0 +1,326 @@
+use crate::{
+    certification::certify_file, FileContent, FileTransfer, FileTransferError, PublicUser, State,
+    TransferOffer,
+};
+use ic_cdk::export::candid::Principal;
+
+use super::download_file::is_file_owned_by_me;
+use super::quotas::{move_usage, release_usage};
+use super::user_info::get_user_key;
+
+/// Offers the ownership of a file to another user.
+///
+/// The file key is encrypted for the recipient, who re-wraps it when accepting the offer.
+/// A new offer for the same file replaces the previous one.
+pub fn offer_file_transfer(
+    state: &mut State,
+    caller: Principal,
+    to: Principal,
+    file_id: u64,
+    file_key_encrypted_for_user: Vec<u8>,
+) -> Result<(), FileTransferError> {
+    if !is_file_owned_by_me(state, file_id, caller) || to == caller {
+        return Err(FileTransferError::PermissionError);
+    }
+    if !state.users.contains_key(&to) {
+        return Err(FileTransferError::UnknownUser);
+    }
+
+    let file = state
+        .file_data
+        .get(&file_id)
+        .ok_or(FileTransferError::NotFoundFile)?;
+    if !matches!(file.content, FileContent::Uploaded { .. }) {
+        return Err(FileTransferError::NotUploadedFile);
+    }
+
+    state.file_transfers.insert(
+        file_id,
+        FileTransfer {
+            to,
+            file_key_encrypted_for_user,
+            offered_at: state.env.now(),
+        },
+    );
+
+    Ok(())
+}
+
+/// Withdraws a pending offer to transfer the ownership of a file.
+pub fn cancel_file_transfer(
+    state: &mut State,
+    caller: Principal,
+    file_id: u64,
+) -> Result<(), FileTransferError> {
+    if !is_file_owned_by_me(state, file_id, caller) {
+        return Err(FileTransferError::PermissionError);
+    }
+
+    state
+        .file_transfers
+        .remove(&file_id)
+        .map(|_| ())
+        .ok_or(FileTransferError::NoOffer)
+}
+
+/// Accepts an offer to become the owner of a file.
+///
+/// The caller supplies the file key wrapped with their own public key. The previous owner
+/// loses access to the file, while the file's shares are kept intact. Previous versions of
+/// the file are deleted, as their keys are only wrapped for the previous owner.
+pub fn accept_file_transfer(
+    state: &mut State,
+    caller: Principal,
+    file_id: u64,
+    owner_key: Vec<u8>,
+) -> Result<(), FileTransferError> {
+    match state.file_transfers.get(&file_id) {
+        Some(transfer) if transfer.to == caller => {}
+        _ => return Err(FileTransferError::NoOffer),
+    }
+
+    let new_owner_public_key = get_user_key(state, caller);
+    let file = state
+        .file_data
+        .get_mut(&file_id)
+        .ok_or(FileTransferError::NotFoundFile)?;
+
+    // Replace the owner key of the current version.
+    match &mut file.content {
+        FileContent::Uploaded {
+            owner_key: current_owner_key,
+            shared_keys,
+            ..
+        } => {
+            *current_owner_key = owner_key;
+            // The new owner no longer needs a share of their own file.
+            shared_keys.remove(&caller);
+        }
+        FileContent::Pending { .. } | FileContent::PartiallyUploaded { .. } => {
+            return Err(FileTransferError::NotUploadedFile)
+        }
+    }
+
+    let previous_owner = file.metadata.requester_principal;
+    file.metadata.requester_principal = caller;
+    file.metadata.user_public_key = new_owner_public_key;
+    let previous_versions = std::mem::take(&mut file.versions);
+
+    for version in previous_versions {
+        let bytes = state.version_bytes(file_id, version.version);
+        release_usage(state, previous_owner, bytes, 0);
+        state.remove_chunks(file_id, version.version, version.num_chunks);
+    }
+    move_usage(state, file_id, previous_owner, caller);
+    certify_file(state, file_id);
+
+    if let Some(files) = state.file_owners.get_mut(&previous_owner) {
+        files.retain(|id| *id != file_id);
+    }
+    state
+        .file_owners
+        .entry(caller)
+        .or_insert_with(Vec::new)
+        .push(file_id);
+
+    if let Some(files) = state.file_shares.get_mut(&caller) {
+        files.retain(|id| *id != file_id);
+    }
+    if let Some(grants) = state.share_grants.get_mut(&file_id) {
+        grants.remove(&caller);
+    }
+    state.download_sessions.remove(&(file_id, caller));
+
+    state.file_transfers.remove(&file_id);
+
+    Ok(())
+}
+
+/// Returns the pending offers to transfer files to the caller.
+pub fn get_transfer_offers(state: &State, caller: Principal) -> Vec<TransferOffer> {
+    state
+        .file_transfers
+        .iter()
+        .filter(|(_, transfer)| transfer.to == caller)
+        .map(|(file_id, transfer)| {
+            let metadata = &state
+                .file_data
+                .get(file_id)
+                .expect("file must exist")
+                .metadata;
+            let owner = state.users.get(&metadata.requester_principal).unwrap();
+            TransferOffer {
+                file_id: *file_id,
+                file_name: metadata.file_name.clone(),
+                from: PublicUser {
+                    username: owner.username.clone(),
+                    public_key: owner.public_key.clone(),
+                    ic_principal: metadata.requester_principal,
+                },
+                file_key_encrypted_for_user: transfer.file_key_encrypted_for_user.clone(),
+                offered_at: transfer.offered_at,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            download_file, get_file_versions, get_requests, get_shared_files, get_usage,
+            request_file, set_user_info, share_file, upload_file, upload_file_version,
+            UploadFileVersionRequest,
+        },
+        FileData, FileDownloadResponse, User,
+    };
+
+    const ALICE: Principal = Principal::anonymous();
+
+    fn bob() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn carol() -> Principal {
+        Principal::from_slice(&[0, 1, 3])
+    }
+
+    fn setup() -> State {
+        let mut state = State::default();
+        for (principal, username, public_key) in [
+            (ALICE, "Alice", vec![1]),
+            (bob(), "Bob", vec![2]),
+            (carol(), "Carol", vec![3]),
+        ] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: username.to_string(),
+                    public_key,
+                },
+            );
+        }
+
+        request_file(ALICE, "request", &mut state);
+        upload_file(
+            0,
+            vec![1, 2, 3],
+            "jpeg".to_string(),
+            vec![10],
+            1,
+            &mut state,
+        )
+        .unwrap();
+        share_file(&mut state, ALICE, carol(), 0, vec![30]);
+        state
+    }
+
+    #[test]
+    fn transfer_updates_ownership_and_keeps_shares() {
+        let mut state = setup();
+
+        assert_eq!(
+            offer_file_transfer(&mut state, ALICE, bob(), 0, vec![20]),
+            Ok(())
+        );
+        assert_eq!(
+            get_transfer_offers(&state, bob())[0].file_key_encrypted_for_user,
+            vec![20]
+        );
+
+        assert_eq!(accept_file_transfer(&mut state, bob(), 0, vec![21]), Ok(()));
+
+        // Bob is the new owner.
+        assert!(get_requests(&state, ALICE).is_empty());
+        assert_eq!(get_requests(&state, bob()).len(), 1);
+        let metadata = &state.file_data.get(&0).unwrap().metadata;
+        assert_eq!(metadata.requester_principal, bob());
+        assert_eq!(metadata.user_public_key, vec![2]);
+        assert_eq!(
+            download_file(&state, 0, 0, bob()),
+            FileDownloadResponse::FoundFile(FileData {
+                contents: vec![1, 2, 3],
+                file_type: "jpeg".to_string(),
+                owner_key: vec![21],
+                num_chunks: 1
+            })
+        );
+        assert_eq!(
+            download_file(&state, 0, 0, ALICE),
+            FileDownloadResponse::PermissionError
+        );
+
+        // Carol's share is intact.
+        assert_eq!(get_shared_files(&state, carol()).len(), 1);
+        assert!(matches!(
+            download_file(&state, 0, 0, carol()),
+            FileDownloadResponse::FoundFile(_)
+        ));
+
+        // The offer is consumed.
+        assert!(get_transfer_offers(&state, bob()).is_empty());
+    }
+
+    #[test]
+    fn only_the_recipient_can_accept() {
+        let mut state = setup();
+        offer_file_transfer(&mut state, ALICE, bob(), 0, vec![20]).unwrap();
+
+        assert_eq!(
+            accept_file_transfer(&mut state, carol(), 0, vec![31]),
+            Err(FileTransferError::NoOffer)
+        );
+    }
+
+    #[test]
+    fn only_the_owner_can_offer() {
+        let mut state = setup();
+
+        assert_eq!(
+            offer_file_transfer(&mut state, carol(), bob(), 0, vec![20]),
+            Err(FileTransferError::PermissionError)
+        );
+    }
+
+    #[test]
+    fn cancelled_offer_cannot_be_accepted() {
+        let mut state = setup();
+        offer_file_transfer(&mut state, ALICE, bob(), 0, vec![20]).unwrap();
+        assert_eq!(cancel_file_transfer(&mut state, ALICE, 0), Ok(()));
+
+        assert_eq!(
+            accept_file_transfer(&mut state, bob(), 0, vec![21]),
+            Err(FileTransferError::NoOffer)
+        );
+    }
+
+    #[test]
+    fn transfer_deletes_previous_versions() {
+        let mut state = setup();
+        upload_file_version(
+            ALICE,
+            UploadFileVersionRequest {
+                file_id: 0,
+                file_type: "jpeg".to_string(),
+                owner_key: vec![11],
+                num_chunks: 1,
+                content: vec![4, 5],
+            },
+            &mut state,
+        )
+        .unwrap();
+        offer_file_transfer(&mut state, ALICE, bob(), 0, vec![20]).unwrap();
+
+        assert_eq!(accept_file_transfer(&mut state, bob(), 0, vec![21]), Ok(()));
+
+        // Only the current version is kept and accounted to Bob.
+        let versions = get_file_versions(&state, bob(), 0).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert!(versions[0].is_current);
+        assert_eq!(state.version_bytes(0, 0), 0);
+        assert_eq!(get_usage(&state, ALICE)[0].bytes, 0);
+        assert_eq!(get_usage(&state, bob())[0].bytes, 2);
+    }
+}
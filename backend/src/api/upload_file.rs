@@ -1,6 +1,13 @@
-use crate::{get_time, FileContent, State, UploadFileError};
+use crate::{
+    certification::{certify_alias, certify_file},
+    ChunkKey, FileContent, State, UploadFileError,
+};
 use std::collections::BTreeMap;
 
+use super::config::has_expired;
+use super::notifications::notify_upload_completed;
+use super::quotas::{add_usage, check_quota, upload_size};
+
 pub fn upload_file(
     file_id: u64,
     contents: Vec<u8>,
@@ -9,6 +16,28 @@ pub fn upload_file(
     num_chunks: u64,
     state: &mut State,
 ) -> Result<(), UploadFileError> {
+    // Check the quotas of the owner before anything is stored.
+    let owner = match state.file_data.get(&file_id) {
+        Some(file) => match file.content {
+            FileContent::Pending { .. }
+                if has_expired(
+                    state,
+                    file.metadata.requested_at,
+                    state.config.request_ttl_ns,
+                ) =>
+            {
+                return Err(UploadFileError::NotRequested)
+            }
+            FileContent::Pending { .. } => file.metadata.requester_principal,
+            FileContent::Uploaded { .. } | FileContent::PartiallyUploaded { .. } => {
+                return Err(UploadFileError::AlreadyUploaded)
+            }
+        },
+        None => return Err(UploadFileError::NotRequested),
+    };
+    check_quota(state, owner, upload_size(&contents, num_chunks), 1)?;
+    add_usage(state, owner, contents.len() as u64, 1);
+
     // Fetch the file.
     let file = match state.file_data.get_mut(&file_id) {
         Some(file) => file,
@@ -36,12 +65,7 @@ pub fn upload_file(
                 };
             }
 
-            file.metadata.uploaded_at = Some(get_time());
-
-            // Add file contents to stable store.
-            let chunk_id = 0;
-            state.file_contents.insert((file_id, chunk_id), contents);
-
+            file.metadata.uploaded_at = Some(state.env.now());
             alias
         }
         FileContent::Uploaded { .. } | FileContent::PartiallyUploaded { .. } => {
@@ -49,11 +73,24 @@ pub fn upload_file(
         }
     };
 
+    // Add file contents to stable store.
+    let chunk_id = 0;
+    let version = state.file_data[&file_id].version;
+    state.insert_chunk(ChunkKey(file_id, version, chunk_id), contents);
+    certify_file(state, file_id);
+
     // The file is now uploaded. Delete the alias from the state.
-    state
-        .file_alias_index
-        .remove(&alias)
-        .expect("alias must exist");
+    // Checklist aliases remain in place, as they are shared by several files.
+    if !state.checklists.contains_key(&alias) {
+        state
+            .file_alias_index
+            .remove(&alias)
+            .expect("alias must exist");
+        certify_alias(state, &alias);
+    }
+    if num_chunks == 1 {
+        notify_upload_completed(state, file_id);
+    }
 
     Ok(())
 }
@@ -107,19 +144,24 @@ mod test {
                         file_name: "request".to_string(),
                         user_public_key: get_user_key(&state, Principal::anonymous()),
                         requester_principal: Principal::anonymous(),
-                        requested_at: get_time(),
-                        uploaded_at: Some(get_time()),
+                        requested_at: state.env.now(),
+                        uploaded_at: Some(state.env.now()),
                     },
                     content: FileContent::Uploaded {
                         file_type: "jpeg".to_string(),
                         owner_key: vec![1,2,3],
                         shared_keys: BTreeMap::new(),
                         num_chunks: 1,
-                    }
+                    },
+                    version: 0,
+                    versions: vec![],
                 }
             }
         );
-        assert_eq!(state.file_contents.get(&(file_id, 0)), Some(vec![1, 2, 3]));
+        assert_eq!(
+            state.file_contents.get(&ChunkKey(file_id, 0, 0)),
+            Some(vec![1, 2, 3])
+        );
 
         // The alias index is empty.
         assert!(state.file_alias_index.is_empty());
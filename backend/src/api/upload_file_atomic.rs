@@ -1,24 +1,26 @@
-use crate::{get_time, File, FileContent, FileMetadata, State};
-use ic_cdk::export::{candid::CandidType, Principal};
-use serde::{Deserialize, Serialize};
+use crate::{
+    certification::certify_file, ChunkKey, File, FileContent, FileMetadata, State,
+    UploadFileAtomicRequest, UploadFileError,
+};
+use ic_cdk::export::Principal;
 use std::collections::BTreeMap;
 
+use super::quotas::{add_usage, check_quota, upload_size};
 use super::user_info::get_user_key;
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct UploadFileAtomicRequest {
-    pub name: String,
-    pub content: Vec<u8>,
-    pub owner_key: Vec<u8>,
-    pub file_type: String,
-    pub num_chunks: u64,
-}
-
 pub fn upload_file_atomic(
     caller: Principal,
     request: UploadFileAtomicRequest,
     state: &mut State,
-) -> u64 {
+) -> Result<u64, UploadFileError> {
+    check_quota(
+        state,
+        caller,
+        upload_size(&request.content, request.num_chunks),
+        1,
+    )?;
+    add_usage(state, caller, request.content.len() as u64, 1);
+
     let file_id = state.generate_file_id();
 
     let content = if request.num_chunks == 1 {
@@ -41,9 +43,7 @@ pub fn upload_file_atomic(
 
     // Add file contents to stable store.
     let chunk_id = 0;
-    state
-        .file_contents
-        .insert((file_id, chunk_id), request.content);
+    state.insert_chunk(ChunkKey(file_id, 0, chunk_id), request.content);
 
     let old_value = state.file_data.insert(
         file_id,
@@ -52,16 +52,19 @@ pub fn upload_file_atomic(
                 file_name: request.name,
                 user_public_key: get_user_key(state, caller),
                 requester_principal: caller,
-                requested_at: get_time(),
-                uploaded_at: Some(get_time()),
+                requested_at: state.env.now(),
+                uploaded_at: Some(state.env.now()),
             },
             content,
+            version: 0,
+            versions: vec![],
         },
     );
 
     if old_value.is_some() {
         panic!("Overwriting an existing file should be impossible.");
     }
+    certify_file(state, file_id);
 
     // Add the caller as the owner of this file.
     state
@@ -70,7 +73,7 @@ pub fn upload_file_atomic(
         .or_insert_with(Vec::new)
         .push(file_id);
 
-    file_id
+    Ok(file_id)
 }
 
 #[cfg(test)]
@@ -103,7 +106,8 @@ mod test {
                 file_type: "image/jpeg".to_string(),
             },
             &mut state,
-        );
+        )
+        .unwrap();
 
         // The file is stored in the state.
         assert_eq!(
@@ -114,19 +118,24 @@ mod test {
                         file_name: "file_name".to_string(),
                         user_public_key: get_user_key(&state, Principal::anonymous()),
                         requester_principal: Principal::anonymous(),
-                        requested_at: get_time(),
-                        uploaded_at: Some(get_time()),
+                        requested_at: state.env.now(),
+                        uploaded_at: Some(state.env.now()),
                     },
                     content: FileContent::Uploaded {
                         file_type: "image/jpeg".to_string(),
                         owner_key: vec![1,2,3],
                         shared_keys: BTreeMap::new(),
                         num_chunks: 1,
-                    }
+                    },
+                    version: 0,
+                    versions: vec![],
                 }
             }
         );
-        assert_eq!(state.file_contents.get(&(0, 0)), Some(vec![1, 2, 3]));
+        assert_eq!(
+            state.file_contents.get(&ChunkKey(0, 0, 0)),
+            Some(vec![1, 2, 3])
+        );
 
         // The alias index is empty.
         assert!(state.file_alias_index.is_empty());
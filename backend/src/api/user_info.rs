@@ -1,8 +1,9 @@
-use crate::{State, User};
+use crate::{certification::certify_user, State, User};
 use ic_cdk::export::candid::Principal;
 
 pub fn set_user_info(state: &mut State, caller: Principal, user: User) {
     state.users.insert(caller, user);
+    certify_user(state, caller);
 }
 
 pub fn username_exists(state: &State, username: String) -> bool {
@@ -0,0 +1,634 @@
+use crate::{
+    PublicWebhook, SharePermission, State, Webhook, WebhookDelivery, WebhookError,
+    WebhookRegistration, MAX_WEBHOOKS, MAX_WEBHOOK_ATTEMPTS, MAX_WEBHOOK_DELIVERIES,
+    WEBHOOK_RETRY_DELAY_NS,
+};
+use hmac::{Hmac, Mac};
+use ic_cdk::export::candid::Principal;
+use serde::{Serialize, Serializer};
+use sha2::Sha256;
+
+const MAX_URL_LENGTH: usize = 2048;
+
+/// The maximum number of requests sent at once, so that a backlog of deliveries doesn't
+/// exhaust the cycles of the canister in a single round.
+const MAX_CONCURRENT_REQUESTS: usize = 10;
+
+/// An event about a file, posted to the webhooks of its owner.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "event")]
+pub(crate) enum WebhookEvent {
+    #[serde(rename = "upload_completed")]
+    UploadCompleted,
+    /// The file was shared with the user, or their permission changed.
+    #[serde(rename = "file_shared")]
+    FileShared {
+        #[serde(serialize_with = "principal_text")]
+        user: Principal,
+        #[serde(serialize_with = "principal_text")]
+        by: Principal,
+        permission: SharePermission,
+    },
+    #[serde(rename = "share_revoked")]
+    ShareRevoked {
+        #[serde(serialize_with = "principal_text")]
+        user: Principal,
+        #[serde(serialize_with = "principal_text")]
+        by: Principal,
+    },
+}
+
+/// The JSON payload of a delivery.
+#[derive(Serialize)]
+struct Payload<'a> {
+    /// The ID of the delivery, which receivers can use to drop duplicates.
+    id: u64,
+    file_id: u64,
+    file_name: &'a str,
+    #[serde(serialize_with = "principal_text")]
+    owner: Principal,
+    created_at: u64,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+fn principal_text<S: Serializer>(principal: &Principal, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&principal.to_text())
+}
+
+/// An HTTP request posting a delivery to its webhook.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WebhookRequest {
+    pub delivery_id: u64,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Registers a webhook the events of the files of the caller are posted to.
+///
+/// The payloads are signed with the secret, which is only returned here.
+pub fn add_webhook(
+    state: &mut State,
+    caller: Principal,
+    url: String,
+    secret: String,
+) -> Result<WebhookRegistration, WebhookError> {
+    // HTTPS outcalls can't be made over plain HTTP.
+    let host = url.strip_prefix("https://").unwrap_or_default();
+    if host.is_empty() || url.len() > MAX_URL_LENGTH || url.contains(char::is_whitespace) {
+        return Err(WebhookError::InvalidUrl);
+    }
+    let registered = state
+        .webhooks
+        .values()
+        .filter(|webhook| webhook.owner == caller)
+        .count();
+    if registered >= MAX_WEBHOOKS {
+        return Err(WebhookError::LimitExceeded);
+    }
+
+    let id = state.generate_webhook_id();
+    state.webhooks.insert(
+        id,
+        Webhook {
+            owner: caller,
+            url,
+            secret: secret.clone(),
+            created_at: state.env.now(),
+        },
+    );
+    Ok(WebhookRegistration { id, secret })
+}
+
+/// Removes a webhook of the caller, along with its pending deliveries.
+pub fn remove_webhook(state: &mut State, caller: Principal, id: u64) -> Result<(), WebhookError> {
+    match state.webhooks.get(&id) {
+        Some(webhook) if webhook.owner == caller => {
+            state.webhooks.remove(&id);
+            state
+                .webhook_deliveries
+                .retain(|_, delivery| delivery.webhook_id != id);
+            Ok(())
+        }
+        _ => Err(WebhookError::NotFound),
+    }
+}
+
+pub fn get_webhooks(state: &State, caller: Principal) -> Vec<PublicWebhook> {
+    state
+        .webhooks
+        .iter()
+        .filter(|(_, webhook)| webhook.owner == caller)
+        .map(|(id, webhook)| PublicWebhook {
+            id: *id,
+            url: webhook.url.clone(),
+            created_at: webhook.created_at,
+        })
+        .collect()
+}
+
+/// Queues a delivery of the event to every webhook of the owner of the file.
+///
+/// The deliveries of every webhook are bounded, so the oldest delivery to the webhook is dropped
+/// when it has too many.
+pub(crate) fn enqueue_webhook_event(state: &mut State, file_id: u64, event: WebhookEvent) {
+    if !state.config.features.webhooks {
+        return;
+    }
+    let Some(file) = state.file_data.get(&file_id) else {
+        return;
+    };
+    let owner = file.metadata.requester_principal;
+    let file_name = file.metadata.file_name.clone();
+    let webhook_ids: Vec<u64> = state
+        .webhooks
+        .iter()
+        .filter(|(_, webhook)| webhook.owner == owner)
+        .map(|(id, _)| *id)
+        .collect();
+
+    let now = state.env.now();
+    for webhook_id in webhook_ids {
+        let id = state.generate_webhook_delivery_id();
+        let payload = Payload {
+            id,
+            file_id,
+            file_name: &file_name,
+            owner,
+            created_at: now,
+            event: &event,
+        };
+        let body = serde_json::to_string(&payload).expect("failed to encode the payload");
+
+        let queued: Vec<u64> = state
+            .webhook_deliveries
+            .iter()
+            .filter(|(_, delivery)| delivery.webhook_id == webhook_id)
+            .map(|(id, _)| *id)
+            .collect();
+        if queued.len() == MAX_WEBHOOK_DELIVERIES {
+            state.webhook_deliveries.remove(&queued[0]);
+        }
+        state.webhook_deliveries.insert(
+            id,
+            WebhookDelivery {
+                webhook_id,
+                body,
+                attempts: 0,
+                next_attempt_at: now,
+            },
+        );
+    }
+}
+
+/// Returns true if a delivery is due, so that the state is only updated when there is
+/// something to send.
+pub fn webhook_deliveries_due(state: &State) -> bool {
+    let now = state.env.now();
+    state
+        .webhook_deliveries
+        .values()
+        .any(|delivery| delivery.next_attempt_at <= now)
+}
+
+/// Returns the requests of the deliveries that are due, and schedules their next attempt.
+///
+/// Deliveries whose last attempt never completed are given up once they have been attempted
+/// `MAX_WEBHOOK_ATTEMPTS` times.
+pub fn take_due_webhook_requests(state: &mut State) -> Vec<WebhookRequest> {
+    let now = state.env.now();
+    let due: Vec<u64> = state
+        .webhook_deliveries
+        .iter()
+        .filter(|(_, delivery)| delivery.next_attempt_at <= now)
+        .map(|(id, _)| *id)
+        .collect();
+
+    let mut requests = vec![];
+    for delivery_id in due {
+        if requests.len() == MAX_CONCURRENT_REQUESTS {
+            break;
+        }
+        let delivery = state.webhook_deliveries.get_mut(&delivery_id).unwrap();
+        if delivery.attempts >= MAX_WEBHOOK_ATTEMPTS {
+            state.webhook_deliveries.remove(&delivery_id);
+            continue;
+        }
+        delivery.attempts += 1;
+        delivery.next_attempt_at = now.saturating_add(retry_delay(delivery.attempts));
+
+        let webhook = &state.webhooks[&delivery.webhook_id];
+        let signature = sign(&webhook.secret, now, &delivery.body);
+        requests.push(WebhookRequest {
+            delivery_id,
+            url: webhook.url.clone(),
+            headers: vec![
+                ("Content-Type".to_string(), "application/json".to_string()),
+                // Every replica sends the request, so the receiver gets it several times.
+                ("Idempotency-Key".to_string(), delivery_id.to_string()),
+                ("X-DocuTrack-Timestamp".to_string(), now.to_string()),
+                (
+                    "X-DocuTrack-Signature".to_string(),
+                    format!("v1={signature}"),
+                ),
+            ],
+            body: delivery.body.clone().into_bytes(),
+        });
+    }
+    requests
+}
+
+/// Records the outcome of a request: the status of the response, or the error of the
+/// outcall.
+///
+/// Deliveries are retried on errors, on timeouts, when they are throttled and when the server
+/// fails, until they have been attempted `MAX_WEBHOOK_ATTEMPTS` times. They are dropped on
+/// the other statuses, which won't change by retrying.
+pub fn complete_webhook_delivery(
+    state: &mut State,
+    delivery_id: u64,
+    outcome: Result<u16, String>,
+) {
+    let Some(delivery) = state.webhook_deliveries.get(&delivery_id) else {
+        return;
+    };
+    let retry = match outcome {
+        Ok(status) => status == 408 || status == 429 || status >= 500,
+        Err(_) => true,
+    };
+    if !retry || delivery.attempts >= MAX_WEBHOOK_ATTEMPTS {
+        state.webhook_deliveries.remove(&delivery_id);
+    }
+}
+
+/// The delay before the attempt following the given one.
+fn retry_delay(attempts: u32) -> u64 {
+    WEBHOOK_RETRY_DELAY_NS.saturating_mul(1 << attempts.saturating_sub(1).min(32))
+}
+
+/// Signs the timestamp and the body of a request with the secret of its webhook.
+///
+/// Receivers check that the hex-encoded HMAC-SHA256 of `"{timestamp}.{body}"` matches, and can
+/// reject old timestamps to prevent replays.
+fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let message = format!("{timestamp}.{body}");
+    hmac_sha256(secret.as_bytes(), message.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::{
+            request_file, revoke_share, set_user_info, share_file, share_file_with_permission,
+            upload_file,
+        },
+        env::{Environment, TestEnvironment},
+        User,
+    };
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[0, 1, 1])
+    }
+
+    fn recipient() -> Principal {
+        Principal::from_slice(&[0, 1, 2])
+    }
+
+    fn setup() -> (State, TestEnvironment) {
+        let env = TestEnvironment::default();
+        let mut state = State::new(Box::new(env.clone()));
+        for principal in [owner(), recipient()] {
+            set_user_info(
+                &mut state,
+                principal,
+                User {
+                    username: principal.to_text(),
+                    public_key: vec![1, 2, 3],
+                },
+            );
+        }
+        (state, env)
+    }
+
+    /// Requests a file of the owner and uploads it, which queues an `upload_completed` event.
+    fn upload(state: &mut State) -> u64 {
+        let file_id = state.file_data.len() as u64;
+        request_file(owner(), "passport", state);
+        upload_file(file_id, vec![1], "pdf".to_string(), vec![2], 1, state).unwrap();
+        file_id
+    }
+
+    fn header<'a>(request: &'a WebhookRequest, name: &str) -> &'a str {
+        request
+            .headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_else(|| panic!("missing header {name}"))
+    }
+
+    /// Takes the due requests and completes all of them with the given outcome, as the
+    /// outcalls of the canister would.
+    fn deliver(state: &mut State, outcome: Result<u16, String>) -> Vec<WebhookRequest> {
+        let requests = take_due_webhook_requests(state);
+        for request in &requests {
+            complete_webhook_delivery(state, request.delivery_id, outcome.clone());
+        }
+        requests
+    }
+
+    fn payload(request: &WebhookRequest) -> serde_json::Value {
+        serde_json::from_slice(&request.body).unwrap()
+    }
+
+    #[test]
+    fn hmac_matches_the_test_vectors() {
+        // Test cases 2 and 6 of RFC 4231.
+        let digest = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            digest
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let digest = hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+        );
+        assert_eq!(
+            digest
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>(),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn webhooks_are_validated() {
+        let (mut state, _) = setup();
+        for url in [
+            "http://example.com/hook",
+            "https://",
+            "https://exa mple.com",
+        ] {
+            assert_eq!(
+                add_webhook(&mut state, owner(), url.to_string(), "secret".to_string()),
+                Err(WebhookError::InvalidUrl)
+            );
+        }
+        let url = format!("https://example.com/{}", "a".repeat(MAX_URL_LENGTH));
+        assert_eq!(
+            add_webhook(&mut state, owner(), url, "secret".to_string()),
+            Err(WebhookError::InvalidUrl)
+        );
+
+        for id in 0..MAX_WEBHOOKS as u64 {
+            let url = format!("https://example.com/{id}");
+            let registration = add_webhook(&mut state, owner(), url, format!("secret {id}"));
+            assert_eq!(
+                registration,
+                Ok(WebhookRegistration {
+                    id,
+                    secret: format!("secret {id}")
+                })
+            );
+        }
+        assert_eq!(
+            add_webhook(
+                &mut state,
+                owner(),
+                "https://example.com".to_string(),
+                "secret".to_string()
+            ),
+            Err(WebhookError::LimitExceeded)
+        );
+        assert_eq!(get_webhooks(&state, owner()).len(), MAX_WEBHOOKS);
+        assert!(get_webhooks(&state, recipient()).is_empty());
+
+        // Only the owner of a webhook can remove it.
+        assert_eq!(
+            remove_webhook(&mut state, recipient(), 0),
+            Err(WebhookError::NotFound)
+        );
+        assert_eq!(remove_webhook(&mut state, owner(), 0), Ok(()));
+        assert_eq!(
+            remove_webhook(&mut state, owner(), 0),
+            Err(WebhookError::NotFound)
+        );
+    }
+
+    #[test]
+    fn signed_events_are_posted_to_the_webhooks_of_the_owner() {
+        let (mut state, env) = setup();
+        let url = "https://example.com/hooks/docutrack".to_string();
+        add_webhook(&mut state, owner(), url.clone(), "secret".to_string()).unwrap();
+
+        let file_id = upload(&mut state);
+        share_file(&mut state, owner(), recipient(), file_id, vec![3]);
+        // Updating the key of a share isn't an event.
+        share_file(&mut state, owner(), recipient(), file_id, vec![4]);
+        revoke_share(&mut state, owner(), recipient(), file_id);
+        assert!(webhook_deliveries_due(&state));
+        let requests = deliver(&mut state, Ok(200));
+        assert_eq!(requests.len(), 3);
+        assert!(state.webhook_deliveries.is_empty());
+        assert!(!webhook_deliveries_due(&state));
+
+        assert_eq!(requests[0].url, url);
+        assert_eq!(header(&requests[0], "Content-Type"), "application/json");
+        let timestamp = header(&requests[0], "X-DocuTrack-Timestamp");
+        assert_eq!(timestamp, env.now().to_string());
+        let body = String::from_utf8(requests[0].body.clone()).unwrap();
+        assert_eq!(
+            header(&requests[0], "X-DocuTrack-Signature"),
+            format!("v1={}", sign("secret", env.now(), &body))
+        );
+        assert_eq!(
+            payload(&requests[0]),
+            serde_json::json!({
+                "id": 0,
+                "event": "upload_completed",
+                "file_id": file_id,
+                "file_name": "passport",
+                "owner": owner().to_text(),
+                "created_at": env.now(),
+            })
+        );
+        assert_eq!(header(&requests[0], "Idempotency-Key"), "0");
+
+        assert_eq!(payload(&requests[1])["event"], "file_shared");
+        assert_eq!(payload(&requests[1])["user"], recipient().to_text());
+        assert_eq!(payload(&requests[1])["by"], owner().to_text());
+        assert_eq!(payload(&requests[1])["permission"], "view");
+        assert_eq!(payload(&requests[2])["event"], "share_revoked");
+
+        // Other users' files aren't posted to the webhook.
+        request_file(recipient(), "visa", &mut state);
+        upload_file(1, vec![1], "pdf".to_string(), vec![2], 1, &mut state).unwrap();
+        assert!(state.webhook_deliveries.is_empty());
+    }
+
+    #[test]
+    fn permission_changes_are_posted() {
+        let (mut state, _) = setup();
+        add_webhook(
+            &mut state,
+            owner(),
+            "https://example.com".to_string(),
+            "secret".to_string(),
+        )
+        .unwrap();
+        let file_id = upload(&mut state);
+        share_file(&mut state, owner(), recipient(), file_id, vec![3]);
+        share_file_with_permission(
+            &mut state,
+            owner(),
+            recipient(),
+            file_id,
+            vec![3],
+            SharePermission::Reshare,
+        );
+
+        let events: Vec<serde_json::Value> = state
+            .webhook_deliveries
+            .values()
+            .map(|delivery| serde_json::from_str(&delivery.body).unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2]["event"], "file_shared");
+        assert_eq!(events[2]["permission"], "reshare");
+    }
+
+    #[test]
+    fn failed_deliveries_are_retried_with_backoff() {
+        let (mut state, env) = setup();
+        let url = "https://example.com/hooks/docutrack".to_string();
+        add_webhook(&mut state, owner(), url, "secret".to_string()).unwrap();
+        upload(&mut state);
+
+        let mut requests = deliver(&mut state, Ok(503));
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            state.webhook_deliveries[&0].next_attempt_at,
+            env.now() + WEBHOOK_RETRY_DELAY_NS
+        );
+        assert!(!webhook_deliveries_due(&state));
+
+        env.advance_time(WEBHOOK_RETRY_DELAY_NS);
+        requests.extend(deliver(&mut state, Ok(429)));
+        // The delay doubles after every attempt.
+        env.advance_time(WEBHOOK_RETRY_DELAY_NS);
+        assert!(deliver(&mut state, Ok(200)).is_empty());
+        env.advance_time(WEBHOOK_RETRY_DELAY_NS);
+        requests.extend(deliver(&mut state, Ok(200)));
+        assert!(state.webhook_deliveries.is_empty());
+
+        // The same delivery was sent every time, signed with the time of the attempt.
+        assert_eq!(requests.len(), 3);
+        for request in &requests {
+            assert_eq!(header(request, "Idempotency-Key"), "0");
+            assert_eq!(request.body, requests[0].body);
+        }
+        assert_eq!(
+            header(&requests[2], "X-DocuTrack-Timestamp"),
+            env.now().to_string()
+        );
+    }
+
+    #[test]
+    fn deliveries_are_given_up() {
+        let (mut state, env) = setup();
+        let url = "https://example.com/hooks/docutrack".to_string();
+        add_webhook(&mut state, owner(), url, "secret".to_string()).unwrap();
+
+        // Client errors won't be fixed by retrying.
+        upload(&mut state);
+        assert_eq!(deliver(&mut state, Ok(400)).len(), 1);
+        assert!(state.webhook_deliveries.is_empty());
+
+        // Unreachable servers are retried until the last attempt.
+        upload(&mut state);
+        for attempt in 1..=MAX_WEBHOOK_ATTEMPTS {
+            let outcome = Err("connection refused".to_string());
+            assert_eq!(deliver(&mut state, outcome).len(), 1);
+            assert_eq!(
+                state.webhook_deliveries.is_empty(),
+                attempt == MAX_WEBHOOK_ATTEMPTS
+            );
+            env.advance_time(retry_delay(attempt));
+        }
+
+        // Attempts that never complete are given up as well.
+        upload(&mut state);
+        for _ in 0..MAX_WEBHOOK_ATTEMPTS {
+            assert_eq!(take_due_webhook_requests(&mut state).len(), 1);
+            env.advance_time(retry_delay(MAX_WEBHOOK_ATTEMPTS));
+        }
+        assert!(take_due_webhook_requests(&mut state).is_empty());
+        assert!(state.webhook_deliveries.is_empty());
+    }
+
+    #[test]
+    fn removed_webhooks_drop_their_deliveries() {
+        let (mut state, _) = setup();
+        for url in ["https://example.com/a", "https://example.com/b"] {
+            add_webhook(&mut state, owner(), url.to_string(), "secret".to_string()).unwrap();
+        }
+        upload(&mut state);
+        assert_eq!(state.webhook_deliveries.len(), 2);
+
+        remove_webhook(&mut state, owner(), 0).unwrap();
+        let requests = take_due_webhook_requests(&mut state);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "https://example.com/b");
+    }
+
+    #[test]
+    fn full_webhooks_drop_their_oldest_deliveries() {
+        let (mut state, _) = setup();
+        add_webhook(
+            &mut state,
+            recipient(),
+            "https://example.com/r".to_string(),
+            "secret".to_string(),
+        )
+        .unwrap();
+        add_webhook(
+            &mut state,
+            owner(),
+            "https://example.com/o".to_string(),
+            "secret".to_string(),
+        )
+        .unwrap();
+        request_file(recipient(), "passport", &mut state);
+        upload_file(0, vec![1], "pdf".to_string(), vec![2], 1, &mut state).unwrap();
+
+        for _ in 0..=MAX_WEBHOOK_DELIVERIES {
+            upload(&mut state);
+        }
+        let queued = |webhook_id| {
+            state
+                .webhook_deliveries
+                .values()
+                .filter(|delivery| delivery.webhook_id == webhook_id)
+                .count()
+        };
+        // The deliveries of the other users aren't dropped.
+        assert_eq!(queued(0), 1);
+        assert_eq!(queued(1), MAX_WEBHOOK_DELIVERIES);
+        assert_eq!(state.webhook_deliveries.keys().nth(1), Some(&2));
+    }
+}
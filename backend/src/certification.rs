@@ -0,0 +1,554 @@
+//! Certification of the data served by queries.
+//!
+//! The file metadata, the hashes of the chunks, the pending aliases and the users are kept in a
+//! Merkle tree whose root hash is the certified data of the canister. Queries return a witness,
+//! a hash tree that reveals the data of the response and prunes everything else, along with the
+//! certificate of the subnet. Clients check that the witness contains the data they received,
+//! and that its root hash is the one in the certificate.
+//!
+//! The tree has the following layout, where numbers are 8-byte big-endian labels and numeric
+//! leaves are LEB128-encoded:
+//!
+//! ```text
+//! files/<file_id>/metadata/{name, owner, requested_at, uploaded_at, status, version, file_type, num_chunks}
+//! files/<file_id>/chunks/<version>/<chunk_id> -> SHA-256 of the chunk
+//! aliases/<alias> -> the IDs of the files requested through the alias
+//! users/<principal>/{username, public_key}
+//! ```
+use crate::{ChunkKey, File, FileContent, State, User};
+use ic_cdk::export::Principal;
+use ic_certified_map::{fork, labeled, leaf_hash, AsHashTree, HashTree, RbTree};
+use serde::{de::Deserializer, ser::Serializer, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub use ic_certified_map::Hash;
+pub type Label = Vec<u8>;
+pub type Path = Vec<Label>;
+
+const FILES: &[u8] = b"files";
+const METADATA: &[u8] = b"metadata";
+const CHUNKS: &[u8] = b"chunks";
+const ALIASES: &[u8] = b"aliases";
+const USERS: &[u8] = b"users";
+
+/// A map from labels to leaves or nested maps, whose root hash is that of its hash tree.
+#[derive(Default)]
+pub struct CertifiedMap {
+    tree: RbTree<Label, Value>,
+}
+
+enum Value {
+    Leaf(Vec<u8>),
+    Map(CertifiedMap),
+}
+
+impl AsHashTree for Value {
+    fn root_hash(&self) -> Hash {
+        match self {
+            Value::Leaf(data) => leaf_hash(data),
+            Value::Map(map) => map.root_hash(),
+        }
+    }
+
+    fn as_hash_tree(&self) -> HashTree<'_> {
+        match self {
+            Value::Leaf(data) => data.as_hash_tree(),
+            Value::Map(map) => map.tree.as_hash_tree(),
+        }
+    }
+}
+
+impl CertifiedMap {
+    pub fn root_hash(&self) -> Hash {
+        self.tree.root_hash()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Inserts a leaf at the given path, replacing what was there.
+    pub fn insert(&mut self, path: &[Label], leaf: Vec<u8>) {
+        let (label, rest) = path.split_first().expect("path must not be empty");
+        if rest.is_empty() {
+            self.tree.insert(label.clone(), Value::Leaf(leaf));
+            return;
+        }
+        if !matches!(self.tree.get(label), Some(Value::Map(_))) {
+            self.tree
+                .insert(label.clone(), Value::Map(CertifiedMap::default()));
+        }
+        self.tree.modify(label, |value| {
+            if let Value::Map(map) = value {
+                map.insert(rest, leaf);
+            }
+        });
+    }
+
+    /// Removes the leaf or the map at the given path, along with the maps left empty.
+    pub fn remove(&mut self, path: &[Label]) {
+        let (label, rest) = path.split_first().expect("path must not be empty");
+        match self.tree.get(label) {
+            Some(Value::Map(_)) if !rest.is_empty() => {}
+            Some(_) if rest.is_empty() => return self.tree.delete(label),
+            _ => return,
+        }
+        let mut is_empty = false;
+        self.tree.modify(label, |value| {
+            if let Value::Map(map) = value {
+                map.remove(rest);
+                is_empty = map.is_empty();
+            }
+        });
+        if is_empty {
+            self.tree.delete(label);
+        }
+    }
+
+    /// Returns a hash tree that reveals the given paths and prunes everything else.
+    /// Paths that lead to a map reveal the whole map.
+    pub fn witness(&self, paths: &[Path]) -> HashTree<'_> {
+        let paths: Vec<&[Label]> = paths.iter().map(|path| path.as_slice()).collect();
+        self.witness_paths(&paths)
+    }
+
+    fn witness_paths(&self, paths: &[&[Label]]) -> HashTree<'_> {
+        let mut labels: Vec<&Label> = paths.iter().map(|path| &path[0]).collect();
+        labels.sort();
+        labels.dedup();
+        labels
+            .into_iter()
+            .map(|label| {
+                let rests: Vec<&[Label]> = paths
+                    .iter()
+                    .filter(|path| &path[0] == label)
+                    .map(|path| &path[1..])
+                    .collect();
+                self.tree.nested_witness(label, |value| match value {
+                    Value::Map(map) if rests.iter().all(|rest| !rest.is_empty()) => {
+                        map.witness_paths(&rests)
+                    }
+                    value => value.as_hash_tree(),
+                })
+            })
+            .reduce(merge_hash_trees)
+            .unwrap_or_else(|| HashTree::Pruned(self.root_hash()))
+    }
+
+    /// Builds the map from its leaves.
+    pub fn from_leaves(leaves: Vec<(Path, Vec<u8>)>) -> Self {
+        let mut map = CertifiedMap::default();
+        for (path, leaf) in leaves {
+            map.insert(&path, leaf);
+        }
+        map
+    }
+
+    /// Returns the leaves of the map, in order.
+    pub fn leaves(&self) -> Vec<(Path, Vec<u8>)> {
+        let mut leaves = vec![];
+        self.collect_leaves(&mut vec![], &mut leaves);
+        leaves
+    }
+
+    fn collect_leaves(&self, path: &mut Path, leaves: &mut Vec<(Path, Vec<u8>)>) {
+        self.tree.for_each(|label, value| {
+            path.push(label.to_vec());
+            match value {
+                Value::Leaf(data) => leaves.push((path.clone(), data.clone())),
+                Value::Map(map) => map.collect_leaves(path, leaves),
+            }
+            path.pop();
+        });
+    }
+}
+
+// Witnesses of the same tree only differ in the subtrees they prune, so they are merged by
+// keeping the revealed side of every pruned subtree.
+fn merge_hash_trees<'a>(lhs: HashTree<'a>, rhs: HashTree<'a>) -> HashTree<'a> {
+    match (lhs, rhs) {
+        (HashTree::Pruned(_), tree) | (tree, HashTree::Pruned(_)) => tree,
+        (HashTree::Fork(lhs), HashTree::Fork(rhs)) => {
+            let ((lhs_left, lhs_right), (rhs_left, rhs_right)) = (*lhs, *rhs);
+            fork(
+                merge_hash_trees(lhs_left, rhs_left),
+                merge_hash_trees(lhs_right, rhs_right),
+            )
+        }
+        (HashTree::Labeled(label, lhs), HashTree::Labeled(_, rhs)) => {
+            labeled(label, merge_hash_trees(*lhs, *rhs))
+        }
+        (lhs, _) => lhs,
+    }
+}
+
+/// Encodes a hash tree in CBOR, as expected by the agents of the Internet Computer.
+pub fn encode_hash_tree(tree: &HashTree) -> Vec<u8> {
+    // The self-describing tag of CBOR.
+    let mut bytes = vec![0xd9, 0xd9, 0xf7];
+    ciborium::ser::into_writer(tree, &mut bytes).expect("failed to encode hash tree");
+    bytes
+}
+
+/// Returns the leaf at the given path, if the tree reveals it.
+#[cfg(test)]
+pub(crate) fn lookup<'a>(tree: &'a HashTree, path: &[&[u8]]) -> Option<&'a [u8]> {
+    match (tree, path.split_first()) {
+        (HashTree::Leaf(data), None) => Some(data),
+        (HashTree::Fork(fork), Some(_)) => lookup(&fork.0, path).or_else(|| lookup(&fork.1, path)),
+        (HashTree::Labeled(label, tree), Some((first, rest))) if label == first => {
+            lookup(tree, rest)
+        }
+        _ => None,
+    }
+}
+
+// The map is stored as its leaves across upgrades, as the nesting of the tree is deep.
+impl Serialize for CertifiedMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.leaves().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CertifiedMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(CertifiedMap::from_leaves)
+    }
+}
+
+fn leb128(mut n: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+pub fn file_metadata_path(file_id: u64) -> Path {
+    vec![
+        FILES.to_vec(),
+        file_id.to_be_bytes().to_vec(),
+        METADATA.to_vec(),
+    ]
+}
+
+pub fn chunk_path(file_id: u64, version: u64, chunk_id: u64) -> Path {
+    vec![
+        FILES.to_vec(),
+        file_id.to_be_bytes().to_vec(),
+        CHUNKS.to_vec(),
+        version.to_be_bytes().to_vec(),
+        chunk_id.to_be_bytes().to_vec(),
+    ]
+}
+
+pub fn alias_path(alias: &str) -> Path {
+    vec![ALIASES.to_vec(), alias.as_bytes().to_vec()]
+}
+
+pub fn user_path(user: Principal) -> Path {
+    vec![USERS.to_vec(), user.as_slice().to_vec()]
+}
+
+fn file_metadata_leaves(file: &File) -> Vec<(&'static str, Vec<u8>)> {
+    let metadata = &file.metadata;
+    let mut leaves = vec![
+        ("name", metadata.file_name.as_bytes().to_vec()),
+        ("owner", metadata.requester_principal.as_slice().to_vec()),
+        ("requested_at", leb128(metadata.requested_at)),
+        ("version", leb128(file.version)),
+    ];
+    if let Some(uploaded_at) = metadata.uploaded_at {
+        leaves.push(("uploaded_at", leb128(uploaded_at)));
+    }
+    let status = match &file.content {
+        FileContent::Pending { .. } => "pending",
+        FileContent::PartiallyUploaded {
+            file_type,
+            num_chunks,
+            ..
+        }
+        | FileContent::Uploaded {
+            file_type,
+            num_chunks,
+            ..
+        } => {
+            leaves.push(("file_type", file_type.as_bytes().to_vec()));
+            leaves.push(("num_chunks", leb128(*num_chunks)));
+            match file.content {
+                FileContent::Uploaded { .. } => "uploaded",
+                _ => "partially_uploaded",
+            }
+        }
+    };
+    leaves.push(("status", status.as_bytes().to_vec()));
+    leaves
+}
+
+/// Certifies the metadata of the file, or removes it if the file was deleted.
+pub(crate) fn certify_file(state: &mut State, file_id: u64) {
+    let path = file_metadata_path(file_id);
+    state.certified_data.remove(&path);
+    if let Some(file) = state.file_data.get(&file_id) {
+        for (field, leaf) in file_metadata_leaves(file) {
+            let mut field_path = path.clone();
+            field_path.push(field.as_bytes().to_vec());
+            state.certified_data.insert(&field_path, leaf);
+        }
+    }
+}
+
+/// Certifies the files requested through the alias, or removes the alias if it's no longer
+/// pending.
+pub(crate) fn certify_alias(state: &mut State, alias: &str) {
+    let path = alias_path(alias);
+    match alias_leaf(state, alias) {
+        Some(leaf) => state.certified_data.insert(&path, leaf),
+        None => state.certified_data.remove(&path),
+    }
+}
+
+/// Returns the IDs of the files requested through the alias, if it's pending.
+fn alias_leaf(state: &State, alias: &str) -> Option<Vec<u8>> {
+    let file_ids = match (
+        state.file_alias_index.get(alias),
+        state.checklists.get(alias),
+    ) {
+        (Some(file_id), _) => vec![*file_id],
+        (None, Some(checklist)) => checklist.file_ids.clone(),
+        (None, None) => vec![],
+    };
+    if file_ids.is_empty() {
+        None
+    } else {
+        Some(file_ids.iter().flat_map(|id| id.to_be_bytes()).collect())
+    }
+}
+
+/// Certifies the username and public key of the user, or removes them if the user was removed.
+pub(crate) fn certify_user(state: &mut State, user: Principal) {
+    let path = user_path(user);
+    state.certified_data.remove(&path);
+    if let Some(info) = state.users.get(&user) {
+        for (field, leaf) in user_leaves(info) {
+            let mut field_path = path.clone();
+            field_path.push(field.as_bytes().to_vec());
+            state.certified_data.insert(&field_path, leaf);
+        }
+    }
+}
+
+fn user_leaves(info: &User) -> [(&'static str, Vec<u8>); 2] {
+    [
+        ("username", info.username.as_bytes().to_vec()),
+        ("public_key", info.public_key.clone()),
+    ]
+}
+
+pub(crate) fn certify_chunk(state: &mut State, key: ChunkKey, chunk: &[u8]) {
+    let ChunkKey(file_id, version, chunk_id) = key;
+    state.certified_data.insert(
+        &chunk_path(file_id, version, chunk_id),
+        Sha256::digest(chunk).to_vec(),
+    );
+}
+
+pub(crate) fn uncertify_chunk(state: &mut State, key: ChunkKey) {
+    let ChunkKey(file_id, version, chunk_id) = key;
+    state
+        .certified_data
+        .remove(&chunk_path(file_id, version, chunk_id));
+}
+
+/// Certifies the metadata of all the files, the pending aliases and the users.
+///
+/// Used to certify the state of canisters that predate certification. Their chunks are
+/// certified in batches by the heartbeat, as hashing all of them wouldn't fit in an upgrade.
+pub(crate) fn certify_existing_data(state: &mut State) {
+    if !state.file_contents.is_empty() {
+        state.uncertified_chunks_cursor = Some(ChunkKey(0, 0, 0));
+    }
+    let mut leaves = state.certified_data.leaves();
+    for (file_id, file) in &state.file_data {
+        for (field, leaf) in file_metadata_leaves(file) {
+            let mut path = file_metadata_path(*file_id);
+            path.push(field.as_bytes().to_vec());
+            leaves.push((path, leaf));
+        }
+    }
+    state.certified_data = CertifiedMap::from_leaves(leaves);
+
+    let aliases: Vec<String> = state
+        .file_alias_index
+        .keys()
+        .chain(state.checklists.keys())
+        .cloned()
+        .collect();
+    for alias in aliases {
+        certify_alias(state, &alias);
+    }
+    let users: Vec<Principal> = state.users.keys().copied().collect();
+    for user in users {
+        certify_user(state, user);
+    }
+}
+
+/// Returns the leaves that certify the state, computed from scratch.
+///
+/// The tree is updated incrementally by the operations, and must always have these leaves,
+/// which `invariants::check_invariants` checks.
+pub(crate) fn expected_leaves(state: &State) -> Vec<(Path, Vec<u8>)> {
+    let mut leaves = vec![];
+    for (file_id, file) in &state.file_data {
+        for (field, leaf) in file_metadata_leaves(file) {
+            let mut path = file_metadata_path(*file_id);
+            path.push(field.as_bytes().to_vec());
+            leaves.push((path, leaf));
+        }
+    }
+    for (key, chunk) in state.file_contents.iter() {
+        // Existing chunks are certified in batches by the heartbeat.
+        if !state.is_chunk_certified(key) {
+            continue;
+        }
+        let ChunkKey(file_id, version, chunk_id) = key;
+        leaves.push((
+            chunk_path(file_id, version, chunk_id),
+            Sha256::digest(chunk).to_vec(),
+        ));
+    }
+    for ((file_id, chunk_id), chunk) in state.legacy_file_contents.iter() {
+        if state.is_legacy_chunk_processed((file_id, chunk_id)) {
+            leaves.push((
+                chunk_path(file_id, 0, chunk_id),
+                Sha256::digest(chunk).to_vec(),
+            ));
+        }
+    }
+    for alias in state.file_alias_index.keys().chain(state.checklists.keys()) {
+        if let Some(leaf) = alias_leaf(state, alias) {
+            leaves.push((alias_path(alias), leaf));
+        }
+    }
+    for (user, info) in &state.users {
+        for (field, leaf) in user_leaves(info) {
+            let mut path = user_path(*user);
+            path.push(field.as_bytes().to_vec());
+            leaves.push((path, leaf));
+        }
+    }
+    leaves
+}
+
+/// Returns the certificate of the certified data, which is only available in queries.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn data_certificate() -> Option<Vec<u8>> {
+    ic_cdk::api::data_certificate()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn data_certificate() -> Option<Vec<u8>> {
+    None
+}
+
+/// Sets the root hash of the tree as the certified data of the canister.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn set_certified_data(root_hash: &Hash) {
+    ic_cdk::api::set_certified_data(root_hash);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn set_certified_data(_root_hash: &Hash) {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(labels: &[&str]) -> Path {
+        labels
+            .iter()
+            .map(|label| label.as_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn root_hash_only_depends_on_the_leaves() {
+        let mut leaves: Vec<(Path, Vec<u8>)> = (0..50u64)
+            .map(|i| {
+                (
+                    vec![
+                        b"files".to_vec(),
+                        (i % 7).to_be_bytes().to_vec(),
+                        i.to_be_bytes().to_vec(),
+                    ],
+                    vec![i as u8],
+                )
+            })
+            .collect();
+        leaves.sort();
+
+        let mut inserted = CertifiedMap::default();
+        for (path, leaf) in leaves.iter().rev() {
+            inserted.insert(path, leaf.clone());
+        }
+        let built = CertifiedMap::from_leaves(leaves.clone());
+        assert_eq!(inserted.root_hash(), built.root_hash());
+        assert_eq!(inserted.leaves(), leaves);
+
+        // Removing leaves gives the same tree as never inserting them.
+        for (path, _) in &leaves[10..] {
+            inserted.remove(path);
+        }
+        assert_eq!(
+            inserted.root_hash(),
+            CertifiedMap::from_leaves(leaves[..10].to_vec()).root_hash()
+        );
+        for (path, _) in &leaves[..10] {
+            inserted.remove(path);
+        }
+        assert!(inserted.is_empty());
+        assert_eq!(inserted.root_hash(), CertifiedMap::default().root_hash());
+    }
+
+    #[test]
+    fn witness_reveals_the_requested_paths() {
+        let mut map = CertifiedMap::default();
+        map.insert(&path(&["a", "x"]), b"1".to_vec());
+        map.insert(&path(&["a", "y"]), b"2".to_vec());
+        map.insert(&path(&["b"]), b"3".to_vec());
+        map.insert(&path(&["c", "z"]), b"4".to_vec());
+
+        let witness = map.witness(&[path(&["a", "y"]), path(&["c"])]);
+        assert_eq!(witness.reconstruct(), map.root_hash());
+        assert_eq!(lookup(&witness, &[b"a", b"y"]), Some(&b"2"[..]));
+        assert_eq!(lookup(&witness, &[b"c", b"z"]), Some(&b"4"[..]));
+        assert_eq!(lookup(&witness, &[b"a", b"x"]), None);
+        assert_eq!(lookup(&witness, &[b"b"]), None);
+
+        // Missing paths are pruned.
+        let witness = map.witness(&[path(&["d"])]);
+        assert_eq!(witness.reconstruct(), map.root_hash());
+        assert_eq!(lookup(&witness, &[b"b"]), None);
+    }
+
+    #[test]
+    fn hash_tree_is_encoded_in_cbor() {
+        let leaf = b"b".to_vec();
+        let tree = fork(labeled(b"a", leaf.as_hash_tree()), HashTree::Empty);
+        assert_eq!(
+            encode_hash_tree(&tree),
+            vec![
+                0xd9, 0xd9, 0xf7, // The self-describing tag.
+                0x83, 0x01, // A fork.
+                0x83, 0x02, 0x41, b'a', // A node labeled "a".
+                0x82, 0x03, 0x41, b'b', // A leaf holding "b".
+                0x81, 0x00, // An empty tree.
+            ]
+        );
+    }
+}
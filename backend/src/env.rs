@@ -0,0 +1,150 @@
+//! The environment the canister runs in: its clock, its randomness and the caller of the
+//! current message.
+//!
+//! The state holds its environment, so that the operations of `api` read the time and draw
+//! randomness from it rather than from the IC. On the IC, it is a [`CanisterEnvironment`].
+//! Elsewhere, e.g., in tests, it is a [`TestEnvironment`], whose clock only moves when told to.
+use ic_cdk::export::candid::Principal;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+pub trait Environment {
+    /// The current time, in nanoseconds since the Unix epoch.
+    fn now(&self) -> u64;
+
+    /// Returns random bytes, e.g., to generate aliases.
+    fn random_bytes(&mut self) -> [u8; 32];
+
+    /// The principal that sent the current message.
+    fn caller(&self) -> Principal;
+}
+
+/// Returns the environment of the state: the IC's on the IC, and a test environment elsewhere.
+pub fn default_env() -> Box<dyn Environment> {
+    #[cfg(target_arch = "wasm32")]
+    return Box::new(CanisterEnvironment::new());
+    #[cfg(not(target_arch = "wasm32"))]
+    return Box::new(TestEnvironment::default());
+}
+
+/// The environment of the canister on the IC.
+pub struct CanisterEnvironment {
+    rng: ChaCha20Rng,
+}
+
+impl CanisterEnvironment {
+    /// Creates an environment whose randomness is seeded with the current time.
+    pub fn new() -> Self {
+        let mut seed = [0; 32];
+        seed[..8].copy_from_slice(&ic_cdk::api::time().to_be_bytes());
+        Self {
+            rng: ChaCha20Rng::from_seed(seed),
+        }
+    }
+}
+
+impl Default for CanisterEnvironment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Environment for CanisterEnvironment {
+    fn now(&self) -> u64 {
+        ic_cdk::api::time()
+    }
+
+    fn random_bytes(&mut self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        self.rng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    fn caller(&self) -> Principal {
+        ic_cdk::api::caller()
+    }
+}
+
+/// An environment controlled by tests.
+///
+/// Its clones share the same clock, randomness and caller, so that a test can keep one to
+/// control the environment of the state it gives another to.
+#[derive(Clone)]
+pub struct TestEnvironment {
+    time: Rc<Cell<u64>>,
+    rng: Rc<RefCell<ChaCha20Rng>>,
+    caller: Rc<Cell<Principal>>,
+}
+
+impl TestEnvironment {
+    /// The time the clock starts at.
+    pub const START_TIME: u64 = 12345;
+
+    pub fn set_time(&self, time: u64) {
+        self.time.set(time);
+    }
+
+    pub fn advance_time(&self, duration_ns: u64) {
+        self.time.set(self.time.get() + duration_ns);
+    }
+
+    /// Restarts the randomness from the given seed. The same seed draws the same bytes, and
+    /// thus generates the same aliases.
+    pub fn set_seed(&self, seed: [u8; 32]) {
+        *self.rng.borrow_mut() = ChaCha20Rng::from_seed(seed);
+    }
+
+    pub fn set_caller(&self, caller: Principal) {
+        self.caller.set(caller);
+    }
+}
+
+impl Default for TestEnvironment {
+    fn default() -> Self {
+        Self {
+            time: Rc::new(Cell::new(Self::START_TIME)),
+            rng: Rc::new(RefCell::new(ChaCha20Rng::from_seed([0; 32]))),
+            caller: Rc::new(Cell::new(Principal::anonymous())),
+        }
+    }
+}
+
+impl Environment for TestEnvironment {
+    fn now(&self) -> u64 {
+        self.time.get()
+    }
+
+    fn random_bytes(&mut self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        self.rng.borrow_mut().fill_bytes(&mut bytes);
+        bytes
+    }
+
+    fn caller(&self) -> Principal {
+        self.caller.get()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_environments_are_shared_by_their_clones() {
+        let env = TestEnvironment::default();
+        let mut clone: Box<dyn Environment> = Box::new(env.clone());
+        assert_eq!(clone.now(), TestEnvironment::START_TIME);
+
+        env.advance_time(10);
+        env.set_caller(Principal::management_canister());
+        assert_eq!(clone.now(), TestEnvironment::START_TIME + 10);
+        assert_eq!(clone.caller(), Principal::management_canister());
+
+        let bytes = clone.random_bytes();
+        assert_ne!(clone.random_bytes(), bytes);
+        env.set_seed([0; 32]);
+        assert_eq!(clone.random_bytes(), bytes);
+    }
+}
@@ -0,0 +1,1097 @@
+//! The invariants of the state.
+//!
+//! The state keeps several indexes of the same data: the owners of files, their shares, their
+//! pending aliases, their chunks in stable memory, the usage of their owners, and the tree
+//! certifying them. The operations of `api` keep them consistent, and rely on it by unwrapping
+//! lookups. [`check_invariants`] checks all of them. In debug builds, it runs after every
+//! update call, which traps if an invariant doesn't hold.
+use crate::certification::{self, CertifiedMap};
+use crate::{
+    ChunkKey, File, FileContent, State, Usage, MAX_NOTIFICATIONS, MAX_WEBHOOKS,
+    MAX_WEBHOOK_ATTEMPTS, MAX_WEBHOOK_DELIVERIES,
+};
+use ic_cdk::export::candid::Principal;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Checks the invariants of the state, and returns the first one that doesn't hold.
+pub fn check_invariants(state: &State) -> Result<(), String> {
+    check_files(state)?;
+    check_owners(state)?;
+    check_aliases(state)?;
+    check_shares(state)?;
+    check_references(state)?;
+    check_chunks(state)?;
+    check_usage(state)?;
+    check_notifications(state)?;
+    check_webhooks(state)?;
+    check_certified_data(state)
+}
+
+fn ensure(condition: bool, violation: impl FnOnce() -> String) -> Result<(), String> {
+    if condition {
+        Ok(())
+    } else {
+        Err(violation())
+    }
+}
+
+fn get_file<'a>(state: &'a State, file_id: u64, referrer: &str) -> Result<&'a File, String> {
+    state
+        .file_data
+        .get(&file_id)
+        .ok_or_else(|| format!("{referrer} refers to file {file_id}, which doesn't exist"))
+}
+
+/// File IDs are never reused, and the status of every file matches its versions.
+fn check_files(state: &State) -> Result<(), String> {
+    for (file_id, file) in &state.file_data {
+        ensure(*file_id < state.file_count, || {
+            format!("file {file_id} has an ID that wasn't generated")
+        })?;
+        match &file.content {
+            FileContent::Pending { .. } => ensure(
+                file.version == 0
+                    && file.versions.is_empty()
+                    && file.metadata.uploaded_at.is_none(),
+                || format!("file {file_id} is pending, yet has been uploaded"),
+            )?,
+            FileContent::PartiallyUploaded { shared_keys, .. } => {
+                ensure(file.metadata.uploaded_at.is_some(), || {
+                    format!("file {file_id} is being uploaded, yet has no upload time")
+                })?;
+                ensure(shared_keys.is_empty(), || {
+                    format!("file {file_id} is being uploaded, yet is shared")
+                })?;
+            }
+            FileContent::Uploaded { .. } => ensure(file.metadata.uploaded_at.is_some(), || {
+                format!("file {file_id} is uploaded, yet has no upload time")
+            })?,
+        }
+        let mut previous = None;
+        for version in &file.versions {
+            ensure(
+                previous < Some(version.version) && version.version < file.version,
+                || format!("the versions of file {file_id} aren't in order"),
+            )?;
+            previous = Some(version.version);
+        }
+    }
+    Ok(())
+}
+
+/// Every file is owned by the principal that requested it, and only by them.
+fn check_owners(state: &State) -> Result<(), String> {
+    let mut owned = BTreeSet::new();
+    for (owner, file_ids) in &state.file_owners {
+        for file_id in file_ids {
+            let file = get_file(state, *file_id, &format!("the files of {owner}"))?;
+            ensure(file.metadata.requester_principal == *owner, || {
+                format!("file {file_id} is owned by {owner}, yet was requested by another user")
+            })?;
+            ensure(owned.insert(*file_id), || {
+                format!("file {file_id} is owned more than once")
+            })?;
+        }
+    }
+    match state
+        .file_data
+        .keys()
+        .find(|file_id| !owned.contains(file_id))
+    {
+        Some(file_id) => Err(format!("file {file_id} has no owner")),
+        None => Ok(()),
+    }
+}
+
+/// An alias is pending if and only if the files requested through it are pending.
+///
+/// Regular aliases are in `file_alias_index`, while checklist aliases are the keys of
+/// `checklists`, which remain after their items are uploaded.
+fn check_aliases(state: &State) -> Result<(), String> {
+    for (alias, file_id) in &state.file_alias_index {
+        let file = get_file(state, *file_id, &format!("alias {alias}"))?;
+        ensure(
+            matches!(&file.content, FileContent::Pending { alias: file_alias } if file_alias == alias.as_str()),
+            || format!("alias {alias} refers to file {file_id}, which isn't pending with it"),
+        )?;
+        ensure(!state.checklists.contains_key(alias), || {
+            format!("alias {alias} is both a regular alias and a checklist alias")
+        })?;
+    }
+    for (alias, checklist) in &state.checklists {
+        ensure(!checklist.file_ids.is_empty(), || {
+            format!("checklist {alias} has no items")
+        })?;
+        for file_id in &checklist.file_ids {
+            let file = get_file(state, *file_id, &format!("checklist {alias}"))?;
+            if let FileContent::Pending { alias: file_alias } = &file.content {
+                ensure(file_alias == alias.as_str(), || {
+                    format!("file {file_id} of checklist {alias} is pending with another alias")
+                })?;
+            }
+        }
+    }
+    for (file_id, file) in &state.file_data {
+        if let FileContent::Pending { alias } = &file.content {
+            let is_requested = state.file_alias_index.get(alias) == Some(file_id)
+                || state
+                    .checklists
+                    .get(alias)
+                    .map_or(false, |checklist| checklist.file_ids.contains(file_id));
+            ensure(is_requested, || {
+                format!("file {file_id} is pending, yet can't be uploaded through its alias")
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Every key of the current version of a file, grant and download session belongs to a share
+/// in `file_shares`.
+fn check_shares(state: &State) -> Result<(), String> {
+    let mut shares = BTreeSet::new();
+    for (user, file_ids) in &state.file_shares {
+        for file_id in file_ids {
+            let file = get_file(state, *file_id, &format!("the shares of {user}"))?;
+            ensure(!matches!(file.content, FileContent::Pending { .. }), || {
+                format!("file {file_id} is shared with {user}, yet is pending")
+            })?;
+            ensure(shares.insert((*file_id, *user)), || {
+                format!("file {file_id} is shared with {user} more than once")
+            })?;
+        }
+    }
+    for (file_id, file) in &state.file_data {
+        if let FileContent::Uploaded { shared_keys, .. }
+        | FileContent::PartiallyUploaded { shared_keys, .. } = &file.content
+        {
+            for user in shared_keys.keys() {
+                ensure(shares.contains(&(*file_id, *user)), || {
+                    format!("file {file_id} has a key for {user}, yet isn't shared with them")
+                })?;
+            }
+        }
+    }
+    for (file_id, grants) in &state.share_grants {
+        get_file(state, *file_id, "the share grants")?;
+        for user in grants.keys() {
+            ensure(shares.contains(&(*file_id, *user)), || {
+                format!("file {file_id} has a grant for {user}, yet isn't shared with them")
+            })?;
+        }
+    }
+    for (file_id, user) in &state.download_sessions {
+        let is_limited = state
+            .share_grants
+            .get(file_id)
+            .and_then(|grants| grants.get(user))
+            .map_or(false, |grant| grant.downloads_remaining.is_some());
+        ensure(is_limited, || {
+            format!("{user} is downloading file {file_id} without a limited share")
+        })?;
+    }
+    Ok(())
+}
+
+/// The transfers, organizations, drop boxes and download tokens only refer to existing files.
+fn check_references(state: &State) -> Result<(), String> {
+    for (file_id, transfer) in &state.file_transfers {
+        let file = get_file(state, *file_id, "a transfer")?;
+        ensure(file.metadata.requester_principal != transfer.to, || {
+            format!("file {file_id} is offered to its owner")
+        })?;
+    }
+    for (file_id, org_id) in &state.file_organizations {
+        get_file(state, *file_id, &format!("organization {org_id}"))?;
+        ensure(state.organizations.contains_key(org_id), || {
+            format!("file {file_id} belongs to organization {org_id}, which doesn't exist")
+        })?;
+    }
+    for (alias, drop_box) in &state.drop_boxes {
+        for upload in &drop_box.uploads {
+            get_file(state, upload.file_id, &format!("drop box {alias}"))?;
+            ensure(
+                state.drop_box_files.get(&upload.file_id) == Some(alias),
+                || format!("file {} of drop box {alias} isn't indexed", upload.file_id),
+            )?;
+        }
+    }
+    ensure(
+        state.drop_box_files.len()
+            == state
+                .drop_boxes
+                .values()
+                .map(|drop_box| drop_box.uploads.len())
+                .sum::<usize>(),
+        || "the index of drop box files has stale entries".to_string(),
+    )?;
+    for grant in state.download_tokens.values() {
+        get_file(state, grant.file_id, "a download token")?;
+    }
+    Ok(())
+}
+
+/// Inboxes are bounded, and their notifications were issued in order.
+fn check_notifications(state: &State) -> Result<(), String> {
+    for (user, inbox) in &state.notifications {
+        ensure(inbox.len() <= MAX_NOTIFICATIONS, || {
+            format!("{user} has {} notifications", inbox.len())
+        })?;
+        let ids: Vec<u64> = inbox.iter().map(|notification| notification.id).collect();
+        ensure(ids.windows(2).all(|pair| pair[0] < pair[1]), || {
+            format!("the notifications of {user} are out of order: {ids:?}")
+        })?;
+        ensure(
+            ids.last().map_or(true, |id| *id < state.notification_count),
+            || format!("{user} has a notification that wasn't issued"),
+        )?;
+    }
+    Ok(())
+}
+
+/// Users are within their limit of webhooks, and the outbox only holds bounded deliveries to
+/// registered webhooks.
+fn check_webhooks(state: &State) -> Result<(), String> {
+    let mut registered: BTreeMap<Principal, usize> = BTreeMap::new();
+    for (id, webhook) in &state.webhooks {
+        ensure(*id < state.webhook_count, || {
+            format!("webhook {id} wasn't registered")
+        })?;
+        *registered.entry(webhook.owner).or_default() += 1;
+    }
+    for (owner, count) in registered {
+        ensure(count <= MAX_WEBHOOKS, || {
+            format!("{owner} has {count} webhooks")
+        })?;
+    }
+
+    let mut queued: BTreeMap<u64, usize> = BTreeMap::new();
+    for delivery in state.webhook_deliveries.values() {
+        *queued.entry(delivery.webhook_id).or_default() += 1;
+    }
+    for (webhook_id, count) in queued {
+        ensure(count <= MAX_WEBHOOK_DELIVERIES, || {
+            format!("{count} deliveries are queued for webhook {webhook_id}")
+        })?;
+    }
+    for (id, delivery) in &state.webhook_deliveries {
+        ensure(*id < state.webhook_delivery_count, || {
+            format!("delivery {id} wasn't queued")
+        })?;
+        ensure(state.webhooks.contains_key(&delivery.webhook_id), || {
+            format!(
+                "delivery {id} is to webhook {}, which doesn't exist",
+                delivery.webhook_id
+            )
+        })?;
+        ensure(delivery.attempts <= MAX_WEBHOOK_ATTEMPTS, || {
+            format!("delivery {id} was attempted {} times", delivery.attempts)
+        })?;
+    }
+    Ok(())
+}
+
+/// The chunks in stable memory are exactly those of the retained versions of the files.
+fn check_chunks(state: &State) -> Result<(), String> {
+    let legacy_chunks = state
+        .legacy_file_contents
+        .iter()
+        .map(|((file_id, chunk_id), chunk)| (ChunkKey(file_id, 0, chunk_id), chunk));
+    for (ChunkKey(file_id, version, chunk_id), _) in state.file_contents.iter().chain(legacy_chunks)
+    {
+        let file = get_file(
+            state,
+            file_id,
+            &format!("chunk {chunk_id} of version {version}"),
+        )?;
+        ensure(chunk_id < num_chunks(file, version).unwrap_or(0), || {
+            format!("chunk {chunk_id} of version {version} of file {file_id} isn't expected")
+        })?;
+    }
+    for (file_id, file) in &state.file_data {
+        let versions = file.versions.iter().map(|version| version.version);
+        for version in versions.chain([file.version]) {
+            let uploaded = state.num_chunks_uploaded(*file_id, version);
+            let expected = match (&file.content, num_chunks(file, version)) {
+                // The chunks of the current version are uploaded one by one.
+                (FileContent::PartiallyUploaded { .. }, _) if version == file.version => continue,
+                (_, num_chunks) => num_chunks.unwrap_or(0),
+            };
+            ensure(uploaded == expected, || {
+                format!(
+                    "version {version} of file {file_id} has {uploaded} chunks instead of {expected}"
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of chunks of the given version of the file, if it has been uploaded.
+fn num_chunks(file: &File, version: u64) -> Option<u64> {
+    if version == file.version {
+        match &file.content {
+            FileContent::Pending { .. } => None,
+            FileContent::Uploaded { num_chunks, .. }
+            | FileContent::PartiallyUploaded { num_chunks, .. } => Some(*num_chunks),
+        }
+    } else {
+        file.versions
+            .iter()
+            .find(|retained| retained.version == version)
+            .map(|retained| retained.num_chunks)
+    }
+}
+
+/// The usage of every user accounts for the files they own that have been uploaded, and the
+/// stored bytes for all the chunks.
+fn check_usage(state: &State) -> Result<(), String> {
+    let mut expected: BTreeMap<Principal, Usage> = BTreeMap::new();
+    let mut stored_bytes = 0;
+    for (file_id, file) in &state.file_data {
+        if matches!(file.content, FileContent::Pending { .. }) {
+            continue;
+        }
+        let versions = file.versions.iter().map(|version| version.version);
+        let bytes: u64 = versions
+            .chain([file.version])
+            .map(|version| state.version_bytes(*file_id, version))
+            .sum();
+        let usage = expected
+            .entry(file.metadata.requester_principal)
+            .or_default();
+        usage.file_count += 1;
+        usage.bytes += bytes;
+        stored_bytes += bytes;
+    }
+
+    for user in expected.keys().chain(state.usage.keys()) {
+        let usage = state.usage.get(user).cloned().unwrap_or_default();
+        let expected = expected.get(user).cloned().unwrap_or_default();
+        ensure(usage == expected, || {
+            format!("the usage of {user} is {usage:?} instead of {expected:?}")
+        })?;
+    }
+    ensure(state.stored_bytes == stored_bytes, || {
+        format!(
+            "{} bytes are accounted as stored instead of {stored_bytes}",
+            state.stored_bytes
+        )
+    })
+}
+
+/// The certified tree, which is updated incrementally, is the one that certifies the state.
+fn check_certified_data(state: &State) -> Result<(), String> {
+    let expected = CertifiedMap::from_leaves(certification::expected_leaves(state));
+    let (leaves, expected_leaves) = (state.certified_data.leaves(), expected.leaves());
+    if let Some(((path, _), _)) = leaves
+        .iter()
+        .zip(&expected_leaves)
+        .find(|(leaf, expected_leaf)| leaf != expected_leaf)
+    {
+        return Err(format!(
+            "the certified leaf at {path:?} doesn't match the state"
+        ));
+    }
+    ensure(leaves.len() == expected_leaves.len(), || {
+        format!(
+            "{} leaves are certified instead of {}",
+            leaves.len(),
+            expected_leaves.len()
+        )
+    })?;
+    ensure(
+        state.certified_data.root_hash() == expected.root_hash(),
+        || "the root hash of the certified tree is stale".to_string(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::{
+        self, CreateDropBoxRequest, UploadFileAtomicRequest, UploadFileVersionRequest,
+        UploadToDropBoxRequest,
+    };
+    use crate::certification::file_metadata_path;
+    use crate::env::TestEnvironment;
+    use crate::{
+        Config, FileDownloadResponse, InitArgs, Role, SharePermission, UploadFileContinueRequest,
+        User,
+    };
+    use proptest::prelude::*;
+
+    const NUM_USERS: u8 = 3;
+    const HOUR_NS: u64 = 60 * 60 * 1_000_000_000;
+
+    fn principal(id: u8) -> Principal {
+        Principal::from_slice(&[0, 1, id])
+    }
+
+    /// The first user, who is also the admin.
+    fn admin() -> Principal {
+        principal(0)
+    }
+
+    fn register(state: &mut State, id: u8) {
+        api::set_user_info(
+            state,
+            principal(id),
+            User {
+                username: format!("user {id}"),
+                public_key: vec![id],
+            },
+        );
+    }
+
+    /// Returns a state with registered users and small limits, so that the operations run into
+    /// them.
+    fn setup(env: &TestEnvironment) -> State {
+        let mut state = State::new(Box::new(env.clone()));
+        // Outside of the canister, the states of a thread share the same stable memory, so the
+        // chunks left by previous states are removed.
+        let keys: Vec<ChunkKey> = state.file_contents.iter().map(|(key, _)| key).collect();
+        for key in keys {
+            state.file_contents.remove(&key);
+        }
+        api::init(
+            &mut state,
+            admin(),
+            InitArgs {
+                admins: None,
+                config: Some(Config {
+                    max_file_versions: 2,
+                    max_user_files: 6,
+                    request_ttl_ns: Some(24 * HOUR_NS),
+                    share_ttl_ns: Some(48 * HOUR_NS),
+                    max_drop_box_ttl_ns: Some(24 * HOUR_NS),
+                    ..Config::default()
+                }),
+                rate_limits: None,
+            },
+        )
+        .unwrap();
+        for id in 0..NUM_USERS {
+            register(&mut state, id);
+        }
+        state
+    }
+
+    /// An update call. Users, files, organizations and drop boxes are referred to by small
+    /// numbers, so that operations often refer to the same ones, which may not exist.
+    #[derive(Clone, Debug)]
+    enum Operation {
+        SetUser {
+            user: u8,
+        },
+        RequestFile {
+            user: u8,
+        },
+        RequestChecklist {
+            user: u8,
+            items: usize,
+        },
+        UploadRequested {
+            file_id: u64,
+            num_chunks: u64,
+            len: usize,
+        },
+        UploadFile {
+            user: u8,
+            num_chunks: u64,
+            len: usize,
+        },
+        UploadChunk {
+            file_id: u64,
+            len: usize,
+        },
+        UploadVersion {
+            user: u8,
+            file_id: u64,
+            num_chunks: u64,
+            len: usize,
+        },
+        CancelVersion {
+            user: u8,
+            file_id: u64,
+        },
+        Share {
+            user: u8,
+            with: u8,
+            file_id: u64,
+            permission: SharePermission,
+        },
+        ShareWithDownloadLimit {
+            user: u8,
+            with: u8,
+            file_id: u64,
+            max_downloads: u64,
+        },
+        Revoke {
+            user: u8,
+            with: u8,
+            file_id: u64,
+        },
+        Download {
+            user: u8,
+            file_id: u64,
+            chunks: u64,
+        },
+        CreateDownloadToken {
+            user: u8,
+            file_id: u64,
+        },
+        OfferTransfer {
+            user: u8,
+            to: u8,
+            file_id: u64,
+        },
+        AcceptTransfer {
+            user: u8,
+            file_id: u64,
+        },
+        CancelTransfer {
+            user: u8,
+            file_id: u64,
+        },
+        CreateOrganization {
+            user: u8,
+        },
+        SetMember {
+            user: u8,
+            org_id: u64,
+            member: u8,
+            role: Role,
+        },
+        RemoveMember {
+            user: u8,
+            org_id: u64,
+            member: u8,
+        },
+        AddFileToOrganization {
+            user: u8,
+            file_id: u64,
+            org_id: u64,
+        },
+        RequestOrganizationFile {
+            user: u8,
+            org_id: u64,
+        },
+        CreateDropBox {
+            user: u8,
+            max_files: Option<u64>,
+        },
+        UploadToDropBox {
+            drop_box: usize,
+            num_chunks: u64,
+            len: usize,
+        },
+        CloseDropBox {
+            user: u8,
+            drop_box: usize,
+        },
+        RemoveFile {
+            file_id: u64,
+        },
+        RemoveUser {
+            user: u8,
+        },
+        AckNotifications {
+            user: u8,
+            up_to: u64,
+        },
+        NotifyExpiringRequests,
+        AddWebhook {
+            user: u8,
+        },
+        RemoveWebhook {
+            user: u8,
+            webhook_id: u64,
+        },
+        DeliverWebhooks {
+            status: u16,
+        },
+        AdvanceTime {
+            hours: u64,
+        },
+    }
+
+    fn operation() -> impl Strategy<Value = Operation> {
+        let user = || 0..NUM_USERS;
+        let file_id = || 0..8u64;
+        let num_chunks = || 1..4u64;
+        let len = || 1..4usize;
+        let org_id = || 0..2u64;
+        let permission = prop_oneof![
+            Just(SharePermission::View),
+            Just(SharePermission::Reshare),
+            Just(SharePermission::CoOwner),
+        ];
+        let role = prop_oneof![Just(Role::Admin), Just(Role::Editor), Just(Role::Viewer)];
+
+        prop_oneof![
+            user().prop_map(|user| Operation::SetUser { user }),
+            user().prop_map(|user| Operation::RequestFile { user }),
+            (user(), 1..4usize)
+                .prop_map(|(user, items)| Operation::RequestChecklist { user, items }),
+            (file_id(), num_chunks(), len()).prop_map(|(file_id, num_chunks, len)| {
+                Operation::UploadRequested {
+                    file_id,
+                    num_chunks,
+                    len,
+                }
+            }),
+            (user(), num_chunks(), len()).prop_map(|(user, num_chunks, len)| {
+                Operation::UploadFile {
+                    user,
+                    num_chunks,
+                    len,
+                }
+            }),
+            (file_id(), len()).prop_map(|(file_id, len)| Operation::UploadChunk { file_id, len }),
+            (user(), file_id(), num_chunks(), len()).prop_map(
+                |(user, file_id, num_chunks, len)| Operation::UploadVersion {
+                    user,
+                    file_id,
+                    num_chunks,
+                    len,
+                }
+            ),
+            (user(), file_id())
+                .prop_map(|(user, file_id)| Operation::CancelVersion { user, file_id }),
+            (user(), user(), file_id(), permission).prop_map(
+                |(user, with, file_id, permission)| Operation::Share {
+                    user,
+                    with,
+                    file_id,
+                    permission,
+                }
+            ),
+            (user(), user(), file_id(), 1..3u64).prop_map(
+                |(user, with, file_id, max_downloads)| Operation::ShareWithDownloadLimit {
+                    user,
+                    with,
+                    file_id,
+                    max_downloads,
+                }
+            ),
+            (user(), user(), file_id()).prop_map(|(user, with, file_id)| Operation::Revoke {
+                user,
+                with,
+                file_id
+            }),
+            (user(), file_id(), 1..4u64).prop_map(|(user, file_id, chunks)| Operation::Download {
+                user,
+                file_id,
+                chunks,
+            }),
+            (user(), file_id())
+                .prop_map(|(user, file_id)| Operation::CreateDownloadToken { user, file_id }),
+            (user(), user(), file_id()).prop_map(|(user, to, file_id)| Operation::OfferTransfer {
+                user,
+                to,
+                file_id
+            }),
+            (user(), file_id())
+                .prop_map(|(user, file_id)| Operation::AcceptTransfer { user, file_id }),
+            (user(), file_id())
+                .prop_map(|(user, file_id)| Operation::CancelTransfer { user, file_id }),
+            user().prop_map(|user| Operation::CreateOrganization { user }),
+            (user(), org_id(), user(), role).prop_map(|(user, org_id, member, role)| {
+                Operation::SetMember {
+                    user,
+                    org_id,
+                    member,
+                    role,
+                }
+            }),
+            (user(), org_id(), user()).prop_map(|(user, org_id, member)| {
+                Operation::RemoveMember {
+                    user,
+                    org_id,
+                    member,
+                }
+            }),
+            (user(), file_id(), org_id()).prop_map(|(user, file_id, org_id)| {
+                Operation::AddFileToOrganization {
+                    user,
+                    file_id,
+                    org_id,
+                }
+            }),
+            (user(), org_id())
+                .prop_map(|(user, org_id)| Operation::RequestOrganizationFile { user, org_id }),
+            (user(), proptest::option::of(1..3u64))
+                .prop_map(|(user, max_files)| Operation::CreateDropBox { user, max_files }),
+            (0..3usize, num_chunks(), len()).prop_map(|(drop_box, num_chunks, len)| {
+                Operation::UploadToDropBox {
+                    drop_box,
+                    num_chunks,
+                    len,
+                }
+            }),
+            (user(), 0..3usize)
+                .prop_map(|(user, drop_box)| Operation::CloseDropBox { user, drop_box }),
+            file_id().prop_map(|file_id| Operation::RemoveFile { file_id }),
+            user().prop_map(|user| Operation::RemoveUser { user }),
+            (user(), 0..32u64)
+                .prop_map(|(user, up_to)| Operation::AckNotifications { user, up_to }),
+            Just(Operation::NotifyExpiringRequests),
+            user().prop_map(|user| Operation::AddWebhook { user }),
+            (user(), 0..8u64)
+                .prop_map(|(user, webhook_id)| Operation::RemoveWebhook { user, webhook_id }),
+            prop_oneof![Just(200u16), Just(400), Just(500)]
+                .prop_map(|status| Operation::DeliverWebhooks { status }),
+            (1..48u64).prop_map(|hours| Operation::AdvanceTime { hours }),
+        ]
+    }
+
+    /// Returns the principal of the user if they are registered. Other users can only register,
+    /// as `inspect_message` rejects their other calls.
+    fn registered(state: &State, id: u8) -> Option<Principal> {
+        state
+            .users
+            .contains_key(&principal(id))
+            .then(|| principal(id))
+    }
+
+    fn nth_drop_box(state: &State, index: usize) -> Option<String> {
+        state.drop_boxes.keys().nth(index).cloned()
+    }
+
+    /// Applies the operation as the canister would, skipping the calls that it rejects or that
+    /// trap on invalid arguments, as a trap rolls back the state.
+    fn apply(state: &mut State, env: &TestEnvironment, operation: Operation) {
+        match operation {
+            Operation::SetUser { user } => register(state, user),
+            Operation::RequestFile { user } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                api::request_file(caller, "request", state);
+            }
+            Operation::RequestChecklist { user, items } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let items = (0..items).map(|item| format!("item {item}")).collect();
+                api::request_checklist(caller, "checklist", items, state);
+            }
+            Operation::UploadRequested {
+                file_id,
+                num_chunks,
+                len,
+            } => {
+                let _ = api::upload_file(
+                    file_id,
+                    vec![0; len],
+                    "text/plain".to_string(),
+                    vec![1],
+                    num_chunks,
+                    state,
+                );
+            }
+            Operation::UploadFile {
+                user,
+                num_chunks,
+                len,
+            } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let request = UploadFileAtomicRequest {
+                    name: "file".to_string(),
+                    content: vec![1; len],
+                    owner_key: vec![1],
+                    file_type: "text/plain".to_string(),
+                    num_chunks,
+                };
+                let _ = api::upload_file_atomic(caller, request, state);
+            }
+            Operation::UploadChunk { file_id, len } => {
+                // Only the next missing chunk of a file being uploaded can be uploaded.
+                let Some(file) = state.file_data.get(&file_id) else {
+                    return;
+                };
+                let FileContent::PartiallyUploaded { num_chunks, .. } = file.content else {
+                    return;
+                };
+                let Some(chunk_id) = (0..num_chunks).find(|chunk_id| {
+                    !state.contains_chunk(ChunkKey(file_id, file.version, *chunk_id))
+                }) else {
+                    return;
+                };
+                let request = UploadFileContinueRequest {
+                    file_id,
+                    chunk_id,
+                    contents: vec![2; len],
+                };
+                let _ = api::upload_file_continue(request, state);
+            }
+            Operation::UploadVersion {
+                user,
+                file_id,
+                num_chunks,
+                len,
+            } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let request = UploadFileVersionRequest {
+                    file_id,
+                    content: vec![3; len],
+                    owner_key: vec![1],
+                    file_type: "text/plain".to_string(),
+                    num_chunks,
+                };
+                let _ = api::upload_file_version(caller, request, state);
+            }
+            Operation::CancelVersion { user, file_id } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::cancel_file_version(state, caller, file_id);
+            }
+            Operation::Share {
+                user,
+                with,
+                file_id,
+                permission,
+            } => {
+                let (Some(caller), Some(with)) = (registered(state, user), registered(state, with))
+                else {
+                    return;
+                };
+                api::share_file_with_permission(state, caller, with, file_id, vec![2], permission);
+            }
+            Operation::ShareWithDownloadLimit {
+                user,
+                with,
+                file_id,
+                max_downloads,
+            } => {
+                let (Some(caller), Some(with)) = (registered(state, user), registered(state, with))
+                else {
+                    return;
+                };
+                api::share_file_with_download_limit(
+                    state,
+                    caller,
+                    with,
+                    file_id,
+                    vec![2],
+                    max_downloads,
+                );
+            }
+            Operation::Revoke {
+                user,
+                with,
+                file_id,
+            } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                api::revoke_share(state, caller, principal(with), file_id);
+            }
+            Operation::Download {
+                user,
+                file_id,
+                chunks,
+            } => {
+                // Downloads the first chunks of the file, leaving the download unfinished if
+                // there are more.
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let mut chunk_id = 0;
+                while chunk_id < chunks {
+                    match api::download_file_audited(state, file_id, chunk_id, caller) {
+                        FileDownloadResponse::FoundFile(data) if chunk_id + 1 < data.num_chunks => {
+                            chunk_id += 1
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            Operation::CreateDownloadToken { user, file_id } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let token = format!("token {}", state.download_tokens.len());
+                let _ = api::create_download_token(state, caller, file_id, token);
+            }
+            Operation::OfferTransfer { user, to, file_id } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::offer_file_transfer(state, caller, principal(to), file_id, vec![3]);
+            }
+            Operation::AcceptTransfer { user, file_id } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::accept_file_transfer(state, caller, file_id, vec![4]);
+            }
+            Operation::CancelTransfer { user, file_id } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::cancel_file_transfer(state, caller, file_id);
+            }
+            Operation::CreateOrganization { user } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::create_organization(state, caller, "organization");
+            }
+            Operation::SetMember {
+                user,
+                org_id,
+                member,
+                role,
+            } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ =
+                    api::set_organization_member(state, caller, org_id, principal(member), role);
+            }
+            Operation::RemoveMember {
+                user,
+                org_id,
+                member,
+            } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::remove_organization_member(state, caller, org_id, principal(member));
+            }
+            Operation::AddFileToOrganization {
+                user,
+                file_id,
+                org_id,
+            } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::add_file_to_organization(state, caller, file_id, org_id);
+            }
+            Operation::RequestOrganizationFile { user, org_id } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::request_organization_file(caller, org_id, "request", state);
+            }
+            Operation::CreateDropBox { user, max_files } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let request = CreateDropBoxRequest {
+                    name: "drop box".to_string(),
+                    expires_at: None,
+                    max_files,
+                    max_bytes: None,
+                };
+                api::create_drop_box(caller, request, state);
+            }
+            Operation::UploadToDropBox {
+                drop_box,
+                num_chunks,
+                len,
+            } => {
+                let Some(alias) = nth_drop_box(state, drop_box) else {
+                    return;
+                };
+                let request = UploadToDropBoxRequest {
+                    alias,
+                    label: "label".to_string(),
+                    file_name: "file".to_string(),
+                    content: vec![4; len],
+                    owner_key: vec![1],
+                    file_type: "text/plain".to_string(),
+                    num_chunks,
+                };
+                let _ = api::upload_to_drop_box(request, state);
+            }
+            Operation::CloseDropBox { user, drop_box } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let Some(alias) = nth_drop_box(state, drop_box) else {
+                    return;
+                };
+                let _ = api::close_drop_box(caller, alias, state);
+            }
+            Operation::RemoveFile { file_id } => {
+                let _ = api::remove_file(state, admin(), file_id);
+            }
+            Operation::RemoveUser { user } => {
+                let _ = api::remove_user(state, admin(), principal(user));
+            }
+            Operation::AckNotifications { user, up_to } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                api::ack_notifications(state, caller, up_to);
+            }
+            Operation::NotifyExpiringRequests => api::notify_expiring_requests(state),
+            Operation::AddWebhook { user } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let url = "https://example.com/hook".to_string();
+                let _ = api::add_webhook(state, caller, url, "secret".to_string());
+            }
+            Operation::RemoveWebhook { user, webhook_id } => {
+                let Some(caller) = registered(state, user) else {
+                    return;
+                };
+                let _ = api::remove_webhook(state, caller, webhook_id);
+            }
+            Operation::DeliverWebhooks { status } => {
+                for request in api::take_due_webhook_requests(state) {
+                    api::complete_webhook_delivery(state, request.delivery_id, Ok(status));
+                }
+            }
+            Operation::AdvanceTime { hours } => env.advance_time(hours * HOUR_NS),
+        }
+    }
+
+    proptest! {
+        // All the invariants are checked after every operation, which is slow in debug builds.
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn operations_maintain_the_invariants(
+            operations in proptest::collection::vec(operation(), 1..64)
+        ) {
+            let env = TestEnvironment::default();
+            let mut state = setup(&env);
+            for operation in operations {
+                apply(&mut state, &env, operation.clone());
+                prop_assert_eq!(check_invariants(&state), Ok(()), "after {:?}", operation);
+            }
+        }
+    }
+
+    #[test]
+    fn violations_are_reported() {
+        let env = TestEnvironment::default();
+        let mut state = setup(&env);
+        api::request_file(principal(1), "request", &mut state);
+        assert_eq!(check_invariants(&state), Ok(()));
+
+        state.file_owners.get_mut(&principal(1)).unwrap().push(42);
+        assert_eq!(
+            check_invariants(&state),
+            Err(format!(
+                "the files of {} refers to file 42, which doesn't exist",
+                principal(1)
+            ))
+        );
+        state.file_owners.get_mut(&principal(1)).unwrap().pop();
+
+        state.certified_data.remove(&file_metadata_path(0));
+        assert!(check_invariants(&state).unwrap_err().contains("certified"));
+    }
+}
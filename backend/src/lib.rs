@@ -1,89 +1,132 @@
 mod aliases;
 pub mod api;
+pub mod certification;
+pub mod env;
+pub mod invariants;
 mod memory;
 mod upgrade;
-use crate::aliases::{AliasGenerator, Randomness};
-use ic_cdk::export::{candid::CandidType, Principal};
-use ic_stable_structures::StableBTreeMap;
+pub mod validation;
+use certification::{CertifiedMap, Hash};
+pub use docutrack_types::*;
+use env::Environment;
+use ic_cdk::export::Principal;
+use ic_stable_structures::{storable::Bound, StableBTreeMap, StableLog, Storable};
 use memory::Memory;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::ops::Bound::{Excluded, Included};
-pub use upgrade::{post_upgrade, pre_upgrade};
+pub use upgrade::{
+    certify_existing_chunks, legacy_chunks_due, migrate_legacy_chunks, post_upgrade, pre_upgrade,
+    uncertified_chunks_due,
+};
 
 thread_local! {
-    /// Initialize the state randomness with the current time.
-    static STATE: RefCell<State> = RefCell::new(State::new(&get_randomness_seed()[..]));
+    static STATE: RefCell<State> = RefCell::new(State::new(env::default_env()));
 }
 
 type FileId = u64;
 type ChunkId = u64;
+type VersionId = u64;
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct User {
-    pub username: String,
-    pub public_key: Vec<u8>,
-}
+/// The maximum number of entries returned by a single page of the audit log.
+pub const MAX_AUDIT_LOG_PAGE_SIZE: u64 = 100;
 
-#[derive(CandidType, Serialize, Deserialize)]
-pub enum SetUserResponse {
-    #[serde(rename = "ok")]
-    Ok,
-    #[serde(rename = "username_exists")]
-    UsernameExists,
-}
+/// The maximum number of entries of the audit log read for a single page of the log of a file.
+pub const MAX_AUDIT_LOG_SCAN: u64 = 10_000;
 
-#[derive(CandidType, Serialize, Deserialize)]
-pub enum WhoamiResponse {
-    #[serde(rename = "known_user")]
-    KnownUser(PublicUser),
-    #[serde(rename = "unknown_user")]
-    UnknownUser,
-}
+/// The maximum number of notifications kept for a user. The oldest ones are dropped first.
+pub const MAX_NOTIFICATIONS: usize = 100;
+
+/// How long before a request expires its requester is notified.
+pub const REQUEST_EXPIRY_NOTICE_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// The maximum number of webhooks a user can register.
+pub const MAX_WEBHOOKS: usize = 5;
+
+/// The maximum number of deliveries waiting to be sent to a webhook. The oldest ones are
+/// dropped first.
+pub const MAX_WEBHOOK_DELIVERIES: usize = 200;
 
-/// File metadata.
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct FileMetadata {
-    pub file_name: String,
-    pub user_public_key: Vec<u8>,
+/// The number of times a webhook delivery is attempted before it is given up.
+pub const MAX_WEBHOOK_ATTEMPTS: u32 = 6;
+
+/// How long after the first failed attempt a webhook delivery is retried. The delay doubles
+/// after every failed attempt.
+pub const WEBHOOK_RETRY_DELAY_NS: u64 = 60 * 1_000_000_000;
+
+/// A named list of documents that are requested through a single alias.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checklist {
+    pub name: String,
     pub requester_principal: Principal,
     pub requested_at: u64,
-    pub uploaded_at: Option<u64>,
+    pub file_ids: Vec<u64>,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub enum FileStatus {
-    #[serde(rename = "pending")]
-    Pending { alias: String, requested_at: u64 },
-    #[serde(rename = "partially_uploaded")]
-    PartiallyUploaded,
-    #[serde(rename = "uploaded")]
-    Uploaded {
-        uploaded_at: u64,
-        document_key: Vec<u8>,
-    },
+/// A link that accepts any number of uploads until it is closed or expires.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DropBox {
+    pub name: String,
+    pub requester_principal: Principal,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub max_files: Option<u64>,
+    pub max_bytes: Option<u64>,
+    /// The number of bytes uploaded to the drop box so far, across all the chunks of its files.
+    pub total_bytes: u64,
+    pub closed: bool,
+    pub uploads: Vec<DropBoxUpload>,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct PublicFileMetadata {
-    pub file_id: u64,
-    pub file_name: String,
-    pub file_status: FileStatus,
-    pub shared_with: Vec<PublicUser>,
+/// An offer to transfer the ownership of a file to another user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileTransfer {
+    pub to: Principal,
+    /// The file key encrypted for the recipient of the offer.
+    pub file_key_encrypted_for_user: Vec<u8>,
+    pub offered_at: u64,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub enum GetAliasInfoError {
-    #[serde(rename = "not_found")]
-    NotFound,
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Organization {
+    pub name: String,
+    pub created_at: u64,
+    pub members: BTreeMap<Principal, Role>,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct AliasInfo {
-    pub file_id: u64,
-    pub file_name: String,
-    pub user: PublicUser,
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareGrant {
+    pub permission: SharePermission,
+    pub granted_by: Principal,
+    pub granted_at: u64,
+    /// The number of downloads left before the share is revoked, if the share is limited.
+    #[serde(default)]
+    pub downloads_remaining: Option<u64>,
+}
+
+/// A URL the events of the files of its owner are posted to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub owner: Principal,
+    pub url: String,
+    /// The key the payloads are signed with, which is only shown to the owner once.
+    pub secret: String,
+    pub created_at: u64,
+}
+
+/// A payload waiting to be posted to a webhook.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub webhook_id: u64,
+    /// The JSON payload, which is built when the event occurs.
+    pub body: String,
+    /// The number of attempts made so far.
+    pub attempts: u32,
+    /// When the next attempt is due. It is scheduled when an attempt is made, so that the
+    /// delivery is retried even if the attempt never completes.
+    pub next_attempt_at: u64,
 }
 
 // A file is composed of its metadata and its content, which is a blob.
@@ -91,6 +134,24 @@ pub struct AliasInfo {
 pub struct File {
     pub metadata: FileMetadata,
     pub content: FileContent,
+    /// The version of the current content. It starts at zero and is incremented
+    /// every time a new version of the file is uploaded.
+    #[serde(default)]
+    pub version: VersionId,
+    /// Previous versions of the file that are still retained, oldest first.
+    #[serde(default)]
+    pub versions: Vec<FileVersion>,
+}
+
+/// A previous version of an uploaded file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileVersion {
+    pub version: VersionId,
+    pub num_chunks: u64,
+    pub file_type: String,
+    pub owner_key: Vec<u8>,
+    pub shared_keys: BTreeMap<Principal, Vec<u8>>,
+    pub uploaded_at: u64,
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -112,42 +173,20 @@ pub enum FileContent {
     },
 }
 
-#[derive(CandidType, Serialize, Deserialize, Debug, PartialEq)]
-pub struct FileData {
-    contents: Vec<u8>,
-    file_type: String,
-    owner_key: Vec<u8>,
-    num_chunks: u64,
-}
-
-#[derive(CandidType, Serialize, Deserialize, PartialEq, Debug)]
-pub enum FileDownloadResponse {
-    #[serde(rename = "not_found_file")]
-    NotFoundFile,
-    #[serde(rename = "not_uploaded_file")]
-    NotUploadedFile,
-    #[serde(rename = "permission_error")]
-    PermissionError,
-    #[serde(rename = "found_file")]
-    FoundFile(FileData),
+/// The timestamps of the recent calls to the throttled endpoints, used to enforce `RateLimits`.
+#[derive(Default)]
+pub struct RateLimiter {
+    pub principal_calls: BTreeMap<Principal, VecDeque<u64>>,
+    pub alias_calls: BTreeMap<String, VecDeque<u64>>,
 }
 
-#[derive(CandidType, Serialize, Deserialize)]
-pub enum UploadFileError {
-    #[serde(rename = "not_requested")]
-    NotRequested,
-    #[serde(rename = "already_uploaded")]
-    AlreadyUploaded,
-}
-
-#[derive(CandidType, Serialize, Deserialize, Debug, PartialEq)]
-pub enum FileSharingResponse {
-    #[serde(rename = "pending_error")]
-    PendingError,
-    #[serde(rename = "permission_error")]
-    PermissionError,
-    #[serde(rename = "ok")]
-    Ok,
+/// Gives the user who created it access to a version of a file over HTTP, until it expires.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadGrant {
+    pub file_id: u64,
+    pub version: VersionId,
+    pub user: Principal,
+    pub expires_at: u64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -171,13 +210,150 @@ pub struct State {
     /// Mapping between a user's principal and the list of files that are shared with them.
     pub file_shares: BTreeMap<Principal, Vec<u64>>,
 
-    /// The contents of the file (stored in stable memory).
+    /// Mapping between file IDs and the grants of their shares, keyed by recipient.
+    /// Shares without a grant were made by the owner before permission levels existed.
+    #[serde(default)]
+    pub share_grants: BTreeMap<u64, BTreeMap<Principal, ShareGrant>>,
+
+    /// The downloads of limited shares that are in progress, as pairs of file ID and recipient.
+    #[serde(default)]
+    pub download_sessions: BTreeSet<(u64, Principal)>,
+
+    /// Mapping between checklist aliases and the checklists of requested files.
+    #[serde(default)]
+    pub checklists: BTreeMap<String, Checklist>,
+
+    /// Mapping between drop box aliases and the drop boxes.
+    #[serde(default)]
+    pub drop_boxes: BTreeMap<String, DropBox>,
+
+    /// Mapping between the IDs of files uploaded to drop boxes and the aliases of the drop boxes.
+    #[serde(default)]
+    pub drop_box_files: BTreeMap<u64, String>,
+
+    /// Mapping between file IDs and pending offers to transfer their ownership.
+    #[serde(default)]
+    pub file_transfers: BTreeMap<u64, FileTransfer>,
+
+    // Keeps track of how many organizations have been created so far
+    // and is used to assign IDs to new organizations.
+    #[serde(default)]
+    organization_count: u64,
+
+    /// Mapping between organization IDs and organizations.
+    #[serde(default)]
+    pub organizations: BTreeMap<u64, Organization>,
+
+    /// Mapping between file IDs and the organizations that own them.
+    #[serde(default)]
+    pub file_organizations: BTreeMap<u64, u64>,
+
+    /// Mapping between a user's principal and the storage consumed by the files they own.
+    #[serde(default)]
+    pub usage: BTreeMap<Principal, Usage>,
+
+    /// The number of bytes stored in `file_contents` across all users.
+    #[serde(default)]
+    pub stored_bytes: u64,
+
+    /// The principals allowed to perform privileged operations, such as reading the whole
+    /// audit log.
+    #[serde(default)]
+    pub admins: BTreeSet<Principal>,
+
+    /// The settings that admins can rotate at runtime.
+    #[serde(default)]
+    pub config: Config,
+
+    /// Whether admins have frozen uploads, e.g., while handling abuse.
+    #[serde(default)]
+    pub uploads_frozen: bool,
+
+    /// The budgets of the throttled endpoints.
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+
+    // Keeps track of the recent calls to the throttled endpoints.
+    // The windows are short, so the calls aren't preserved across upgrades.
+    #[serde(skip)]
+    pub rate_limiter: RateLimiter,
+
+    /// The download tokens that give access to files over HTTP.
+    #[serde(default)]
+    pub download_tokens: BTreeMap<String, DownloadGrant>,
+
+    /// Mapping between a user's principal and their notifications, oldest first.
+    #[serde(default)]
+    pub notifications: BTreeMap<Principal, VecDeque<Notification>>,
+
+    // Keeps track of how many notifications have been sent so far
+    // and is used to assign IDs to new notifications.
+    #[serde(default)]
+    notification_count: u64,
+
+    /// The time until which the requesters of expiring requests have been notified.
+    #[serde(default)]
+    pub expiry_notices_sent_until: u64,
+
+    /// The webhooks of all users, keyed by their ID.
+    #[serde(default)]
+    pub webhooks: BTreeMap<u64, Webhook>,
+
+    // Keeps track of how many webhooks have been registered so far
+    // and is used to assign IDs to new webhooks.
+    #[serde(default)]
+    webhook_count: u64,
+
+    /// The payloads waiting to be posted to webhooks, keyed by their ID, oldest first.
+    #[serde(default)]
+    pub webhook_deliveries: BTreeMap<u64, WebhookDelivery>,
+
+    // Keeps track of how many webhook deliveries have been made so far
+    // and is used to assign IDs to new deliveries.
+    #[serde(default)]
+    webhook_delivery_count: u64,
+
+    /// The Merkle tree of the file metadata, chunk hashes, pending aliases and users.
+    /// Its root hash is the certified data of the canister.
+    #[serde(default)]
+    pub certified_data: CertifiedMap,
+
+    /// The key of the next chunk of `file_contents` to certify, or `None` once all of them are.
+    /// Canisters that predate certification certify their chunks in batches in the heartbeat.
+    #[serde(default)]
+    pub uncertified_chunks_cursor: Option<ChunkKey>,
+
+    // The root hash that was last set as the certified data, which isn't preserved across
+    // upgrades.
+    #[serde(skip)]
+    certified_root: Option<Hash>,
+
+    /// An append-only log of the accesses to and mutations of files (stored in stable memory).
+    #[serde(skip, default = "init_audit_log")]
+    pub audit_log: StableLog<AuditEntry, Memory, Memory>,
+
+    /// An append-only log of the privileged operations of admins (stored in stable memory).
+    #[serde(skip, default = "init_admin_log")]
+    pub admin_log: StableLog<AdminLogEntry, Memory, Memory>,
+
+    /// The contents of the file (stored in stable memory), keyed by file, version and chunk.
     #[serde(skip, default = "init_file_contents")]
-    pub file_contents: StableBTreeMap<(FileId, ChunkId), Vec<u8>, Memory>,
-
-    // Generates aliases for file requests.
-    #[serde(skip, default = "init_alias_generator")]
-    alias_generator: AliasGenerator,
+    pub file_contents: StableBTreeMap<ChunkKey, Vec<u8>, Memory>,
+
+    /// The chunks stored before files were versioned (in stable memory), keyed by file and
+    /// chunk. They are the chunks of the first version of their files, and are read in place,
+    /// as copying them to `file_contents` wouldn't fit in an upgrade.
+    #[serde(skip, default = "init_legacy_file_contents")]
+    pub legacy_file_contents: StableBTreeMap<(u64, u64), Vec<u8>, Memory>,
+
+    /// The key of the next legacy chunk to account to the owner of its file and to certify,
+    /// or `None` once all of them are. Legacy chunks are processed in batches by the heartbeat.
+    #[serde(default = "first_legacy_chunk")]
+    pub legacy_chunks_cursor: Option<(u64, u64)>,
+
+    /// The clock, randomness and caller the operations use.
+    #[serde(skip, default = "env::default_env")]
+    pub env: Box<dyn Environment>,
 }
 
 impl State {
@@ -189,7 +365,31 @@ impl State {
         file_id
     }
 
-    fn new(rand_seed: &[u8]) -> Self {
+    pub(crate) fn generate_organization_id(&mut self) -> u64 {
+        let org_id = self.organization_count;
+        self.organization_count += 1;
+        org_id
+    }
+
+    pub(crate) fn generate_notification_id(&mut self) -> u64 {
+        let id = self.notification_count;
+        self.notification_count += 1;
+        id
+    }
+
+    pub(crate) fn generate_webhook_id(&mut self) -> u64 {
+        let id = self.webhook_count;
+        self.webhook_count += 1;
+        id
+    }
+
+    pub(crate) fn generate_webhook_delivery_id(&mut self) -> u64 {
+        let id = self.webhook_delivery_count;
+        self.webhook_delivery_count += 1;
+        id
+    }
+
+    pub fn new(env: Box<dyn Environment>) -> Self {
         Self {
             file_count: 0,
             users: BTreeMap::new(),
@@ -197,22 +397,175 @@ impl State {
             file_alias_index: BTreeMap::new(),
             file_owners: BTreeMap::new(),
             file_shares: BTreeMap::new(),
-            alias_generator: AliasGenerator::new(Randomness::try_from(rand_seed).unwrap()),
+            share_grants: BTreeMap::new(),
+            download_sessions: BTreeSet::new(),
+            checklists: BTreeMap::new(),
+            drop_boxes: BTreeMap::new(),
+            drop_box_files: BTreeMap::new(),
+            file_transfers: BTreeMap::new(),
+            organization_count: 0,
+            organizations: BTreeMap::new(),
+            file_organizations: BTreeMap::new(),
+            usage: BTreeMap::new(),
+            stored_bytes: 0,
+            admins: BTreeSet::new(),
+            config: Config::default(),
+            uploads_frozen: false,
+            rate_limits: RateLimits::default(),
+            rate_limiter: RateLimiter::default(),
+            download_tokens: BTreeMap::new(),
+            notifications: BTreeMap::new(),
+            notification_count: 0,
+            expiry_notices_sent_until: 0,
+            webhooks: BTreeMap::new(),
+            webhook_count: 0,
+            webhook_deliveries: BTreeMap::new(),
+            webhook_delivery_count: 0,
+            certified_data: CertifiedMap::default(),
+            uncertified_chunks_cursor: None,
+            certified_root: None,
+            audit_log: init_audit_log(),
+            admin_log: init_admin_log(),
+            env,
             file_contents: init_file_contents(),
+            legacy_file_contents: init_legacy_file_contents(),
+            legacy_chunks_cursor: None,
         }
     }
 
-    /// Returns the number of uploaded chunks for the given file id
-    pub(crate) fn num_chunks_uploaded(&self, file_id: u64) -> u64 {
+    /// Returns a new alias, drawn from the randomness of the environment.
+    pub(crate) fn generate_alias(&mut self) -> String {
+        aliases::generate_alias(self.env.random_bytes())
+    }
+
+    /// Returns a chunk, which is read from the legacy chunks if it predates versioning.
+    pub(crate) fn get_chunk(&self, key: ChunkKey) -> Option<Vec<u8>> {
+        let ChunkKey(file_id, version, chunk_id) = key;
+        self.file_contents.get(&key).or_else(|| match version {
+            0 => self.legacy_file_contents.get(&(file_id, chunk_id)),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn contains_chunk(&self, key: ChunkKey) -> bool {
+        let ChunkKey(file_id, version, chunk_id) = key;
+        self.file_contents.contains_key(&key)
+            || (version == 0 && self.legacy_file_contents.contains_key(&(file_id, chunk_id)))
+    }
+
+    /// Returns the number of uploaded chunks for the given file id and version.
+    pub(crate) fn num_chunks_uploaded(&self, file_id: u64, version: VersionId) -> u64 {
+        let legacy_chunks = match version {
+            0 => self.legacy_chunks(file_id).count() as u64,
+            _ => 0,
+        };
         self.file_contents
-            .range((Included((file_id, 0u64)), Excluded(((file_id + 1), 0u64))))
+            .range((
+                Included(ChunkKey(file_id, version, 0)),
+                Excluded(ChunkKey(file_id, version + 1, 0)),
+            ))
             .count() as u64
+            + legacy_chunks
+    }
+
+    /// Returns the number of bytes accounted for the given file version.
+    ///
+    /// Legacy chunks are only accounted once the heartbeat has processed them.
+    pub(crate) fn version_bytes(&self, file_id: u64, version: VersionId) -> u64 {
+        let legacy_bytes: u64 = match version {
+            0 => self
+                .legacy_chunks(file_id)
+                .filter(|(key, _)| self.is_legacy_chunk_processed(*key))
+                .map(|(_, chunk)| chunk.len() as u64)
+                .sum(),
+            _ => 0,
+        };
+        self.file_contents
+            .range((
+                Included(ChunkKey(file_id, version, 0)),
+                Excluded(ChunkKey(file_id, version + 1, 0)),
+            ))
+            .map(|(_, chunk)| chunk.len() as u64)
+            .sum::<u64>()
+            + legacy_bytes
+    }
+
+    fn legacy_chunks(&self, file_id: u64) -> impl Iterator<Item = ((u64, u64), Vec<u8>)> + '_ {
+        self.legacy_file_contents
+            .range((Included((file_id, 0)), Excluded((file_id + 1, 0))))
+    }
+
+    /// Returns true if the legacy chunk has been accounted and certified.
+    pub(crate) fn is_legacy_chunk_processed(&self, key: (u64, u64)) -> bool {
+        self.legacy_chunks_cursor
+            .map_or(true, |cursor| key < cursor)
+    }
+
+    /// Returns true if the chunk of `file_contents` is certified, or will be once stored.
+    pub(crate) fn is_chunk_certified(&self, key: ChunkKey) -> bool {
+        self.uncertified_chunks_cursor
+            .map_or(true, |cursor| key < cursor)
+    }
+
+    /// Stores a chunk in stable memory and certifies its hash.
+    pub(crate) fn insert_chunk(&mut self, key: ChunkKey, contents: Vec<u8>) {
+        // Chunks past the cursor are certified along with the existing ones.
+        if self.is_chunk_certified(key) {
+            certification::certify_chunk(self, key, &contents);
+        }
+        self.file_contents.insert(key, contents);
+    }
+
+    /// Removes all the chunks of the given file version from stable memory.
+    pub(crate) fn remove_chunks(&mut self, file_id: u64, version: VersionId, num_chunks: u64) {
+        for chunk_id in 0..num_chunks {
+            let key = ChunkKey(file_id, version, chunk_id);
+            self.file_contents.remove(&key);
+            if version == 0 {
+                self.legacy_file_contents.remove(&(file_id, chunk_id));
+            }
+            certification::uncertify_chunk(self, key);
+        }
+    }
+
+    /// Sets the certified data of the canister if the certified tree has changed.
+    fn update_certified_data(&mut self) {
+        let root_hash = self.certified_data.root_hash();
+        if self.certified_root != Some(root_hash) {
+            certification::set_certified_data(&root_hash);
+            self.certified_root = Some(root_hash);
+        }
     }
 }
 
+/// The key of a chunk in `file_contents`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChunkKey(pub FileId, pub VersionId, pub ChunkId);
+
+impl Storable for ChunkKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        // Big-endian encoding preserves the ordering of the keys.
+        let mut bytes = Vec::with_capacity(24);
+        bytes.extend_from_slice(&self.0.to_be_bytes());
+        bytes.extend_from_slice(&self.1.to_be_bytes());
+        bytes.extend_from_slice(&self.2.to_be_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let read = |i: usize| u64::from_be_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap());
+        ChunkKey(read(0), read(1), read(2))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 24,
+        is_fixed_size: true,
+    };
+}
+
 impl Default for State {
     fn default() -> Self {
-        State::new(vec![0; 32].as_slice())
+        State::new(env::default_env())
     }
 }
 
@@ -226,77 +579,63 @@ pub fn with_state<R>(f: impl FnOnce(&State) -> R) -> R {
 /// A helper method to mutate the state.
 ///
 /// Precondition: the state is already initialized.
+///
+/// The certified data is updated with the changes, so this must not be used in queries that
+/// change the certified tree.
+///
+/// In debug builds, the invariants of the state are checked after the changes, and a violation
+/// traps, which rolls back the changes.
 pub fn with_state_mut<R>(f: impl FnOnce(&mut State) -> R) -> R {
-    STATE.with(|cell| f(&mut cell.borrow_mut()))
+    STATE.with(|cell| {
+        let mut state = cell.borrow_mut();
+        let result = f(&mut state);
+        #[cfg(debug_assertions)]
+        if let Err(violation) = invariants::check_invariants(&state) {
+            panic!("an invariant of the state doesn't hold: {violation}");
+        }
+        state.update_certified_data();
+        result
+    })
 }
 
 /// Returns an unused file alias.
 pub fn generate_alias() -> String {
-    with_state_mut(|s| s.alias_generator.next())
-}
-
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct PublicUser {
-    pub username: String,
-    pub public_key: Vec<u8>,
-    pub ic_principal: Principal,
-}
-
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub enum GetUsersResponse {
-    #[serde(rename = "permission_error")]
-    PermissionError,
-    #[serde(rename = "users")]
-    Users(Vec<PublicUser>),
-}
-
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct UploadFileRequest {
-    pub file_id: u64,
-    pub file_content: Vec<u8>,
-    pub file_type: String,
-    pub owner_key: Vec<u8>,
-    pub num_chunks: u64,
-}
-
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct UploadFileContinueRequest {
-    pub file_id: u64,
-    pub chunk_id: u64,
-    pub contents: Vec<u8>,
+    with_state_mut(|s| s.generate_alias())
 }
 
-#[cfg(target_arch = "wasm32")]
-pub fn get_time() -> u64 {
-    ic_cdk::api::time()
+pub fn ceil_division(dividend: usize, divisor: usize) -> usize {
+    if dividend % divisor == 0 {
+        dividend / divisor
+    } else {
+        dividend / divisor + 1
+    }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-pub fn get_time() -> u64 {
-    // This is used only in tests and we need a fixed value we can test against.
-    12345
+fn init_file_contents() -> StableBTreeMap<ChunkKey, Vec<u8>, Memory> {
+    StableBTreeMap::init(crate::memory::get_file_contents_memory())
 }
 
-fn get_randomness_seed() -> Vec<u8> {
-    // this is an array of u8 of length 8.
-    let time_seed = ic_cdk::api::time().to_be_bytes();
-    // we need to extend this to an array of size 32 by adding to it an array of size 24 full of 0s.
-    let zeroes_arr: [u8; 24] = [0; 24];
-    [&time_seed[..], &zeroes_arr[..]].concat()
+fn init_legacy_file_contents() -> StableBTreeMap<(u64, u64), Vec<u8>, Memory> {
+    StableBTreeMap::init(crate::memory::get_legacy_file_contents_memory())
 }
 
-fn init_alias_generator() -> AliasGenerator {
-    AliasGenerator::new(Randomness::try_from(get_randomness_seed().as_slice()).unwrap())
+// States that predate versioning have legacy chunks, which are processed from the first one.
+fn first_legacy_chunk() -> Option<(u64, u64)> {
+    Some((0, 0))
 }
 
-pub fn ceil_division(dividend: usize, divisor: usize) -> usize {
-    if dividend % divisor == 0 {
-        dividend / divisor
-    } else {
-        dividend / divisor + 1
-    }
+fn init_audit_log() -> StableLog<AuditEntry, Memory, Memory> {
+    StableLog::init(
+        crate::memory::get_audit_log_index_memory(),
+        crate::memory::get_audit_log_data_memory(),
+    )
+    .expect("failed to initialize the audit log")
 }
 
-fn init_file_contents() -> StableBTreeMap<(FileId, ChunkId), Vec<u8>, Memory> {
-    StableBTreeMap::init(crate::memory::get_file_contents_memory())
+fn init_admin_log() -> StableLog<AdminLogEntry, Memory, Memory> {
+    StableLog::init(
+        crate::memory::get_admin_log_index_memory(),
+        crate::memory::get_admin_log_data_memory(),
+    )
+    .expect("failed to initialize the admin log")
 }
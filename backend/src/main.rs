@@ -1,8 +1,15 @@
-use backend::api::UploadFileAtomicRequest;
+use backend::api::{
+    CreateDropBoxRequest, UploadFileAtomicRequest, UploadFileVersionRequest, UploadToDropBoxRequest,
+};
 use backend::*;
-use ic_cdk::api::caller;
-use ic_cdk::export::candid::Principal;
-use ic_cdk_macros::{post_upgrade, pre_upgrade, query, update};
+use ic_cdk::api::call::{accept_message, arg_data_raw, arg_data_raw_size, method_name};
+use ic_cdk::api::management_canister::http_request::{
+    self as outcall, CanisterHttpRequestArgument, HttpHeader, HttpMethod, TransformArgs,
+    TransformContext, TransformFunc,
+};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::export::candid::{decode_args, Principal};
+use ic_cdk_macros::{heartbeat, init, inspect_message, post_upgrade, pre_upgrade, query, update};
 
 #[update]
 fn set_user(username: String, public_key: Vec<u8>) -> SetUserResponse {
@@ -13,7 +20,7 @@ fn set_user(username: String, public_key: Vec<u8>) -> SetUserResponse {
             username,
             public_key,
         };
-        with_state_mut(|s| backend::api::set_user_info(s, caller(), user));
+        with_state_mut(|s| backend::api::set_user_info(s, s.env.caller(), user));
         SetUserResponse::Ok
     }
 }
@@ -25,24 +32,29 @@ fn username_exists(username: String) -> bool {
 
 #[query]
 fn who_am_i() -> WhoamiResponse {
-    with_state(|s| match s.users.get(&ic_cdk::api::caller()) {
+    with_state(|s| match s.users.get(&s.env.caller()) {
         None => WhoamiResponse::UnknownUser,
         Some(user) => WhoamiResponse::KnownUser(PublicUser {
             username: user.username.clone(),
             public_key: user.public_key.clone(),
-            ic_principal: ic_cdk::api::caller(),
+            ic_principal: s.env.caller(),
         }),
     })
 }
 
 #[query]
 fn get_requests() -> Vec<PublicFileMetadata> {
-    with_state(|s| backend::api::get_requests(s, caller()))
+    with_state(|s| backend::api::get_requests(s, s.env.caller()))
+}
+
+#[query]
+fn get_requests_certified() -> Certified<Vec<PublicFileMetadata>> {
+    with_state(|s| backend::api::get_requests_certified(s, s.env.caller()))
 }
 
 #[query]
 fn get_shared_files() -> Vec<PublicFileMetadata> {
-    with_state(|s| backend::api::get_shared_files(s, caller()))
+    with_state(|s| backend::api::get_shared_files(s, s.env.caller()))
 }
 
 #[query]
@@ -50,38 +62,232 @@ fn get_alias_info(alias: String) -> Result<AliasInfo, GetAliasInfoError> {
     with_state(|s| backend::api::get_alias_info(s, alias))
 }
 
+#[query]
+fn get_alias_info_certified(alias: String) -> Certified<Result<AliasInfo, GetAliasInfoError>> {
+    with_state(|s| backend::api::get_alias_info_certified(s, alias))
+}
+
 #[update]
 fn upload_file(request: UploadFileRequest) -> Result<(), UploadFileError> {
+    with_state(backend::api::check_uploads_allowed)?;
+    let config = with_state(backend::api::get_config);
+    validation::validate_upload(
+        &config,
+        &request.file_content,
+        request.num_chunks,
+        &request.file_type,
+    )?;
     with_state_mut(|s| {
-        backend::api::upload_file(
+        let file_id = request.file_id;
+        let alias = backend::api::pending_alias(s, file_id).map(str::to_string);
+        backend::api::consume_rate_limit(s, s.env.caller(), alias.as_deref())?;
+        let result = backend::api::upload_file(
             request.file_id,
             request.file_content,
             request.file_type,
             request.owner_key,
             request.num_chunks,
             s,
-        )
+        );
+        if result.is_ok() {
+            backend::api::record_upload(s, s.env.caller(), file_id, 0);
+        }
+        result
+    })
+}
+
+#[update]
+fn upload_file_atomic(request: UploadFileAtomicRequest) -> Result<u64, UploadFileError> {
+    with_state(backend::api::check_uploads_allowed)?;
+    let config = with_state(backend::api::get_config);
+    validation::validate_file_name(&request.name)?;
+    validation::validate_upload(
+        &config,
+        &request.content,
+        request.num_chunks,
+        &request.file_type,
+    )?;
+    with_state_mut(|s| {
+        let result = backend::api::upload_file_atomic(s.env.caller(), request, s);
+        if let Ok(file_id) = result {
+            backend::api::record_upload(s, s.env.caller(), file_id, 0);
+        }
+        result
+    })
+}
+
+#[update]
+fn upload_file_continue(request: UploadFileContinueRequest) -> Result<(), UploadFileError> {
+    with_state(backend::api::check_uploads_allowed)?;
+    let config = with_state(backend::api::get_config);
+    validation::validate_chunk(&config, &request.contents)?;
+    with_state(|s| backend::api::validate_chunk_size(s, &request))?;
+    with_state_mut(|s| {
+        backend::api::consume_chunk_rate_limit(s, s.env.caller(), request.file_id)?;
+        let (file_id, chunk_id) = (request.file_id, request.chunk_id);
+        backend::api::upload_file_continue(request, s)?;
+        backend::api::record_upload(s, s.env.caller(), file_id, chunk_id);
+        Ok(())
     })
 }
 
 #[update]
-fn upload_file_atomic(request: UploadFileAtomicRequest) -> u64 {
-    with_state_mut(|s| backend::api::upload_file_atomic(caller(), request, s))
+fn upload_file_version(request: UploadFileVersionRequest) -> Result<u64, FileVersionError> {
+    require_feature(|features| features.file_versions);
+    with_state(backend::api::check_uploads_allowed)?;
+    let config = with_state(backend::api::get_config);
+    validation::validate_upload(
+        &config,
+        &request.content,
+        request.num_chunks,
+        &request.file_type,
+    )?;
+    with_state_mut(|s| {
+        let file_id = request.file_id;
+        let result = backend::api::upload_file_version(s.env.caller(), request, s);
+        if result.is_ok() {
+            backend::api::record_upload(s, s.env.caller(), file_id, 0);
+        }
+        result
+    })
 }
 
 #[update]
-fn upload_file_continue(request: UploadFileContinueRequest) {
-    with_state_mut(|s| backend::api::upload_file_continue(request, s))
+fn cancel_file_version(file_id: u64) -> Result<u64, FileVersionError> {
+    require_feature(|features| features.file_versions);
+    with_state_mut(|s| backend::api::cancel_file_version(s, s.env.caller(), file_id))
+}
+
+#[query]
+fn get_file_versions(file_id: u64) -> Result<Vec<PublicFileVersion>, FileVersionError> {
+    with_state(|s| backend::api::get_file_versions(s, s.env.caller(), file_id))
 }
 
 #[update]
 fn request_file(request_name: String) -> String {
-    with_state_mut(|s| backend::api::request_file(caller(), request_name, s))
+    with_state_mut(|s| {
+        consume_rate_limit_or_trap(s);
+        backend::api::request_file(s.env.caller(), request_name, s)
+    })
+}
+
+#[update]
+fn request_checklist(name: String, items: Vec<String>) -> String {
+    require_feature(|features| features.checklists);
+    with_state_mut(|s| {
+        consume_rate_limit_or_trap(s);
+        backend::api::request_checklist(s.env.caller(), name, items, s)
+    })
+}
+
+// Not every endpoint of a feature has an error type, so calls to disabled features trap.
+fn require_feature(is_enabled: impl FnOnce(&Features) -> bool) {
+    if !with_state(|s| is_enabled(&s.config.features)) {
+        ic_cdk::trap("this feature is disabled");
+    }
+}
+
+// The request endpoints have no error type, so calls exceeding the rate limits trap.
+fn consume_rate_limit_or_trap(state: &mut State) {
+    if backend::api::consume_rate_limit(state, state.env.caller(), None).is_err() {
+        ic_cdk::trap("rate limit exceeded");
+    }
+}
+
+#[query]
+fn get_checklists() -> Vec<PublicChecklist> {
+    with_state(|s| backend::api::get_checklists(s, s.env.caller()))
+}
+
+#[update]
+fn create_drop_box(request: CreateDropBoxRequest) -> String {
+    require_feature(|features| features.drop_boxes);
+    with_state_mut(|s| backend::api::create_drop_box(s.env.caller(), request, s))
+}
+
+#[update]
+fn close_drop_box(alias: String) -> Result<(), DropBoxError> {
+    with_state_mut(|s| backend::api::close_drop_box(s.env.caller(), alias, s))
+}
+
+#[query]
+fn get_drop_box_info(alias: String) -> Result<DropBoxInfo, DropBoxError> {
+    with_state(|s| backend::api::get_drop_box_info(s, alias))
+}
+
+#[query]
+fn get_drop_boxes() -> Vec<PublicDropBox> {
+    with_state(|s| backend::api::get_drop_boxes(s, s.env.caller()))
+}
+
+#[update]
+fn upload_to_drop_box(request: UploadToDropBoxRequest) -> Result<u64, DropBoxError> {
+    require_feature(|features| features.drop_boxes);
+    with_state(backend::api::check_uploads_allowed)?;
+    let config = with_state(backend::api::get_config);
+    validation::validate_file_name(&request.file_name)?;
+    validation::validate_upload(
+        &config,
+        &request.content,
+        request.num_chunks,
+        &request.file_type,
+    )?;
+    with_state_mut(|s| {
+        backend::api::consume_rate_limit(s, s.env.caller(), Some(&request.alias))?;
+        let result = backend::api::upload_to_drop_box(request, s);
+        if let Ok(file_id) = result {
+            backend::api::record_upload(s, s.env.caller(), file_id, 0);
+        }
+        result
+    })
 }
 
 #[query]
 fn download_file(file_id: u64, chunk_id: u64) -> FileDownloadResponse {
-    with_state(|s| backend::api::download_file(s, file_id, chunk_id, caller()))
+    with_state(|s| backend::api::download_file(s, file_id, chunk_id, s.env.caller()))
+}
+
+#[query]
+fn download_file_certified(file_id: u64, chunk_id: u64) -> Certified<FileDownloadResponse> {
+    with_state(|s| backend::api::download_file_certified(s, file_id, chunk_id, s.env.caller()))
+}
+
+// Same as `download_file`, but records the download in the audit log.
+#[update]
+fn download_file_audited(file_id: u64, chunk_id: u64) -> FileDownloadResponse {
+    with_state_mut(|s| backend::api::download_file_audited(s, file_id, chunk_id, s.env.caller()))
+}
+
+// Issues a short-lived token to download the file over HTTP.
+#[update]
+async fn create_download_token(file_id: u64) -> Result<DownloadToken, DownloadTokenError> {
+    // The token is a capability, so it's drawn from the randomness of the IC.
+    let (bytes,) = raw_rand()
+        .await
+        .unwrap_or_else(|(_, err)| ic_cdk::trap(&format!("failed to get randomness: {err}")));
+    let token: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    with_state_mut(|s| backend::api::create_download_token(s, s.env.caller(), file_id, token))
+}
+
+#[query]
+fn get_file_audit_log(
+    file_id: u64,
+    start: u64,
+    limit: u64,
+) -> Result<FileAuditLogPage, AuditLogError> {
+    with_state(|s| backend::api::get_file_audit_log(s, s.env.caller(), file_id, start, limit))
+}
+
+#[query]
+fn get_audit_log(start: u64, limit: u64) -> Result<AuditLogPage, AuditLogError> {
+    with_state(|s| backend::api::get_audit_log(s, s.env.caller(), start, limit))
+}
+
+#[query]
+fn download_file_version(file_id: u64, version: u64, chunk_id: u64) -> FileDownloadResponse {
+    with_state(|s| {
+        backend::api::download_file_version(s, file_id, version, chunk_id, s.env.caller())
+    })
 }
 
 #[update]
@@ -91,7 +297,51 @@ fn share_file(
     file_key_encrypted_for_user: Vec<u8>,
 ) -> FileSharingResponse {
     with_state_mut(|s| {
-        backend::api::share_file(s, caller(), user_id, file_id, file_key_encrypted_for_user)
+        backend::api::share_file(
+            s,
+            s.env.caller(),
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+        )
+    })
+}
+
+#[update]
+fn share_file_with_permission(
+    user_id: Principal,
+    file_id: u64,
+    file_key_encrypted_for_user: Vec<u8>,
+    permission: SharePermission,
+) -> FileSharingResponse {
+    with_state_mut(|s| {
+        backend::api::share_file_with_permission(
+            s,
+            s.env.caller(),
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            permission,
+        )
+    })
+}
+
+#[update]
+fn share_file_with_download_limit(
+    user_id: Principal,
+    file_id: u64,
+    file_key_encrypted_for_user: Vec<u8>,
+    max_downloads: u64,
+) -> FileSharingResponse {
+    with_state_mut(|s| {
+        backend::api::share_file_with_download_limit(
+            s,
+            s.env.caller(),
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+            max_downloads,
+        )
     })
 }
 
@@ -103,19 +353,331 @@ fn share_file_with_users(
 ) {
     with_state_mut(|s| {
         for (id, key) in user_id.iter().zip(file_key_encrypted_for_user.iter()) {
-            backend::api::share_file(s, caller(), *id, file_id, key.clone());
+            backend::api::share_file(s, s.env.caller(), *id, file_id, key.clone());
         }
     });
 }
 
 #[update]
 fn revoke_share(user_id: Principal, file_id: u64) -> FileSharingResponse {
-    with_state_mut(|s| backend::api::revoke_share(s, caller(), user_id, file_id))
+    with_state_mut(|s| backend::api::revoke_share(s, s.env.caller(), user_id, file_id))
+}
+
+#[query]
+fn get_file_shares(file_id: u64) -> Vec<PublicShare> {
+    with_state(|s| backend::api::get_file_shares(s, s.env.caller(), file_id))
+}
+
+#[update]
+fn offer_file_transfer(
+    user_id: Principal,
+    file_id: u64,
+    file_key_encrypted_for_user: Vec<u8>,
+) -> Result<(), FileTransferError> {
+    with_state_mut(|s| {
+        backend::api::offer_file_transfer(
+            s,
+            s.env.caller(),
+            user_id,
+            file_id,
+            file_key_encrypted_for_user,
+        )
+    })
+}
+
+#[update]
+fn cancel_file_transfer(file_id: u64) -> Result<(), FileTransferError> {
+    with_state_mut(|s| backend::api::cancel_file_transfer(s, s.env.caller(), file_id))
+}
+
+#[update]
+fn accept_file_transfer(file_id: u64, owner_key: Vec<u8>) -> Result<(), FileTransferError> {
+    with_state_mut(|s| backend::api::accept_file_transfer(s, s.env.caller(), file_id, owner_key))
+}
+
+#[query]
+fn get_transfer_offers() -> Vec<TransferOffer> {
+    with_state(|s| backend::api::get_transfer_offers(s, s.env.caller()))
+}
+
+#[update]
+fn create_organization(name: String) -> Result<u64, OrganizationError> {
+    require_feature(|features| features.organizations);
+    with_state_mut(|s| backend::api::create_organization(s, s.env.caller(), name))
+}
+
+#[update]
+fn set_organization_member(
+    org_id: u64,
+    user_id: Principal,
+    role: Role,
+) -> Result<(), OrganizationError> {
+    require_feature(|features| features.organizations);
+    with_state_mut(|s| {
+        backend::api::set_organization_member(s, s.env.caller(), org_id, user_id, role)
+    })
+}
+
+#[update]
+fn remove_organization_member(org_id: u64, user_id: Principal) -> Result<(), OrganizationError> {
+    with_state_mut(|s| backend::api::remove_organization_member(s, s.env.caller(), org_id, user_id))
+}
+
+#[update]
+fn add_file_to_organization(file_id: u64, org_id: u64) -> Result<(), OrganizationError> {
+    require_feature(|features| features.organizations);
+    with_state_mut(|s| backend::api::add_file_to_organization(s, s.env.caller(), file_id, org_id))
+}
+
+#[update]
+fn request_organization_file(
+    org_id: u64,
+    request_name: String,
+) -> Result<String, OrganizationError> {
+    require_feature(|features| features.organizations);
+    with_state_mut(|s| {
+        backend::api::request_organization_file(s.env.caller(), org_id, request_name, s)
+    })
+}
+
+#[query]
+fn get_organizations() -> Vec<PublicOrganization> {
+    with_state(|s| backend::api::get_organizations(s, s.env.caller()))
+}
+
+#[query]
+fn get_organization_files(org_id: u64) -> Result<Vec<PublicFileMetadata>, OrganizationError> {
+    with_state(|s| backend::api::get_organization_files(s, s.env.caller(), org_id))
+}
+
+#[query]
+fn get_usage() -> Vec<UserUsage> {
+    with_state(|s| backend::api::get_usage(s, s.env.caller()))
+}
+
+#[query]
+fn get_notifications() -> Vec<Notification> {
+    with_state(|s| backend::api::get_notifications(s, s.env.caller()))
+}
+
+#[query]
+fn get_unread_notification_count() -> u64 {
+    with_state(|s| backend::api::get_unread_notification_count(s, s.env.caller()))
+}
+
+#[update]
+fn ack_notifications(up_to: u64) {
+    with_state_mut(|s| backend::api::ack_notifications(s, s.env.caller(), up_to))
+}
+
+// Registers a webhook. Its secret is drawn from the randomness of the IC, like download tokens.
+#[update]
+async fn add_webhook(url: String) -> Result<WebhookRegistration, WebhookError> {
+    require_feature(|features| features.webhooks);
+    let (bytes,) = raw_rand()
+        .await
+        .unwrap_or_else(|(_, err)| ic_cdk::trap(&format!("failed to get randomness: {err}")));
+    let secret: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    with_state_mut(|s| backend::api::add_webhook(s, s.env.caller(), url, secret))
+}
+
+#[update]
+fn remove_webhook(webhook_id: u64) -> Result<(), WebhookError> {
+    with_state_mut(|s| backend::api::remove_webhook(s, s.env.caller(), webhook_id))
+}
+
+#[query]
+fn get_webhooks() -> Vec<PublicWebhook> {
+    with_state(|s| backend::api::get_webhooks(s, s.env.caller()))
+}
+
+#[query]
+fn get_rate_limits() -> RateLimits {
+    with_state(backend::api::get_rate_limits)
+}
+
+#[update]
+fn set_rate_limits(rate_limits: RateLimits) -> Result<(), AdminError> {
+    with_state_mut(|s| backend::api::set_rate_limits(s, s.env.caller(), rate_limits))
+}
+
+#[update]
+fn add_admin(user_id: Principal) -> Result<(), AdminError> {
+    with_state_mut(|s| backend::api::add_admin(s, s.env.caller(), user_id))
+}
+
+#[update]
+fn remove_admin(user_id: Principal) -> Result<(), AdminError> {
+    with_state_mut(|s| backend::api::remove_admin(s, s.env.caller(), user_id))
+}
+
+#[query]
+fn get_storage_usage() -> Result<StorageUsage, AdminError> {
+    with_state(|s| backend::api::get_storage_usage(s, s.env.caller()))
+}
+
+#[update]
+fn remove_file(file_id: u64) -> Result<(), AdminError> {
+    with_state_mut(|s| backend::api::remove_file(s, s.env.caller(), file_id))
+}
+
+#[update]
+fn remove_user(user_id: Principal) -> Result<(), AdminError> {
+    with_state_mut(|s| backend::api::remove_user(s, s.env.caller(), user_id))
+}
+
+#[update]
+fn set_uploads_frozen(frozen: bool) -> Result<(), AdminError> {
+    with_state_mut(|s| backend::api::set_uploads_frozen(s, s.env.caller(), frozen))
+}
+
+#[update]
+fn set_config(config: Config) -> Result<(), AdminError> {
+    with_state_mut(|s| backend::api::set_config(s, s.env.caller(), config))
+}
+
+#[query]
+fn get_admin_log(start: u64, limit: u64) -> Result<AdminLogPage, AdminError> {
+    with_state(|s| backend::api::get_admin_log(s, s.env.caller(), start, limit))
 }
 
 #[query]
 fn get_users() -> GetUsersResponse {
-    with_state(|s| backend::api::get_users(s, caller()))
+    with_state(|s| backend::api::get_users(s, s.env.caller()))
+}
+
+#[inspect_message]
+fn inspect_message() {
+    let method = method_name();
+    let arg_size = arg_data_raw_size();
+
+    // Only the file ID of the sharing endpoints is decoded. Arguments that can't be decoded
+    // have no file ID, and are rejected when the call is executed. The payload size is checked
+    // before decoding.
+    let shared_file_id = if arg_size > backend::api::MAX_ARG_SIZE {
+        None
+    } else {
+        let arg = arg_data_raw();
+        match method.as_str() {
+            "share_file" => decode_args::<(Principal, u64, Vec<u8>)>(&arg)
+                .ok()
+                .map(|args| args.1),
+            "share_file_with_permission" => {
+                decode_args::<(Principal, u64, Vec<u8>, SharePermission)>(&arg)
+                    .ok()
+                    .map(|args| args.1)
+            }
+            "share_file_with_download_limit" => decode_args::<(Principal, u64, Vec<u8>, u64)>(&arg)
+                .ok()
+                .map(|args| args.1),
+            "share_file_with_users" => decode_args::<(Vec<Principal>, u64, Vec<Vec<u8>>)>(&arg)
+                .ok()
+                .map(|args| args.1),
+            _ => None,
+        }
+    };
+
+    let message = backend::api::Message {
+        method: &method,
+        arg_size,
+        shared_file_id,
+    };
+    if with_state(|s| backend::api::inspect_message(s, s.env.caller(), &message)).is_ok() {
+        accept_message();
+    }
+}
+
+#[query]
+fn get_metrics() -> Metrics {
+    with_state(backend::api::get_metrics)
+}
+
+#[query]
+fn http_request(request: HttpRequest) -> HttpResponse {
+    with_state(|s| backend::api::http_request(s, ic_cdk::api::id(), request))
+}
+
+#[query]
+fn http_request_streaming_callback(token: StreamingCallbackToken) -> StreamingCallbackHttpResponse {
+    with_state(|s| backend::api::http_request_streaming_callback(s, token))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("failed to stream the file: {err:?}")))
+}
+
+#[query]
+fn get_config() -> Config {
+    with_state(backend::api::get_config)
+}
+
+#[heartbeat]
+fn heartbeat() {
+    // The state is only updated when a check is due, as every update recertifies the state.
+    if with_state(backend::api::expiry_check_due) {
+        with_state_mut(backend::api::notify_expiring_requests);
+    }
+    if with_state(backend::api::webhook_deliveries_due) {
+        for request in with_state_mut(backend::api::take_due_webhook_requests) {
+            ic_cdk::spawn(deliver_webhook(request));
+        }
+    }
+    if with_state(backend::legacy_chunks_due) {
+        with_state_mut(backend::migrate_legacy_chunks);
+    }
+    if with_state(backend::uncertified_chunks_due) {
+        with_state_mut(backend::certify_existing_chunks);
+    }
+}
+
+// Webhooks only answer with a status, so a small response is enough.
+const MAX_WEBHOOK_RESPONSE_BYTES: u64 = 4 * 1024;
+
+async fn deliver_webhook(request: backend::api::WebhookRequest) {
+    let argument = CanisterHttpRequestArgument {
+        url: request.url,
+        method: HttpMethod::POST,
+        headers: request
+            .headers
+            .into_iter()
+            .map(|(name, value)| HttpHeader { name, value })
+            .collect(),
+        body: Some(request.body),
+        max_response_bytes: Some(MAX_WEBHOOK_RESPONSE_BYTES),
+        transform: Some(TransformContext {
+            function: TransformFunc(candid::Func {
+                principal: ic_cdk::api::id(),
+                method: "transform_webhook_response".to_string(),
+            }),
+            context: vec![],
+        }),
+    };
+    let outcome = match outcall::http_request(argument).await {
+        Ok((response,)) => u16::try_from(response.status.0)
+            .map_err(|_| "the status of the response is invalid".to_string()),
+        Err((code, message)) => Err(format!("{code:?}: {message}")),
+    };
+    with_state_mut(|s| backend::api::complete_webhook_delivery(s, request.delivery_id, outcome));
+}
+
+// The replicas must agree on the response of an outcall, but the headers and body of the
+// responses they get may differ, e.g., in their dates. Only the status is kept.
+#[query]
+fn transform_webhook_response(args: TransformArgs) -> outcall::HttpResponse {
+    outcall::HttpResponse {
+        status: args.response.status,
+        headers: vec![],
+        body: vec![],
+    }
+}
+
+#[init]
+fn init(args: Option<CanisterArgs>) {
+    let args = match args {
+        Some(CanisterArgs::Init(args)) => args,
+        Some(CanisterArgs::Upgrade(_)) => ic_cdk::trap("expected init arguments"),
+        None => InitArgs::default(),
+    };
+    if let Err(err) = with_state_mut(|s| backend::api::init(s, s.env.caller(), args)) {
+        ic_cdk::trap(&format!("invalid config: {err:?}"));
+    }
 }
 
 #[pre_upgrade]
@@ -124,8 +686,16 @@ fn pre_upgrade() {
 }
 
 #[post_upgrade]
-fn post_upgrade() {
+fn post_upgrade(args: Option<CanisterArgs>) {
     backend::post_upgrade();
+    let args = match args {
+        Some(CanisterArgs::Upgrade(args)) => args,
+        Some(CanisterArgs::Init(_)) => ic_cdk::trap("expected upgrade arguments"),
+        None => UpgradeArgs::default(),
+    };
+    if let Err(err) = with_state_mut(|s| backend::api::upgrade(s, s.env.caller(), args)) {
+        ic_cdk::trap(&format!("invalid config: {err:?}"));
+    }
 }
 
 fn main() {}
@@ -4,7 +4,19 @@ use std::cell::RefCell;
 
 const UPGRADES: MemoryId = MemoryId::new(0);
 
-const FILE_CONTENTS: MemoryId = MemoryId::new(1);
+// The chunks of the files before they were versioned, keyed by `(file_id, chunk_id)`. They are
+// read in place as the chunks of the first version of their files.
+const LEGACY_FILE_CONTENTS: MemoryId = MemoryId::new(1);
+
+const AUDIT_LOG_INDEX: MemoryId = MemoryId::new(2);
+
+const AUDIT_LOG_DATA: MemoryId = MemoryId::new(3);
+
+const ADMIN_LOG_INDEX: MemoryId = MemoryId::new(4);
+
+const ADMIN_LOG_DATA: MemoryId = MemoryId::new(5);
+
+const FILE_CONTENTS: MemoryId = MemoryId::new(6);
 
 pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 
@@ -19,6 +31,26 @@ pub fn get_upgrades_memory() -> Memory {
     MEMORY_MANAGER.with(|m| m.borrow().get(UPGRADES))
 }
 
+pub fn get_legacy_file_contents_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(LEGACY_FILE_CONTENTS))
+}
+
 pub fn get_file_contents_memory() -> Memory {
     MEMORY_MANAGER.with(|m| m.borrow().get(FILE_CONTENTS))
 }
+
+pub fn get_audit_log_index_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(AUDIT_LOG_INDEX))
+}
+
+pub fn get_audit_log_data_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(AUDIT_LOG_DATA))
+}
+
+pub fn get_admin_log_index_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(ADMIN_LOG_INDEX))
+}
+
+pub fn get_admin_log_data_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(ADMIN_LOG_DATA))
+}
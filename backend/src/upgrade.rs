@@ -1,4 +1,9 @@
-use crate::{memory, STATE};
+use crate::{
+    api::add_usage,
+    certification::{certify_chunk, certify_existing_data},
+    memory, ChunkKey, FileContent, State, STATE,
+};
+use ic_cdk::export::candid::Principal;
 use ic_stable_structures::{writer::Writer, Memory as _};
 
 pub fn pre_upgrade() {
@@ -29,6 +34,297 @@ pub fn post_upgrade() {
     memory.read(4, &mut state_bytes);
 
     // Deserialize and set the state.
-    let state = ciborium::de::from_reader(&*state_bytes).expect("failed to decode state");
+    let mut state: State =
+        ciborium::de::from_reader(&*state_bytes).expect("failed to decode state");
+
+    // Canisters that predate the certification of their data have an empty tree.
+    if state.certified_data.is_empty() {
+        certify_existing_data(&mut state);
+    }
+    count_legacy_files(&mut state);
+    index_drop_box_files(&mut state);
     STATE.with(|s| *s.borrow_mut() = state);
 }
+
+// The most bytes of chunks that are processed in a heartbeat.
+const CHUNKS_BATCH_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Accounts the files uploaded before usage was tracked to their owners.
+///
+/// Their bytes are accounted by `migrate_legacy_chunks`, as reading all of their chunks
+/// wouldn't fit in an upgrade.
+fn count_legacy_files(state: &mut State) {
+    // Uploaded files are always accounted once usage is tracked.
+    if !state.usage.is_empty() {
+        return;
+    }
+    let owners: Vec<Principal> = state
+        .file_data
+        .values()
+        .filter(|file| !matches!(file.content, FileContent::Pending { .. }))
+        .map(|file| file.metadata.requester_principal)
+        .collect();
+    for owner in owners {
+        add_usage(state, owner, 0, 1);
+    }
+}
+
+/// Indexes the files of the drop boxes that were created before the index existed.
+fn index_drop_box_files(state: &mut State) {
+    if !state.drop_box_files.is_empty() {
+        return;
+    }
+    for (alias, drop_box) in &state.drop_boxes {
+        for upload in &drop_box.uploads {
+            state.drop_box_files.insert(upload.file_id, alias.clone());
+        }
+    }
+}
+
+/// Returns true if some legacy chunks are yet to be accounted and certified.
+pub fn legacy_chunks_due(state: &State) -> bool {
+    state.legacy_chunks_cursor.is_some()
+}
+
+/// Accounts the next batch of chunks stored before files were versioned to the owners of their
+/// files, and certifies them.
+pub fn migrate_legacy_chunks(state: &mut State) {
+    let Some(cursor) = state.legacy_chunks_cursor else {
+        return;
+    };
+    let mut batch = vec![];
+    let mut batch_bytes = 0;
+    let mut next = None;
+    for (key, chunk) in state.legacy_file_contents.range(cursor..) {
+        if batch_bytes >= CHUNKS_BATCH_BYTES {
+            next = Some(key);
+            break;
+        }
+        batch_bytes += chunk.len() as u64;
+        batch.push((key, chunk));
+    }
+    for ((file_id, chunk_id), chunk) in batch {
+        let owner = state
+            .file_data
+            .get(&file_id)
+            .filter(|file| !matches!(file.content, FileContent::Pending { .. }))
+            .map(|file| file.metadata.requester_principal);
+        if let Some(owner) = owner {
+            add_usage(state, owner, chunk.len() as u64, 0);
+        }
+        certify_chunk(state, ChunkKey(file_id, 0, chunk_id), &chunk);
+    }
+    state.legacy_chunks_cursor = next;
+}
+
+/// Returns true if some chunks stored before the data was certified are yet to be certified.
+pub fn uncertified_chunks_due(state: &State) -> bool {
+    state.uncertified_chunks_cursor.is_some()
+}
+
+/// Certifies the next batch of chunks stored before the data was certified.
+pub fn certify_existing_chunks(state: &mut State) {
+    let Some(cursor) = state.uncertified_chunks_cursor else {
+        return;
+    };
+    let mut batch = vec![];
+    let mut batch_bytes = 0;
+    let mut next = None;
+    for (key, chunk) in state.file_contents.range(cursor..) {
+        if batch_bytes >= CHUNKS_BATCH_BYTES {
+            next = Some(key);
+            break;
+        }
+        batch_bytes += chunk.len() as u64;
+        batch.push((key, chunk));
+    }
+    for (key, chunk) in batch {
+        certify_chunk(state, key, &chunk);
+    }
+    state.uncertified_chunks_cursor = next;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::Memory;
+    use crate::{
+        api::download_file, certification, invariants::check_invariants, with_state,
+        FileDownloadResponse, Usage, User,
+    };
+    use ic_stable_structures::StableBTreeMap;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    // The layout of the state before files were versioned.
+    #[derive(Serialize)]
+    struct BaselineState {
+        file_count: u64,
+        users: BTreeMap<Principal, User>,
+        file_data: BTreeMap<u64, BaselineFile>,
+        file_alias_index: BTreeMap<String, u64>,
+        file_owners: BTreeMap<Principal, Vec<u64>>,
+        file_shares: BTreeMap<Principal, Vec<u64>>,
+    }
+
+    #[derive(Serialize)]
+    struct BaselineFile {
+        metadata: BaselineFileMetadata,
+        content: FileContent,
+    }
+
+    #[derive(Serialize)]
+    struct BaselineFileMetadata {
+        file_name: String,
+        user_public_key: Vec<u8>,
+        requester_principal: Principal,
+        requested_at: u64,
+        uploaded_at: Option<u64>,
+    }
+
+    #[test]
+    fn chunks_of_unversioned_files_are_migrated() {
+        let owner = Principal::from_slice(&[0, 1, 1]);
+        let mut legacy: StableBTreeMap<(u64, u64), Vec<u8>, Memory> =
+            StableBTreeMap::init(memory::get_legacy_file_contents_memory());
+        legacy.insert((0, 0), vec![1, 2]);
+        legacy.insert((0, 1), vec![3]);
+
+        let state = BaselineState {
+            file_count: 1,
+            users: BTreeMap::from([(
+                owner,
+                User {
+                    username: "John".to_string(),
+                    public_key: vec![1; 32],
+                },
+            )]),
+            file_data: BTreeMap::from([(
+                0,
+                BaselineFile {
+                    metadata: BaselineFileMetadata {
+                        file_name: "notes".to_string(),
+                        user_public_key: vec![1; 32],
+                        requester_principal: owner,
+                        requested_at: 1,
+                        uploaded_at: Some(2),
+                    },
+                    content: FileContent::Uploaded {
+                        num_chunks: 2,
+                        file_type: "txt".to_string(),
+                        owner_key: vec![4],
+                        shared_keys: BTreeMap::new(),
+                    },
+                },
+            )]),
+            file_alias_index: BTreeMap::new(),
+            file_owners: BTreeMap::from([(owner, vec![0])]),
+            file_shares: BTreeMap::new(),
+        };
+        let mut state_bytes = vec![];
+        ciborium::ser::into_writer(&state, &mut state_bytes).unwrap();
+        let mut memory = memory::get_upgrades_memory();
+        let mut writer = Writer::new(&mut memory, 0);
+        writer
+            .write(&(state_bytes.len() as u32).to_le_bytes())
+            .unwrap();
+        writer.write(&state_bytes).unwrap();
+
+        post_upgrade();
+
+        // The chunks are read in place.
+        let chunk = |state: &State, chunk_id| match download_file(state, 0, chunk_id, owner) {
+            FileDownloadResponse::FoundFile(file) => file.contents,
+            _ => panic!("chunk {chunk_id} wasn't found"),
+        };
+        with_state(|state| {
+            assert_eq!(chunk(state, 0), vec![1, 2]);
+            assert_eq!(chunk(state, 1), vec![3]);
+            assert_eq!(state.file_contents.len(), 0);
+            assert_eq!(state.legacy_file_contents.len(), 2);
+            assert_eq!(
+                state.usage.get(&owner),
+                Some(&Usage {
+                    file_count: 1,
+                    bytes: 0
+                })
+            );
+            assert!(legacy_chunks_due(state));
+            assert_eq!(check_invariants(state), Ok(()));
+        });
+
+        // The heartbeat accounts and certifies them.
+        crate::with_state_mut(migrate_legacy_chunks);
+        with_state(|state| {
+            assert!(!legacy_chunks_due(state));
+            assert_eq!(
+                state.usage.get(&owner),
+                Some(&Usage {
+                    file_count: 1,
+                    bytes: 3
+                })
+            );
+            assert_eq!(check_invariants(state), Ok(()));
+        });
+
+        // The files are only accounted once.
+        pre_upgrade();
+        post_upgrade();
+        with_state(|state| {
+            assert_eq!(chunk(state, 1), vec![3]);
+            assert!(!legacy_chunks_due(state));
+            assert_eq!(
+                state.usage.get(&owner).map(|usage| usage.file_count),
+                Some(1)
+            );
+            assert_eq!(state.stored_bytes, 3);
+            assert_eq!(check_invariants(state), Ok(()));
+        });
+    }
+
+    #[test]
+    fn legacy_chunks_are_migrated_in_batches() {
+        let mut state = State::default();
+        let chunk = vec![0; CHUNKS_BATCH_BYTES as usize / 2];
+        for chunk_id in 0..5 {
+            state
+                .legacy_file_contents
+                .insert((0, chunk_id), chunk.clone());
+        }
+        state.legacy_chunks_cursor = Some((0, 0));
+
+        migrate_legacy_chunks(&mut state);
+        assert_eq!(state.legacy_chunks_cursor, Some((0, 2)));
+        migrate_legacy_chunks(&mut state);
+        assert_eq!(state.legacy_chunks_cursor, Some((0, 4)));
+        migrate_legacy_chunks(&mut state);
+        assert_eq!(state.legacy_chunks_cursor, None);
+        assert_eq!(certification::expected_leaves(&state).len(), 5);
+    }
+
+    #[test]
+    fn existing_chunks_are_certified_in_batches() {
+        let mut state = State::default();
+        let chunk = vec![0; CHUNKS_BATCH_BYTES as usize / 2];
+        for chunk_id in 0..4 {
+            state
+                .file_contents
+                .insert(ChunkKey(0, 0, chunk_id), chunk.clone());
+        }
+        certify_existing_data(&mut state);
+        assert!(uncertified_chunks_due(&state));
+        assert!(state.certified_data.is_empty());
+
+        // Chunks stored in the meantime are certified along with the existing ones.
+        state.insert_chunk(ChunkKey(1, 0, 0), vec![1]);
+        assert!(state.certified_data.is_empty());
+
+        while uncertified_chunks_due(&state) {
+            certify_existing_chunks(&mut state);
+        }
+        let expected =
+            crate::certification::CertifiedMap::from_leaves(certification::expected_leaves(&state));
+        assert_eq!(expected.leaves().len(), 5);
+        assert_eq!(state.certified_data.leaves(), expected.leaves());
+    }
+}
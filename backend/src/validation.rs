@@ -0,0 +1,256 @@
+//! Validation of the requests of the upload endpoints.
+//!
+//! Requests are validated before they reach the state, so that malformed uploads are rejected
+//! before anything is stored. The size limits are part of the `Config`.
+use crate::{Config, ValidationError};
+
+pub use crate::{MAX_CHUNK_SIZE, MAX_FILE_SIZE};
+
+/// The maximum length of a file name, in characters.
+pub const MAX_FILE_NAME_LENGTH: usize = 255;
+
+/// The file types that can be uploaded.
+///
+/// The check is advisory: the type is declared by the uploader and the canister can't verify
+/// it against the contents, which are encrypted. `application/octet-stream` is accepted for
+/// files whose type is unknown, so any file can be uploaded under that type.
+pub const ALLOWED_FILE_TYPES: &[&str] = &[
+    "application/pdf",
+    "application/msword",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.ms-excel",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.ms-powerpoint",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    "application/vnd.oasis.opendocument.text",
+    "application/vnd.oasis.opendocument.spreadsheet",
+    "application/zip",
+    "application/octet-stream",
+    "image/jpg",
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "image/heic",
+    "text/plain",
+    "text/csv",
+];
+
+/// Validates the first chunk of an upload along with the size and type of the whole file.
+pub fn validate_upload(
+    config: &Config,
+    first_chunk: &[u8],
+    num_chunks: u64,
+    file_type: &str,
+) -> Result<(), ValidationError> {
+    if num_chunks == 0 {
+        return Err(ValidationError::InvalidNumChunks);
+    }
+    validate_chunk(config, first_chunk)?;
+
+    // All chunks but the last one have the size of the first chunk.
+    // The division is done in 64 bits, as `usize` can't hold the maximum size on wasm32.
+    let max_num_chunks = config.max_file_size.div_ceil(first_chunk.len() as u64);
+    if num_chunks > max_num_chunks {
+        return Err(ValidationError::FileTooLarge);
+    }
+
+    if !ALLOWED_FILE_TYPES.contains(&file_type) {
+        return Err(ValidationError::UnsupportedFileType);
+    }
+
+    Ok(())
+}
+
+/// Validates a chunk of an upload.
+pub fn validate_chunk(config: &Config, chunk: &[u8]) -> Result<(), ValidationError> {
+    if chunk.is_empty() {
+        return Err(ValidationError::EmptyChunk);
+    }
+    if chunk.len() as u64 > config.max_chunk_size {
+        return Err(ValidationError::ChunkTooLarge);
+    }
+    Ok(())
+}
+
+/// Validates the size of a chunk that follows the first chunk of a file.
+///
+/// All chunks but the last one have the size of the first chunk, and the last one isn't
+/// larger, so that files can't exceed the size checked by `validate_upload`.
+pub fn validate_chunk_size(
+    first_chunk_size: usize,
+    chunk: &[u8],
+    is_last: bool,
+) -> Result<(), ValidationError> {
+    let is_valid = if is_last {
+        chunk.len() <= first_chunk_size
+    } else {
+        chunk.len() == first_chunk_size
+    };
+    if !is_valid {
+        return Err(ValidationError::InvalidChunkSize);
+    }
+    Ok(())
+}
+
+/// Validates the name of a file.
+///
+/// Names can't be blank, can't exceed `MAX_FILE_NAME_LENGTH` characters, and can't contain
+/// control characters or path separators.
+pub fn validate_file_name(name: &str) -> Result<(), ValidationError> {
+    if name.trim().is_empty() {
+        return Err(ValidationError::EmptyFileName);
+    }
+    if name.chars().count() > MAX_FILE_NAME_LENGTH {
+        return Err(ValidationError::FileNameTooLong);
+    }
+    if name
+        .chars()
+        .any(|c| c.is_control() || c == '/' || c == '\\')
+    {
+        return Err(ValidationError::InvalidFileName);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The first chunk, the number of chunks, the file type and the expected result.
+    type UploadCase<'a> = (&'a [u8], u64, &'a str, Result<(), ValidationError>);
+
+    #[test]
+    fn validates_uploads() {
+        let chunk = vec![0; 1000];
+        let max_num_chunks = MAX_FILE_SIZE / 1000 + 1;
+
+        let cases: Vec<UploadCase> = vec![
+            (&chunk, 1, "application/pdf", Ok(())),
+            (&chunk, max_num_chunks, "image/png", Ok(())),
+            (
+                &chunk,
+                0,
+                "image/png",
+                Err(ValidationError::InvalidNumChunks),
+            ),
+            (&[], 1, "image/png", Err(ValidationError::EmptyChunk)),
+            (
+                &[0; MAX_CHUNK_SIZE + 1],
+                1,
+                "image/png",
+                Err(ValidationError::ChunkTooLarge),
+            ),
+            (
+                &chunk,
+                max_num_chunks + 1,
+                "image/png",
+                Err(ValidationError::FileTooLarge),
+            ),
+            (
+                &chunk,
+                1,
+                "text/html",
+                Err(ValidationError::UnsupportedFileType),
+            ),
+            (&chunk, 1, "", Err(ValidationError::UnsupportedFileType)),
+        ];
+
+        for (first_chunk, num_chunks, file_type, expected) in cases {
+            assert_eq!(
+                validate_upload(&Config::default(), first_chunk, num_chunks, file_type),
+                expected,
+                "num_chunks: {num_chunks}, file_type: {file_type:?}, chunk size: {}",
+                first_chunk.len()
+            );
+        }
+    }
+
+    #[test]
+    fn validates_chunks() {
+        let cases: Vec<(Vec<u8>, Result<(), ValidationError>)> = vec![
+            (vec![0], Ok(())),
+            (vec![0; MAX_CHUNK_SIZE], Ok(())),
+            (vec![], Err(ValidationError::EmptyChunk)),
+            (
+                vec![0; MAX_CHUNK_SIZE + 1],
+                Err(ValidationError::ChunkTooLarge),
+            ),
+        ];
+
+        for (chunk, expected) in cases {
+            assert_eq!(
+                validate_chunk(&Config::default(), &chunk),
+                expected,
+                "chunk size: {}",
+                chunk.len()
+            );
+        }
+    }
+
+    #[test]
+    fn validates_chunk_sizes() {
+        let cases: Vec<(usize, bool, Result<(), ValidationError>)> = vec![
+            (10, false, Ok(())),
+            (10, true, Ok(())),
+            (1, true, Ok(())),
+            (9, false, Err(ValidationError::InvalidChunkSize)),
+            (11, false, Err(ValidationError::InvalidChunkSize)),
+            (11, true, Err(ValidationError::InvalidChunkSize)),
+        ];
+
+        for (size, is_last, expected) in cases {
+            assert_eq!(
+                validate_chunk_size(10, &vec![0; size], is_last),
+                expected,
+                "chunk size: {size}, is_last: {is_last}"
+            );
+        }
+    }
+
+    #[test]
+    fn limits_come_from_the_config() {
+        let config = Config {
+            max_chunk_size: 10,
+            max_file_size: 100,
+            ..Config::default()
+        };
+
+        assert_eq!(validate_chunk(&config, &[0; 10]), Ok(()));
+        assert_eq!(
+            validate_chunk(&config, &[0; 11]),
+            Err(ValidationError::ChunkTooLarge)
+        );
+        assert_eq!(validate_upload(&config, &[0; 10], 10, "text/plain"), Ok(()));
+        assert_eq!(
+            validate_upload(&config, &[0; 10], 11, "text/plain"),
+            Err(ValidationError::FileTooLarge)
+        );
+    }
+
+    #[test]
+    fn validates_file_names() {
+        let longest = "a".repeat(MAX_FILE_NAME_LENGTH);
+        let too_long = "a".repeat(MAX_FILE_NAME_LENGTH + 1);
+        // Multi-byte characters count as one character each.
+        let longest_multi_byte = "é".repeat(MAX_FILE_NAME_LENGTH);
+
+        let cases: Vec<(&str, Result<(), ValidationError>)> = vec![
+            ("tax return 2022.pdf", Ok(())),
+            ("Lohnausweis_März.pdf", Ok(())),
+            (&longest, Ok(())),
+            (&longest_multi_byte, Ok(())),
+            ("", Err(ValidationError::EmptyFileName)),
+            ("   ", Err(ValidationError::EmptyFileName)),
+            (&too_long, Err(ValidationError::FileNameTooLong)),
+            ("../passwd", Err(ValidationError::InvalidFileName)),
+            ("C:\\file.pdf", Err(ValidationError::InvalidFileName)),
+            ("file\n.pdf", Err(ValidationError::InvalidFileName)),
+            ("file\u{0}.pdf", Err(ValidationError::InvalidFileName)),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(validate_file_name(name), expected, "name: {name:?}");
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use docutrack_client::{Agent, AgentError, Principal};
+
+/// Calls the canister on the IC with `ic-agent`.
+pub struct IcAgent {
+    agent: ic_agent::Agent,
+    canister_id: Principal,
+}
+
+impl IcAgent {
+    pub async fn new(
+        url: &str,
+        identity: impl ic_agent::Identity + 'static,
+        canister_id: Principal,
+        fetch_root_key: bool,
+    ) -> Result<Self, ic_agent::AgentError> {
+        let agent = ic_agent::Agent::builder()
+            .with_url(url)
+            .with_identity(identity)
+            .build()?;
+        // Only local replicas have another root key than the IC's, which is built in.
+        if fetch_root_key {
+            agent.fetch_root_key().await?;
+        }
+        Ok(Self { agent, canister_id })
+    }
+}
+
+#[async_trait(?Send)]
+impl Agent for IcAgent {
+    async fn query(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError> {
+        self.agent
+            .query(&self.canister_id, method)
+            .with_arg(arg)
+            .call()
+            .await
+            .map_err(to_agent_error)
+    }
+
+    async fn update(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError> {
+        self.agent
+            .update(&self.canister_id, method)
+            .with_arg(arg)
+            .call_and_wait()
+            .await
+            .map_err(to_agent_error)
+    }
+}
+
+fn to_agent_error(err: ic_agent::AgentError) -> AgentError {
+    match err {
+        ic_agent::AgentError::ReplicaError { .. } => AgentError::Rejected(err.to_string()),
+        err => AgentError::Transport(err.to_string()),
+    }
+}
@@ -0,0 +1,232 @@
+//! The commands of the CLI. They are generic over the agent, so that they can be tested
+//! against the mock.
+use docutrack_client::{
+    Agent, Client, FileDownloadResponse, GetUsersResponse, Principal, PublicFileMetadata,
+    SetUserResponse, SharePermission, UploadOptions,
+};
+use docutrack_format::rsa::RsaPrivateKey;
+use docutrack_format::{
+    decrypt_file, encrypt_file, generate_file_key, public_key, unwrap_file_key, wrap_file_key,
+};
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The number of chunks downloaded at the same time, as many as are uploaded.
+const DOWNLOAD_CONCURRENCY: usize = 5;
+
+/// Registers the user under the given name, along with their public key.
+pub async fn register<A: Agent>(
+    client: &Client<A>,
+    key: &RsaPrivateKey,
+    username: String,
+) -> Result<()> {
+    match client.set_user(username, public_key(key)).await? {
+        SetUserResponse::Ok => Ok(()),
+        SetUserResponse::UsernameExists => Err("the username is already taken".into()),
+    }
+}
+
+/// Requests a file and returns the alias under which it can be uploaded.
+pub async fn request<A: Agent>(client: &Client<A>, name: String) -> Result<String> {
+    Ok(client.request_file(name).await?)
+}
+
+/// Encrypts and uploads a file, returning its id.
+///
+/// Without an alias, the file is uploaded for the user under the given name. With an alias, the
+/// file is uploaded as the first pending file of the request, and is encrypted for the user who
+/// requested it.
+pub async fn upload<A: Agent>(
+    client: &Client<A>,
+    key: &RsaPrivateKey,
+    name: String,
+    file_type: String,
+    contents: &[u8],
+    alias: Option<String>,
+) -> Result<u64> {
+    let file_key = generate_file_key();
+    let encrypted = encrypt_file(contents, &file_key);
+    let options = UploadOptions::default();
+
+    match alias {
+        None => {
+            let owner_key = wrap_file_key(&file_key, &public_key(key))?;
+            Ok(client
+                .upload(name, file_type, owner_key, &encrypted, &options)
+                .await?)
+        }
+        Some(alias) => {
+            let info = client
+                .get_alias_info(alias)
+                .await?
+                .map_err(|_| "no file is requested under this alias")?;
+            let item = info
+                .items
+                .iter()
+                .find(|item| item.pending)
+                .ok_or("all the files of the request are uploaded")?;
+            let owner_key = wrap_file_key(&file_key, &info.user.public_key)?;
+            client
+                .upload_requested(item.file_id, file_type, owner_key, &encrypted, &options)
+                .await?;
+            Ok(item.file_id)
+        }
+    }
+}
+
+/// Downloads and decrypts a file, returning its name and contents.
+pub async fn download<A: Agent>(
+    client: &Client<A>,
+    key: &RsaPrivateKey,
+    file_id: u64,
+) -> Result<(String, Vec<u8>)> {
+    let name = list(client)
+        .await?
+        .into_iter()
+        .find(|file| file.file_id == file_id)
+        .map(|file| file.file_name)
+        .ok_or("the file doesn't exist or isn't shared with you")?;
+    let file = client.download(file_id, DOWNLOAD_CONCURRENCY).await?;
+    let file_key = unwrap_file_key(&file.owner_key, key)?;
+    Ok((name, decrypt_file(&file.contents, &file_key)?))
+}
+
+/// Shares a file with a registered user, giving them the file key encrypted for them.
+pub async fn share<A: Agent>(
+    client: &Client<A>,
+    key: &RsaPrivateKey,
+    file_id: u64,
+    user: Principal,
+    permission: SharePermission,
+) -> Result<()> {
+    let users = match client.get_users().await? {
+        GetUsersResponse::Users(users) => users,
+        GetUsersResponse::PermissionError => return Err("anonymous users can't share files".into()),
+    };
+    let recipient = users
+        .into_iter()
+        .find(|recipient| recipient.ic_principal == user)
+        .ok_or("the user isn't registered")?;
+
+    let owner_key = match client.download_file(file_id, 0).await? {
+        FileDownloadResponse::FoundFile(data) => data.owner_key,
+        _ => return Err("the file doesn't exist, isn't uploaded or isn't shared with you".into()),
+    };
+    let file_key = unwrap_file_key(&owner_key, key)?;
+    let shared_key = wrap_file_key(&file_key, &recipient.public_key)?;
+    Ok(client
+        .share_with(file_id, vec![(user, shared_key)], permission)
+        .await?)
+}
+
+pub async fn revoke<A: Agent>(client: &Client<A>, file_id: u64, user: Principal) -> Result<()> {
+    Ok(client.revoke_shares(file_id, &[user]).await?)
+}
+
+/// Lists the files requested or uploaded by the user, followed by the files shared with them.
+pub async fn list<A: Agent>(client: &Client<A>) -> Result<Vec<PublicFileMetadata>> {
+    let mut files = client.get_requests().await?;
+    files.extend(client.get_shared_files().await?);
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use docutrack_client::mock::MockAgent;
+    use docutrack_client::{DownloadError, Error, FileStatus};
+    use futures::executor::block_on;
+    use rand::rngs::OsRng;
+
+    fn principal(id: u8) -> Principal {
+        Principal::from_slice(&[0, 1, id])
+    }
+
+    fn test_user_key() -> RsaPrivateKey {
+        // Smaller keys are generated in tests, which are otherwise slow.
+        RsaPrivateKey::new(&mut OsRng, 1024).unwrap()
+    }
+
+    #[test]
+    fn requested_files_are_encrypted_for_the_requester() {
+        let agent = MockAgent::new(principal(1));
+        let (requester, uploader) = (
+            Client::new(agent.clone()),
+            Client::new(agent.with_caller(principal(2))),
+        );
+        let key = test_user_key();
+        block_on(register(&requester, &key, "John".to_string())).unwrap();
+
+        let alias = block_on(request(&requester, "passport".to_string())).unwrap();
+        // The uploader doesn't need to be registered.
+        let file_id = block_on(upload(
+            &uploader,
+            &test_user_key(),
+            "ignored".to_string(),
+            "image/png".to_string(),
+            b"passport",
+            Some(alias.clone()),
+        ))
+        .unwrap();
+        assert!(block_on(upload(
+            &uploader,
+            &test_user_key(),
+            "ignored".to_string(),
+            "image/png".to_string(),
+            b"passport",
+            Some(alias),
+        ))
+        .is_err());
+
+        let files = block_on(list(&requester)).unwrap();
+        assert!(matches!(files[0].file_status, FileStatus::Uploaded { .. }));
+        assert_eq!(
+            block_on(download(&requester, &key, file_id)).unwrap(),
+            ("passport".to_string(), b"passport".to_vec())
+        );
+    }
+
+    #[test]
+    fn files_are_shared_with_their_key() {
+        let agent = MockAgent::new(principal(1));
+        let (owner, recipient) = (
+            Client::new(agent.clone()),
+            Client::new(agent.with_caller(principal(2))),
+        );
+        let (owner_key, recipient_key) = (test_user_key(), test_user_key());
+        block_on(register(&owner, &owner_key, "John".to_string())).unwrap();
+        block_on(register(&recipient, &recipient_key, "Jane".to_string())).unwrap();
+        assert!(block_on(register(&recipient, &recipient_key, "John".to_string())).is_err());
+
+        let file_id = block_on(upload(
+            &owner,
+            &owner_key,
+            "notes.txt".to_string(),
+            "text/plain".to_string(),
+            b"notes",
+            None,
+        ))
+        .unwrap();
+        assert!(block_on(download(&recipient, &recipient_key, file_id)).is_err());
+
+        block_on(share(
+            &owner,
+            &owner_key,
+            file_id,
+            principal(2),
+            SharePermission::View,
+        ))
+        .unwrap();
+        assert_eq!(
+            block_on(download(&recipient, &recipient_key, file_id)).unwrap(),
+            ("notes.txt".to_string(), b"notes".to_vec())
+        );
+
+        block_on(revoke(&owner, file_id, principal(2))).unwrap();
+        assert!(block_on(list(&recipient)).unwrap().is_empty());
+        assert!(matches!(
+            block_on(recipient.download(file_id, 1)),
+            Err(Error::Download(DownloadError::PermissionError))
+        ));
+    }
+}
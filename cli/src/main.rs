@@ -0,0 +1,231 @@
+//! A command-line client of DocuTrack, e.g., to script requests and downloads.
+//!
+//! Files are encrypted and decrypted like in the web app, so the files uploaded with either are
+//! available in both.
+use clap::{Parser, Subcommand, ValueEnum};
+use docutrack_client::{Client, FileStatus, Principal, SharePermission};
+use std::path::{Path, PathBuf};
+
+mod agent;
+mod commands;
+mod profile;
+
+use agent::IcAgent;
+use commands::Result;
+use profile::Profile;
+
+#[derive(Parser)]
+#[command(
+    name = "docutrack",
+    about = "Request, upload, download and share files on DocuTrack"
+)]
+struct Cli {
+    /// The directory of the identity and the key of the user. Defaults to `~/.docutrack`.
+    #[arg(long, env = "DOCUTRACK_HOME")]
+    home: Option<PathBuf>,
+
+    #[arg(long, env = "DOCUTRACK_URL", default_value = "https://icp0.io")]
+    url: String,
+
+    #[arg(
+        long,
+        env = "DOCUTRACK_CANISTER_ID",
+        default_value = "wjwei-yaaaa-aaaam-aabva-cai"
+    )]
+    canister_id: String,
+
+    /// Fetches the root key of the replica. Only for local replicas.
+    #[arg(long)]
+    fetch_root_key: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Creates the identity and the key of the user, unless they exist.
+    Init,
+    /// Registers the user under a name, along with their public key.
+    Register { username: String },
+    /// Requests a file, and prints the alias under which it can be uploaded.
+    Request { name: String },
+    /// Uploads a file of the user, or a requested file when an alias is given.
+    Upload {
+        path: PathBuf,
+        /// The alias of the request the file is uploaded for.
+        #[arg(long)]
+        alias: Option<String>,
+        /// The name of the file. Defaults to the name of the uploaded file.
+        #[arg(long)]
+        name: Option<String>,
+        /// The MIME type of the file. Defaults to the type of its extension.
+        #[arg(long = "type")]
+        file_type: Option<String>,
+    },
+    /// Downloads and decrypts a file.
+    Download {
+        file_id: u64,
+        /// Where the file is written. Defaults to its name, in the current directory.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Shares a file with a registered user.
+    Share {
+        file_id: u64,
+        user: String,
+        #[arg(long, value_enum, default_value = "view")]
+        permission: Permission,
+    },
+    /// Revokes the share of a file with a user.
+    Revoke { file_id: u64, user: String },
+    /// Lists the files of the user, and those shared with them.
+    Ls,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Permission {
+    View,
+    Reshare,
+    CoOwner,
+}
+
+impl From<Permission> for SharePermission {
+    fn from(permission: Permission) -> Self {
+        match permission {
+            Permission::View => Self::View,
+            Permission::Reshare => Self::Reshare,
+            Permission::CoOwner => Self::CoOwner,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run(Cli::parse()).await {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let home = match cli.home {
+        Some(home) => home,
+        None => PathBuf::from(std::env::var("HOME")?).join(".docutrack"),
+    };
+    let profile = Profile::new(home);
+    if let Command::Init = cli.command {
+        if profile.init()? {
+            println!(
+                "Created {} and {}",
+                profile.identity_path().display(),
+                profile.key_path().display()
+            );
+        }
+        return Ok(());
+    }
+
+    let canister_id = Principal::from_text(&cli.canister_id)?;
+    let agent = IcAgent::new(
+        &cli.url,
+        profile.identity()?,
+        canister_id,
+        cli.fetch_root_key,
+    )
+    .await?;
+    let client = Client::new(agent);
+
+    match cli.command {
+        Command::Init => unreachable!("handled above"),
+        Command::Register { username } => {
+            commands::register(&client, &profile.key()?, username).await?
+        }
+        Command::Request { name } => println!("{}", commands::request(&client, name).await?),
+        Command::Upload {
+            path,
+            alias,
+            name,
+            file_type,
+        } => {
+            let contents = std::fs::read(&path)?;
+            let name = match name {
+                Some(name) => name,
+                None => file_name(&path)?,
+            };
+            let file_type = file_type.unwrap_or_else(|| file_type_of(&path).to_string());
+            let file_id =
+                commands::upload(&client, &profile.key()?, name, file_type, &contents, alias)
+                    .await?;
+            println!("{file_id}");
+        }
+        Command::Download { file_id, output } => {
+            let (name, contents) = commands::download(&client, &profile.key()?, file_id).await?;
+            // The name is chosen by whoever uploaded the file, so only its last component is used.
+            let output = match output {
+                Some(output) => output,
+                None => PathBuf::from(file_name(Path::new(&name))?),
+            };
+            std::fs::write(&output, contents)?;
+            println!("{}", output.display());
+        }
+        Command::Share {
+            file_id,
+            user,
+            permission,
+        } => {
+            let user = Principal::from_text(user)?;
+            commands::share(&client, &profile.key()?, file_id, user, permission.into()).await?
+        }
+        Command::Revoke { file_id, user } => {
+            commands::revoke(&client, file_id, Principal::from_text(user)?).await?
+        }
+        Command::Ls => {
+            for file in commands::list(&client).await? {
+                let status = match file.file_status {
+                    FileStatus::Pending { alias, .. } => format!("pending ({alias})"),
+                    FileStatus::PartiallyUploaded => "partially uploaded".to_string(),
+                    FileStatus::Uploaded { .. } => "uploaded".to_string(),
+                };
+                println!("{}\t{status}\t{}", file.file_id, file.file_name);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    Ok(path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("the path has no valid file name")?
+        .to_string())
+}
+
+/// Returns the MIME type of the file from its extension, among the types the canister accepts.
+fn file_type_of(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "zip" => "application/zip",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        _ => "application/octet-stream",
+    }
+}
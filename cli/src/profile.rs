@@ -0,0 +1,94 @@
+//! The local files of the user: the identity they call the canister with, and the RSA key their
+//! file keys are encrypted for.
+use docutrack_format::generate_user_key;
+use docutrack_format::rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding};
+use docutrack_format::rsa::RsaPrivateKey;
+use ic_agent::identity::BasicIdentity;
+use ring::rand::SystemRandom;
+use ring::signature::Ed25519KeyPair;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub struct Profile {
+    dir: PathBuf,
+}
+
+impl Profile {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The Ed25519 identity, in the PEM format of `dfx`, so that it can be imported with
+    /// `dfx identity import`.
+    pub fn identity_path(&self) -> PathBuf {
+        self.dir.join("identity.pem")
+    }
+
+    /// The RSA key, in the PKCS#8 PEM format.
+    pub fn key_path(&self) -> PathBuf {
+        self.dir.join("key.pem")
+    }
+
+    /// Creates the identity and the key, unless they exist. Returns whether any was created.
+    pub fn init(&self) -> io::Result<bool> {
+        fs::create_dir_all(&self.dir)?;
+        let mut created = false;
+
+        if !self.identity_path().exists() {
+            let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "failed to generate a key"))?;
+            let pem = pem::encode(&pem::Pem {
+                tag: "PRIVATE KEY".to_string(),
+                contents: document.as_ref().to_vec(),
+            });
+            write_private(self.identity_path(), pem.as_bytes())?;
+            created = true;
+        }
+
+        if !self.key_path().exists() {
+            let pem = generate_user_key()
+                .to_pkcs8_pem(LineEnding::LF)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+            write_private(self.key_path(), pem.as_bytes())?;
+            created = true;
+        }
+
+        Ok(created)
+    }
+
+    pub fn identity(&self) -> Result<BasicIdentity, String> {
+        BasicIdentity::from_pem_file(self.identity_path()).map_err(|err| {
+            format!(
+                "failed to read the identity at {}: {err}. Run `docutrack init` first.",
+                self.identity_path().display()
+            )
+        })
+    }
+
+    pub fn key(&self) -> Result<RsaPrivateKey, String> {
+        RsaPrivateKey::read_pkcs8_pem_file(self.key_path()).map_err(|err| {
+            format!(
+                "failed to read the key at {}: {err}. Run `docutrack init` first.",
+                self.key_path().display()
+            )
+        })
+    }
+}
+
+/// Writes a file that only its owner can read.
+fn write_private(path: PathBuf, contents: &[u8]) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?
+            .write_all(contents)
+    }
+    #[cfg(not(unix))]
+    fs::write(path, contents)
+}
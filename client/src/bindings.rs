@@ -0,0 +1,549 @@
+//! The bindings of the methods of the canister, in the order of `service.did`.
+use crate::*;
+
+impl<A: Agent> Client<A> {
+    pub async fn set_user(
+        &self,
+        username: String,
+        public_key: Vec<u8>,
+    ) -> Result<SetUserResponse, Error> {
+        self.update("set_user", (username, public_key))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn username_exists(&self, username: String) -> Result<bool, Error> {
+        self.query("username_exists", (username,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn who_am_i(&self) -> Result<WhoamiResponse, Error> {
+        self.query("who_am_i", ()).await.map(|(reply,)| reply)
+    }
+
+    pub async fn request_file(&self, request_name: String) -> Result<String, Error> {
+        self.update("request_file", (request_name,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn request_checklist(
+        &self,
+        name: String,
+        items: Vec<String>,
+    ) -> Result<String, Error> {
+        self.update("request_checklist", (name, items))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_checklists(&self) -> Result<Vec<PublicChecklist>, Error> {
+        self.query("get_checklists", ()).await.map(|(reply,)| reply)
+    }
+
+    pub async fn get_alias_info(
+        &self,
+        alias: String,
+    ) -> Result<Result<AliasInfo, GetAliasInfoError>, Error> {
+        self.query("get_alias_info", (alias,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn upload_file(
+        &self,
+        request: UploadFileRequest,
+    ) -> Result<Result<(), UploadFileError>, Error> {
+        self.update("upload_file", (request,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn create_drop_box(&self, request: CreateDropBoxRequest) -> Result<String, Error> {
+        self.update("create_drop_box", (request,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn close_drop_box(&self, alias: String) -> Result<Result<(), DropBoxError>, Error> {
+        self.update("close_drop_box", (alias,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_drop_box_info(
+        &self,
+        alias: String,
+    ) -> Result<Result<DropBoxInfo, DropBoxError>, Error> {
+        self.query("get_drop_box_info", (alias,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_drop_boxes(&self) -> Result<Vec<PublicDropBox>, Error> {
+        self.query("get_drop_boxes", ()).await.map(|(reply,)| reply)
+    }
+
+    pub async fn upload_to_drop_box(
+        &self,
+        request: UploadToDropBoxRequest,
+    ) -> Result<Result<u64, DropBoxError>, Error> {
+        self.update("upload_to_drop_box", (request,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn download_file(
+        &self,
+        file_id: u64,
+        chunk_id: u64,
+    ) -> Result<FileDownloadResponse, Error> {
+        self.query("download_file", (file_id, chunk_id))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_requests(&self) -> Result<Vec<PublicFileMetadata>, Error> {
+        self.query("get_requests", ()).await.map(|(reply,)| reply)
+    }
+
+    pub async fn get_shared_files(&self) -> Result<Vec<PublicFileMetadata>, Error> {
+        self.query("get_shared_files", ())
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn upload_file_atomic(
+        &self,
+        request: UploadFileAtomicRequest,
+    ) -> Result<Result<u64, UploadFileError>, Error> {
+        self.update("upload_file_atomic", (request,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn upload_file_continue(
+        &self,
+        request: UploadFileContinueRequest,
+    ) -> Result<Result<(), UploadFileError>, Error> {
+        self.update("upload_file_continue", (request,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn upload_file_version(
+        &self,
+        request: UploadFileVersionRequest,
+    ) -> Result<Result<u64, FileVersionError>, Error> {
+        self.update("upload_file_version", (request,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn cancel_file_version(
+        &self,
+        file_id: u64,
+    ) -> Result<Result<u64, FileVersionError>, Error> {
+        self.update("cancel_file_version", (file_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_file_versions(
+        &self,
+        file_id: u64,
+    ) -> Result<Result<Vec<PublicFileVersion>, FileVersionError>, Error> {
+        self.query("get_file_versions", (file_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn download_file_audited(
+        &self,
+        file_id: u64,
+        chunk_id: u64,
+    ) -> Result<FileDownloadResponse, Error> {
+        self.update("download_file_audited", (file_id, chunk_id))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn create_download_token(
+        &self,
+        file_id: u64,
+    ) -> Result<Result<DownloadToken, DownloadTokenError>, Error> {
+        self.update("create_download_token", (file_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn download_file_certified(
+        &self,
+        file_id: u64,
+        chunk_id: u64,
+    ) -> Result<Certified<FileDownloadResponse>, Error> {
+        self.query("download_file_certified", (file_id, chunk_id))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_alias_info_certified(
+        &self,
+        alias: String,
+    ) -> Result<Certified<Result<AliasInfo, GetAliasInfoError>>, Error> {
+        self.query("get_alias_info_certified", (alias,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_requests_certified(
+        &self,
+    ) -> Result<Certified<Vec<PublicFileMetadata>>, Error> {
+        self.query("get_requests_certified", ())
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_file_audit_log(
+        &self,
+        file_id: u64,
+        start: u64,
+        limit: u64,
+    ) -> Result<Result<FileAuditLogPage, AuditLogError>, Error> {
+        self.query("get_file_audit_log", (file_id, start, limit))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_audit_log(
+        &self,
+        start: u64,
+        limit: u64,
+    ) -> Result<Result<AuditLogPage, AuditLogError>, Error> {
+        self.query("get_audit_log", (start, limit))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn download_file_version(
+        &self,
+        file_id: u64,
+        version: u64,
+        chunk_id: u64,
+    ) -> Result<FileDownloadResponse, Error> {
+        self.query("download_file_version", (file_id, version, chunk_id))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn share_file(
+        &self,
+        user_id: Principal,
+        file_id: u64,
+        file_key_encrypted_for_user: Vec<u8>,
+    ) -> Result<FileSharingResponse, Error> {
+        self.update(
+            "share_file",
+            (user_id, file_id, file_key_encrypted_for_user),
+        )
+        .await
+        .map(|(reply,)| reply)
+    }
+
+    pub async fn share_file_with_permission(
+        &self,
+        user_id: Principal,
+        file_id: u64,
+        file_key_encrypted_for_user: Vec<u8>,
+        permission: SharePermission,
+    ) -> Result<FileSharingResponse, Error> {
+        self.update(
+            "share_file_with_permission",
+            (user_id, file_id, file_key_encrypted_for_user, permission),
+        )
+        .await
+        .map(|(reply,)| reply)
+    }
+
+    pub async fn share_file_with_download_limit(
+        &self,
+        user_id: Principal,
+        file_id: u64,
+        file_key_encrypted_for_user: Vec<u8>,
+        max_downloads: u64,
+    ) -> Result<FileSharingResponse, Error> {
+        self.update(
+            "share_file_with_download_limit",
+            (user_id, file_id, file_key_encrypted_for_user, max_downloads),
+        )
+        .await
+        .map(|(reply,)| reply)
+    }
+
+    pub async fn share_file_with_users(
+        &self,
+        user_id: Vec<Principal>,
+        file_id: u64,
+        file_key_encrypted_for_user: Vec<Vec<u8>>,
+    ) -> Result<(), Error> {
+        self.update(
+            "share_file_with_users",
+            (user_id, file_id, file_key_encrypted_for_user),
+        )
+        .await
+    }
+
+    pub async fn revoke_share(
+        &self,
+        user_id: Principal,
+        file_id: u64,
+    ) -> Result<FileSharingResponse, Error> {
+        self.update("revoke_share", (user_id, file_id))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_file_shares(&self, file_id: u64) -> Result<Vec<PublicShare>, Error> {
+        self.query("get_file_shares", (file_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn offer_file_transfer(
+        &self,
+        user_id: Principal,
+        file_id: u64,
+        file_key_encrypted_for_user: Vec<u8>,
+    ) -> Result<Result<(), FileTransferError>, Error> {
+        self.update(
+            "offer_file_transfer",
+            (user_id, file_id, file_key_encrypted_for_user),
+        )
+        .await
+        .map(|(reply,)| reply)
+    }
+
+    pub async fn cancel_file_transfer(
+        &self,
+        file_id: u64,
+    ) -> Result<Result<(), FileTransferError>, Error> {
+        self.update("cancel_file_transfer", (file_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn accept_file_transfer(
+        &self,
+        file_id: u64,
+        owner_key: Vec<u8>,
+    ) -> Result<Result<(), FileTransferError>, Error> {
+        self.update("accept_file_transfer", (file_id, owner_key))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_transfer_offers(&self) -> Result<Vec<TransferOffer>, Error> {
+        self.query("get_transfer_offers", ())
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn create_organization(
+        &self,
+        name: String,
+    ) -> Result<Result<u64, OrganizationError>, Error> {
+        self.update("create_organization", (name,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn set_organization_member(
+        &self,
+        org_id: u64,
+        user_id: Principal,
+        role: Role,
+    ) -> Result<Result<(), OrganizationError>, Error> {
+        self.update("set_organization_member", (org_id, user_id, role))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn remove_organization_member(
+        &self,
+        org_id: u64,
+        user_id: Principal,
+    ) -> Result<Result<(), OrganizationError>, Error> {
+        self.update("remove_organization_member", (org_id, user_id))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn add_file_to_organization(
+        &self,
+        file_id: u64,
+        org_id: u64,
+    ) -> Result<Result<(), OrganizationError>, Error> {
+        self.update("add_file_to_organization", (file_id, org_id))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn request_organization_file(
+        &self,
+        org_id: u64,
+        request_name: String,
+    ) -> Result<Result<String, OrganizationError>, Error> {
+        self.update("request_organization_file", (org_id, request_name))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_organizations(&self) -> Result<Vec<PublicOrganization>, Error> {
+        self.query("get_organizations", ())
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_organization_files(
+        &self,
+        org_id: u64,
+    ) -> Result<Result<Vec<PublicFileMetadata>, OrganizationError>, Error> {
+        self.query("get_organization_files", (org_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_usage(&self) -> Result<Vec<UserUsage>, Error> {
+        self.query("get_usage", ()).await.map(|(reply,)| reply)
+    }
+
+    pub async fn get_notifications(&self) -> Result<Vec<Notification>, Error> {
+        self.query("get_notifications", ())
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_unread_notification_count(&self) -> Result<u64, Error> {
+        self.query("get_unread_notification_count", ())
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn ack_notifications(&self, up_to: u64) -> Result<(), Error> {
+        self.update("ack_notifications", (up_to,)).await
+    }
+
+    pub async fn add_webhook(
+        &self,
+        url: String,
+    ) -> Result<Result<WebhookRegistration, WebhookError>, Error> {
+        self.update("add_webhook", (url,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn remove_webhook(&self, webhook_id: u64) -> Result<Result<(), WebhookError>, Error> {
+        self.update("remove_webhook", (webhook_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_webhooks(&self) -> Result<Vec<PublicWebhook>, Error> {
+        self.query("get_webhooks", ()).await.map(|(reply,)| reply)
+    }
+
+    pub async fn get_rate_limits(&self) -> Result<RateLimits, Error> {
+        self.query("get_rate_limits", ())
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn set_rate_limits(
+        &self,
+        rate_limits: RateLimits,
+    ) -> Result<Result<(), AdminError>, Error> {
+        self.update("set_rate_limits", (rate_limits,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn add_admin(&self, user_id: Principal) -> Result<Result<(), AdminError>, Error> {
+        self.update("add_admin", (user_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn remove_admin(&self, user_id: Principal) -> Result<Result<(), AdminError>, Error> {
+        self.update("remove_admin", (user_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_storage_usage(&self) -> Result<Result<StorageUsage, AdminError>, Error> {
+        self.query("get_storage_usage", ())
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn remove_file(&self, file_id: u64) -> Result<Result<(), AdminError>, Error> {
+        self.update("remove_file", (file_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn remove_user(&self, user_id: Principal) -> Result<Result<(), AdminError>, Error> {
+        self.update("remove_user", (user_id,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn set_uploads_frozen(&self, frozen: bool) -> Result<Result<(), AdminError>, Error> {
+        self.update("set_uploads_frozen", (frozen,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn set_config(&self, config: Config) -> Result<Result<(), AdminError>, Error> {
+        self.update("set_config", (config,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_admin_log(
+        &self,
+        start: u64,
+        limit: u64,
+    ) -> Result<Result<AdminLogPage, AdminError>, Error> {
+        self.query("get_admin_log", (start, limit))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_metrics(&self) -> Result<Metrics, Error> {
+        self.query("get_metrics", ()).await.map(|(reply,)| reply)
+    }
+
+    pub async fn http_request(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        self.query("http_request", (request,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn http_request_streaming_callback(
+        &self,
+        token: StreamingCallbackToken,
+    ) -> Result<StreamingCallbackHttpResponse, Error> {
+        self.query("http_request_streaming_callback", (token,))
+            .await
+            .map(|(reply,)| reply)
+    }
+
+    pub async fn get_config(&self) -> Result<Config, Error> {
+        self.query("get_config", ()).await.map(|(reply,)| reply)
+    }
+
+    pub async fn get_users(&self) -> Result<GetUsersResponse, Error> {
+        self.query("get_users", ()).await.map(|(reply,)| reply)
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{Agent, Client, Error, FileData, FileDownloadResponse};
+use futures::{stream, StreamExt, TryStreamExt};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DownloadError {
+    NotFoundFile,
+    NotUploadedFile,
+    PermissionError,
+    /// A new version of the file was uploaded during the download. Downloading it again gets
+    /// the new version.
+    VersionChanged,
+}
+
+/// A downloaded file, still encrypted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DownloadedFile {
+    pub file_type: String,
+    /// The file key, encrypted for the caller.
+    pub owner_key: Vec<u8>,
+    pub contents: Vec<u8>,
+}
+
+impl<A: Agent> Client<A> {
+    /// Downloads a file, fetching up to `concurrency` chunks at the same time.
+    ///
+    /// The number of chunks is only known once the first one is downloaded, so the others are
+    /// fetched after it.
+    pub async fn download(
+        &self,
+        file_id: u64,
+        concurrency: usize,
+    ) -> Result<DownloadedFile, Error> {
+        let first = self.download_chunk(file_id, 0).await?;
+        let others: Vec<FileData> = stream::iter(1..first.num_chunks)
+            .map(|chunk_id| self.download_chunk(file_id, chunk_id))
+            .buffered(concurrency.max(1))
+            .try_collect()
+            .await?;
+
+        let mut contents = first.contents;
+        for chunk in others {
+            // The chunks of another version are encrypted with another key.
+            if chunk.owner_key != first.owner_key || chunk.num_chunks != first.num_chunks {
+                return Err(Error::Download(DownloadError::VersionChanged));
+            }
+            contents.extend(chunk.contents);
+        }
+        Ok(DownloadedFile {
+            file_type: first.file_type,
+            owner_key: first.owner_key,
+            contents,
+        })
+    }
+
+    async fn download_chunk(&self, file_id: u64, chunk_id: u64) -> Result<FileData, Error> {
+        match self.download_file(file_id, chunk_id).await? {
+            FileDownloadResponse::FoundFile(data) => Ok(data),
+            FileDownloadResponse::NotFoundFile => Err(Error::Download(DownloadError::NotFoundFile)),
+            FileDownloadResponse::NotUploadedFile => {
+                Err(Error::Download(DownloadError::NotUploadedFile))
+            }
+            FileDownloadResponse::PermissionError => {
+                Err(Error::Download(DownloadError::PermissionError))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::MockAgent;
+    use crate::{Principal, UploadFileContinueRequest, UploadFileVersionRequest, UploadOptions};
+    use backend::api;
+    use futures::executor::block_on;
+
+    #[test]
+    fn downloads_only_accessible_files() {
+        let owner = MockAgent::new(Principal::from_slice(&[0, 1, 1]));
+        let other = Client::new(owner.with_caller(Principal::from_slice(&[0, 1, 2])));
+        let owner = Client::new(owner);
+        block_on(owner.set_user("John".to_string(), vec![1, 2, 3])).unwrap();
+
+        let file_id = block_on(owner.upload(
+            "file".to_string(),
+            "image/jpeg".to_string(),
+            vec![4, 5, 6],
+            &[1, 2, 3],
+            &UploadOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            block_on(other.download(file_id, 1)),
+            Err(Error::Download(DownloadError::PermissionError))
+        );
+        assert_eq!(
+            block_on(owner.download(file_id, 1)),
+            Ok(DownloadedFile {
+                file_type: "image/jpeg".to_string(),
+                owner_key: vec![4, 5, 6],
+                contents: vec![1, 2, 3],
+            })
+        );
+    }
+
+    #[test]
+    fn detects_new_versions_during_downloads() {
+        let owner = Principal::from_slice(&[0, 1, 1]);
+        let client = Client::new(MockAgent::new(owner));
+        block_on(client.set_user("John".to_string(), vec![1, 2, 3])).unwrap();
+        let file_id = block_on(client.upload(
+            "file".to_string(),
+            "image/jpeg".to_string(),
+            vec![4, 5, 6],
+            &[1, 2, 3],
+            &UploadOptions {
+                chunk_size: 2,
+                ..UploadOptions::default()
+            },
+        ))
+        .unwrap();
+
+        // A new version is uploaded right after the first chunk is downloaded.
+        client.agent().after("download_file", move |state| {
+            api::upload_file_version(
+                owner,
+                UploadFileVersionRequest {
+                    file_id,
+                    content: vec![7, 8],
+                    owner_key: vec![9],
+                    file_type: "image/jpeg".to_string(),
+                    num_chunks: 2,
+                },
+                state,
+            )
+            .unwrap();
+            api::upload_file_continue(
+                UploadFileContinueRequest {
+                    file_id,
+                    chunk_id: 1,
+                    contents: vec![10],
+                },
+                state,
+            )
+            .unwrap();
+        });
+        assert_eq!(
+            block_on(client.download(file_id, 1)),
+            Err(Error::Download(DownloadError::VersionChanged))
+        );
+        assert_eq!(
+            block_on(client.download(file_id, 1)).unwrap().contents,
+            vec![7, 8, 10]
+        );
+    }
+}
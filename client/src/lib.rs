@@ -0,0 +1,140 @@
+//! A client of the DocuTrack canister.
+//!
+//! [`Client`] has a typed binding for every method of `backend/service.did`, along with helpers
+//! for the multi-call protocols: chunked uploads, parallel downloads and share management.
+//!
+//! The client doesn't talk to the IC itself. It encodes and decodes the Candid arguments and
+//! hands them to an [`Agent`], which is implemented on top of `ic-agent` by applications, and by
+//! [`mock::MockAgent`] in tests.
+//!
+//! Files are uploaded and downloaded as they are stored by the canister, i.e., encrypted. The
+//! encryption of the contents and of the file keys is up to the caller, e.g., with the
+//! `docutrack-format` crate.
+use async_trait::async_trait;
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use std::fmt;
+
+mod bindings;
+mod download;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod share;
+mod upload;
+
+pub use candid::Principal;
+pub use docutrack_types::{
+    AdminError, AdminLogPage, AliasInfo, AliasItem, AuditEntry, AuditLogError, AuditLogPage,
+    CanisterArgs, Certified, Config, CreateDropBoxRequest, DownloadToken, DownloadTokenError,
+    DropBoxError, DropBoxInfo, FileAuditLogPage, FileData, FileDownloadResponse,
+    FileSharingResponse, FileStatus, FileTransferError, FileVersionError, GetAliasInfoError,
+    GetUsersResponse, HttpRequest, HttpResponse, Metrics, Notification, NotificationEvent,
+    OrganizationError, PublicChecklist, PublicDropBox, PublicFileMetadata, PublicFileVersion,
+    PublicOrganization, PublicShare, PublicUser, PublicWebhook, RateLimits, Role, SetUserResponse,
+    SharePermission, StorageUsage, StreamingCallbackHttpResponse, StreamingCallbackToken,
+    TransferOffer, UploadFileAtomicRequest, UploadFileContinueRequest, UploadFileError,
+    UploadFileRequest, UploadFileVersionRequest, UploadToDropBoxRequest, UserUsage, WebhookError,
+    WebhookRegistration, WhoamiResponse,
+};
+pub use download::{DownloadError, DownloadedFile};
+pub use upload::{UploadOptions, CHUNK_SIZE};
+
+/// Makes the calls to the canister.
+///
+/// The arguments and the replies are Candid-encoded. The futures don't need to be `Send`, so
+/// that the mock can hold the state of the canister, which isn't.
+#[async_trait(?Send)]
+pub trait Agent {
+    async fn query(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError>;
+
+    async fn update(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AgentError {
+    /// The call didn't go through, or its reply was lost. The call may have been executed.
+    Transport(String),
+    /// The call was rejected, e.g., because the canister trapped.
+    Rejected(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Agent(AgentError),
+    /// The arguments or the reply don't match the interface of the canister.
+    Candid(String),
+    Upload(UploadFileError),
+    Download(DownloadError),
+    Share(FileSharingResponse),
+}
+
+impl From<AgentError> for Error {
+    fn from(err: AgentError) -> Self {
+        Self::Agent(err)
+    }
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(message) => write!(f, "transport error: {message}"),
+            Self::Rejected(message) => write!(f, "call rejected: {message}"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Agent(err) => err.fmt(f),
+            Self::Candid(message) => write!(f, "candid error: {message}"),
+            Self::Upload(err) => write!(f, "upload failed: {err:?}"),
+            Self::Download(err) => write!(f, "download failed: {err:?}"),
+            Self::Share(response) => write!(f, "share failed: {response:?}"),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+impl std::error::Error for Error {}
+
+/// A client of the DocuTrack canister, calling it through the agent.
+pub struct Client<A> {
+    agent: A,
+}
+
+impl<A: Agent> Client<A> {
+    pub fn new(agent: A) -> Self {
+        Self { agent }
+    }
+
+    pub fn agent(&self) -> &A {
+        &self.agent
+    }
+
+    async fn query<Args, R>(&self, method: &str, args: Args) -> Result<R, Error>
+    where
+        Args: ArgumentEncoder,
+        R: for<'a> ArgumentDecoder<'a>,
+    {
+        let reply = self.agent.query(method, encode(args)?).await?;
+        decode(&reply)
+    }
+
+    async fn update<Args, R>(&self, method: &str, args: Args) -> Result<R, Error>
+    where
+        Args: ArgumentEncoder,
+        R: for<'a> ArgumentDecoder<'a>,
+    {
+        let reply = self.agent.update(method, encode(args)?).await?;
+        decode(&reply)
+    }
+}
+
+fn encode<Args: ArgumentEncoder>(args: Args) -> Result<Vec<u8>, Error> {
+    candid::encode_args(args).map_err(|err| Error::Candid(err.to_string()))
+}
+
+fn decode<R: for<'a> ArgumentDecoder<'a>>(bytes: &[u8]) -> Result<R, Error> {
+    candid::decode_args(bytes).map_err(|err| Error::Candid(err.to_string()))
+}
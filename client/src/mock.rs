@@ -0,0 +1,301 @@
+//! An in-process agent, running the logic of the canister on a state held in memory.
+use crate::{
+    Agent, AgentError, PublicUser, SetUserResponse, UploadFileAtomicRequest,
+    UploadFileContinueRequest, UploadFileRequest, UploadFileVersionRequest, WhoamiResponse,
+};
+use async_trait::async_trait;
+use backend::{api, env::TestEnvironment, State, User};
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use candid::Principal;
+use std::cell::{RefCell, RefMut};
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+/// A failure injected with [`MockAgent::fail`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The request never reaches the canister.
+    Dropped,
+    /// The canister executes the call, but the reply is lost.
+    Lost,
+}
+
+type Hook = Box<dyn FnOnce(&mut State)>;
+
+struct Canister {
+    state: State,
+    /// The environment of the state, through which the caller of each call is set.
+    env: TestEnvironment,
+    calls: BTreeMap<String, u64>,
+    failures: BTreeMap<String, VecDeque<Failure>>,
+    hooks: BTreeMap<String, Hook>,
+}
+
+impl Default for Canister {
+    fn default() -> Self {
+        let env = TestEnvironment::default();
+        Self {
+            state: State::new(Box::new(env.clone())),
+            env,
+            calls: BTreeMap::new(),
+            failures: BTreeMap::new(),
+            hooks: BTreeMap::new(),
+        }
+    }
+}
+
+/// Calls an in-memory canister as the given caller.
+///
+/// The methods call the functions of `backend::api`, without the checks that are made in
+/// `main.rs`, e.g., the validation of uploads and rate limits. Like on the IC, the changes of a
+/// call are rolled back when it traps.
+#[derive(Clone)]
+pub struct MockAgent {
+    canister: Rc<RefCell<Canister>>,
+    caller: Principal,
+}
+
+impl MockAgent {
+    pub fn new(caller: Principal) -> Self {
+        Self {
+            canister: Rc::default(),
+            caller,
+        }
+    }
+
+    /// Returns an agent calling the same canister, as another caller.
+    pub fn with_caller(&self, caller: Principal) -> Self {
+        Self {
+            canister: self.canister.clone(),
+            caller,
+        }
+    }
+
+    /// Makes the next calls of the method fail, one failure per call.
+    pub fn fail(&self, method: &str, failures: Vec<Failure>) {
+        self.canister_mut()
+            .failures
+            .entry(method.to_string())
+            .or_default()
+            .extend(failures);
+    }
+
+    /// Runs the hook on the state once, after the next call of the method is executed.
+    pub fn after(&self, method: &str, hook: impl FnOnce(&mut State) + 'static) {
+        self.canister_mut()
+            .hooks
+            .insert(method.to_string(), Box::new(hook));
+    }
+
+    /// Returns the number of calls of the method that reached the canister.
+    pub fn calls(&self, method: &str) -> u64 {
+        self.canister_mut().calls.get(method).copied().unwrap_or(0)
+    }
+
+    /// Runs `f` on the state of the canister, e.g., to set up a test.
+    pub fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        f(&mut self.canister_mut().state)
+    }
+
+    fn canister_mut(&self) -> RefMut<'_, Canister> {
+        self.canister.borrow_mut()
+    }
+
+    fn call(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError> {
+        let mut canister = self.canister_mut();
+        let failure = canister
+            .failures
+            .get_mut(method)
+            .and_then(VecDeque::pop_front);
+        if failure == Some(Failure::Dropped) {
+            return Err(AgentError::Transport("the request was dropped".to_string()));
+        }
+        *canister.calls.entry(method.to_string()).or_default() += 1;
+        canister.env.set_caller(self.caller);
+        let reply = execute(&mut canister.state, self.caller, method, &arg);
+        if let Some(hook) = canister.hooks.remove(method) {
+            hook(&mut canister.state);
+        }
+        match failure {
+            Some(_) => Err(AgentError::Transport("the reply was lost".to_string())),
+            None => reply,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Agent for MockAgent {
+    async fn query(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError> {
+        self.call(method, arg)
+    }
+
+    async fn update(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError> {
+        self.call(method, arg)
+    }
+}
+
+fn execute(
+    state: &mut State,
+    caller: Principal,
+    method: &str,
+    arg: &[u8],
+) -> Result<Vec<u8>, AgentError> {
+    let mut snapshot = vec![];
+    ciborium::ser::into_writer(&*state, &mut snapshot).expect("failed to snapshot the state");
+
+    catch_unwind(AssertUnwindSafe(|| {
+        let reply = dispatch(state, caller, method, arg);
+        // Like the canister in debug builds, the call traps if it breaks an invariant.
+        #[cfg(debug_assertions)]
+        if let Err(violation) = backend::invariants::check_invariants(state) {
+            panic!("an invariant of the state doesn't hold: {violation}");
+        }
+        reply
+    }))
+    .unwrap_or_else(|panic| {
+        roll_back(state, &snapshot);
+        let message = panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| {
+                panic
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+            })
+            .unwrap_or_default();
+        Err(AgentError::Rejected(format!(
+            "the canister trapped: {message}"
+        )))
+    })
+}
+
+/// Restores the state from the snapshot taken before the call.
+///
+/// The structures in stable memory aren't serialized, so they are carried over. The methods of
+/// the canister trap before writing to them. The environment isn't serialized either, and keeps
+/// its clock.
+fn roll_back(state: &mut State, snapshot: &[u8]) {
+    let mut restored: State =
+        ciborium::de::from_reader(snapshot).expect("failed to roll back the state");
+    std::mem::swap(&mut restored.file_contents, &mut state.file_contents);
+    std::mem::swap(&mut restored.audit_log, &mut state.audit_log);
+    std::mem::swap(&mut restored.admin_log, &mut state.admin_log);
+    std::mem::swap(&mut restored.env, &mut state.env);
+    *state = restored;
+}
+
+fn dispatch(
+    state: &mut State,
+    caller: Principal,
+    method: &str,
+    arg: &[u8],
+) -> Result<Vec<u8>, AgentError> {
+    match method {
+        "set_user" => {
+            let (username, public_key): (String, Vec<u8>) = decode(arg)?;
+            if api::username_exists(state, username.clone()) {
+                return encode((SetUserResponse::UsernameExists,));
+            }
+            let user = User {
+                username,
+                public_key,
+            };
+            api::set_user_info(state, caller, user);
+            encode((SetUserResponse::Ok,))
+        }
+        "username_exists" => {
+            let (username,) = decode(arg)?;
+            encode((api::username_exists(state, username),))
+        }
+        "who_am_i" => encode((match state.users.get(&caller) {
+            None => WhoamiResponse::UnknownUser,
+            Some(user) => WhoamiResponse::KnownUser(PublicUser {
+                username: user.username.clone(),
+                public_key: user.public_key.clone(),
+                ic_principal: caller,
+            }),
+        },)),
+        "request_file" => {
+            let (request_name,): (String,) = decode(arg)?;
+            encode((api::request_file(caller, request_name, state),))
+        }
+        "get_alias_info" => {
+            let (alias,) = decode(arg)?;
+            encode((api::get_alias_info(state, alias),))
+        }
+        "upload_file" => {
+            let (request,): (UploadFileRequest,) = decode(arg)?;
+            encode((api::upload_file(
+                request.file_id,
+                request.file_content,
+                request.file_type,
+                request.owner_key,
+                request.num_chunks,
+                state,
+            ),))
+        }
+        "upload_file_atomic" => {
+            let (request,): (UploadFileAtomicRequest,) = decode(arg)?;
+            encode((api::upload_file_atomic(caller, request, state),))
+        }
+        "upload_file_continue" => {
+            let (request,): (UploadFileContinueRequest,) = decode(arg)?;
+            encode((api::upload_file_continue(request, state),))
+        }
+        "upload_file_version" => {
+            let (request,): (UploadFileVersionRequest,) = decode(arg)?;
+            encode((api::upload_file_version(caller, request, state),))
+        }
+        "cancel_file_version" => {
+            let (file_id,) = decode(arg)?;
+            encode((api::cancel_file_version(state, caller, file_id),))
+        }
+        "download_file" => {
+            let (file_id, chunk_id) = decode(arg)?;
+            encode((api::download_file(state, file_id, chunk_id, caller),))
+        }
+        "get_requests" => encode((api::get_requests(state, caller),)),
+        "get_shared_files" => encode((api::get_shared_files(state, caller),)),
+        "share_file" => {
+            let (user_id, file_id, key) = decode(arg)?;
+            encode((api::share_file(state, caller, user_id, file_id, key),))
+        }
+        "share_file_with_permission" => {
+            let (user_id, file_id, key, permission) = decode(arg)?;
+            encode((api::share_file_with_permission(
+                state, caller, user_id, file_id, key, permission,
+            ),))
+        }
+        "revoke_share" => {
+            let (user_id, file_id) = decode(arg)?;
+            encode((api::revoke_share(state, caller, user_id, file_id),))
+        }
+        "get_users" => encode((api::get_users(state, caller),)),
+        "get_file_shares" => {
+            let (file_id,) = decode(arg)?;
+            encode((api::get_file_shares(state, caller, file_id),))
+        }
+        "get_notifications" => encode((api::get_notifications(state, caller),)),
+        "get_unread_notification_count" => {
+            encode((api::get_unread_notification_count(state, caller),))
+        }
+        "ack_notifications" => {
+            let (up_to,) = decode(arg)?;
+            api::ack_notifications(state, caller, up_to);
+            encode(())
+        }
+        _ => Err(AgentError::Rejected(format!(
+            "the mock doesn't support `{method}`"
+        ))),
+    }
+}
+
+fn decode<Args: for<'a> ArgumentDecoder<'a>>(arg: &[u8]) -> Result<Args, AgentError> {
+    candid::decode_args(arg)
+        .map_err(|err| AgentError::Rejected(format!("invalid arguments: {err}")))
+}
+
+fn encode<R: ArgumentEncoder>(reply: R) -> Result<Vec<u8>, AgentError> {
+    Ok(candid::encode_args(reply).expect("failed to encode the reply"))
+}
@@ -0,0 +1,145 @@
+use crate::{Agent, Client, Error, FileSharingResponse, Principal, SharePermission};
+use futures::future::try_join_all;
+
+impl<A: Agent> Client<A> {
+    /// Shares the file with each user at the given permission, along with the file key
+    /// encrypted for them.
+    pub async fn share_with(
+        &self,
+        file_id: u64,
+        shares: Vec<(Principal, Vec<u8>)>,
+        permission: SharePermission,
+    ) -> Result<(), Error> {
+        try_join_all(shares.into_iter().map(|(user, key)| async move {
+            check(
+                self.share_file_with_permission(user, file_id, key, permission)
+                    .await?,
+            )
+        }))
+        .await
+        .map(|_| ())
+    }
+
+    /// Revokes the shares of the file with each user.
+    pub async fn revoke_shares(&self, file_id: u64, users: &[Principal]) -> Result<(), Error> {
+        try_join_all(
+            users
+                .iter()
+                .map(|user| async move { check(self.revoke_share(*user, file_id).await?) }),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Makes the file shared with exactly the given users: the other shares are revoked, and
+    /// the keys of the users are set, e.g., after a new version of the file was uploaded.
+    ///
+    /// The permissions of existing shares are kept, and new shares only allow viewing the file.
+    pub async fn set_shares(
+        &self,
+        file_id: u64,
+        shares: Vec<(Principal, Vec<u8>)>,
+    ) -> Result<(), Error> {
+        let revoked: Vec<Principal> = self
+            .get_file_shares(file_id)
+            .await?
+            .into_iter()
+            .map(|share| share.user.ic_principal)
+            .filter(|user| shares.iter().all(|(shared_with, _)| shared_with != user))
+            .collect();
+        self.revoke_shares(file_id, &revoked).await?;
+
+        try_join_all(
+            shares.into_iter().map(|(user, key)| async move {
+                check(self.share_file(user, file_id, key).await?)
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+fn check(response: FileSharingResponse) -> Result<(), Error> {
+    match response {
+        FileSharingResponse::Ok => Ok(()),
+        response => Err(Error::Share(response)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::MockAgent;
+    use crate::UploadOptions;
+    use futures::executor::block_on;
+
+    fn user(id: u8) -> Principal {
+        Principal::from_slice(&[0, 1, id])
+    }
+
+    fn shared_with<A: Agent>(
+        client: &Client<A>,
+        file_id: u64,
+    ) -> Vec<(Principal, SharePermission)> {
+        block_on(client.get_file_shares(file_id))
+            .unwrap()
+            .into_iter()
+            .map(|share| (share.user.ic_principal, share.permission))
+            .collect()
+    }
+
+    #[test]
+    fn manages_the_shares_of_a_file() {
+        let agent = MockAgent::new(user(1));
+        for id in 1..=4 {
+            let client = Client::new(agent.with_caller(user(id)));
+            block_on(client.set_user(format!("user {id}"), vec![id])).unwrap();
+        }
+        let owner = Client::new(agent.clone());
+        let file_id = block_on(owner.upload(
+            "file".to_string(),
+            "image/jpeg".to_string(),
+            vec![1],
+            &[1, 2, 3],
+            &UploadOptions::default(),
+        ))
+        .unwrap();
+
+        block_on(owner.share_with(
+            file_id,
+            vec![(user(2), vec![2]), (user(3), vec![3])],
+            SharePermission::Reshare,
+        ))
+        .unwrap();
+        assert_eq!(
+            shared_with(&owner, file_id),
+            vec![
+                (user(2), SharePermission::Reshare),
+                (user(3), SharePermission::Reshare)
+            ]
+        );
+
+        // User 2 is kept with a new key, user 3 is revoked and user 4 is added.
+        block_on(owner.set_shares(file_id, vec![(user(2), vec![5]), (user(4), vec![4])])).unwrap();
+        assert_eq!(
+            shared_with(&owner, file_id),
+            vec![
+                (user(2), SharePermission::Reshare),
+                (user(4), SharePermission::View)
+            ]
+        );
+        let recipient = Client::new(agent.with_caller(user(2)));
+        assert_eq!(
+            block_on(recipient.download(file_id, 1)).unwrap().owner_key,
+            vec![5]
+        );
+
+        // Only the owner can revoke the shares.
+        assert_eq!(
+            block_on(recipient.revoke_shares(file_id, &[user(4)])),
+            Err(Error::Share(FileSharingResponse::PermissionError))
+        );
+        block_on(owner.revoke_shares(file_id, &[user(2), user(4)])).unwrap();
+        assert_eq!(shared_with(&owner, file_id), vec![]);
+    }
+}
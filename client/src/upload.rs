@@ -0,0 +1,220 @@
+use crate::{
+    Agent, AgentError, Client, Error, UploadFileAtomicRequest, UploadFileContinueRequest,
+    UploadFileRequest,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+
+/// The size of the chunks files are split into, the same as in the frontend.
+pub const CHUNK_SIZE: usize = 2_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UploadOptions {
+    pub chunk_size: usize,
+    /// The number of chunks uploaded at the same time.
+    pub concurrency: usize,
+    /// The number of times a chunk is sent before giving up on transport errors.
+    pub max_attempts: u32,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: CHUNK_SIZE,
+            concurrency: 5,
+            max_attempts: 3,
+        }
+    }
+}
+
+impl<A: Agent> Client<A> {
+    /// Uploads a file of the caller and returns its id.
+    ///
+    /// The first chunk is sent with `upload_file_atomic`, which creates the file, and the others
+    /// with `upload_file_continue`. Only the latter are retried: the file would be created twice
+    /// if the reply of the first call was lost.
+    pub async fn upload(
+        &self,
+        name: String,
+        file_type: String,
+        owner_key: Vec<u8>,
+        contents: &[u8],
+        options: &UploadOptions,
+    ) -> Result<u64, Error> {
+        let chunks = split(contents, options.chunk_size);
+        let file_id = self
+            .upload_file_atomic(UploadFileAtomicRequest {
+                name,
+                content: chunks[0].to_vec(),
+                owner_key,
+                file_type,
+                num_chunks: chunks.len() as u64,
+            })
+            .await?
+            .map_err(Error::Upload)?;
+        self.upload_chunks(file_id, &chunks, options).await?;
+        Ok(file_id)
+    }
+
+    /// Uploads the file requested under `file_id`, as returned by `get_alias_info`.
+    pub async fn upload_requested(
+        &self,
+        file_id: u64,
+        file_type: String,
+        owner_key: Vec<u8>,
+        contents: &[u8],
+        options: &UploadOptions,
+    ) -> Result<(), Error> {
+        let chunks = split(contents, options.chunk_size);
+        self.upload_file(UploadFileRequest {
+            file_id,
+            file_content: chunks[0].to_vec(),
+            file_type,
+            owner_key,
+            num_chunks: chunks.len() as u64,
+        })
+        .await?
+        .map_err(Error::Upload)?;
+        self.upload_chunks(file_id, &chunks, options).await
+    }
+
+    /// Uploads all the chunks but the first one, which is sent along with the file.
+    async fn upload_chunks(
+        &self,
+        file_id: u64,
+        chunks: &[&[u8]],
+        options: &UploadOptions,
+    ) -> Result<(), Error> {
+        stream::iter(chunks.iter().enumerate().skip(1))
+            .map(|(chunk_id, chunk)| {
+                self.upload_chunk(file_id, chunk_id as u64, chunk, options.max_attempts)
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .try_collect()
+            .await
+    }
+
+    async fn upload_chunk(
+        &self,
+        file_id: u64,
+        chunk_id: u64,
+        chunk: &[u8],
+        max_attempts: u32,
+    ) -> Result<(), Error> {
+        let mut attempt = 1;
+        loop {
+            let request = UploadFileContinueRequest {
+                file_id,
+                chunk_id,
+                contents: chunk.to_vec(),
+            };
+            match self.upload_file_continue(request).await {
+                Ok(result) => return result.map_err(Error::Upload),
+                // The chunk was uploaded by an attempt whose reply was lost. If it was the last
+                // chunk, the file is complete, and no longer accepts chunks.
+                Err(Error::Agent(AgentError::Rejected(message)))
+                    if attempt > 1 && was_uploaded(&message) =>
+                {
+                    return Ok(())
+                }
+                Err(Error::Agent(AgentError::Transport(_))) if attempt < max_attempts => {
+                    attempt += 1
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+fn was_uploaded(rejection: &str) -> bool {
+    rejection.contains("chunk already uploaded")
+        || rejection.contains("expected a partially uploaded file")
+}
+
+/// Splits the contents into chunks. Empty contents are uploaded as a single empty chunk.
+fn split(contents: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    if contents.is_empty() {
+        return vec![contents];
+    }
+    contents.chunks(chunk_size.max(1)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{Failure, MockAgent};
+    use crate::Principal;
+    use futures::executor::block_on;
+
+    fn options() -> UploadOptions {
+        UploadOptions {
+            chunk_size: 2,
+            concurrency: 2,
+            max_attempts: 2,
+        }
+    }
+
+    #[test]
+    fn uploads_files_in_chunks() {
+        let client = Client::new(MockAgent::new(Principal::from_slice(&[0, 1, 1])));
+        block_on(client.set_user("John".to_string(), vec![1, 2, 3])).unwrap();
+
+        let contents = [1, 2, 3, 4, 5];
+        let file_id = block_on(client.upload(
+            "file".to_string(),
+            "image/jpeg".to_string(),
+            vec![4, 5, 6],
+            &contents,
+            &options(),
+        ))
+        .unwrap();
+
+        let file = block_on(client.download(file_id, 2)).unwrap();
+        assert_eq!(file.contents, contents);
+        assert_eq!(client.agent().calls("upload_file_continue"), 2);
+    }
+
+    #[test]
+    fn retries_chunks_on_transport_errors() {
+        let client = Client::new(MockAgent::new(Principal::from_slice(&[0, 1, 1])));
+        block_on(client.set_user("John".to_string(), vec![1, 2, 3])).unwrap();
+
+        // The first attempt never reaches the canister, and the reply of the second is lost.
+        client.agent().fail(
+            "upload_file_continue",
+            vec![Failure::Dropped, Failure::Lost],
+        );
+        let file_id = block_on(client.upload(
+            "file".to_string(),
+            "image/jpeg".to_string(),
+            vec![4, 5, 6],
+            &[1, 2, 3],
+            &UploadOptions {
+                max_attempts: 3,
+                ..options()
+            },
+        ))
+        .unwrap();
+        assert_eq!(
+            block_on(client.download(file_id, 1)).unwrap().contents,
+            vec![1, 2, 3]
+        );
+
+        // Transport errors are given up on after `max_attempts`.
+        client.agent().fail(
+            "upload_file_continue",
+            vec![Failure::Dropped, Failure::Dropped],
+        );
+        assert_eq!(
+            block_on(client.upload(
+                "file".to_string(),
+                "image/jpeg".to_string(),
+                vec![4, 5, 6],
+                &[1, 2, 3],
+                &options(),
+            )),
+            Err(Error::Agent(AgentError::Transport(
+                "the request was dropped".to_string()
+            )))
+        );
+    }
+}
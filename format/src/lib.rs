@@ -0,0 +1,293 @@
+//! The format of the files stored by DocuTrack, as encrypted by the web app in
+//! `frontend/src/frontend/src/lib/crypto.js` and uploaded by `lib/services/upload.ts`.
+//!
+//! The canister only stores bytes, so this format is shared by its clients: a file uploaded by
+//! one of them can be decrypted by any other.
+//!
+//! # User keys
+//!
+//! Every user has an RSA key pair of [`USER_KEY_BITS`] bits, with the public exponent 65537. The
+//! public key is registered with the canister (`set_user`) DER-encoded, in the
+//! SubjectPublicKeyInfo (SPKI) format.
+//!
+//! # Files
+//!
+//! Every file is encrypted with its own random 256-bit key, the file key, with AES-256-GCM and
+//! no associated data:
+//!
+//! ```text
+//! encrypted file = IV (12 bytes) || ciphertext || tag (16 bytes)
+//! ```
+//!
+//! The IV is random, and must never be reused with a file key. The ciphertext is as long as the
+//! file, so the encrypted file is [`OVERHEAD`] bytes longer.
+//!
+//! # Wrapped file keys
+//!
+//! The file key is given to every user with access to the file, as the `owner_key` of the file or
+//! the key of a share, encrypted with their public key: RSA-OAEP, with SHA-256 as the hash and in
+//! MGF1, and an empty label. The encryption is randomized, so a file key wraps to different
+//! bytes every time.
+//!
+//! # Chunks
+//!
+//! The encrypted file, not the plaintext, is split into chunks of [`CHUNK_SIZE`] bytes: chunk
+//! `i` holds bytes `i * CHUNK_SIZE..(i + 1) * CHUNK_SIZE`, and only the last one may be shorter.
+//! The chunks aren't encrypted on their own, so a file can only be decrypted once all of them are
+//! downloaded and concatenated.
+//!
+//! # Test vectors
+//!
+//! `test_vectors.json` holds files encrypted with a fixed file key and IV, along with the file
+//! key wrapped for a fixed user key, and the chunk lengths of encrypted files of several sizes.
+//! They are checked by the tests of this crate and of the frontend
+//! (`lib/crypto.vectors.test.js`), so that both implementations agree byte for byte. All bytes
+//! are hex-encoded.
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::{rngs::OsRng, RngCore};
+use rsa::pkcs8::{DecodePublicKey, EncodePublicKey};
+use rsa::{Oaep, PublicKey, RsaPrivateKey, RsaPublicKey};
+use sha2::Sha256;
+use std::fmt;
+
+pub use rsa;
+
+/// The size of the RSA keys of the users, as generated by the frontend.
+pub const USER_KEY_BITS: usize = 4096;
+
+pub const FILE_KEY_SIZE: usize = 32;
+
+pub const IV_SIZE: usize = 12;
+
+pub const TAG_SIZE: usize = 16;
+
+/// The number of bytes an encrypted file has on top of its plaintext.
+pub const OVERHEAD: usize = IV_SIZE + TAG_SIZE;
+
+/// The size of the chunks encrypted files are split into, the largest the canister accepts.
+pub const CHUNK_SIZE: usize = 2_000_000;
+
+pub type FileKey = [u8; FILE_KEY_SIZE];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    InvalidPublicKey,
+    UndecryptableKey,
+    /// The file is too short to hold an IV and a tag, or it was altered or encrypted with another
+    /// key.
+    UndecryptableFile,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPublicKey => write!(f, "invalid public key"),
+            Self::UndecryptableKey => write!(f, "the file key can't be decrypted"),
+            Self::UndecryptableFile => write!(f, "the file can't be decrypted"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn generate_user_key() -> RsaPrivateKey {
+    RsaPrivateKey::new(&mut OsRng, USER_KEY_BITS).expect("failed to generate a key")
+}
+
+/// Returns the public key of the user in the SPKI format, as registered with the canister.
+pub fn public_key(key: &RsaPrivateKey) -> Vec<u8> {
+    RsaPublicKey::from(key)
+        .to_public_key_der()
+        .expect("failed to encode the public key")
+        .as_bytes()
+        .to_vec()
+}
+
+pub fn generate_file_key() -> FileKey {
+    let mut key = [0; FILE_KEY_SIZE];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// Encrypts the file key for the user with the given public key, in the SPKI format.
+pub fn wrap_file_key(file_key: &FileKey, public_key: &[u8]) -> Result<Vec<u8>, Error> {
+    let public_key =
+        RsaPublicKey::from_public_key_der(public_key).map_err(|_| Error::InvalidPublicKey)?;
+    public_key
+        .encrypt(&mut OsRng, Oaep::new::<Sha256>(), file_key)
+        .map_err(|_| Error::InvalidPublicKey)
+}
+
+/// Decrypts a file key that was encrypted for the user.
+pub fn unwrap_file_key(wrapped: &[u8], key: &RsaPrivateKey) -> Result<FileKey, Error> {
+    key.decrypt(Oaep::new::<Sha256>(), wrapped)
+        .ok()
+        .and_then(|file_key| file_key.try_into().ok())
+        .ok_or(Error::UndecryptableKey)
+}
+
+/// Encrypts a file with a random IV.
+pub fn encrypt_file(contents: &[u8], file_key: &FileKey) -> Vec<u8> {
+    let mut iv = [0; IV_SIZE];
+    OsRng.fill_bytes(&mut iv);
+    encrypt_file_with_iv(contents, file_key, &iv)
+}
+
+/// Encrypts a file with the given IV, e.g., to reproduce the test vectors. The IV must never be
+/// reused with a file key, so [`encrypt_file`] should be used otherwise.
+pub fn encrypt_file_with_iv(contents: &[u8], file_key: &FileKey, iv: &[u8; IV_SIZE]) -> Vec<u8> {
+    let ciphertext = Aes256Gcm::new(file_key.into())
+        .encrypt(Nonce::from_slice(iv), contents)
+        .expect("failed to encrypt the file");
+    [iv.as_slice(), &ciphertext].concat()
+}
+
+pub fn decrypt_file(encrypted: &[u8], file_key: &FileKey) -> Result<Vec<u8>, Error> {
+    if encrypted.len() < OVERHEAD {
+        return Err(Error::UndecryptableFile);
+    }
+    let (iv, ciphertext) = encrypted.split_at(IV_SIZE);
+    Aes256Gcm::new(file_key.into())
+        .decrypt(Nonce::from_slice(iv), ciphertext)
+        .map_err(|_| Error::UndecryptableFile)
+}
+
+/// Splits an encrypted file into the chunks it is uploaded in.
+pub fn chunks(encrypted: &[u8]) -> impl ExactSizeIterator<Item = &[u8]> {
+    encrypted.chunks(CHUNK_SIZE)
+}
+
+/// Returns the number of chunks of an encrypted file, as declared when uploading it.
+pub fn num_chunks(encrypted_len: usize) -> u64 {
+    ((encrypted_len + CHUNK_SIZE - 1) / CHUNK_SIZE) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rsa::pkcs8::DecodePrivateKey;
+    use rsa::PublicKeyParts;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct TestVectors {
+        user_key: UserKeyVector,
+        files: Vec<FileVector>,
+        chunks: Vec<ChunksVector>,
+    }
+
+    #[derive(Deserialize)]
+    struct UserKeyVector {
+        private_key_pkcs8: String,
+        public_key_spki: String,
+    }
+
+    #[derive(Deserialize)]
+    struct FileVector {
+        file_key: String,
+        iv: String,
+        plaintext: String,
+        encrypted: String,
+        wrapped_file_key: String,
+    }
+
+    #[derive(Deserialize)]
+    struct ChunksVector {
+        encrypted_len: usize,
+        chunk_lengths: Vec<usize>,
+    }
+
+    fn test_vectors() -> TestVectors {
+        serde_json::from_str(include_str!("../test_vectors.json")).unwrap()
+    }
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        hex::decode(hex).unwrap()
+    }
+
+    fn test_user_key() -> RsaPrivateKey {
+        // Smaller keys are generated in tests, which are otherwise slow.
+        RsaPrivateKey::new(&mut OsRng, 1024).unwrap()
+    }
+
+    #[test]
+    fn files_match_the_test_vectors() {
+        let vectors = test_vectors();
+        let key =
+            RsaPrivateKey::from_pkcs8_der(&bytes(&vectors.user_key.private_key_pkcs8)).unwrap();
+        assert_eq!(public_key(&key), bytes(&vectors.user_key.public_key_spki));
+        assert_eq!(RsaPublicKey::from(&key).size() * 8, USER_KEY_BITS);
+
+        for file in vectors.files {
+            let file_key: FileKey = bytes(&file.file_key).try_into().unwrap();
+            let iv = bytes(&file.iv).try_into().unwrap();
+            let plaintext = bytes(&file.plaintext);
+            let encrypted = bytes(&file.encrypted);
+
+            assert_eq!(encrypt_file_with_iv(&plaintext, &file_key, &iv), encrypted);
+            assert_eq!(encrypted.len(), plaintext.len() + OVERHEAD);
+            assert_eq!(decrypt_file(&encrypted, &file_key), Ok(plaintext));
+            assert_eq!(
+                unwrap_file_key(&bytes(&file.wrapped_file_key), &key),
+                Ok(file_key)
+            );
+        }
+    }
+
+    #[test]
+    fn chunks_match_the_test_vectors() {
+        for vector in test_vectors().chunks {
+            let encrypted = vec![0; vector.encrypted_len];
+            let lengths: Vec<_> = chunks(&encrypted).map(<[u8]>::len).collect();
+            assert_eq!(lengths, vector.chunk_lengths);
+            assert_eq!(num_chunks(vector.encrypted_len), lengths.len() as u64);
+        }
+    }
+
+    #[test]
+    fn files_are_encrypted_for_users() {
+        let key = test_user_key();
+        let file_key = generate_file_key();
+
+        let wrapped = wrap_file_key(&file_key, &public_key(&key)).unwrap();
+        assert_eq!(unwrap_file_key(&wrapped, &key), Ok(file_key));
+        // The wrapping is randomized.
+        assert_ne!(
+            wrap_file_key(&file_key, &public_key(&key)).unwrap(),
+            wrapped
+        );
+        assert_eq!(
+            unwrap_file_key(&wrapped, &test_user_key()),
+            Err(Error::UndecryptableKey)
+        );
+        assert_eq!(
+            wrap_file_key(&file_key, b"not a key"),
+            Err(Error::InvalidPublicKey)
+        );
+
+        let encrypted = encrypt_file(b"contents", &file_key);
+        assert_eq!(encrypted.len(), b"contents".len() + OVERHEAD);
+        assert_eq!(
+            decrypt_file(&encrypted, &file_key),
+            Ok(b"contents".to_vec())
+        );
+        // A new IV is drawn for every encryption.
+        assert_ne!(encrypt_file(b"contents", &file_key), encrypted);
+
+        assert_eq!(
+            decrypt_file(&encrypted, &generate_file_key()),
+            Err(Error::UndecryptableFile)
+        );
+        assert_eq!(
+            decrypt_file(&encrypted[..OVERHEAD - 1], &file_key),
+            Err(Error::UndecryptableFile)
+        );
+        let mut altered = encrypted;
+        altered[IV_SIZE] ^= 1;
+        assert_eq!(
+            decrypt_file(&altered, &file_key),
+            Err(Error::UndecryptableFile)
+        );
+    }
+}
@@ -1,10 +1,148 @@
 import type { Principal } from '@dfinity/principal';
 import type { ActorMethod } from '@dfinity/agent';
 
+export type admin_action = { 'admin_added' : { 'user' : Principal } } |
+  { 'uploads_frozen' : null } |
+  { 'uploads_unfrozen' : null } |
+  { 'file_removed' : { 'owner' : Principal, 'file_id' : file_id } } |
+  { 'admin_removed' : { 'user' : Principal } } |
+  { 'config_updated' : { 'config' : config } } |
+  { 'rate_limits_updated' : { 'rate_limits' : rate_limits } } |
+  { 'user_removed' : { 'user' : Principal } };
+export type admin_error = { 'invalid_config' : config_error } |
+  { 'permission_error' : null } |
+  { 'last_organization_admin' : null } |
+  { 'not_found_file' : null } |
+  { 'unknown_user' : null } |
+  { 'last_admin' : null };
+export interface admin_log_entry {
+  'action' : admin_action,
+  'timestamp' : bigint,
+  'caller' : Principal,
+}
+export interface admin_log_page {
+  'total' : bigint,
+  'entries' : Array<admin_log_entry>,
+}
+export interface alias_item {
+  'pending' : boolean,
+  'file_name' : string,
+  'file_id' : file_id,
+}
+export interface audit_entry {
+  'event' : audit_event,
+  'timestamp' : bigint,
+  'caller' : Principal,
+}
+export type audit_event = {
+    'share_revoked' : { 'user' : Principal, 'file_id' : file_id },
+  } |
+  { 'chunk_downloaded' : { 'chunk_id' : bigint, 'file_id' : file_id } } |
+  { 'file_requested' : { 'file_id' : file_id } } |
+  {
+    'file_shared' : {
+      'permission' : share_permission,
+      'user' : Principal,
+      'file_id' : file_id,
+    },
+  } |
+  { 'chunk_uploaded' : { 'chunk_id' : bigint, 'file_id' : file_id } } |
+  { 'file_uploaded' : { 'file_id' : file_id } };
+export type audit_log_error = { 'permission_error' : null } |
+  { 'not_found_file' : null };
+export type canister_args = { 'init' : init_args } |
+  { 'upgrade' : upgrade_args };
+export interface certified_alias_info {
+  'certificate' : [] | [Uint8Array | number[]],
+  'data' : get_alias_info_response,
+  'witness' : Uint8Array | number[],
+}
+export interface certified_download_file_response {
+  'certificate' : [] | [Uint8Array | number[]],
+  'data' : download_file_response,
+  'witness' : Uint8Array | number[],
+}
+export interface certified_requests {
+  'certificate' : [] | [Uint8Array | number[]],
+  'data' : Array<file_metadata>,
+  'witness' : Uint8Array | number[],
+}
+export interface checklist {
+  'alias' : string,
+  'name' : string,
+  'requested_at' : bigint,
+  'items' : Array<file_metadata>,
+}
+export interface config {
+  'features' : features,
+  'share_ttl_ns' : [] | [bigint],
+  'max_stored_bytes' : bigint,
+  'max_user_bytes' : bigint,
+  'max_file_versions' : bigint,
+  'request_ttl_ns' : [] | [bigint],
+  'max_user_files' : bigint,
+  'download_token_ttl_ns' : bigint,
+  'max_chunk_size' : bigint,
+  'max_file_size' : bigint,
+  'max_drop_box_ttl_ns' : [] | [bigint],
+}
+export type config_error = { 'zero_chunk_size' : null } |
+  { 'zero_file_versions' : null } |
+  { 'zero_file_size' : null } |
+  { 'zero_ttl' : null };
+export interface create_drop_box_request {
+  'name' : string,
+  'max_bytes' : [] | [bigint],
+  'expires_at' : [] | [bigint],
+  'max_files' : [] | [bigint],
+}
 export type download_file_response = { 'found_file' : found_file } |
   { 'permission_error' : null } |
   { 'not_uploaded_file' : null } |
   { 'not_found_file' : null };
+export interface download_token {
+  'url' : string,
+  'token' : string,
+  'owner_key' : Uint8Array | number[],
+  'file_type' : string,
+  'num_chunks' : bigint,
+  'expires_at' : bigint,
+}
+export type download_token_error = { 'permission_error' : null } |
+  { 'not_uploaded_file' : null } |
+  { 'not_found_file' : null };
+export interface drop_box {
+  'closed' : boolean,
+  'alias' : string,
+  'uploads' : Array<drop_box_upload>,
+  'name' : string,
+  'total_bytes' : bigint,
+  'created_at' : bigint,
+  'max_bytes' : [] | [bigint],
+  'expires_at' : [] | [bigint],
+  'max_files' : [] | [bigint],
+}
+export type drop_box_error = { 'closed' : null } |
+  { 'limit_exceeded' : null } |
+  { 'expired' : null } |
+  { 'permission_error' : null } |
+  { 'uploads_frozen' : null } |
+  { 'rate_limited' : rate_limit_error } |
+  { 'not_found' : null } |
+  { 'quota_exceeded' : quota_error } |
+  { 'invalid_request' : validation_error };
+export interface drop_box_upload {
+  'label' : string,
+  'uploaded_at' : bigint,
+  'file_id' : file_id,
+}
+export interface features {
+  'file_versions' : boolean,
+  'webhooks' : boolean,
+  'drop_boxes' : boolean,
+  'checklists' : boolean,
+  'organizations' : boolean,
+}
 export interface file {
   'contents' : [] | [Uint8Array | number[]],
   'metadata' : file_metadata,
@@ -22,8 +160,29 @@ export type file_status = { 'partially_uploaded' : null } |
     'uploaded' : {
       'document_key' : Uint8Array | number[],
       'uploaded_at' : bigint,
-    }
+    },
   };
+export type file_transfer_error = { 'permission_error' : null } |
+  { 'not_uploaded_file' : null } |
+  { 'not_found_file' : null } |
+  { 'unknown_user' : null } |
+  { 'no_offer' : null };
+export type file_transfer_response = { 'Ok' : null } |
+  { 'Err' : file_transfer_error };
+export interface file_version {
+  'file_type' : string,
+  'version' : bigint,
+  'num_chunks' : bigint,
+  'is_current' : boolean,
+  'uploaded_at' : bigint,
+}
+export type file_version_error = { 'permission_error' : null } |
+  { 'uploads_frozen' : null } |
+  { 'no_pending_version' : null } |
+  { 'not_uploaded_file' : null } |
+  { 'not_found_file' : null } |
+  { 'quota_exceeded' : quota_error } |
+  { 'invalid_request' : validation_error };
 export interface found_file {
   'contents' : Uint8Array | number[],
   'owner_key' : Uint8Array | number[],
@@ -31,13 +190,155 @@ export interface found_file {
   'num_chunks' : bigint,
 }
 export type get_alias_info_response = {
-    'Ok' : { 'user' : user, 'file_name' : string, 'file_id' : file_id }
+    'Ok' : {
+      'user' : user,
+      'file_name' : string,
+      'items' : Array<alias_item>,
+      'file_id' : file_id,
+    },
   } |
   { 'Err' : { 'not_found' : null } };
 export type get_users_response = { 'permission_error' : null } |
   { 'users' : Array<user> };
+export type header_field = [string, string];
+export interface http_outcall_header {
+  'value' : string,
+  'name' : string,
+}
+export interface http_outcall_response {
+  'status' : bigint,
+  'body' : Uint8Array | number[],
+  'headers' : Array<http_outcall_header>,
+}
+export interface http_request {
+  'url' : string,
+  'method' : string,
+  'body' : Uint8Array | number[],
+  'headers' : Array<header_field>,
+}
+export interface http_response {
+  'body' : Uint8Array | number[],
+  'headers' : Array<header_field>,
+  'streaming_strategy' : [] | [streaming_strategy],
+  'status_code' : number,
+}
+export interface init_args {
+  'admins' : [] | [Array<Principal>],
+  'rate_limits' : [] | [rate_limits],
+  'config' : [] | [config],
+}
+export interface metrics {
+  'user_count' : bigint,
+  'stable_memory_bytes' : bigint,
+  'share_count' : bigint,
+  'partially_uploaded_file_count' : bigint,
+  'heap_memory_bytes' : bigint,
+  'uploaded_file_count' : bigint,
+  'pending_file_count' : bigint,
+  'chunk_count' : bigint,
+  'cycle_balance' : bigint,
+  'timestamp' : bigint,
+  'stored_bytes' : bigint,
+  'pending_alias_count' : bigint,
+}
+export interface notification {
+  'id' : bigint,
+  'read' : boolean,
+  'created_at' : bigint,
+  'file_name' : string,
+  'event' : notification_event,
+  'file_id' : file_id,
+}
+export type notification_event = { 'share_revoked' : { 'by' : Principal } } |
+  { 'request_expiring' : { 'expires_at' : bigint } } |
+  { 'file_shared' : { 'by' : Principal, 'permission' : share_permission } } |
+  { 'upload_completed' : null };
+export interface organization {
+  'members' : Array<organization_member>,
+  'name' : string,
+  'org_id' : bigint,
+  'role' : role,
+}
+export type organization_error = { 'permission_error' : null } |
+  { 'not_found' : null } |
+  { 'not_found_file' : null } |
+  { 'unknown_user' : null } |
+  { 'last_admin' : null };
+export interface organization_member {
+  'role' : role,
+  'user' : user,
+}
+export type organization_response = { 'Ok' : null } |
+  { 'Err' : organization_error };
+export type quota_error = { 'user_bytes' : null } |
+  { 'user_files' : null } |
+  { 'capacity' : null };
+export interface rate_limit {
+  'max_calls' : bigint,
+  'window_ns' : bigint,
+}
+export type rate_limit_error = { 'alias' : null } |
+  { 'caller' : null };
+export interface rate_limits {
+  'per_principal' : rate_limit,
+  'anonymous' : rate_limit,
+  'per_alias' : rate_limit,
+}
+export type role = { 'admin' : null } |
+  { 'editor' : null } |
+  { 'viewer' : null };
+export type set_user_response = { 'ok' : null } |
+  { 'username_exists' : null };
+export interface share {
+  'permission' : share_permission,
+  'user' : user,
+  'granted_at' : bigint,
+  'granted_by' : Principal,
+  'downloads_remaining' : [] | [bigint],
+}
 export type share_file_response = { 'ok' : null } |
   { 'permission_error' : null };
+export type share_permission = { 'view' : null } |
+  { 'co_owner' : null } |
+  { 'reshare' : null };
+export interface storage_usage {
+  'user_count' : bigint,
+  'max_stored_bytes' : bigint,
+  'users' : Array<user_usage>,
+  'stored_bytes' : bigint,
+  'file_count' : bigint,
+}
+export interface streaming_callback_http_response {
+  'token' : [] | [streaming_callback_token],
+  'body' : Uint8Array | number[],
+}
+export interface streaming_callback_token {
+  'token' : string,
+  'chunk_id' : bigint,
+  'file_id' : file_id,
+}
+export type streaming_strategy = {
+    'Callback' : {
+      'token' : streaming_callback_token,
+      'callback' : [Principal, string],
+    },
+  };
+export interface transfer_offer {
+  'file_key_encrypted_for_user' : Uint8Array | number[],
+  'from' : user,
+  'file_name' : string,
+  'offered_at' : bigint,
+  'file_id' : file_id,
+}
+export interface transform_args {
+  'context' : Uint8Array | number[],
+  'response' : http_outcall_response,
+}
+export interface upgrade_args {
+  'admins' : [] | [Array<Principal>],
+  'rate_limits' : [] | [rate_limits],
+  'config' : [] | [config],
+}
 export interface upload_file_atomic_request {
   'content' : Uint8Array | number[],
   'owner_key' : Uint8Array | number[],
@@ -50,8 +351,13 @@ export interface upload_file_continue_request {
   'chunk_id' : bigint,
   'file_id' : file_id,
 }
-export type upload_file_error = { 'not_requested' : null } |
-  { 'already_uploaded' : null };
+export type upload_file_error = { 'uploads_frozen' : null } |
+  { 'drop_box_limit_exceeded' : null } |
+  { 'rate_limited' : rate_limit_error } |
+  { 'not_requested' : null } |
+  { 'already_uploaded' : null } |
+  { 'quota_exceeded' : quota_error } |
+  { 'invalid_request' : validation_error };
 export interface upload_file_request {
   'owner_key' : Uint8Array | number[],
   'file_type' : string,
@@ -61,43 +367,267 @@ export interface upload_file_request {
 }
 export type upload_file_response = { 'Ok' : null } |
   { 'Err' : upload_file_error };
+export interface upload_file_version_request {
+  'content' : Uint8Array | number[],
+  'owner_key' : Uint8Array | number[],
+  'file_type' : string,
+  'num_chunks' : bigint,
+  'file_id' : file_id,
+}
+export interface upload_to_drop_box_request {
+  'content' : Uint8Array | number[],
+  'alias' : string,
+  'owner_key' : Uint8Array | number[],
+  'file_name' : string,
+  'file_type' : string,
+  'label' : string,
+  'num_chunks' : bigint,
+}
 export interface user {
+  'username' : string,
   'public_key' : Uint8Array | number[],
   'ic_principal' : Principal,
-  'username' : string,
 }
-export type who_am_i_response = {
-    'known_user' : { 'username' : string,}
-  } |
+export interface user_usage {
+  'user' : Principal,
+  'max_bytes' : bigint,
+  'bytes' : bigint,
+  'file_count' : bigint,
+  'max_files' : bigint,
+}
+export type validation_error = { 'invalid_chunk_size' : null } |
+  { 'invalid_file_name' : null } |
+  { 'file_name_too_long' : null } |
+  { 'empty_file_name' : null } |
+  { 'unsupported_file_type' : null } |
+  { 'empty_chunk' : null } |
+  { 'chunk_too_large' : null } |
+  { 'invalid_num_chunks' : null } |
+  { 'file_too_large' : null };
+export interface webhook {
+  'id' : bigint,
+  'url' : string,
+  'created_at' : bigint,
+}
+export type webhook_error = { 'limit_exceeded' : null } |
+  { 'not_found' : null } |
+  { 'invalid_url' : null };
+export interface webhook_registration {
+  'id' : bigint,
+  'secret' : string,
+}
+export type who_am_i_response = { 'known_user' : { 'username' : string } } |
   { 'unknown_user' : null };
-export type set_user_response = {
-    'ok' : null;
-  } |
-  { 'username_exists' : null };
 export interface _SERVICE {
+  'accept_file_transfer' : ActorMethod<
+    [file_id, Uint8Array | number[]],
+    file_transfer_response
+  >,
+  'ack_notifications' : ActorMethod<[bigint], undefined>,
+  'add_admin' : ActorMethod<
+    [Principal],
+    { 'Ok' : null } |
+      { 'Err' : admin_error }
+  >,
+  'add_file_to_organization' : ActorMethod<
+    [file_id, bigint],
+    organization_response
+  >,
+  'add_webhook' : ActorMethod<
+    [string],
+    { 'Ok' : webhook_registration } |
+      { 'Err' : webhook_error }
+  >,
+  'cancel_file_transfer' : ActorMethod<[file_id], file_transfer_response>,
+  'cancel_file_version' : ActorMethod<
+    [file_id],
+    { 'Ok' : bigint } |
+      { 'Err' : file_version_error }
+  >,
+  'close_drop_box' : ActorMethod<
+    [string],
+    { 'Ok' : null } |
+      { 'Err' : drop_box_error }
+  >,
+  'create_download_token' : ActorMethod<
+    [file_id],
+    { 'Ok' : download_token } |
+      { 'Err' : download_token_error }
+  >,
+  'create_drop_box' : ActorMethod<[create_drop_box_request], string>,
+  'create_organization' : ActorMethod<
+    [string],
+    { 'Ok' : bigint } |
+      { 'Err' : organization_error }
+  >,
   'download_file' : ActorMethod<[file_id, bigint], download_file_response>,
+  'download_file_audited' : ActorMethod<
+    [file_id, bigint],
+    download_file_response
+  >,
+  'download_file_certified' : ActorMethod<
+    [file_id, bigint],
+    certified_download_file_response
+  >,
+  'download_file_version' : ActorMethod<
+    [file_id, bigint, bigint],
+    download_file_response
+  >,
+  'get_admin_log' : ActorMethod<
+    [bigint, bigint],
+    { 'Ok' : admin_log_page } |
+      { 'Err' : admin_error }
+  >,
   'get_alias_info' : ActorMethod<[string], get_alias_info_response>,
+  'get_alias_info_certified' : ActorMethod<[string], certified_alias_info>,
+  'get_audit_log' : ActorMethod<
+    [bigint, bigint],
+    { 'Ok' : { 'total' : bigint, 'entries' : Array<audit_entry> } } |
+      { 'Err' : audit_log_error }
+  >,
+  'get_checklists' : ActorMethod<[], Array<checklist>>,
+  'get_config' : ActorMethod<[], config>,
+  'get_drop_box_info' : ActorMethod<
+    [string],
+    { 'Ok' : { 'name' : string, 'user' : user } } |
+      { 'Err' : drop_box_error }
+  >,
+  'get_drop_boxes' : ActorMethod<[], Array<drop_box>>,
+  'get_file_audit_log' : ActorMethod<
+    [file_id, bigint, bigint],
+    { 'Ok' : { 'next' : [] | [bigint], 'entries' : Array<audit_entry> } } |
+      { 'Err' : audit_log_error }
+  >,
+  'get_file_shares' : ActorMethod<[file_id], Array<share>>,
+  'get_file_versions' : ActorMethod<
+    [file_id],
+    { 'Ok' : Array<file_version> } |
+      { 'Err' : file_version_error }
+  >,
+  'get_metrics' : ActorMethod<[], metrics>,
+  'get_notifications' : ActorMethod<[], Array<notification>>,
+  'get_organization_files' : ActorMethod<
+    [bigint],
+    { 'Ok' : Array<file_metadata> } |
+      { 'Err' : organization_error }
+  >,
+  'get_organizations' : ActorMethod<[], Array<organization>>,
+  'get_rate_limits' : ActorMethod<[], rate_limits>,
   'get_requests' : ActorMethod<[], Array<file_metadata>>,
+  'get_requests_certified' : ActorMethod<[], certified_requests>,
   'get_shared_files' : ActorMethod<[], Array<file_metadata>>,
+  'get_storage_usage' : ActorMethod<
+    [],
+    { 'Ok' : storage_usage } |
+      { 'Err' : admin_error }
+  >,
+  'get_transfer_offers' : ActorMethod<[], Array<transfer_offer>>,
+  'get_unread_notification_count' : ActorMethod<[], bigint>,
+  'get_usage' : ActorMethod<[], Array<user_usage>>,
   'get_users' : ActorMethod<[], get_users_response>,
+  'get_webhooks' : ActorMethod<[], Array<webhook>>,
   'hello_world' : ActorMethod<[], string>,
+  'http_request' : ActorMethod<[http_request], http_response>,
+  'http_request_streaming_callback' : ActorMethod<
+    [streaming_callback_token],
+    streaming_callback_http_response
+  >,
+  'offer_file_transfer' : ActorMethod<
+    [Principal, file_id, Uint8Array | number[]],
+    file_transfer_response
+  >,
+  'remove_admin' : ActorMethod<
+    [Principal],
+    { 'Ok' : null } |
+      { 'Err' : admin_error }
+  >,
+  'remove_file' : ActorMethod<
+    [file_id],
+    { 'Ok' : null } |
+      { 'Err' : admin_error }
+  >,
+  'remove_organization_member' : ActorMethod<
+    [bigint, Principal],
+    organization_response
+  >,
+  'remove_user' : ActorMethod<
+    [Principal],
+    { 'Ok' : null } |
+      { 'Err' : admin_error }
+  >,
+  'remove_webhook' : ActorMethod<
+    [bigint],
+    { 'Ok' : null } |
+      { 'Err' : webhook_error }
+  >,
+  'request_checklist' : ActorMethod<[string, Array<string>], string>,
   'request_file' : ActorMethod<[string], string>,
+  'request_organization_file' : ActorMethod<
+    [bigint, string],
+    { 'Ok' : string } |
+      { 'Err' : organization_error }
+  >,
   'revoke_share' : ActorMethod<[Principal, file_id], share_file_response>,
+  'set_config' : ActorMethod<
+    [config],
+    { 'Ok' : null } |
+      { 'Err' : admin_error }
+  >,
+  'set_organization_member' : ActorMethod<
+    [bigint, Principal, role],
+    organization_response
+  >,
+  'set_rate_limits' : ActorMethod<
+    [rate_limits],
+    { 'Ok' : null } |
+      { 'Err' : admin_error }
+  >,
+  'set_uploads_frozen' : ActorMethod<
+    [boolean],
+    { 'Ok' : null } |
+      { 'Err' : admin_error }
+  >,
   'set_user' : ActorMethod<[string, Uint8Array | number[]], set_user_response>,
   'share_file' : ActorMethod<
     [Principal, file_id, Uint8Array | number[]],
     share_file_response
   >,
+  'share_file_with_download_limit' : ActorMethod<
+    [Principal, file_id, Uint8Array | number[], bigint],
+    share_file_response
+  >,
+  'share_file_with_permission' : ActorMethod<
+    [Principal, file_id, Uint8Array | number[], share_permission],
+    share_file_response
+  >,
   'share_file_with_users' : ActorMethod<
     [Array<Principal>, file_id, Array<Uint8Array | number[]>],
     undefined
   >,
+  'transform_webhook_response' : ActorMethod<
+    [transform_args],
+    http_outcall_response
+  >,
   'upload_file' : ActorMethod<[upload_file_request], upload_file_response>,
-  'upload_file_atomic' : ActorMethod<[upload_file_atomic_request], file_id>,
+  'upload_file_atomic' : ActorMethod<
+    [upload_file_atomic_request],
+    { 'Ok' : file_id } |
+      { 'Err' : upload_file_error }
+  >,
   'upload_file_continue' : ActorMethod<
     [upload_file_continue_request],
-    undefined
+    upload_file_response
+  >,
+  'upload_file_version' : ActorMethod<
+    [upload_file_version_request],
+    { 'Ok' : bigint } |
+      { 'Err' : file_version_error }
+  >,
+  'upload_to_drop_box' : ActorMethod<
+    [upload_to_drop_box_request],
+    { 'Ok' : file_id } |
+      { 'Err' : drop_box_error }
   >,
-  'username_exists' : ActorMethod<[string],boolean>,
+  'username_exists' : ActorMethod<[string], boolean>,
   'who_am_i' : ActorMethod<[], who_am_i_response>,
 }
@@ -1,5 +1,109 @@
 export const idlFactory = ({ IDL }) => {
   const file_id = IDL.Nat64;
+  const file_transfer_error = IDL.Variant({
+    'permission_error' : IDL.Null,
+    'not_uploaded_file' : IDL.Null,
+    'not_found_file' : IDL.Null,
+    'unknown_user' : IDL.Null,
+    'no_offer' : IDL.Null,
+  });
+  const file_transfer_response = IDL.Variant({
+    'Ok' : IDL.Null,
+    'Err' : file_transfer_error,
+  });
+  const config_error = IDL.Variant({
+    'zero_chunk_size' : IDL.Null,
+    'zero_file_versions' : IDL.Null,
+    'zero_file_size' : IDL.Null,
+    'zero_ttl' : IDL.Null,
+  });
+  const admin_error = IDL.Variant({
+    'invalid_config' : config_error,
+    'permission_error' : IDL.Null,
+    'last_organization_admin' : IDL.Null,
+    'not_found_file' : IDL.Null,
+    'unknown_user' : IDL.Null,
+    'last_admin' : IDL.Null,
+  });
+  const organization_error = IDL.Variant({
+    'permission_error' : IDL.Null,
+    'not_found' : IDL.Null,
+    'not_found_file' : IDL.Null,
+    'unknown_user' : IDL.Null,
+    'last_admin' : IDL.Null,
+  });
+  const organization_response = IDL.Variant({
+    'Ok' : IDL.Null,
+    'Err' : organization_error,
+  });
+  const webhook_registration = IDL.Record({
+    'id' : IDL.Nat64,
+    'secret' : IDL.Text,
+  });
+  const webhook_error = IDL.Variant({
+    'limit_exceeded' : IDL.Null,
+    'not_found' : IDL.Null,
+    'invalid_url' : IDL.Null,
+  });
+  const quota_error = IDL.Variant({
+    'user_bytes' : IDL.Null,
+    'user_files' : IDL.Null,
+    'capacity' : IDL.Null,
+  });
+  const validation_error = IDL.Variant({
+    'invalid_chunk_size' : IDL.Null,
+    'invalid_file_name' : IDL.Null,
+    'file_name_too_long' : IDL.Null,
+    'empty_file_name' : IDL.Null,
+    'unsupported_file_type' : IDL.Null,
+    'empty_chunk' : IDL.Null,
+    'chunk_too_large' : IDL.Null,
+    'invalid_num_chunks' : IDL.Null,
+    'file_too_large' : IDL.Null,
+  });
+  const file_version_error = IDL.Variant({
+    'permission_error' : IDL.Null,
+    'uploads_frozen' : IDL.Null,
+    'no_pending_version' : IDL.Null,
+    'not_uploaded_file' : IDL.Null,
+    'not_found_file' : IDL.Null,
+    'quota_exceeded' : quota_error,
+    'invalid_request' : validation_error,
+  });
+  const rate_limit_error = IDL.Variant({
+    'alias' : IDL.Null,
+    'caller' : IDL.Null,
+  });
+  const drop_box_error = IDL.Variant({
+    'closed' : IDL.Null,
+    'limit_exceeded' : IDL.Null,
+    'expired' : IDL.Null,
+    'permission_error' : IDL.Null,
+    'uploads_frozen' : IDL.Null,
+    'rate_limited' : rate_limit_error,
+    'not_found' : IDL.Null,
+    'quota_exceeded' : quota_error,
+    'invalid_request' : validation_error,
+  });
+  const download_token = IDL.Record({
+    'url' : IDL.Text,
+    'token' : IDL.Text,
+    'owner_key' : IDL.Vec(IDL.Nat8),
+    'file_type' : IDL.Text,
+    'num_chunks' : IDL.Nat64,
+    'expires_at' : IDL.Nat64,
+  });
+  const download_token_error = IDL.Variant({
+    'permission_error' : IDL.Null,
+    'not_uploaded_file' : IDL.Null,
+    'not_found_file' : IDL.Null,
+  });
+  const create_drop_box_request = IDL.Record({
+    'name' : IDL.Text,
+    'max_bytes' : IDL.Opt(IDL.Nat64),
+    'expires_at' : IDL.Opt(IDL.Nat64),
+    'max_files' : IDL.Opt(IDL.Nat64),
+  });
   const found_file = IDL.Record({
     'contents' : IDL.Vec(IDL.Nat8),
     'owner_key' : IDL.Vec(IDL.Nat8),
@@ -12,19 +116,121 @@ export const idlFactory = ({ IDL }) => {
     'not_uploaded_file' : IDL.Null,
     'not_found_file' : IDL.Null,
   });
+  const certified_download_file_response = IDL.Record({
+    'certificate' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'data' : download_file_response,
+    'witness' : IDL.Vec(IDL.Nat8),
+  });
+  const features = IDL.Record({
+    'file_versions' : IDL.Bool,
+    'webhooks' : IDL.Bool,
+    'drop_boxes' : IDL.Bool,
+    'checklists' : IDL.Bool,
+    'organizations' : IDL.Bool,
+  });
+  const config = IDL.Record({
+    'features' : features,
+    'share_ttl_ns' : IDL.Opt(IDL.Nat64),
+    'max_stored_bytes' : IDL.Nat64,
+    'max_user_bytes' : IDL.Nat64,
+    'max_file_versions' : IDL.Nat64,
+    'request_ttl_ns' : IDL.Opt(IDL.Nat64),
+    'max_user_files' : IDL.Nat64,
+    'download_token_ttl_ns' : IDL.Nat64,
+    'max_chunk_size' : IDL.Nat64,
+    'max_file_size' : IDL.Nat64,
+    'max_drop_box_ttl_ns' : IDL.Opt(IDL.Nat64),
+  });
+  const rate_limit = IDL.Record({
+    'max_calls' : IDL.Nat64,
+    'window_ns' : IDL.Nat64,
+  });
+  const rate_limits = IDL.Record({
+    'per_principal' : rate_limit,
+    'anonymous' : rate_limit,
+    'per_alias' : rate_limit,
+  });
+  const admin_action = IDL.Variant({
+    'admin_added' : IDL.Record({ 'user' : IDL.Principal }),
+    'uploads_frozen' : IDL.Null,
+    'uploads_unfrozen' : IDL.Null,
+    'file_removed' : IDL.Record({
+      'owner' : IDL.Principal,
+      'file_id' : file_id,
+    }),
+    'admin_removed' : IDL.Record({ 'user' : IDL.Principal }),
+    'config_updated' : IDL.Record({ 'config' : config }),
+    'rate_limits_updated' : IDL.Record({ 'rate_limits' : rate_limits }),
+    'user_removed' : IDL.Record({ 'user' : IDL.Principal }),
+  });
+  const admin_log_entry = IDL.Record({
+    'action' : admin_action,
+    'timestamp' : IDL.Nat64,
+    'caller' : IDL.Principal,
+  });
+  const admin_log_page = IDL.Record({
+    'total' : IDL.Nat64,
+    'entries' : IDL.Vec(admin_log_entry),
+  });
   const user = IDL.Record({
+    'username' : IDL.Text,
     'public_key' : IDL.Vec(IDL.Nat8),
     'ic_principal' : IDL.Principal,
-    'username' : IDL.Text,
+  });
+  const alias_item = IDL.Record({
+    'pending' : IDL.Bool,
+    'file_name' : IDL.Text,
+    'file_id' : file_id,
   });
   const get_alias_info_response = IDL.Variant({
     'Ok' : IDL.Record({
       'user' : user,
       'file_name' : IDL.Text,
+      'items' : IDL.Vec(alias_item),
       'file_id' : file_id,
     }),
     'Err' : IDL.Variant({ 'not_found' : IDL.Null }),
   });
+  const certified_alias_info = IDL.Record({
+    'certificate' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'data' : get_alias_info_response,
+    'witness' : IDL.Vec(IDL.Nat8),
+  });
+  const share_permission = IDL.Variant({
+    'view' : IDL.Null,
+    'co_owner' : IDL.Null,
+    'reshare' : IDL.Null,
+  });
+  const audit_event = IDL.Variant({
+    'share_revoked' : IDL.Record({
+      'user' : IDL.Principal,
+      'file_id' : file_id,
+    }),
+    'chunk_downloaded' : IDL.Record({
+      'chunk_id' : IDL.Nat64,
+      'file_id' : file_id,
+    }),
+    'file_requested' : IDL.Record({ 'file_id' : file_id }),
+    'file_shared' : IDL.Record({
+      'permission' : share_permission,
+      'user' : IDL.Principal,
+      'file_id' : file_id,
+    }),
+    'chunk_uploaded' : IDL.Record({
+      'chunk_id' : IDL.Nat64,
+      'file_id' : file_id,
+    }),
+    'file_uploaded' : IDL.Record({ 'file_id' : file_id }),
+  });
+  const audit_entry = IDL.Record({
+    'event' : audit_event,
+    'timestamp' : IDL.Nat64,
+    'caller' : IDL.Principal,
+  });
+  const audit_log_error = IDL.Variant({
+    'permission_error' : IDL.Null,
+    'not_found_file' : IDL.Null,
+  });
   const file_status = IDL.Variant({
     'partially_uploaded' : IDL.Null,
     'pending' : IDL.Record({ 'alias' : IDL.Text, 'requested_at' : IDL.Nat64 }),
@@ -39,14 +245,173 @@ export const idlFactory = ({ IDL }) => {
     'shared_with' : IDL.Vec(user),
     'file_id' : file_id,
   });
+  const checklist = IDL.Record({
+    'alias' : IDL.Text,
+    'name' : IDL.Text,
+    'requested_at' : IDL.Nat64,
+    'items' : IDL.Vec(file_metadata),
+  });
+  const drop_box_upload = IDL.Record({
+    'label' : IDL.Text,
+    'uploaded_at' : IDL.Nat64,
+    'file_id' : file_id,
+  });
+  const drop_box = IDL.Record({
+    'closed' : IDL.Bool,
+    'alias' : IDL.Text,
+    'uploads' : IDL.Vec(drop_box_upload),
+    'name' : IDL.Text,
+    'total_bytes' : IDL.Nat64,
+    'created_at' : IDL.Nat64,
+    'max_bytes' : IDL.Opt(IDL.Nat64),
+    'expires_at' : IDL.Opt(IDL.Nat64),
+    'max_files' : IDL.Opt(IDL.Nat64),
+  });
+  const share = IDL.Record({
+    'permission' : share_permission,
+    'user' : user,
+    'granted_at' : IDL.Nat64,
+    'granted_by' : IDL.Principal,
+    'downloads_remaining' : IDL.Opt(IDL.Nat64),
+  });
+  const file_version = IDL.Record({
+    'file_type' : IDL.Text,
+    'version' : IDL.Nat64,
+    'num_chunks' : IDL.Nat64,
+    'is_current' : IDL.Bool,
+    'uploaded_at' : IDL.Nat64,
+  });
+  const metrics = IDL.Record({
+    'user_count' : IDL.Nat64,
+    'stable_memory_bytes' : IDL.Nat64,
+    'share_count' : IDL.Nat64,
+    'partially_uploaded_file_count' : IDL.Nat64,
+    'heap_memory_bytes' : IDL.Nat64,
+    'uploaded_file_count' : IDL.Nat64,
+    'pending_file_count' : IDL.Nat64,
+    'chunk_count' : IDL.Nat64,
+    'cycle_balance' : IDL.Nat,
+    'timestamp' : IDL.Nat64,
+    'stored_bytes' : IDL.Nat64,
+    'pending_alias_count' : IDL.Nat64,
+  });
+  const notification_event = IDL.Variant({
+    'share_revoked' : IDL.Record({ 'by' : IDL.Principal }),
+    'request_expiring' : IDL.Record({ 'expires_at' : IDL.Nat64 }),
+    'file_shared' : IDL.Record({
+      'by' : IDL.Principal,
+      'permission' : share_permission,
+    }),
+    'upload_completed' : IDL.Null,
+  });
+  const notification = IDL.Record({
+    'id' : IDL.Nat64,
+    'read' : IDL.Bool,
+    'created_at' : IDL.Nat64,
+    'file_name' : IDL.Text,
+    'event' : notification_event,
+    'file_id' : file_id,
+  });
+  const role = IDL.Variant({
+    'admin' : IDL.Null,
+    'editor' : IDL.Null,
+    'viewer' : IDL.Null,
+  });
+  const organization_member = IDL.Record({ 'role' : role, 'user' : user });
+  const organization = IDL.Record({
+    'members' : IDL.Vec(organization_member),
+    'name' : IDL.Text,
+    'org_id' : IDL.Nat64,
+    'role' : role,
+  });
+  const certified_requests = IDL.Record({
+    'certificate' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'data' : IDL.Vec(file_metadata),
+    'witness' : IDL.Vec(IDL.Nat8),
+  });
+  const user_usage = IDL.Record({
+    'user' : IDL.Principal,
+    'max_bytes' : IDL.Nat64,
+    'bytes' : IDL.Nat64,
+    'file_count' : IDL.Nat64,
+    'max_files' : IDL.Nat64,
+  });
+  const storage_usage = IDL.Record({
+    'user_count' : IDL.Nat64,
+    'max_stored_bytes' : IDL.Nat64,
+    'users' : IDL.Vec(user_usage),
+    'stored_bytes' : IDL.Nat64,
+    'file_count' : IDL.Nat64,
+  });
+  const transfer_offer = IDL.Record({
+    'file_key_encrypted_for_user' : IDL.Vec(IDL.Nat8),
+    'from' : user,
+    'file_name' : IDL.Text,
+    'offered_at' : IDL.Nat64,
+    'file_id' : file_id,
+  });
   const get_users_response = IDL.Variant({
     'permission_error' : IDL.Null,
     'users' : IDL.Vec(user),
   });
+  const webhook = IDL.Record({
+    'id' : IDL.Nat64,
+    'url' : IDL.Text,
+    'created_at' : IDL.Nat64,
+  });
+  const header_field = IDL.Tuple(IDL.Text, IDL.Text);
+  const http_request = IDL.Record({
+    'url' : IDL.Text,
+    'method' : IDL.Text,
+    'body' : IDL.Vec(IDL.Nat8),
+    'headers' : IDL.Vec(header_field),
+  });
+  const streaming_callback_token = IDL.Record({
+    'token' : IDL.Text,
+    'chunk_id' : IDL.Nat64,
+    'file_id' : file_id,
+  });
+  const streaming_callback_http_response = IDL.Record({
+    'token' : IDL.Opt(streaming_callback_token),
+    'body' : IDL.Vec(IDL.Nat8),
+  });
+  const streaming_strategy = IDL.Variant({
+    'Callback' : IDL.Record({
+      'token' : streaming_callback_token,
+      'callback' : IDL.Func(
+          [streaming_callback_token],
+          [streaming_callback_http_response],
+          ['query'],
+        ),
+    }),
+  });
+  const http_response = IDL.Record({
+    'body' : IDL.Vec(IDL.Nat8),
+    'headers' : IDL.Vec(header_field),
+    'streaming_strategy' : IDL.Opt(streaming_strategy),
+    'status_code' : IDL.Nat16,
+  });
   const share_file_response = IDL.Variant({
     'ok' : IDL.Null,
     'permission_error' : IDL.Null,
   });
+  const set_user_response = IDL.Variant({
+    'ok' : IDL.Null,
+    'username_exists' : IDL.Null,
+  });
+  const http_outcall_header = IDL.Record({
+    'value' : IDL.Text,
+    'name' : IDL.Text,
+  });
+  const http_outcall_response = IDL.Record({
+    'status' : IDL.Nat,
+    'body' : IDL.Vec(IDL.Nat8),
+    'headers' : IDL.Vec(http_outcall_header),
+  });
+  const transform_args = IDL.Record({
+    'context' : IDL.Vec(IDL.Nat8),
+    'response' : http_outcall_response,
+  });
   const upload_file_request = IDL.Record({
     'owner_key' : IDL.Vec(IDL.Nat8),
     'file_type' : IDL.Text,
@@ -55,8 +420,13 @@ export const idlFactory = ({ IDL }) => {
     'file_id' : file_id,
   });
   const upload_file_error = IDL.Variant({
+    'uploads_frozen' : IDL.Null,
+    'drop_box_limit_exceeded' : IDL.Null,
+    'rate_limited' : rate_limit_error,
     'not_requested' : IDL.Null,
     'already_uploaded' : IDL.Null,
+    'quota_exceeded' : quota_error,
+    'invalid_request' : validation_error,
   });
   const upload_file_response = IDL.Variant({
     'Ok' : IDL.Null,
@@ -74,57 +444,343 @@ export const idlFactory = ({ IDL }) => {
     'chunk_id' : IDL.Nat64,
     'file_id' : file_id,
   });
+  const upload_file_version_request = IDL.Record({
+    'content' : IDL.Vec(IDL.Nat8),
+    'owner_key' : IDL.Vec(IDL.Nat8),
+    'file_type' : IDL.Text,
+    'num_chunks' : IDL.Nat64,
+    'file_id' : file_id,
+  });
+  const upload_to_drop_box_request = IDL.Record({
+    'content' : IDL.Vec(IDL.Nat8),
+    'alias' : IDL.Text,
+    'owner_key' : IDL.Vec(IDL.Nat8),
+    'file_name' : IDL.Text,
+    'file_type' : IDL.Text,
+    'label' : IDL.Text,
+    'num_chunks' : IDL.Nat64,
+  });
   const who_am_i_response = IDL.Variant({
-    'known_user' : IDL.Record({
-      'username' : IDL.Text,
-    }),
+    'known_user' : IDL.Record({ 'username' : IDL.Text }),
     'unknown_user' : IDL.Null,
   });
-  const set_user_response = IDL.Variant({
-      'ok' : IDL.Null,
-      'username_exists' : IDL.Null,
-  }); 
   return IDL.Service({
+    'accept_file_transfer' : IDL.Func(
+        [file_id, IDL.Vec(IDL.Nat8)],
+        [file_transfer_response],
+        [],
+      ),
+    'ack_notifications' : IDL.Func([IDL.Nat64], [], []),
+    'add_admin' : IDL.Func(
+        [IDL.Principal],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : admin_error })],
+        [],
+      ),
+    'add_file_to_organization' : IDL.Func(
+        [file_id, IDL.Nat64],
+        [organization_response],
+        [],
+      ),
+    'add_webhook' : IDL.Func(
+        [IDL.Text],
+        [IDL.Variant({ 'Ok' : webhook_registration, 'Err' : webhook_error })],
+        [],
+      ),
+    'cancel_file_transfer' : IDL.Func([file_id], [file_transfer_response], []),
+    'cancel_file_version' : IDL.Func(
+        [file_id],
+        [IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : file_version_error })],
+        [],
+      ),
+    'close_drop_box' : IDL.Func(
+        [IDL.Text],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : drop_box_error })],
+        [],
+      ),
+    'create_download_token' : IDL.Func(
+        [file_id],
+        [IDL.Variant({ 'Ok' : download_token, 'Err' : download_token_error })],
+        [],
+      ),
+    'create_drop_box' : IDL.Func([create_drop_box_request], [IDL.Text], []),
+    'create_organization' : IDL.Func(
+        [IDL.Text],
+        [IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : organization_error })],
+        [],
+      ),
     'download_file' : IDL.Func(
         [file_id, IDL.Nat64],
         [download_file_response],
         ['query'],
       ),
+    'download_file_audited' : IDL.Func(
+        [file_id, IDL.Nat64],
+        [download_file_response],
+        [],
+      ),
+    'download_file_certified' : IDL.Func(
+        [file_id, IDL.Nat64],
+        [certified_download_file_response],
+        ['query'],
+      ),
+    'download_file_version' : IDL.Func(
+        [file_id, IDL.Nat64, IDL.Nat64],
+        [download_file_response],
+        ['query'],
+      ),
+    'get_admin_log' : IDL.Func(
+        [IDL.Nat64, IDL.Nat64],
+        [IDL.Variant({ 'Ok' : admin_log_page, 'Err' : admin_error })],
+        ['query'],
+      ),
     'get_alias_info' : IDL.Func(
         [IDL.Text],
         [get_alias_info_response],
         ['query'],
       ),
+    'get_alias_info_certified' : IDL.Func(
+        [IDL.Text],
+        [certified_alias_info],
+        ['query'],
+      ),
+    'get_audit_log' : IDL.Func(
+        [IDL.Nat64, IDL.Nat64],
+        [IDL.Variant({
+          'Ok' : IDL.Record({
+            'total' : IDL.Nat64,
+            'entries' : IDL.Vec(audit_entry),
+          }),
+          'Err' : audit_log_error,
+        })],
+        ['query'],
+      ),
+    'get_checklists' : IDL.Func([], [IDL.Vec(checklist)], ['query']),
+    'get_config' : IDL.Func([], [config], ['query']),
+    'get_drop_box_info' : IDL.Func(
+        [IDL.Text],
+        [IDL.Variant({
+          'Ok' : IDL.Record({ 'name' : IDL.Text, 'user' : user }),
+          'Err' : drop_box_error,
+        })],
+        ['query'],
+      ),
+    'get_drop_boxes' : IDL.Func([], [IDL.Vec(drop_box)], ['query']),
+    'get_file_audit_log' : IDL.Func(
+        [file_id, IDL.Nat64, IDL.Nat64],
+        [IDL.Variant({
+          'Ok' : IDL.Record({
+            'next' : IDL.Opt(IDL.Nat64),
+            'entries' : IDL.Vec(audit_entry),
+          }),
+          'Err' : audit_log_error,
+        })],
+        ['query'],
+      ),
+    'get_file_shares' : IDL.Func([file_id], [IDL.Vec(share)], ['query']),
+    'get_file_versions' : IDL.Func(
+        [file_id],
+        [IDL.Variant({
+          'Ok' : IDL.Vec(file_version),
+          'Err' : file_version_error,
+        })],
+        ['query'],
+      ),
+    'get_metrics' : IDL.Func([], [metrics], ['query']),
+    'get_notifications' : IDL.Func([], [IDL.Vec(notification)], ['query']),
+    'get_organization_files' : IDL.Func(
+        [IDL.Nat64],
+        [IDL.Variant({
+          'Ok' : IDL.Vec(file_metadata),
+          'Err' : organization_error,
+        })],
+        ['query'],
+      ),
+    'get_organizations' : IDL.Func([], [IDL.Vec(organization)], ['query']),
+    'get_rate_limits' : IDL.Func([], [rate_limits], ['query']),
     'get_requests' : IDL.Func([], [IDL.Vec(file_metadata)], ['query']),
+    'get_requests_certified' : IDL.Func([], [certified_requests], ['query']),
     'get_shared_files' : IDL.Func([], [IDL.Vec(file_metadata)], ['query']),
+    'get_storage_usage' : IDL.Func(
+        [],
+        [IDL.Variant({ 'Ok' : storage_usage, 'Err' : admin_error })],
+        ['query'],
+      ),
+    'get_transfer_offers' : IDL.Func([], [IDL.Vec(transfer_offer)], ['query']),
+    'get_unread_notification_count' : IDL.Func([], [IDL.Nat64], ['query']),
+    'get_usage' : IDL.Func([], [IDL.Vec(user_usage)], ['query']),
     'get_users' : IDL.Func([], [get_users_response], ['query']),
+    'get_webhooks' : IDL.Func([], [IDL.Vec(webhook)], ['query']),
     'hello_world' : IDL.Func([], [IDL.Text], []),
+    'http_request' : IDL.Func([http_request], [http_response], ['query']),
+    'http_request_streaming_callback' : IDL.Func(
+        [streaming_callback_token],
+        [streaming_callback_http_response],
+        ['query'],
+      ),
+    'offer_file_transfer' : IDL.Func(
+        [IDL.Principal, file_id, IDL.Vec(IDL.Nat8)],
+        [file_transfer_response],
+        [],
+      ),
+    'remove_admin' : IDL.Func(
+        [IDL.Principal],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : admin_error })],
+        [],
+      ),
+    'remove_file' : IDL.Func(
+        [file_id],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : admin_error })],
+        [],
+      ),
+    'remove_organization_member' : IDL.Func(
+        [IDL.Nat64, IDL.Principal],
+        [organization_response],
+        [],
+      ),
+    'remove_user' : IDL.Func(
+        [IDL.Principal],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : admin_error })],
+        [],
+      ),
+    'remove_webhook' : IDL.Func(
+        [IDL.Nat64],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : webhook_error })],
+        [],
+      ),
+    'request_checklist' : IDL.Func(
+        [IDL.Text, IDL.Vec(IDL.Text)],
+        [IDL.Text],
+        [],
+      ),
     'request_file' : IDL.Func([IDL.Text], [IDL.Text], []),
+    'request_organization_file' : IDL.Func(
+        [IDL.Nat64, IDL.Text],
+        [IDL.Variant({ 'Ok' : IDL.Text, 'Err' : organization_error })],
+        [],
+      ),
     'revoke_share' : IDL.Func(
         [IDL.Principal, file_id],
         [share_file_response],
         [],
       ),
-    'set_user' : IDL.Func([IDL.Text, IDL.Vec(IDL.Nat8)], [set_user_response], []),
+    'set_config' : IDL.Func(
+        [config],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : admin_error })],
+        [],
+      ),
+    'set_organization_member' : IDL.Func(
+        [IDL.Nat64, IDL.Principal, role],
+        [organization_response],
+        [],
+      ),
+    'set_rate_limits' : IDL.Func(
+        [rate_limits],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : admin_error })],
+        [],
+      ),
+    'set_uploads_frozen' : IDL.Func(
+        [IDL.Bool],
+        [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : admin_error })],
+        [],
+      ),
+    'set_user' : IDL.Func(
+        [IDL.Text, IDL.Vec(IDL.Nat8)],
+        [set_user_response],
+        [],
+      ),
     'share_file' : IDL.Func(
         [IDL.Principal, file_id, IDL.Vec(IDL.Nat8)],
         [share_file_response],
         [],
       ),
+    'share_file_with_download_limit' : IDL.Func(
+        [IDL.Principal, file_id, IDL.Vec(IDL.Nat8), IDL.Nat64],
+        [share_file_response],
+        [],
+      ),
+    'share_file_with_permission' : IDL.Func(
+        [IDL.Principal, file_id, IDL.Vec(IDL.Nat8), share_permission],
+        [share_file_response],
+        [],
+      ),
     'share_file_with_users' : IDL.Func(
         [IDL.Vec(IDL.Principal), file_id, IDL.Vec(IDL.Vec(IDL.Nat8))],
         [],
         [],
       ),
+    'transform_webhook_response' : IDL.Func(
+        [transform_args],
+        [http_outcall_response],
+        ['query'],
+      ),
     'upload_file' : IDL.Func([upload_file_request], [upload_file_response], []),
     'upload_file_atomic' : IDL.Func(
         [upload_file_atomic_request],
-        [file_id],
+        [IDL.Variant({ 'Ok' : file_id, 'Err' : upload_file_error })],
+        [],
+      ),
+    'upload_file_continue' : IDL.Func(
+        [upload_file_continue_request],
+        [upload_file_response],
+        [],
+      ),
+    'upload_file_version' : IDL.Func(
+        [upload_file_version_request],
+        [IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : file_version_error })],
+        [],
+      ),
+    'upload_to_drop_box' : IDL.Func(
+        [upload_to_drop_box_request],
+        [IDL.Variant({ 'Ok' : file_id, 'Err' : drop_box_error })],
         [],
       ),
-    'upload_file_continue' : IDL.Func([upload_file_continue_request], [], []),
     'username_exists' : IDL.Func([IDL.Text], [IDL.Bool], ['query']),
     'who_am_i' : IDL.Func([], [who_am_i_response], ['query']),
   });
 };
-export const init = ({ IDL }) => { return []; };
+export const init = ({ IDL }) => {
+  const features = IDL.Record({
+    'file_versions' : IDL.Bool,
+    'webhooks' : IDL.Bool,
+    'drop_boxes' : IDL.Bool,
+    'checklists' : IDL.Bool,
+    'organizations' : IDL.Bool,
+  });
+  const config = IDL.Record({
+    'features' : features,
+    'share_ttl_ns' : IDL.Opt(IDL.Nat64),
+    'max_stored_bytes' : IDL.Nat64,
+    'max_user_bytes' : IDL.Nat64,
+    'max_file_versions' : IDL.Nat64,
+    'request_ttl_ns' : IDL.Opt(IDL.Nat64),
+    'max_user_files' : IDL.Nat64,
+    'download_token_ttl_ns' : IDL.Nat64,
+    'max_chunk_size' : IDL.Nat64,
+    'max_file_size' : IDL.Nat64,
+    'max_drop_box_ttl_ns' : IDL.Opt(IDL.Nat64),
+  });
+  const rate_limit = IDL.Record({
+    'max_calls' : IDL.Nat64,
+    'window_ns' : IDL.Nat64,
+  });
+  const rate_limits = IDL.Record({
+    'per_principal' : rate_limit,
+    'anonymous' : rate_limit,
+    'per_alias' : rate_limit,
+  });
+  const init_args = IDL.Record({
+    'admins' : IDL.Opt(IDL.Vec(IDL.Principal)),
+    'rate_limits' : IDL.Opt(rate_limits),
+    'config' : IDL.Opt(config),
+  });
+  const upgrade_args = IDL.Record({
+    'admins' : IDL.Opt(IDL.Vec(IDL.Principal)),
+    'rate_limits' : IDL.Opt(rate_limits),
+    'config' : IDL.Opt(config),
+  });
+  const canister_args = IDL.Variant({
+    'init' : init_args,
+    'upgrade' : upgrade_args,
+  });
+  return [IDL.Opt(canister_args)];
+};
@@ -0,0 +1,69 @@
+import { expect, test } from "@jest/globals";
+import "fake-indexeddb/auto";
+import { default as crypto } from "./crypto";
+// The test vectors of the format, which are also checked by `format/src/lib.rs`.
+import vectors from "../../../../../format/test_vectors.json";
+
+const { subtle } = globalThis.crypto;
+
+function fromHex(hex) {
+  const bytes = new Uint8Array(hex.length / 2);
+  for (let i = 0; i < bytes.length; i++) {
+    bytes[i] = parseInt(hex.substr(i * 2, 2), 16);
+  }
+  return bytes.buffer;
+}
+
+async function importPrivateKey() {
+  return await subtle.importKey(
+    "pkcs8",
+    fromHex(vectors.user_key.private_key_pkcs8),
+    { name: "RSA-OAEP", hash: "SHA-256" },
+    false,
+    ["decrypt"]
+  );
+}
+
+test("decrypts the files of the test vectors.", async () => {
+  for (const file of vectors.files) {
+    const contents = await crypto.decryptFile(
+      fromHex(file.encrypted),
+      fromHex(file.file_key)
+    );
+    expect(new Uint8Array(contents)).toEqual(
+      new Uint8Array(fromHex(file.plaintext))
+    );
+  }
+});
+
+test("unwraps the file keys of the test vectors.", async () => {
+  const privateKey = await importPrivateKey();
+  for (const file of vectors.files) {
+    const fileKey = await subtle.decrypt(
+      { name: "RSA-OAEP" },
+      privateKey,
+      fromHex(file.wrapped_file_key)
+    );
+    expect(new Uint8Array(fileKey)).toEqual(
+      new Uint8Array(fromHex(file.file_key))
+    );
+  }
+});
+
+test("wraps file keys for the public key of the test vectors.", async () => {
+  const privateKey = await importPrivateKey();
+  for (const file of vectors.files) {
+    const wrapped = await crypto.encryptForUser(
+      fromHex(file.file_key),
+      fromHex(vectors.user_key.public_key_spki)
+    );
+    const fileKey = await subtle.decrypt(
+      { name: "RSA-OAEP" },
+      privateKey,
+      wrapped
+    );
+    expect(new Uint8Array(fileKey)).toEqual(
+      new Uint8Array(fromHex(file.file_key))
+    );
+  }
+});
@@ -95,13 +95,21 @@ export class UploadService {
           return;
         }
       } else {
-        fileId = await this.actor.upload_file_atomic({
+        const res = await this.actor.upload_file_atomic({
           content: firstChunk,
           owner_key: new Uint8Array(encryptedFileKey),
           name: fileName,
           file_type: dataType,
           num_chunks: BigInt(numChunks),
         });
+
+        if (enumIs(res, "Err")) {
+          onError(
+            "An error occurred while uploading the file. Please try again."
+          );
+          return;
+        }
+        fileId = res.Ok;
       }
 
       onChunkUploaded(0, firstChunk.length);
@@ -145,11 +153,14 @@ export class UploadService {
           return;
         }
         const chunk = content.subarray(i * CHUNK_SIZE, (i + 1) * CHUNK_SIZE);
-        await this.actor.upload_file_continue({
+        const res = await this.actor.upload_file_continue({
           file_id: fileId,
           contents: chunk,
           chunk_id: BigInt(i),
         });
+        if (enumIs(res, "Err")) {
+          throw new Error(`Failed to upload chunk ${i}`);
+        }
         onChunkUploaded(i, chunk.length);
       })
     );
@@ -0,0 +1,202 @@
+//! A harness running the wasm of the canister in PocketIC, a local simulator of the IC.
+//!
+//! Unlike the unit tests of the backend, which call `backend::api` on a `State`, the tests of
+//! this crate go through everything a call goes through on the IC: Candid encoding,
+//! `inspect_message`, the checks of `main.rs`, traps and their rollbacks, and upgrades, with
+//! the real clock and randomness. The canister is called with the typed bindings of
+//! `docutrack-client`, as any user would.
+//!
+//! The tests need the wasm of the backend and the PocketIC server:
+//!
+//! ```text
+//! cargo build --release --target wasm32-unknown-unknown -p backend
+//! POCKET_IC_BIN=<path to pocket-ic> cargo test -p integration-tests
+//! ```
+//!
+//! The wasm is read from `BACKEND_WASM` if it is set. The tests fail when either is missing,
+//! and they don't rebuild the wasm. They are skipped instead if `SKIP_INTEGRATION_TESTS` is
+//! set, so that `cargo test` can run the other tests of the workspace without them.
+use async_trait::async_trait;
+use backend::{CanisterArgs, InitArgs};
+use candid::Principal;
+use docutrack_client::{Agent, AgentError, Client};
+use pocket_ic::common::rest::{
+    CanisterHttpReply, CanisterHttpRequest, CanisterHttpResponse, MockCanisterHttpResponse,
+};
+use pocket_ic::{PocketIc, WasmResult};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// The cycles given to the canister, plenty for any test.
+const INITIAL_CYCLES: u128 = 100_000_000_000_000;
+
+/// A canister installed in its own PocketIC instance.
+pub struct TestEnv {
+    pic: Rc<PocketIc>,
+    canister_id: Principal,
+    wasm: Vec<u8>,
+}
+
+impl TestEnv {
+    /// Installs the canister with the default settings, or returns `None` if the tests are
+    /// skipped.
+    pub fn new() -> Option<Self> {
+        Self::with_args(InitArgs::default())
+    }
+
+    /// Installs the canister with the given arguments, or returns `None` if the tests are
+    /// skipped.
+    pub fn with_args(args: InitArgs) -> Option<Self> {
+        if std::env::var_os("SKIP_INTEGRATION_TESTS").is_some() {
+            eprintln!("skipped: SKIP_INTEGRATION_TESTS is set");
+            return None;
+        }
+        if std::env::var_os("POCKET_IC_BIN").is_none() {
+            panic!("POCKET_IC_BIN isn't set, set SKIP_INTEGRATION_TESTS to skip the tests");
+        }
+        let path = wasm_path();
+        let wasm = std::fs::read(&path).unwrap_or_else(|err| {
+            panic!(
+                "failed to read {}: {err}, set SKIP_INTEGRATION_TESTS to skip the tests",
+                path.display()
+            )
+        });
+
+        let pic = PocketIc::new();
+        let canister_id = pic.create_canister_with_settings(Some(to_pic_principal(admin())), None);
+        pic.add_cycles(canister_id, INITIAL_CYCLES);
+        pic.install_canister(
+            canister_id,
+            wasm.clone(),
+            encode_args(Some(CanisterArgs::Init(args))),
+            Some(to_pic_principal(admin())),
+        );
+        Some(Self {
+            pic: Rc::new(pic),
+            canister_id: from_pic_principal(canister_id),
+            wasm,
+        })
+    }
+
+    /// Returns a client calling the canister as the given principal.
+    pub fn client(&self, sender: Principal) -> Client<PocketIcAgent> {
+        Client::new(PocketIcAgent {
+            pic: self.pic.clone(),
+            canister_id: self.canister_id,
+            sender,
+        })
+    }
+
+    /// Upgrades the canister to the same wasm, as the admin. Returns the error if the upgrade
+    /// fails, in which case the canister keeps running the previous version and state.
+    pub fn upgrade(&self, args: Option<CanisterArgs>) -> Result<(), String> {
+        self.pic
+            .upgrade_canister(
+                to_pic_principal(self.canister_id),
+                self.wasm.clone(),
+                encode_args(args),
+                Some(to_pic_principal(admin())),
+            )
+            .map_err(|err| format!("{err:?}"))
+    }
+
+    pub fn advance_time(&self, duration: Duration) {
+        self.pic.advance_time(duration);
+        // The new time is only seen by the canister from the next round.
+        self.pic.tick();
+    }
+
+    /// Runs a round, and returns the HTTP outcalls of the canister that haven't been answered.
+    pub fn http_requests(&self) -> Vec<CanisterHttpRequest> {
+        self.pic.tick();
+        self.pic.get_canister_http()
+    }
+
+    /// Answers an HTTP outcall of the canister with the given status, and runs the round in
+    /// which the canister gets the response.
+    pub fn reply_to_http_request(&self, request: &CanisterHttpRequest, status: u16) {
+        self.pic
+            .mock_canister_http_response(MockCanisterHttpResponse {
+                subnet_id: request.subnet_id,
+                request_id: request.request_id,
+                response: CanisterHttpResponse::CanisterHttpReply(CanisterHttpReply {
+                    status,
+                    headers: vec![],
+                    body: vec![],
+                }),
+                additional_responses: vec![],
+            });
+        self.pic.tick();
+    }
+}
+
+/// Calls a canister of PocketIC as the given sender.
+///
+/// Update calls are submitted as ingress messages, so they are inspected by the canister.
+pub struct PocketIcAgent {
+    pic: Rc<PocketIc>,
+    canister_id: Principal,
+    sender: Principal,
+}
+
+#[async_trait(?Send)]
+impl Agent for PocketIcAgent {
+    async fn query(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError> {
+        let result = self.pic.query_call(
+            to_pic_principal(self.canister_id),
+            to_pic_principal(self.sender),
+            method,
+            arg,
+        );
+        to_reply(result)
+    }
+
+    async fn update(&self, method: &str, arg: Vec<u8>) -> Result<Vec<u8>, AgentError> {
+        let result = self.pic.update_call(
+            to_pic_principal(self.canister_id),
+            to_pic_principal(self.sender),
+            method,
+            arg,
+        );
+        to_reply(result)
+    }
+}
+
+fn to_reply(result: Result<WasmResult, pocket_ic::UserError>) -> Result<Vec<u8>, AgentError> {
+    match result {
+        Ok(WasmResult::Reply(reply)) => Ok(reply),
+        Ok(WasmResult::Reject(message)) => Err(AgentError::Rejected(message)),
+        // Traps, and messages rejected by `inspect_message`.
+        Err(err) => Err(AgentError::Rejected(err.description)),
+    }
+}
+
+/// The principal that installs and upgrades the canister, which makes it an admin.
+pub fn admin() -> Principal {
+    Principal::from_slice(&[0, 2, 0])
+}
+
+pub fn user(id: u8) -> Principal {
+    Principal::from_slice(&[0, 1, id])
+}
+
+fn wasm_path() -> PathBuf {
+    match std::env::var_os("BACKEND_WASM") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../target/wasm32-unknown-unknown/release/backend.wasm"),
+    }
+}
+
+fn encode_args(args: Option<CanisterArgs>) -> Vec<u8> {
+    candid::encode_one(args).expect("failed to encode the arguments")
+}
+
+fn to_pic_principal(principal: Principal) -> pocket_ic_candid::Principal {
+    pocket_ic_candid::Principal::from_slice(principal.as_slice())
+}
+
+fn from_pic_principal(principal: pocket_ic_candid::Principal) -> Principal {
+    Principal::from_slice(principal.as_slice())
+}
@@ -0,0 +1,143 @@
+use backend::{Config, GetAliasInfoError, InitArgs, SetUserResponse, SharePermission};
+use docutrack_client::{DownloadError, Error, FileStatus, Principal, UploadOptions, CHUNK_SIZE};
+use futures::executor::block_on;
+use integration_tests::{user, TestEnv};
+use std::time::Duration;
+
+/// Contents spanning several chunks, the last one being shorter.
+fn contents() -> Vec<u8> {
+    (0..2 * CHUNK_SIZE + 12345).map(|i| i as u8).collect()
+}
+
+#[test]
+fn requested_files_are_uploaded_in_chunks_by_anyone_with_the_alias() {
+    let Some(env) = TestEnv::new() else { return };
+    let requester = env.client(user(1));
+    let uploader = env.client(Principal::anonymous());
+    assert!(matches!(
+        block_on(requester.set_user("John".to_string(), vec![1; 32])).unwrap(),
+        SetUserResponse::Ok
+    ));
+
+    let alias = block_on(requester.request_file("passport".to_string())).unwrap();
+    let info = block_on(uploader.get_alias_info(alias.clone()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.file_name, "passport");
+    assert_eq!(info.user.public_key, vec![1; 32]);
+
+    let contents = contents();
+    block_on(uploader.upload_requested(
+        info.file_id,
+        "application/pdf".to_string(),
+        vec![2; 32],
+        &contents,
+        &UploadOptions::default(),
+    ))
+    .unwrap();
+
+    // The alias can't be used anymore, and only the requester can download the file.
+    assert_eq!(
+        block_on(uploader.get_alias_info(alias)).unwrap(),
+        Err(GetAliasInfoError::NotFound)
+    );
+    assert_eq!(
+        block_on(uploader.download(info.file_id, 5)),
+        Err(Error::Download(DownloadError::PermissionError))
+    );
+    let file = block_on(requester.download(info.file_id, 5)).unwrap();
+    assert_eq!(file.contents, contents);
+    assert_eq!(file.owner_key, vec![2; 32]);
+    assert_eq!(file.file_type, "application/pdf");
+    assert!(matches!(
+        block_on(requester.get_requests()).unwrap()[0].file_status,
+        FileStatus::Uploaded { .. }
+    ));
+}
+
+#[test]
+fn files_are_shared_between_users() {
+    let Some(env) = TestEnv::new() else { return };
+    let (owner, recipient, other) = (
+        env.client(user(1)),
+        env.client(user(2)),
+        env.client(user(3)),
+    );
+    for (client, username) in [(&owner, "John"), (&recipient, "Jane"), (&other, "Jack")] {
+        block_on(client.set_user(username.to_string(), vec![1; 32])).unwrap();
+    }
+
+    let contents = contents();
+    let file_id = block_on(owner.upload(
+        "notes.txt".to_string(),
+        "text/plain".to_string(),
+        vec![1; 32],
+        &contents,
+        &UploadOptions::default(),
+    ))
+    .unwrap();
+    assert_eq!(
+        block_on(recipient.download(file_id, 5)),
+        Err(Error::Download(DownloadError::PermissionError))
+    );
+
+    block_on(owner.share_with(
+        file_id,
+        vec![(user(2), vec![2; 32])],
+        SharePermission::Reshare,
+    ))
+    .unwrap();
+    let file = block_on(recipient.download(file_id, 5)).unwrap();
+    assert_eq!(file.contents, contents);
+    assert_eq!(file.owner_key, vec![2; 32]);
+
+    // The recipient may reshare the file, and the owner may revoke the reshares.
+    block_on(recipient.share_with(file_id, vec![(user(3), vec![3; 32])], SharePermission::View))
+        .unwrap();
+    assert_eq!(block_on(other.get_shared_files()).unwrap().len(), 1);
+    block_on(owner.revoke_shares(file_id, &[user(2), user(3)])).unwrap();
+    for client in [&recipient, &other] {
+        assert!(block_on(client.get_shared_files()).unwrap().is_empty());
+        assert_eq!(
+            block_on(client.download(file_id, 5)),
+            Err(Error::Download(DownloadError::PermissionError))
+        );
+    }
+}
+
+#[test]
+fn requests_expire() {
+    const HOUR_NS: u64 = 60 * 60 * 1_000_000_000;
+    let Some(env) = TestEnv::with_args(InitArgs {
+        config: Some(Config {
+            request_ttl_ns: Some(HOUR_NS),
+            ..Config::default()
+        }),
+        ..InitArgs::default()
+    }) else {
+        return;
+    };
+    let requester = env.client(user(1));
+    block_on(requester.set_user("John".to_string(), vec![1; 32])).unwrap();
+    let alias = block_on(requester.request_file("passport".to_string())).unwrap();
+
+    env.advance_time(Duration::from_nanos(HOUR_NS / 2));
+    let info = block_on(requester.get_alias_info(alias.clone()))
+        .unwrap()
+        .unwrap();
+    env.advance_time(Duration::from_nanos(HOUR_NS));
+    assert_eq!(
+        block_on(requester.get_alias_info(alias)).unwrap(),
+        Err(GetAliasInfoError::NotFound)
+    );
+    assert!(
+        block_on(env.client(Principal::anonymous()).upload_requested(
+            info.file_id,
+            "application/pdf".to_string(),
+            vec![2; 32],
+            b"passport",
+            &UploadOptions::default(),
+        ))
+        .is_err()
+    );
+}
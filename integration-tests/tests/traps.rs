@@ -0,0 +1,81 @@
+use backend::{Config, Features, InitArgs, StreamingCallbackToken, UploadFileContinueRequest};
+use docutrack_client::{AgentError, Error, Principal};
+use futures::executor::block_on;
+use integration_tests::{user, TestEnv};
+
+fn rejected<T>(result: Result<T, Error>) -> String {
+    match result {
+        Err(Error::Agent(AgentError::Rejected(message))) => message,
+        Err(err) => panic!("expected the call to be rejected, got: {err}"),
+        Ok(_) => panic!("expected the call to be rejected"),
+    }
+}
+
+#[test]
+fn calls_to_disabled_features_trap() {
+    let Some(env) = TestEnv::with_args(InitArgs {
+        config: Some(Config {
+            features: Features {
+                checklists: false,
+                ..Features::default()
+            },
+            ..Config::default()
+        }),
+        ..InitArgs::default()
+    }) else {
+        return;
+    };
+    let client = env.client(user(1));
+    block_on(client.set_user("John".to_string(), vec![1; 32])).unwrap();
+
+    let message = rejected(block_on(
+        client.request_checklist("onboarding".to_string(), vec!["passport".to_string()]),
+    ));
+    assert!(message.contains("this feature is disabled"), "{message}");
+    assert!(block_on(client.get_checklists()).unwrap().is_empty());
+    // The other features still work.
+    block_on(client.request_file("passport".to_string())).unwrap();
+}
+
+#[test]
+fn messages_are_inspected() {
+    let Some(env) = TestEnv::new() else { return };
+
+    // Anonymous callers can't register, and unregistered users can only register.
+    rejected(block_on(
+        env.client(Principal::anonymous())
+            .set_user("John".to_string(), vec![1; 32]),
+    ));
+    let client = env.client(user(1));
+    rejected(block_on(client.request_file("passport".to_string())));
+    block_on(client.set_user("John".to_string(), vec![1; 32])).unwrap();
+    block_on(client.request_file("passport".to_string())).unwrap();
+
+    // Users can only share the files they may share.
+    rejected(block_on(client.share_file(user(2), 42, vec![2; 32])));
+
+    // Chunks larger than the configured size are rejected before they are decoded.
+    let config = block_on(client.get_config()).unwrap();
+    rejected(block_on(client.upload_file_continue(
+        UploadFileContinueRequest {
+            file_id: 0,
+            chunk_id: 1,
+            contents: vec![0; config.max_chunk_size as usize + 64 * 1024 + 1],
+        },
+    )));
+}
+
+#[test]
+fn streaming_unknown_files_traps() {
+    let Some(env) = TestEnv::new() else { return };
+    let client = env.client(Principal::anonymous());
+
+    let message = rejected(block_on(client.http_request_streaming_callback(
+        StreamingCallbackToken {
+            file_id: 42,
+            chunk_id: 1,
+            token: "unknown".to_string(),
+        },
+    )));
+    assert!(message.contains("failed to stream the file"), "{message}");
+}
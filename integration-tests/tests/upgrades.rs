@@ -0,0 +1,106 @@
+use backend::{CanisterArgs, Config, InitArgs, SharePermission, UpgradeArgs};
+use docutrack_client::{UploadOptions, CHUNK_SIZE};
+use futures::executor::block_on;
+use integration_tests::{admin, user, TestEnv};
+
+/// Uploads a file spanning several chunks, shares it, and requests another one. Returns the id
+/// of the uploaded file and its contents.
+fn populate(env: &TestEnv) -> (u64, Vec<u8>) {
+    let (owner, recipient) = (env.client(user(1)), env.client(user(2)));
+    block_on(owner.set_user("John".to_string(), vec![1; 32])).unwrap();
+    block_on(recipient.set_user("Jane".to_string(), vec![2; 32])).unwrap();
+
+    let contents: Vec<u8> = (0..3 * CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+    let file_id = block_on(owner.upload(
+        "notes.txt".to_string(),
+        "text/plain".to_string(),
+        vec![1; 32],
+        &contents,
+        &UploadOptions::default(),
+    ))
+    .unwrap();
+    block_on(owner.share_with(file_id, vec![(user(2), vec![2; 32])], SharePermission::View))
+        .unwrap();
+    block_on(owner.request_file("passport".to_string())).unwrap();
+    (file_id, contents)
+}
+
+#[test]
+fn files_survive_upgrades() {
+    let Some(env) = TestEnv::new() else { return };
+    let (file_id, contents) = populate(&env);
+    let (owner, recipient) = (env.client(user(1)), env.client(user(2)));
+    let requests = block_on(owner.get_requests()).unwrap();
+    let shared_files = block_on(recipient.get_shared_files()).unwrap();
+
+    env.upgrade(None).unwrap();
+
+    assert_eq!(block_on(owner.get_requests()).unwrap(), requests);
+    assert_eq!(
+        block_on(recipient.get_shared_files()).unwrap(),
+        shared_files
+    );
+    // The contents are in stable memory, and the keys in the serialized state.
+    let file = block_on(recipient.download(file_id, 5)).unwrap();
+    assert_eq!(file.contents, contents);
+    assert_eq!(file.owner_key, vec![2; 32]);
+
+    // Files can still be uploaded, without reusing the ids of existing files.
+    let new_file_id = block_on(owner.upload(
+        "other.txt".to_string(),
+        "text/plain".to_string(),
+        vec![1; 32],
+        b"other",
+        &UploadOptions::default(),
+    ))
+    .unwrap();
+    assert!(requests.iter().all(|file| file.file_id != new_file_id));
+}
+
+#[test]
+fn upgrades_change_the_given_settings() {
+    let Some(env) = TestEnv::new() else { return };
+    populate(&env);
+    let config = Config {
+        max_file_versions: 2,
+        ..Config::default()
+    };
+
+    env.upgrade(Some(CanisterArgs::Upgrade(UpgradeArgs {
+        admins: Some(vec![user(1)]),
+        config: Some(config.clone()),
+        rate_limits: None,
+    })))
+    .unwrap();
+
+    let owner = env.client(user(1));
+    assert_eq!(block_on(owner.get_config()).unwrap(), config);
+    // The new admin is added to the admin who upgraded the canister.
+    assert!(block_on(owner.get_storage_usage()).unwrap().is_ok());
+    assert!(block_on(env.client(admin()).get_storage_usage())
+        .unwrap()
+        .is_ok());
+    // The settings that aren't given are kept.
+    env.upgrade(None).unwrap();
+    assert_eq!(block_on(owner.get_config()).unwrap(), config);
+}
+
+#[test]
+fn failed_upgrades_keep_the_state() {
+    let Some(env) = TestEnv::new() else { return };
+    let (file_id, contents) = populate(&env);
+    let owner = env.client(user(1));
+    let requests = block_on(owner.get_requests()).unwrap();
+
+    // The upgrade traps in `post_upgrade`, so the canister keeps running the previous version.
+    let err = env
+        .upgrade(Some(CanisterArgs::Init(InitArgs::default())))
+        .unwrap_err();
+    assert!(err.contains("expected upgrade arguments"), "{err}");
+
+    assert_eq!(block_on(owner.get_requests()).unwrap(), requests);
+    assert_eq!(
+        block_on(owner.download(file_id, 5)).unwrap().contents,
+        contents
+    );
+}
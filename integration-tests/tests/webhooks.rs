@@ -0,0 +1,70 @@
+use backend::{SetUserResponse, WEBHOOK_RETRY_DELAY_NS};
+use docutrack_client::{Principal, UploadOptions};
+use futures::executor::block_on;
+use integration_tests::{user, TestEnv};
+use pocket_ic::common::rest::{CanisterHttpMethod, CanisterHttpRequest};
+use std::time::Duration;
+
+fn header<'a>(request: &'a CanisterHttpRequest, name: &str) -> &'a str {
+    request
+        .headers
+        .iter()
+        .find(|header| header.name == name)
+        .map(|header| header.value.as_str())
+        .unwrap_or_else(|| panic!("missing header {name}"))
+}
+
+#[test]
+fn uploads_are_posted_to_webhooks_until_they_are_delivered() {
+    let Some(env) = TestEnv::new() else { return };
+    let owner = env.client(user(1));
+    let uploader = env.client(Principal::anonymous());
+    assert!(matches!(
+        block_on(owner.set_user("John".to_string(), vec![1; 32])).unwrap(),
+        SetUserResponse::Ok
+    ));
+    let url = "https://example.com/hooks/docutrack".to_string();
+    block_on(owner.add_webhook(url.clone())).unwrap().unwrap();
+
+    let alias = block_on(owner.request_file("passport".to_string())).unwrap();
+    let file_id = block_on(uploader.get_alias_info(alias))
+        .unwrap()
+        .unwrap()
+        .file_id;
+    block_on(uploader.upload_requested(
+        file_id,
+        "application/pdf".to_string(),
+        vec![2; 32],
+        &[1, 2, 3],
+        &UploadOptions::default(),
+    ))
+    .unwrap();
+
+    // The heartbeat posts the event with an outcall.
+    let requests = env.http_requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.http_method, CanisterHttpMethod::POST);
+    assert_eq!(request.url, url);
+    assert_eq!(header(request, "Content-Type"), "application/json");
+    assert!(header(request, "X-DocuTrack-Signature").starts_with("v1="));
+    let body = String::from_utf8(request.body.clone()).unwrap();
+    assert!(body.contains(r#""event":"upload_completed""#), "{body}");
+
+    // Server errors are retried after a delay, with the same delivery.
+    env.reply_to_http_request(request, 503);
+    assert!(env.http_requests().is_empty());
+    env.advance_time(Duration::from_nanos(WEBHOOK_RETRY_DELAY_NS));
+    let retries = env.http_requests();
+    assert_eq!(retries.len(), 1);
+    assert_eq!(
+        header(&retries[0], "Idempotency-Key"),
+        header(request, "Idempotency-Key")
+    );
+    assert_eq!(retries[0].body, request.body);
+
+    // Delivered events aren't posted again.
+    env.reply_to_http_request(&retries[0], 200);
+    env.advance_time(Duration::from_nanos(8 * WEBHOOK_RETRY_DELAY_NS));
+    assert!(env.http_requests().is_empty());
+}
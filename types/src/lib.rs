@@ -0,0 +1,991 @@
+//! The types of the interface of the DocuTrack canister, as described by `backend/service.did`.
+//!
+//! They are shared by the canister and its clients, so that the clients don't depend on the
+//! canister itself and the bindings can't drift from the interface.
+use candid::{CandidType, Func, Principal};
+#[cfg(feature = "storable")]
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "storable")]
+use std::borrow::Cow;
+
+// The defaults of the limits in `Config`.
+
+/// The default maximum size of a chunk. Chunks have to fit in an ingress message.
+pub const MAX_CHUNK_SIZE: usize = 2_000_000;
+
+/// The default maximum size of a file, including the overhead of its encryption.
+pub const MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// The maximum number of versions of a file that are kept, including the current one.
+/// When a new version is uploaded, the oldest versions beyond this limit are deleted.
+pub const MAX_FILE_VERSIONS: usize = 10;
+
+/// The maximum number of bytes a user can store, including previous versions of files.
+pub const MAX_USER_BYTES: u64 = 1 << 30;
+
+/// The maximum number of files a user can store.
+pub const MAX_USER_FILES: u64 = 1_000;
+
+/// The maximum number of bytes of files stored by the canister across all users.
+pub const MAX_STORED_BYTES: u64 = 64 << 30;
+
+/// How long a download token gives access to a file over HTTP, by default.
+pub const DOWNLOAD_TOKEN_TTL_NS: u64 = 5 * 60 * 1_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct User {
+    pub username: String,
+    pub public_key: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize)]
+pub enum SetUserResponse {
+    #[serde(rename = "ok")]
+    Ok,
+    #[serde(rename = "username_exists")]
+    UsernameExists,
+}
+
+#[derive(CandidType, Serialize, Deserialize)]
+pub enum WhoamiResponse {
+    #[serde(rename = "known_user")]
+    KnownUser(PublicUser),
+    #[serde(rename = "unknown_user")]
+    UnknownUser,
+}
+
+/// File metadata.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub file_name: String,
+    pub user_public_key: Vec<u8>,
+    pub requester_principal: Principal,
+    pub requested_at: u64,
+    pub uploaded_at: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    #[serde(rename = "pending")]
+    Pending { alias: String, requested_at: u64 },
+    #[serde(rename = "partially_uploaded")]
+    PartiallyUploaded,
+    #[serde(rename = "uploaded")]
+    Uploaded {
+        uploaded_at: u64,
+        document_key: Vec<u8>,
+    },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicFileMetadata {
+    pub file_id: u64,
+    pub file_name: String,
+    pub file_status: FileStatus,
+    pub shared_with: Vec<PublicUser>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum GetAliasInfoError {
+    #[serde(rename = "not_found")]
+    NotFound,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AliasInfo {
+    pub file_id: u64,
+    pub file_name: String,
+    pub user: PublicUser,
+    /// The files requested through the alias. A checklist alias has one item per requested
+    /// document, while a regular alias has a single item.
+    pub items: Vec<AliasItem>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AliasItem {
+    pub file_id: u64,
+    pub file_name: String,
+    /// Whether the file still needs to be uploaded.
+    pub pending: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DropBoxUpload {
+    pub file_id: u64,
+    pub label: String,
+    pub uploaded_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DropBoxInfo {
+    pub name: String,
+    pub user: PublicUser,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicDropBox {
+    pub alias: String,
+    pub name: String,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub max_files: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub total_bytes: u64,
+    pub closed: bool,
+    pub uploads: Vec<DropBoxUpload>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum DropBoxError {
+    #[serde(rename = "not_found")]
+    NotFound,
+    #[serde(rename = "closed")]
+    Closed,
+    #[serde(rename = "expired")]
+    Expired,
+    #[serde(rename = "limit_exceeded")]
+    LimitExceeded,
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "quota_exceeded")]
+    QuotaExceeded(QuotaError),
+    #[serde(rename = "invalid_request")]
+    InvalidRequest(ValidationError),
+    #[serde(rename = "rate_limited")]
+    RateLimited(RateLimitError),
+    #[serde(rename = "uploads_frozen")]
+    UploadsFrozen,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransferOffer {
+    pub file_id: u64,
+    pub file_name: String,
+    pub from: PublicUser,
+    pub file_key_encrypted_for_user: Vec<u8>,
+    pub offered_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum FileTransferError {
+    #[serde(rename = "not_found_file")]
+    NotFoundFile,
+    #[serde(rename = "not_uploaded_file")]
+    NotUploadedFile,
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "unknown_user")]
+    UnknownUser,
+    #[serde(rename = "no_offer")]
+    NoOffer,
+}
+
+/// The role of a member in an organization.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Can manage members, share files and revoke shares of the organization's files.
+    #[serde(rename = "admin")]
+    Admin,
+    /// Can share the organization's files and request files on its behalf.
+    #[serde(rename = "editor")]
+    Editor,
+    /// Can only download the organization's files that were shared with them.
+    #[serde(rename = "viewer")]
+    Viewer,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OrganizationMember {
+    pub user: PublicUser,
+    pub role: Role,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicOrganization {
+    pub org_id: u64,
+    pub name: String,
+    pub role: Role,
+    pub members: Vec<OrganizationMember>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum OrganizationError {
+    #[serde(rename = "not_found")]
+    NotFound,
+    #[serde(rename = "not_found_file")]
+    NotFoundFile,
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "unknown_user")]
+    UnknownUser,
+    #[serde(rename = "last_admin")]
+    LastAdmin,
+}
+
+/// The level of access a share grants to its recipient.
+#[derive(
+    CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum SharePermission {
+    /// Can only download the file.
+    #[serde(rename = "view")]
+    View,
+    /// Can download the file and share it with others, up to the same level.
+    #[serde(rename = "reshare")]
+    Reshare,
+    /// Can share the file at any level and revoke the shares of others.
+    #[serde(rename = "co_owner")]
+    CoOwner,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicShare {
+    pub user: PublicUser,
+    pub permission: SharePermission,
+    pub granted_by: Principal,
+    pub granted_at: u64,
+    pub downloads_remaining: Option<u64>,
+}
+
+/// An access to or a mutation of a file that is recorded in the audit log.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AuditEvent {
+    #[serde(rename = "file_requested")]
+    FileRequested { file_id: u64 },
+    #[serde(rename = "chunk_uploaded")]
+    ChunkUploaded { file_id: u64, chunk_id: u64 },
+    /// All the chunks of the file have been uploaded.
+    #[serde(rename = "file_uploaded")]
+    FileUploaded { file_id: u64 },
+    #[serde(rename = "chunk_downloaded")]
+    ChunkDownloaded { file_id: u64, chunk_id: u64 },
+    #[serde(rename = "file_shared")]
+    FileShared {
+        file_id: u64,
+        user: Principal,
+        permission: SharePermission,
+    },
+    #[serde(rename = "share_revoked")]
+    ShareRevoked { file_id: u64, user: Principal },
+}
+
+impl AuditEvent {
+    pub fn file_id(&self) -> u64 {
+        match self {
+            AuditEvent::FileRequested { file_id }
+            | AuditEvent::ChunkUploaded { file_id, .. }
+            | AuditEvent::FileUploaded { file_id }
+            | AuditEvent::ChunkDownloaded { file_id, .. }
+            | AuditEvent::FileShared { file_id, .. }
+            | AuditEvent::ShareRevoked { file_id, .. } => *file_id,
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub caller: Principal,
+    pub event: AuditEvent,
+}
+
+#[cfg(feature = "storable")]
+impl Storable for AuditEntry {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(self, &mut bytes).expect("failed to encode audit entry");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        ciborium::de::from_reader(&*bytes).expect("failed to decode audit entry")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditEntry>,
+    /// The total number of entries in the log.
+    pub total: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileAuditLogPage {
+    pub entries: Vec<AuditEntry>,
+    /// The index of the log to read the next page from, if the end of the log wasn't reached.
+    pub next: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AuditLogError {
+    #[serde(rename = "not_found_file")]
+    NotFoundFile,
+    #[serde(rename = "permission_error")]
+    PermissionError,
+}
+
+/// An event that a user is notified of, about one of their files or a file shared with them.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum NotificationEvent {
+    /// Someone else uploaded the file, e.g., through a request or a drop box.
+    #[serde(rename = "upload_completed")]
+    UploadCompleted,
+    #[serde(rename = "file_shared")]
+    FileShared {
+        by: Principal,
+        permission: SharePermission,
+    },
+    #[serde(rename = "share_revoked")]
+    ShareRevoked { by: Principal },
+    /// The request hasn't been uploaded and expires soon.
+    #[serde(rename = "request_expiring")]
+    RequestExpiring { expires_at: u64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub id: u64,
+    pub file_id: u64,
+    /// The name of the file when the notification was sent.
+    pub file_name: String,
+    pub event: NotificationEvent,
+    pub created_at: u64,
+    /// Whether the user has acknowledged the notification.
+    pub read: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicWebhook {
+    pub id: u64,
+    pub url: String,
+    pub created_at: u64,
+}
+
+/// A new webhook, along with the secret its payloads are signed with.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WebhookRegistration {
+    pub id: u64,
+    pub secret: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum WebhookError {
+    /// The URL isn't an HTTPS URL, or is too long.
+    #[serde(rename = "invalid_url")]
+    InvalidUrl,
+    /// The caller has registered `MAX_WEBHOOKS` webhooks.
+    #[serde(rename = "limit_exceeded")]
+    LimitExceeded,
+    #[serde(rename = "not_found")]
+    NotFound,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicChecklist {
+    pub alias: String,
+    pub name: String,
+    pub requested_at: u64,
+    pub items: Vec<PublicFileMetadata>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicFileVersion {
+    pub version: u64,
+    pub file_type: String,
+    pub num_chunks: u64,
+    pub uploaded_at: u64,
+    pub is_current: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, PartialEq)]
+pub struct FileData {
+    pub contents: Vec<u8>,
+    pub file_type: String,
+    pub owner_key: Vec<u8>,
+    pub num_chunks: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, PartialEq, Debug)]
+pub enum FileDownloadResponse {
+    #[serde(rename = "not_found_file")]
+    NotFoundFile,
+    #[serde(rename = "not_uploaded_file")]
+    NotUploadedFile,
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "found_file")]
+    FoundFile(FileData),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum UploadFileError {
+    #[serde(rename = "not_requested")]
+    NotRequested,
+    #[serde(rename = "already_uploaded")]
+    AlreadyUploaded,
+    #[serde(rename = "quota_exceeded")]
+    QuotaExceeded(QuotaError),
+    #[serde(rename = "invalid_request")]
+    InvalidRequest(ValidationError),
+    #[serde(rename = "rate_limited")]
+    RateLimited(RateLimitError),
+    #[serde(rename = "uploads_frozen")]
+    UploadsFrozen,
+    #[serde(rename = "drop_box_limit_exceeded")]
+    DropBoxLimitExceeded,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum FileVersionError {
+    #[serde(rename = "not_found_file")]
+    NotFoundFile,
+    #[serde(rename = "not_uploaded_file")]
+    NotUploadedFile,
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "quota_exceeded")]
+    QuotaExceeded(QuotaError),
+    #[serde(rename = "invalid_request")]
+    InvalidRequest(ValidationError),
+    #[serde(rename = "uploads_frozen")]
+    UploadsFrozen,
+    #[serde(rename = "no_pending_version")]
+    NoPendingVersion,
+}
+
+/// The storage limit that an upload would exceed.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaError {
+    #[serde(rename = "user_files")]
+    UserFiles,
+    #[serde(rename = "user_bytes")]
+    UserBytes,
+    /// The canister as a whole is out of capacity.
+    #[serde(rename = "capacity")]
+    Capacity,
+}
+
+impl From<QuotaError> for UploadFileError {
+    fn from(err: QuotaError) -> Self {
+        UploadFileError::QuotaExceeded(err)
+    }
+}
+
+impl From<QuotaError> for FileVersionError {
+    fn from(err: QuotaError) -> Self {
+        FileVersionError::QuotaExceeded(err)
+    }
+}
+
+impl From<QuotaError> for DropBoxError {
+    fn from(err: QuotaError) -> Self {
+        DropBoxError::QuotaExceeded(err)
+    }
+}
+
+/// The reason an upload request is rejected by the validation of the canister.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// Files have at least one chunk.
+    #[serde(rename = "invalid_num_chunks")]
+    InvalidNumChunks,
+    #[serde(rename = "empty_chunk")]
+    EmptyChunk,
+    #[serde(rename = "chunk_too_large")]
+    ChunkTooLarge,
+    /// A chunk other than the last one doesn't have the size of the first chunk, or the last
+    /// chunk is larger than the first one.
+    #[serde(rename = "invalid_chunk_size")]
+    InvalidChunkSize,
+    #[serde(rename = "file_too_large")]
+    FileTooLarge,
+    #[serde(rename = "unsupported_file_type")]
+    UnsupportedFileType,
+    #[serde(rename = "empty_file_name")]
+    EmptyFileName,
+    #[serde(rename = "file_name_too_long")]
+    FileNameTooLong,
+    /// The file name contains control characters or path separators.
+    #[serde(rename = "invalid_file_name")]
+    InvalidFileName,
+}
+
+impl From<ValidationError> for UploadFileError {
+    fn from(err: ValidationError) -> Self {
+        UploadFileError::InvalidRequest(err)
+    }
+}
+
+impl From<ValidationError> for FileVersionError {
+    fn from(err: ValidationError) -> Self {
+        FileVersionError::InvalidRequest(err)
+    }
+}
+
+impl From<ValidationError> for DropBoxError {
+    fn from(err: ValidationError) -> Self {
+        DropBoxError::InvalidRequest(err)
+    }
+}
+
+/// A budget of calls within a sliding window.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_calls: u64,
+    pub window_ns: u64,
+}
+
+/// The budgets of the throttled endpoints, i.e., the endpoints that request and upload files.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimits {
+    /// The budget of each authenticated caller.
+    pub per_principal: RateLimit,
+    /// The budget shared by the anonymous calls that aren't made through an alias. Anonymous
+    /// calls through an alias only count against the budget of the alias.
+    pub anonymous: RateLimit,
+    /// The budget of each alias, counting the uploads started through it.
+    pub per_alias: RateLimit,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        const MINUTE_NS: u64 = 60 * 1_000_000_000;
+        Self {
+            per_principal: RateLimit {
+                max_calls: 300,
+                window_ns: MINUTE_NS,
+            },
+            anonymous: RateLimit {
+                max_calls: 1_000,
+                window_ns: MINUTE_NS,
+            },
+            per_alias: RateLimit {
+                max_calls: 50,
+                window_ns: 60 * MINUTE_NS,
+            },
+        }
+    }
+}
+
+/// The budget that a call would exceed.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitError {
+    #[serde(rename = "caller")]
+    Caller,
+    #[serde(rename = "alias")]
+    Alias,
+}
+
+impl From<RateLimitError> for UploadFileError {
+    fn from(err: RateLimitError) -> Self {
+        UploadFileError::RateLimited(err)
+    }
+}
+
+impl From<RateLimitError> for DropBoxError {
+    fn from(err: RateLimitError) -> Self {
+        DropBoxError::RateLimited(err)
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdminError {
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "not_found_file")]
+    NotFoundFile,
+    #[serde(rename = "unknown_user")]
+    UnknownUser,
+    /// The canister must keep at least one admin.
+    #[serde(rename = "last_admin")]
+    LastAdmin,
+    /// Every organization must keep at least one admin.
+    #[serde(rename = "last_organization_admin")]
+    LastOrganizationAdmin,
+    #[serde(rename = "invalid_config")]
+    InvalidConfig(ConfigError),
+}
+
+/// The arguments of the canister, given either at installation or at upgrade.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum CanisterArgs {
+    #[serde(rename = "init")]
+    Init(InitArgs),
+    #[serde(rename = "upgrade")]
+    Upgrade(UpgradeArgs),
+}
+
+/// The arguments of the canister installation. Settings that aren't given take their defaults.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InitArgs {
+    /// Admins in addition to the principal installing the canister.
+    pub admins: Option<Vec<Principal>>,
+    pub config: Option<Config>,
+    pub rate_limits: Option<RateLimits>,
+}
+
+/// The arguments of a canister upgrade. Settings that aren't given are kept.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UpgradeArgs {
+    /// Admins in addition to the current ones and the principal upgrading the canister.
+    pub admins: Option<Vec<Principal>>,
+    pub config: Option<Config>,
+    pub rate_limits: Option<RateLimits>,
+}
+
+/// The settings of the canister. They are given at installation or upgrade, and admins can
+/// rotate them at runtime.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    pub max_chunk_size: u64,
+    /// The maximum size of a file, including the overhead of its encryption.
+    pub max_file_size: u64,
+    /// The number of versions of a file that are kept, including the current one.
+    pub max_file_versions: u64,
+    pub max_user_bytes: u64,
+    pub max_user_files: u64,
+    pub max_stored_bytes: u64,
+    /// How long a request can be uploaded to, or `None` if requests never expire.
+    pub request_ttl_ns: Option<u64>,
+    /// How long a share gives access to a file, or `None` if shares never expire.
+    /// Sharing the file again with the same user renews the share.
+    pub share_ttl_ns: Option<u64>,
+    /// The longest time a drop box accepts uploads, or `None` if drop boxes may never expire.
+    pub max_drop_box_ttl_ns: Option<u64>,
+    /// How long a download token gives access to a file over HTTP.
+    pub download_token_ttl_ns: u64,
+    pub features: Features,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_chunk_size: MAX_CHUNK_SIZE as u64,
+            max_file_size: MAX_FILE_SIZE,
+            max_file_versions: MAX_FILE_VERSIONS as u64,
+            max_user_bytes: MAX_USER_BYTES,
+            max_user_files: MAX_USER_FILES,
+            max_stored_bytes: MAX_STORED_BYTES,
+            request_ttl_ns: None,
+            share_ttl_ns: None,
+            max_drop_box_ttl_ns: None,
+            download_token_ttl_ns: DOWNLOAD_TOKEN_TTL_NS,
+            features: Features::default(),
+        }
+    }
+}
+
+/// The features that can be turned off. All of them are enabled by default.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Features {
+    pub checklists: bool,
+    pub drop_boxes: bool,
+    pub file_versions: bool,
+    pub organizations: bool,
+    pub webhooks: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            checklists: true,
+            drop_boxes: true,
+            file_versions: true,
+            organizations: true,
+            webhooks: true,
+        }
+    }
+}
+
+/// A setting of a `Config` that would break the canister, e.g., by rejecting every upload.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    #[serde(rename = "zero_chunk_size")]
+    ZeroChunkSize,
+    #[serde(rename = "zero_file_size")]
+    ZeroFileSize,
+    /// At least the current version of a file is kept.
+    #[serde(rename = "zero_file_versions")]
+    ZeroFileVersions,
+    /// TTLs are either not set or positive.
+    #[serde(rename = "zero_ttl")]
+    ZeroTtl,
+}
+
+/// Uploads are rejected while an admin has frozen them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UploadsFrozenError;
+
+impl From<UploadsFrozenError> for UploadFileError {
+    fn from(_: UploadsFrozenError) -> Self {
+        UploadFileError::UploadsFrozen
+    }
+}
+
+impl From<UploadsFrozenError> for FileVersionError {
+    fn from(_: UploadsFrozenError) -> Self {
+        FileVersionError::UploadsFrozen
+    }
+}
+
+impl From<UploadsFrozenError> for DropBoxError {
+    fn from(_: UploadsFrozenError) -> Self {
+        DropBoxError::UploadsFrozen
+    }
+}
+
+/// A privileged operation that is recorded in the admin log.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminAction {
+    #[serde(rename = "admin_added")]
+    AdminAdded { user: Principal },
+    #[serde(rename = "admin_removed")]
+    AdminRemoved { user: Principal },
+    #[serde(rename = "file_removed")]
+    FileRemoved { file_id: u64, owner: Principal },
+    #[serde(rename = "user_removed")]
+    UserRemoved { user: Principal },
+    #[serde(rename = "uploads_frozen")]
+    UploadsFrozen,
+    #[serde(rename = "uploads_unfrozen")]
+    UploadsUnfrozen,
+    #[serde(rename = "config_updated")]
+    ConfigUpdated { config: Config },
+    #[serde(rename = "rate_limits_updated")]
+    RateLimitsUpdated { rate_limits: RateLimits },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminLogEntry {
+    pub timestamp: u64,
+    pub caller: Principal,
+    pub action: AdminAction,
+}
+
+#[cfg(feature = "storable")]
+impl Storable for AdminLogEntry {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(self, &mut bytes).expect("failed to encode admin log entry");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        ciborium::de::from_reader(&*bytes).expect("failed to decode admin log entry")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminLogPage {
+    pub entries: Vec<AdminLogEntry>,
+    /// The total number of entries in the log.
+    pub total: u64,
+}
+
+/// The storage consumed across the canister, for moderation.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StorageUsage {
+    pub stored_bytes: u64,
+    pub max_stored_bytes: u64,
+    pub file_count: u64,
+    pub user_count: u64,
+    /// The usage of every user, largest first.
+    pub users: Vec<UserUsage>,
+}
+
+/// Measures of the health of the canister, for monitoring.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Metrics {
+    pub timestamp: u64,
+    pub user_count: u64,
+    pub pending_file_count: u64,
+    pub partially_uploaded_file_count: u64,
+    pub uploaded_file_count: u64,
+    /// The number of stored chunks, across all versions.
+    pub chunk_count: u64,
+    /// The number of bytes in `file_contents`, across all versions.
+    pub stored_bytes: u64,
+    /// The number of aliases through which files can still be uploaded.
+    pub pending_alias_count: u64,
+    pub share_count: u64,
+    pub heap_memory_bytes: u64,
+    pub stable_memory_bytes: u64,
+    pub cycle_balance: u128,
+}
+
+/// A query response along with the proof that it matches the certified data of the canister.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Certified<T> {
+    pub data: T,
+    /// A hash tree revealing the certified data of the response, encoded in CBOR.
+    pub witness: Vec<u8>,
+    /// The certificate of the root hash of the tree, which is only available in queries.
+    pub certificate: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub streaming_strategy: Option<StreamingStrategy>,
+}
+
+/// How the HTTP gateway fetches the rest of a response body.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum StreamingStrategy {
+    Callback {
+        callback: Func,
+        token: StreamingCallbackToken,
+    },
+}
+
+/// Identifies the next chunk of a file download served over HTTP.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StreamingCallbackToken {
+    pub file_id: u64,
+    pub chunk_id: u64,
+    /// The download token the download was started with.
+    pub token: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StreamingCallbackHttpResponse {
+    pub body: Vec<u8>,
+    /// The token of the next chunk, or `None` once the last chunk is served.
+    pub token: Option<StreamingCallbackToken>,
+}
+
+/// A download token, along with what is needed to decrypt the file it gives access to.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DownloadToken {
+    pub token: String,
+    /// The path at which the file is served by the HTTP gateway.
+    pub url: String,
+    pub expires_at: u64,
+    pub file_type: String,
+    /// The file key, encrypted for the user.
+    pub owner_key: Vec<u8>,
+    pub num_chunks: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum DownloadTokenError {
+    #[serde(rename = "not_found_file")]
+    NotFoundFile,
+    #[serde(rename = "not_uploaded_file")]
+    NotUploadedFile,
+    /// The file isn't accessible to the caller, or is only shared for a limited number of
+    /// downloads, which have to be counted by `download_file_audited`.
+    #[serde(rename = "permission_error")]
+    PermissionError,
+}
+
+/// The storage consumed by a user.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub file_count: u64,
+    pub bytes: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserUsage {
+    pub user: Principal,
+    pub file_count: u64,
+    pub bytes: u64,
+    pub max_files: u64,
+    pub max_bytes: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, PartialEq)]
+pub enum FileSharingResponse {
+    #[serde(rename = "pending_error")]
+    PendingError,
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "ok")]
+    Ok,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicUser {
+    pub username: String,
+    pub public_key: Vec<u8>,
+    pub ic_principal: Principal,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum GetUsersResponse {
+    #[serde(rename = "permission_error")]
+    PermissionError,
+    #[serde(rename = "users")]
+    Users(Vec<PublicUser>),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UploadFileRequest {
+    pub file_id: u64,
+    pub file_content: Vec<u8>,
+    pub file_type: String,
+    pub owner_key: Vec<u8>,
+    pub num_chunks: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UploadFileContinueRequest {
+    pub file_id: u64,
+    pub chunk_id: u64,
+    pub contents: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CreateDropBoxRequest {
+    pub name: String,
+    pub expires_at: Option<u64>,
+    pub max_files: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UploadToDropBoxRequest {
+    pub alias: String,
+    pub label: String,
+    pub file_name: String,
+    pub content: Vec<u8>,
+    pub owner_key: Vec<u8>,
+    pub file_type: String,
+    pub num_chunks: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UploadFileVersionRequest {
+    pub file_id: u64,
+    pub content: Vec<u8>,
+    pub owner_key: Vec<u8>,
+    pub file_type: String,
+    pub num_chunks: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UploadFileAtomicRequest {
+    pub name: String,
+    pub content: Vec<u8>,
+    pub owner_key: Vec<u8>,
+    pub file_type: String,
+    pub num_chunks: u64,
+}